@@ -1,5 +1,10 @@
 use std::env;
 
+use crate::{
+    models::{CompanyHeader, DocumentSettings, LoginLimits, SessionSettings, StockSettings},
+    utils::password::PasswordPolicy,
+};
+
 /// Application configuration
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
@@ -7,7 +12,26 @@ pub struct Config {
     pub database_url: String,
     pub server_host: String,
     pub server_port: u16,
+    /// Hours without a request before a session ends
     pub session_duration_hours: i64,
+    /// Days without a request before a "remember me" session ends
+    pub remember_me_days: i64,
+    /// Minutes between sweeps that delete expired sessions
+    pub session_purge_minutes: i64,
+    /// Seconds between runs of the worker that sends queued webhooks
+    pub webhook_poll_seconds: i64,
+    /// Directory uploaded files such as product images are stored in
+    pub media_dir: String,
+    /// Business name, address, phone and closing line printed on invoices and receipts
+    pub company: CompanyHeader,
+    /// TrueType font with Persian glyphs used for PDF documents
+    pub pdf_font_path: String,
+    /// Throttling and lockout of failed logins
+    pub login_limits: LoginLimits,
+    /// Rules for new passwords
+    pub password_policy: PasswordPolicy,
+    /// When a product counts as running low on stock
+    pub stock: StockSettings,
 }
 
 impl Config {
@@ -25,6 +49,54 @@ impl Config {
                 .ok()
                 .and_then(|h| h.parse().ok())
                 .unwrap_or(24),
+            remember_me_days: env_number("REMEMBER_ME_DAYS", 30),
+            session_purge_minutes: env_number("SESSION_PURGE_MINUTES", 60),
+            webhook_poll_seconds: env_number("WEBHOOK_POLL_SECONDS", 5),
+            media_dir: env::var("MEDIA_DIR").unwrap_or_else(|_| "media".to_string()),
+            company: CompanyHeader {
+                name: env::var("COMPANY_NAME").unwrap_or_else(|_| "رومیلند".to_string()),
+                address: env::var("COMPANY_ADDRESS").unwrap_or_default(),
+                phone: env::var("COMPANY_PHONE").unwrap_or_default(),
+                footer: env::var("DOCUMENT_FOOTER").unwrap_or_default(),
+            },
+            pdf_font_path: env::var("PDF_FONT_PATH")
+                .unwrap_or_else(|_| "static/fonts/Vazirmatn-Regular.ttf".to_string()),
+            login_limits: LoginLimits {
+                max_failures_per_username: env_number("LOGIN_MAX_FAILURES", 5),
+                max_failures_per_ip: env_number("LOGIN_MAX_FAILURES_PER_IP", 20),
+                lockout_minutes: env_number("LOGIN_LOCKOUT_MINUTES", 15),
+                backoff_base_seconds: env_number("LOGIN_BACKOFF_SECONDS", 1),
+                trusted_proxy_hops: env_number(
+                    "TRUSTED_PROXY_HOPS",
+                    env_flag("TRUST_X_FORWARDED_FOR", false) as i64,
+                )
+                .max(0) as usize,
+            },
+            password_policy: PasswordPolicy {
+                min_length: env_number("PASSWORD_MIN_LENGTH", 8).max(1) as usize,
+                require_letter: env_flag("PASSWORD_REQUIRE_LETTER", true),
+                require_digit: env_flag("PASSWORD_REQUIRE_DIGIT", true),
+            },
+            stock: StockSettings {
+                low_stock_threshold: env_number("LOW_STOCK_THRESHOLD", 5).clamp(0, i32::MAX as i64)
+                    as i32,
+            },
+        }
+    }
+
+    /// Settings for printed and PDF documents
+    pub fn document_settings(&self) -> DocumentSettings {
+        DocumentSettings {
+            company: self.company.clone(),
+            font_path: self.pdf_font_path.clone().into(),
+        }
+    }
+
+    /// Lifetimes of login sessions
+    pub fn session_settings(&self) -> SessionSettings {
+        SessionSettings {
+            idle_timeout_hours: self.session_duration_hours,
+            remember_me_days: self.remember_me_days,
         }
     }
 
@@ -39,3 +111,20 @@ impl Default for Config {
         Self::from_env()
     }
 }
+
+/// Read a numeric setting, falling back to `default` when unset or invalid
+fn env_number(key: &str, default: i64) -> i64 {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Read a true/false setting, falling back to `default` when unset or invalid
+fn env_flag(key: &str, default: bool) -> bool {
+    match env::var(key).map(|value| value.to_lowercase()).as_deref() {
+        Ok("true" | "1" | "yes") => true,
+        Ok("false" | "0" | "no") => false,
+        _ => default,
+    }
+}
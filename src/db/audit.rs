@@ -0,0 +1,60 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{Executor, Sqlite};
+
+use crate::models::{audit::diff, AuditAction, AuditEntity};
+
+/// Fields whose values never go into the audit log, only the fact that they changed
+const SECRET_FIELDS: &[&str] = &["password_hash"];
+
+/// Record a change to a record in the audit log.
+///
+/// Pass the record as it was before the change (`None` when it was created) and
+/// after it (`None` when it was deleted). An update that changed nothing is not recorded.
+pub async fn record_audit<'e, T: Serialize>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    user_id: Option<i64>,
+    entity: AuditEntity,
+    entity_id: i64,
+    old: Option<&T>,
+    new: Option<&T>,
+) -> Result<(), sqlx::Error> {
+    let action = match (old.is_some(), new.is_some()) {
+        (false, _) => AuditAction::Create,
+        (true, true) => AuditAction::Update,
+        (true, false) => AuditAction::Delete,
+    };
+
+    let old = old.and_then(|record| serde_json::to_value(record).ok());
+    let new = new.and_then(|record| serde_json::to_value(record).ok());
+    let changes = diff(old.as_ref(), new.as_ref(), SECRET_FIELDS);
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    record_changes(executor, user_id, entity, entity_id, action, changes).await
+}
+
+/// Record a change described field by field, for changes not made by saving a whole record
+pub async fn record_changes<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    user_id: Option<i64>,
+    entity: AuditEntity,
+    entity_id: i64,
+    action: AuditAction,
+    changes: Map<String, Value>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO audit_log (user_id, entity_type, entity_id, action, changes, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(entity.as_str())
+    .bind(entity_id)
+    .bind(action.as_str())
+    .bind(Value::Object(changes).to_string())
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -1,152 +1,1026 @@
-use crate::error::AppResult;
-use sqlx::{Pool, Sqlite};
+use crate::{
+    error::{AppError, AppResult},
+    models::duplicate::name_key,
+    utils::date::normalize_stored_date,
+};
+use sqlx::{Connection, Pool, Sqlite, SqliteConnection};
+use std::{future::Future, pin::Pin};
 
-/// Run all database migrations
+/// A numbered schema change, applied once and recorded in `schema_migrations`
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub steps: &'static [Step],
+    /// Turn foreign key enforcement off while the migration runs.
+    ///
+    /// Needed to rebuild a table that other tables reference, since dropping it
+    /// would otherwise cascade. Violations are still checked before committing.
+    pub disable_foreign_keys: bool,
+}
+
+/// One step of a migration
+pub enum Step {
+    /// Run a single SQL statement
+    Sql(&'static str),
+    /// Add a column unless the table already has it.
+    ///
+    /// Databases created before versioned migrations got some columns from
+    /// ad-hoc `ALTER TABLE` calls, so their presence cannot be assumed.
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+    /// Rewrite existing rows with Rust code, for changes SQL cannot express
+    Data(DataMigration),
+}
+
+/// A data migration, run on the migration's connection inside its transaction
+pub type DataMigration =
+    for<'c> fn(&'c mut SqliteConnection) -> Pin<Box<dyn Future<Output = Result<(), sqlx::Error>> + Send + 'c>>;
+
+/// All migrations in the order they must be applied.
+///
+/// Never edit or reorder a migration that has been released; add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_initial_schema",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS customers (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    full_name TEXT NOT NULL,
+                    company TEXT NOT NULL,
+                    email TEXT NOT NULL DEFAULT '',
+                    phone_number TEXT NOT NULL,
+                    sales_count INTEGER NOT NULL DEFAULT 0,
+                    city TEXT NOT NULL DEFAULT '',
+                    address TEXT NOT NULL DEFAULT '',
+                    notes TEXT NOT NULL,
+                    job_title TEXT NOT NULL DEFAULT '',
+                    coordinates TEXT NOT NULL DEFAULT ''
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS users (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    username TEXT NOT NULL UNIQUE,
+                    password_hash TEXT NOT NULL,
+                    full_name TEXT NOT NULL,
+                    role TEXT NOT NULL CHECK(role IN ('admin', 'user')),
+                    created_at TEXT DEFAULT (datetime('now'))
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS sessions (
+                    id TEXT PRIMARY KEY,
+                    user_id INTEGER NOT NULL,
+                    expires_at TEXT NOT NULL,
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS products (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL,
+                    description TEXT NOT NULL,
+                    price REAL NOT NULL,
+                    stock INTEGER NOT NULL,
+                    image_url TEXT,
+                    created_at TEXT DEFAULT (datetime('now'))
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS product_variants (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    variant_name TEXT NOT NULL DEFAULT '',
+                    product_id INTEGER NOT NULL,
+                    description TEXT NOT NULL DEFAULT '',
+                    price REAL NOT NULL,
+                    stock INTEGER NOT NULL,
+                    created_at TEXT DEFAULT (datetime('now')),
+                    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS transactions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    customer_id INTEGER NOT NULL,
+                    amount REAL NOT NULL,
+                    transaction_type TEXT NOT NULL,
+                    description TEXT,
+                    transaction_date TEXT NOT NULL DEFAULT '',
+                    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions(expires_at)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_users_username ON users(username)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_products_name ON products(name)"),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_transactions_customer_id ON transactions(customer_id)",
+            ),
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "add_customer_city",
+        disable_foreign_keys: false,
+        steps: &[Step::AddColumn {
+            table: "customers",
+            column: "city",
+            definition: "TEXT NOT NULL DEFAULT ''",
+        }],
+    },
+    Migration {
+        version: 3,
+        name: "add_customer_sales_count",
+        disable_foreign_keys: false,
+        steps: &[Step::AddColumn {
+            table: "customers",
+            column: "sales_count",
+            definition: "INTEGER NOT NULL DEFAULT 0",
+        }],
+    },
+    Migration {
+        version: 4,
+        name: "add_customer_coordinates",
+        disable_foreign_keys: false,
+        steps: &[Step::AddColumn {
+            table: "customers",
+            column: "coordinates",
+            definition: "TEXT NOT NULL DEFAULT ''",
+        }],
+    },
+    Migration {
+        version: 5,
+        name: "add_transaction_direction",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::AddColumn {
+                table: "transactions",
+                column: "direction",
+                definition: "TEXT NOT NULL DEFAULT 'credit' CHECK(direction IN ('debit', 'credit'))",
+            },
+            // Credit sales are the only existing entries that leave the customer owing
+            Step::Sql("UPDATE transactions SET direction = 'debit' WHERE transaction_type = 'Credit'"),
+        ],
+    },
+    Migration {
+        version: 6,
+        name: "create_cheques",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS cheques (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    transaction_id INTEGER NOT NULL UNIQUE,
+                    cheque_number TEXT NOT NULL,
+                    bank_name TEXT NOT NULL,
+                    due_date TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'received'
+                        CHECK(status IN ('received', 'deposited', 'cleared', 'bounced', 'returned')),
+                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS cheque_status_history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    cheque_id INTEGER NOT NULL,
+                    status TEXT NOT NULL,
+                    note TEXT NOT NULL DEFAULT '',
+                    changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    changed_by INTEGER,
+                    FOREIGN KEY (cheque_id) REFERENCES cheques(id) ON DELETE CASCADE,
+                    FOREIGN KEY (changed_by) REFERENCES users(id) ON DELETE SET NULL
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_cheques_due_date ON cheques(due_date)"),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_cheque_status_history_cheque_id ON cheque_status_history(cheque_id)",
+            ),
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "store_dates_as_iso",
+        disable_foreign_keys: false,
+        steps: &[Step::Data(|conn| Box::pin(store_dates_as_iso(conn)))],
+    },
+    Migration {
+        version: 8,
+        name: "store_money_as_integer_rials",
+        disable_foreign_keys: true,
+        // SQLite keeps REAL affinity on a column, so the tables are rebuilt with
+        // INTEGER columns and the toman amounts converted to rials on the way
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE transactions_new (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    customer_id INTEGER NOT NULL,
+                    amount INTEGER NOT NULL,
+                    transaction_type TEXT NOT NULL,
+                    description TEXT,
+                    transaction_date TEXT NOT NULL DEFAULT '',
+                    direction TEXT NOT NULL DEFAULT 'credit' CHECK(direction IN ('debit', 'credit')),
+                    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                INSERT INTO transactions_new
+                    (id, customer_id, amount, transaction_type, description, transaction_date, direction)
+                SELECT id, customer_id, CAST(ROUND(amount * 10) AS INTEGER), transaction_type,
+                       description, transaction_date, direction
+                FROM transactions
+                "#,
+            ),
+            Step::Sql("DROP TABLE transactions"),
+            Step::Sql("ALTER TABLE transactions_new RENAME TO transactions"),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_transactions_customer_id ON transactions(customer_id)",
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE products_new (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL,
+                    description TEXT NOT NULL,
+                    price INTEGER NOT NULL,
+                    stock INTEGER NOT NULL,
+                    image_url TEXT,
+                    created_at TEXT DEFAULT (datetime('now'))
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                INSERT INTO products_new (id, name, description, price, stock, image_url, created_at)
+                SELECT id, name, description, CAST(ROUND(price * 10) AS INTEGER), stock, image_url, created_at
+                FROM products
+                "#,
+            ),
+            Step::Sql("DROP TABLE products"),
+            Step::Sql("ALTER TABLE products_new RENAME TO products"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_products_name ON products(name)"),
+            Step::Sql(
+                r#"
+                CREATE TABLE product_variants_new (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    variant_name TEXT NOT NULL DEFAULT '',
+                    product_id INTEGER NOT NULL,
+                    description TEXT NOT NULL DEFAULT '',
+                    price INTEGER NOT NULL,
+                    stock INTEGER NOT NULL,
+                    created_at TEXT DEFAULT (datetime('now')),
+                    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                INSERT INTO product_variants_new
+                    (id, variant_name, product_id, description, price, stock, created_at)
+                SELECT id, variant_name, product_id, description, CAST(ROUND(price * 10) AS INTEGER),
+                       stock, created_at
+                FROM product_variants
+                "#,
+            ),
+            Step::Sql("DROP TABLE product_variants"),
+            Step::Sql("ALTER TABLE product_variants_new RENAME TO product_variants"),
+        ],
+    },
+    Migration {
+        version: 9,
+        name: "create_invoices",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS invoices (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    customer_id INTEGER NOT NULL,
+                    invoice_date TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'draft'
+                        CHECK(status IN ('draft', 'confirmed', 'cancelled')),
+                    notes TEXT NOT NULL DEFAULT '',
+                    transaction_id INTEGER,
+                    created_by INTEGER,
+                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+                    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE SET NULL,
+                    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS invoice_items (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    invoice_id INTEGER NOT NULL,
+                    product_id INTEGER,
+                    variant_id INTEGER,
+                    description TEXT NOT NULL,
+                    quantity INTEGER NOT NULL CHECK(quantity > 0),
+                    unit_price INTEGER NOT NULL CHECK(unit_price >= 0),
+                    discount INTEGER NOT NULL DEFAULT 0 CHECK(discount >= 0),
+                    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE,
+                    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE SET NULL,
+                    FOREIGN KEY (variant_id) REFERENCES product_variants(id) ON DELETE SET NULL
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_invoices_customer_id ON invoices(customer_id)"),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_invoice_items_invoice_id ON invoice_items(invoice_id)",
+            ),
+        ],
+    },
+    Migration {
+        version: 10,
+        name: "create_documents",
+        disable_foreign_keys: false,
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS documents (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL CHECK(kind IN ('invoice', 'receipt')),
+                source_id INTEGER NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(kind, source_id)
+            )
+            "#,
+        )],
+    },
+    Migration {
+        version: 11,
+        name: "add_session_csrf_token",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::AddColumn {
+                table: "sessions",
+                column: "csrf_token",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            // Sessions that were open before the upgrade get a token too
+            Step::Sql(
+                "UPDATE sessions SET csrf_token = lower(hex(randomblob(16))) WHERE csrf_token = ''",
+            ),
+        ],
+    },
+    Migration {
+        version: 12,
+        name: "create_login_throttling",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS login_failures (
+                    scope TEXT NOT NULL CHECK(scope IN ('username', 'ip')),
+                    key TEXT NOT NULL,
+                    failures INTEGER NOT NULL DEFAULT 0,
+                    last_failure_at TEXT NOT NULL,
+                    locked_until TEXT,
+                    PRIMARY KEY (scope, key)
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS lockout_events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    scope TEXT NOT NULL CHECK(scope IN ('username', 'ip')),
+                    key TEXT NOT NULL,
+                    event TEXT NOT NULL CHECK(event IN ('locked', 'unlocked')),
+                    locked_until TEXT,
+                    user_id INTEGER,
+                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+                )
+                "#,
+            ),
+        ],
+    },
+    Migration {
+        version: 13,
+        name: "add_session_remember_me",
+        disable_foreign_keys: false,
+        steps: &[Step::AddColumn {
+            table: "sessions",
+            column: "remember_me",
+            definition: "INTEGER NOT NULL DEFAULT 0",
+        }],
+    },
+    Migration {
+        version: 14,
+        name: "add_session_details",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::AddColumn {
+                table: "sessions",
+                column: "public_id",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            Step::AddColumn {
+                table: "sessions",
+                column: "ip_address",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            Step::AddColumn {
+                table: "sessions",
+                column: "user_agent",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            Step::AddColumn {
+                table: "sessions",
+                column: "created_at",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            Step::AddColumn {
+                table: "sessions",
+                column: "last_seen_at",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            // Sessions open before the upgrade are dated to it
+            Step::Sql(
+                r#"
+                UPDATE sessions SET
+                    public_id = lower(hex(randomblob(8))),
+                    created_at = strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'),
+                    last_seen_at = strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now')
+                WHERE public_id = ''
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id)"),
+        ],
+    },
+    Migration {
+        version: 15,
+        name: "add_user_must_change_password",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::AddColumn {
+                table: "users",
+                column: "must_change_password",
+                definition: "INTEGER NOT NULL DEFAULT 0",
+            },
+            Step::Data(|conn| Box::pin(flag_default_admin_password(conn))),
+        ],
+    },
+    Migration {
+        version: 16,
+        name: "add_user_is_active",
+        disable_foreign_keys: false,
+        steps: &[Step::AddColumn {
+            table: "users",
+            column: "is_active",
+            definition: "INTEGER NOT NULL DEFAULT 1",
+        }],
+    },
+    Migration {
+        version: 17,
+        name: "create_roles",
+        disable_foreign_keys: true,
+        // `users.role` was limited to admin and user by a CHECK constraint, which
+        // SQLite cannot drop, so the table is rebuilt without it
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS roles (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    display_name TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS role_permissions (
+                    role_id INTEGER NOT NULL,
+                    permission TEXT NOT NULL,
+                    PRIMARY KEY (role_id, permission),
+                    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            // The admin role is given every permission in code, so it needs no rows
+            Step::Sql(
+                "INSERT OR IGNORE INTO roles (name, display_name) VALUES ('admin', 'مدیر'), ('user', 'کاربر')",
+            ),
+            // Regular users keep day-to-day work; deleting records and exporting become opt-in
+            Step::Sql(
+                r#"
+                INSERT OR IGNORE INTO role_permissions (role_id, permission)
+                SELECT roles.id, granted.permission
+                FROM roles,
+                    (SELECT 'customers.edit' AS permission
+                     UNION ALL SELECT 'transactions.edit'
+                     UNION ALL SELECT 'cheques.manage'
+                     UNION ALL SELECT 'invoices.manage'
+                     UNION ALL SELECT 'catalog.manage') AS granted
+                WHERE roles.name = 'user'
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE users_new (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    username TEXT NOT NULL UNIQUE,
+                    password_hash TEXT NOT NULL,
+                    full_name TEXT NOT NULL,
+                    role TEXT NOT NULL,
+                    created_at TEXT DEFAULT (datetime('now')),
+                    must_change_password INTEGER NOT NULL DEFAULT 0,
+                    is_active INTEGER NOT NULL DEFAULT 1
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                INSERT INTO users_new
+                    (id, username, password_hash, full_name, role, created_at, must_change_password, is_active)
+                SELECT id, username, password_hash, full_name, role, created_at, must_change_password, is_active
+                FROM users
+                "#,
+            ),
+            Step::Sql("DROP TABLE users"),
+            Step::Sql("ALTER TABLE users_new RENAME TO users"),
+        ],
+    },
+    Migration {
+        version: 18,
+        name: "create_audit_log",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS audit_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id INTEGER,
+                    entity_type TEXT NOT NULL,
+                    entity_id INTEGER NOT NULL,
+                    action TEXT NOT NULL CHECK(action IN ('create', 'update', 'delete')),
+                    changes TEXT NOT NULL DEFAULT '{}',
+                    created_at TEXT NOT NULL,
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+                )
+                "#,
+            ),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(entity_type, entity_id)",
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_audit_log_user_id ON audit_log(user_id)"),
+        ],
+    },
+    Migration {
+        version: 19,
+        name: "create_api_tokens",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS api_tokens (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id INTEGER NOT NULL,
+                    name TEXT NOT NULL,
+                    token_prefix TEXT NOT NULL,
+                    token_hash TEXT NOT NULL UNIQUE,
+                    scopes TEXT NOT NULL DEFAULT '',
+                    created_at TEXT NOT NULL,
+                    expires_at TEXT NOT NULL,
+                    last_used_at TEXT,
+                    revoked_at TEXT,
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens(user_id)"),
+        ],
+    },
+    Migration {
+        version: 20,
+        name: "create_webhooks",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS webhook_endpoints (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    url TEXT NOT NULL,
+                    description TEXT NOT NULL DEFAULT '',
+                    secret TEXT NOT NULL,
+                    events TEXT NOT NULL DEFAULT '',
+                    is_active BOOLEAN NOT NULL DEFAULT 1,
+                    created_at TEXT NOT NULL
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    endpoint_id INTEGER NOT NULL,
+                    event TEXT NOT NULL,
+                    payload TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'pending' CHECK(status IN ('pending', 'delivered', 'failed')),
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    next_attempt_at TEXT,
+                    last_attempt_at TEXT,
+                    response_status INTEGER,
+                    response_body TEXT,
+                    created_at TEXT NOT NULL,
+                    FOREIGN KEY (endpoint_id) REFERENCES webhook_endpoints(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at)",
+            ),
+            Step::Sql(
+                "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_endpoint_id ON webhook_deliveries(endpoint_id)",
+            ),
+        ],
+    },
+    Migration {
+        version: 21,
+        name: "create_customer_tags_and_segments",
+        disable_foreign_keys: false,
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS tags (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                    created_at TEXT NOT NULL
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS customer_tags (
+                    customer_id INTEGER NOT NULL,
+                    tag_id INTEGER NOT NULL,
+                    created_at TEXT NOT NULL,
+                    PRIMARY KEY (customer_id, tag_id),
+                    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+                    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_customer_tags_tag_id ON customer_tags(tag_id)"),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS segments (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    description TEXT NOT NULL DEFAULT '',
+                    tags TEXT NOT NULL DEFAULT '',
+                    tag_mode TEXT NOT NULL DEFAULT '',
+                    city TEXT NOT NULL DEFAULT '',
+                    min_sales TEXT NOT NULL DEFAULT '',
+                    max_sales TEXT NOT NULL DEFAULT '',
+                    min_balance TEXT NOT NULL DEFAULT '',
+                    max_balance TEXT NOT NULL DEFAULT '',
+                    created_by INTEGER,
+                    created_at TEXT NOT NULL,
+                    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+                )
+                "#,
+            ),
+        ],
+    },
+    Migration {
+        version: 22,
+        name: "add_customer_duplicate_keys",
+        disable_foreign_keys: false,
+        // Duplicate checks look customers up by phone, email and folded name
+        // rather than reading every customer
+        steps: &[
+            Step::AddColumn {
+                table: "customers",
+                column: "name_key",
+                definition: "TEXT NOT NULL DEFAULT ''",
+            },
+            Step::Data(|conn| Box::pin(fill_customer_name_keys(conn))),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_customers_phone_number ON customers(phone_number)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_customers_email ON customers(email)"),
+        ],
+    },
+];
+
+/// Applied/pending state of a migration, as reported by `migrate status`
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<String>,
+}
+
+/// Apply every pending migration in order
 pub async fn run_migrations(pool: &Pool<Sqlite>) -> AppResult<()> {
-    // Customers table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS customers (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            full_name TEXT NOT NULL,
-            company TEXT NOT NULL,
-            email TEXT NOT NULL DEFAULT '',
-            phone_number TEXT NOT NULL,
-            sales_count INTEGER NOT NULL DEFAULT 0,
-            city TEXT NOT NULL DEFAULT '',
-            address TEXT NOT NULL DEFAULT '',
-            notes TEXT NOT NULL,
-            job_title TEXT NOT NULL DEFAULT '',
-            coordinates TEXT NOT NULL DEFAULT ''
-        )
-        "#,
+    ensure_migrations_table(pool).await?;
+
+    let applied = applied_versions(pool).await?;
+    let mut applied_count = 0;
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+    {
+        apply_migration(pool, migration).await?;
+        println!("🔧 Applied migration {}", migration_label(migration));
+        applied_count += 1;
+    }
+
+    if applied_count == 0 {
+        println!("✅ Database schema is up to date");
+    } else {
+        println!(
+            "✅ Database migrations completed successfully ({} applied)",
+            applied_count
+        );
+    }
+    Ok(())
+}
+
+/// Report every known migration and whether it has been applied.
+///
+/// Versions recorded in the database but unknown to this build are listed too,
+/// with the name they were applied under.
+pub async fn migration_status(pool: &Pool<Sqlite>) -> AppResult<Vec<MigrationStatus>> {
+    ensure_migrations_table(pool).await?;
+
+    let applied = sqlx::query_as::<_, (i64, String, String)>(
+        "SELECT version, name, applied_at FROM schema_migrations ORDER BY version",
     )
-    .execute(pool)
+    .fetch_all(pool)
     .await?;
 
-    // Users table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            username TEXT NOT NULL UNIQUE,
-            password_hash TEXT NOT NULL,
-            full_name TEXT NOT NULL,
-            role TEXT NOT NULL CHECK(role IN ('admin', 'user')),
-            created_at TEXT DEFAULT (datetime('now'))
-        )
-        "#,
+    let mut statuses: Vec<MigrationStatus> = MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            name: migration.name.to_string(),
+            applied_at: applied
+                .iter()
+                .find(|(version, _, _)| *version == migration.version)
+                .map(|(_, _, applied_at)| applied_at.clone()),
+        })
+        .collect();
+
+    for (version, name, applied_at) in applied {
+        if !MIGRATIONS.iter().any(|migration| migration.version == version) {
+            statuses.push(MigrationStatus {
+                version,
+                name,
+                applied_at: Some(applied_at),
+            });
+        }
+    }
+
+    statuses.sort_by_key(|status| status.version);
+    Ok(statuses)
+}
+
+/// List the columns of every table, so drifted databases can be compared
+pub async fn schema_columns(pool: &Pool<Sqlite>) -> AppResult<Vec<(String, Vec<String>)>> {
+    let tables = sqlx::query_scalar::<_, String>(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
     )
-    .execute(pool)
+    .fetch_all(pool)
     .await?;
 
-    // Sessions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS sessions (
-            id TEXT PRIMARY KEY,
-            user_id INTEGER NOT NULL,
-            expires_at TEXT NOT NULL,
-            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+    let mut schema = Vec::with_capacity(tables.len());
+    for table in tables {
+        let columns = sqlx::query_scalar::<_, String>(
+            "SELECT name FROM pragma_table_info(?) ORDER BY cid",
         )
-        "#,
-    )
-    .execute(pool)
-    .await?;
+        .bind(&table)
+        .fetch_all(pool)
+        .await?;
+        schema.push((table, columns));
+    }
 
-    // Products
+    Ok(schema)
+}
+
+async fn ensure_migrations_table(pool: &Pool<Sqlite>) -> AppResult<()> {
     sqlx::query(
         r#"
-        CREATE TABLE IF NOT EXISTS products (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
             name TEXT NOT NULL,
-            description TEXT NOT NULL,
-            price REAL NOT NULL,
-            stock INTEGER NOT NULL,
-            image_url TEXT,
-            created_at TEXT DEFAULT (datetime('now'))
+            applied_at TEXT NOT NULL DEFAULT (datetime('now'))
         )
         "#,
     )
     .execute(pool)
     .await?;
 
-    // Product variants
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS product_variants (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            variant_name TEXT NOT NULL DEFAULT '',
-            product_id INTEGER NOT NULL,
-            description TEXT NOT NULL DEFAULT '',
-            price REAL NOT NULL,
-            stock INTEGER NOT NULL,
-            created_at TEXT DEFAULT (datetime('now')),
-            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    Ok(())
+}
 
-    // Transactions
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS transactions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            customer_id INTEGER NOT NULL,
-            amount REAL NOT NULL,
-            transaction_type TEXT NOT NULL,
-            description TEXT,
-            transaction_date TEXT NOT NULL DEFAULT '',
-            FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
-        )
-        "#,
+async fn applied_versions(pool: &Pool<Sqlite>) -> AppResult<Vec<i64>> {
+    Ok(
+        sqlx::query_scalar::<_, i64>("SELECT version FROM schema_migrations")
+            .fetch_all(pool)
+            .await?,
     )
-    .execute(pool)
-    .await?;
+}
 
-    // Create indexes for better performance
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id)")
-        .execute(pool)
-        .await?;
+/// Run all steps of a migration and record it, in a single transaction
+async fn apply_migration(pool: &Pool<Sqlite>, migration: &Migration) -> AppResult<()> {
+    let fail = |e: sqlx::Error| {
+        AppError::Internal(format!(
+            "Migration {} failed: {}",
+            migration_label(migration),
+            e
+        ))
+    };
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions(expires_at)")
-        .execute(pool)
-        .await?;
+    let mut conn = pool.acquire().await.map_err(fail)?;
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_username ON users(username)")
-        .execute(pool)
-        .await?;
+    // The pragma has no effect inside a transaction, so it is set around it
+    if migration.disable_foreign_keys {
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await
+            .map_err(fail)?;
+    }
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_products_name ON products(name)")
-        .execute(pool)
+    let result = apply_steps(&mut conn, migration).await;
+
+    if migration.disable_foreign_keys {
+        sqlx::query("PRAGMA foreign_keys = ON")
+            .execute(&mut *conn)
+            .await
+            .map_err(fail)?;
+    }
+
+    result.map_err(fail)
+}
+
+async fn apply_steps(conn: &mut SqliteConnection, migration: &Migration) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    for step in migration.steps {
+        apply_step(&mut tx, step).await?;
+    }
+
+    if migration.disable_foreign_keys {
+        let violations: Vec<(String, Option<i64>)> =
+            sqlx::query_as("SELECT \"table\", rowid FROM pragma_foreign_key_check")
+                .fetch_all(&mut *tx)
+                .await?;
+        if let Some((table, rowid)) = violations.first() {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} foreign key violation(s), first in {} row {:?}",
+                violations.len(),
+                table,
+                rowid
+            )));
+        }
+    }
+
+    sqlx::query("INSERT INTO schema_migrations (version, name) VALUES (?, ?)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .execute(&mut *tx)
         .await?;
 
-    sqlx::query(
-        "CREATE INDEX IF NOT EXISTS idx_transactions_customer_id ON transactions(customer_id)",
-    )
-    .execute(pool)
-    .await?;
+    tx.commit().await
+}
 
-    // Previous migrations
-    let _ = sqlx::query("ALTER TABLE customers ADD COLUMN city TEXT NOT NULL DEFAULT ''")
-        .execute(pool)
-        .await;
+async fn apply_step(conn: &mut SqliteConnection, step: &Step) -> Result<(), sqlx::Error> {
+    match step {
+        Step::Sql(sql) => {
+            sqlx::query(sql).execute(&mut *conn).await?;
+        }
+        Step::AddColumn {
+            table,
+            column,
+            definition,
+        } => {
+            let exists: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?",
+            )
+            .bind(table)
+            .bind(column)
+            .fetch_one(&mut *conn)
+            .await?;
 
-    let _ = sqlx::query("ALTER TABLE customers ADD COLUMN sales_count INTEGER NOT NULL DEFAULT 0")
-        .execute(pool)
-        .await;
+            if exists == 0 {
+                sqlx::query(&format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, column, definition
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+        Step::Data(migrate) => migrate(conn).await?,
+    }
+    Ok(())
+}
 
-    // New
-    let _ = sqlx::query("ALTER TABLE customers ADD COLUMN coordinates TEXT NOT NULL DEFAULT ''")
-        .execute(pool)
-        .await;
+/// Rewrite transaction and cheque dates, entered as Shamsi text, as ISO dates.
+///
+/// Values that cannot be read as a date are left untouched and reported.
+async fn store_dates_as_iso(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    for (table, column) in [("transactions", "transaction_date"), ("cheques", "due_date")] {
+        let rows = sqlx::query_as::<_, (i64, String)>(&format!("SELECT id, {} FROM {}", column, table))
+            .fetch_all(&mut *conn)
+            .await?;
+
+        for (id, value) in rows {
+            match normalize_stored_date(&value) {
+                Some(iso) if iso != value => {
+                    sqlx::query(&format!("UPDATE {} SET {} = ? WHERE id = ?", table, column))
+                        .bind(iso)
+                        .bind(id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Some(_) => {}
+                None => println!(
+                    "⚠️  Could not read {}.{} of row {}: {:?}",
+                    table, column, id, value
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Store each customer's name folded for duplicate checks
+async fn fill_customer_name_keys(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query_as::<_, (i64, String)>("SELECT id, full_name FROM customers")
+        .fetch_all(&mut *conn)
+        .await?;
 
-    println!("✅ Database migrations completed successfully");
+    for (id, full_name) in rows {
+        sqlx::query("UPDATE customers SET name_key = ? WHERE id = ?")
+            .bind(name_key(&full_name))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
     Ok(())
 }
 
+/// Make the seeded admin change its password if it still has the default one
+async fn flag_default_admin_password(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    use crate::utils::password::verify_password;
+
+    let admin = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, password_hash FROM users WHERE username = 'admin'",
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((id, password_hash)) = admin {
+        if verify_password(DEFAULT_ADMIN_PASSWORD, &password_hash).unwrap_or(false) {
+            sqlx::query("UPDATE users SET must_change_password = 1 WHERE id = ?")
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            println!("⚠️  The admin user still has the default password; it must be changed at next login");
+        }
+    }
+    Ok(())
+}
+
+fn migration_label(migration: &Migration) -> String {
+    format!("{:04}_{}", migration.version, migration.name)
+}
+
+/// Password of the admin created on first start, which has to be changed at first login
+const DEFAULT_ADMIN_PASSWORD: &str = "admin123";
+
 /// Create default admin user if no users exist
 pub async fn create_default_admin(pool: &Pool<Sqlite>) -> AppResult<()> {
     use crate::utils::password::hash_password;
@@ -156,11 +1030,11 @@ pub async fn create_default_admin(pool: &Pool<Sqlite>) -> AppResult<()> {
         .await?;
 
     if user_count.0 == 0 {
-        let password_hash = hash_password("admin123")?;
+        let password_hash = hash_password(DEFAULT_ADMIN_PASSWORD)?;
 
         sqlx::query(
-            "INSERT INTO users (username, password_hash, full_name, role)
-             VALUES (?, ?, ?, ?)",
+            "INSERT INTO users (username, password_hash, full_name, role, must_change_password)
+             VALUES (?, ?, ?, ?, 1)",
         )
         .bind("admin")
         .bind(password_hash)
@@ -170,7 +1044,7 @@ pub async fn create_default_admin(pool: &Pool<Sqlite>) -> AppResult<()> {
         .await?;
 
         println!("👤 Created default admin user (username: admin, password: admin123)");
-        println!("⚠️  IMPORTANT: Change the default password after first login!");
+        println!("⚠️  IMPORTANT: You will be asked to change the default password at first login");
     }
 
     Ok(())
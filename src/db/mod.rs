@@ -1,2 +1,5 @@
+pub mod audit;
 pub mod connection;
-pub mod migrations;
\ No newline at end of file
+pub mod migrations;
+pub mod sessions;
+pub mod webhooks;
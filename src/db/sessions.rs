@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use sqlx::{Pool, Sqlite};
+
+/// Delete every session past its expiry, returning how many were removed
+pub async fn purge_expired_sessions(pool: &Pool<Sqlite>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE expires_at <= ?")
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Sign a user out everywhere, optionally keeping one session, returning how many were removed
+pub async fn delete_user_sessions(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    keep_session_id: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE user_id = ? AND id != ?")
+        .bind(user_id)
+        .bind(keep_session_id.unwrap_or_default())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Purge expired sessions in the background every `interval`
+pub fn spawn_session_purger(pool: Pool<Sqlite>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match purge_expired_sessions(&pool).await {
+                Ok(0) => {}
+                Ok(count) => println!("🧹 Purged {} expired sessions", count),
+                Err(e) => eprintln!("⚠️  Warning: Failed to purge expired sessions: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role)
+             VALUES ('ali', '', 'علی', 'user'), ('sara', '', 'سارا', 'user')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn add_session(
+        pool: &Pool<Sqlite>,
+        id: &str,
+        user_id: i64,
+        expires_in: chrono::Duration,
+    ) {
+        sqlx::query("INSERT INTO sessions (id, user_id, expires_at) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(user_id)
+            .bind((Utc::now() + expires_in).to_rfc3339())
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn session_ids(pool: &Pool<Sqlite>) -> Vec<String> {
+        sqlx::query_scalar("SELECT id FROM sessions ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_purge_deletes_only_expired_sessions() {
+        let pool = test_pool().await;
+        add_session(&pool, "expired", 1, chrono::Duration::hours(-1)).await;
+        add_session(&pool, "just-expired", 2, chrono::Duration::seconds(-1)).await;
+        add_session(&pool, "open", 1, chrono::Duration::hours(1)).await;
+        add_session(&pool, "remembered", 2, chrono::Duration::days(30)).await;
+
+        assert_eq!(purge_expired_sessions(&pool).await.unwrap(), 2);
+        assert_eq!(session_ids(&pool).await, vec!["open", "remembered"]);
+        assert_eq!(purge_expired_sessions(&pool).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_delete_user_sessions() {
+        let pool = test_pool().await;
+        add_session(&pool, "ali-phone", 1, chrono::Duration::hours(1)).await;
+        add_session(&pool, "ali-laptop", 1, chrono::Duration::hours(1)).await;
+        add_session(&pool, "ali-tablet", 1, chrono::Duration::hours(1)).await;
+        add_session(&pool, "sara-phone", 2, chrono::Duration::hours(1)).await;
+
+        assert_eq!(
+            delete_user_sessions(&pool, 1, Some("ali-phone"))
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(session_ids(&pool).await, vec!["ali-phone", "sara-phone"]);
+
+        assert_eq!(delete_user_sessions(&pool, 1, None).await.unwrap(), 1);
+        assert_eq!(session_ids(&pool).await, vec!["sara-phone"]);
+    }
+}
@@ -0,0 +1,570 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use sqlx::{Executor, FromRow, Pool, Sqlite};
+
+use crate::models::{
+    webhook::{
+        retry_delay, sign_payload, WebhookPayload, DELIVERY_HEADER, EVENT_HEADER, MAX_ATTEMPTS,
+        SIGNATURE_HEADER, TIMESTAMP_HEADER,
+    },
+    DeliveryStatus, Product, StockSettings, WebhookEvent,
+};
+
+/// Deliveries attempted per run of the worker; the rest wait for the next run
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+/// Deliveries to one endpoint attempted per run, so an endpoint with a backlog cannot
+/// take the whole batch
+const DELIVERIES_PER_ENDPOINT: i64 = 10;
+
+/// Characters of an endpoint's response kept in the delivery log
+const RESPONSE_BODY_LIMIT: usize = 1000;
+
+/// Queue an event for every active endpoint subscribed to it.
+///
+/// Call it in the same transaction as the change it reports, so a change that is rolled
+/// back is never announced; the worker sends the deliveries once they are committed.
+pub async fn queue_webhook<'e, T: Serialize>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    event: WebhookEvent,
+    data: &T,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let payload = serde_json::to_string(&WebhookPayload {
+        id: uuid::Uuid::new_v4().to_string(),
+        event: event.as_str(),
+        created_at: &now,
+        data,
+    })
+    .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (endpoint_id, event, payload, next_attempt_at, created_at)
+         SELECT id, ?, ?, ?, ? FROM webhook_endpoints
+         WHERE is_active = 1 AND instr(' ' || events || ' ', ?) > 0",
+    )
+    .bind(event.as_str())
+    .bind(&payload)
+    .bind(&now)
+    .bind(&now)
+    .bind(format!(" {} ", event.as_str()))
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Queue `product.stock_low` if a change of stock from `stock_before` just took the product low
+pub async fn queue_stock_low<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    stock: StockSettings,
+    stock_before: i32,
+    product: &Product,
+) -> Result<(), sqlx::Error> {
+    if !stock.fell_to_low_stock(stock_before, product.stock) {
+        return Ok(());
+    }
+    queue_webhook(executor, WebhookEvent::ProductStockLow, product).await
+}
+
+/// Queue a fresh copy of a delivery, returning its id, or `None` if there was no such delivery.
+///
+/// The payload is sent unchanged, so receivers can tell it is the same event by its `id`.
+pub async fn redeliver_webhook(
+    pool: &Pool<Sqlite>,
+    delivery_id: i64,
+) -> Result<Option<i64>, sqlx::Error> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let result = sqlx::query(
+        "INSERT INTO webhook_deliveries (endpoint_id, event, payload, next_attempt_at, created_at)
+         SELECT endpoint_id, event, payload, ?, ? FROM webhook_deliveries WHERE id = ?",
+    )
+    .bind(&now)
+    .bind(&now)
+    .bind(delivery_id)
+    .execute(pool)
+    .await?;
+
+    Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+}
+
+/// HTTP client the worker sends deliveries with
+pub fn webhook_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        // A redirect could point the signed payload at an internal address
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(concat!("Rumiland-CRM/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to build the webhook HTTP client")
+}
+
+/// A pending delivery with what is needed to send it. Not `Debug`, so the endpoint's
+/// secret cannot end up in a log.
+#[derive(FromRow)]
+struct DueDelivery {
+    id: i64,
+    endpoint_id: i64,
+    event: String,
+    payload: String,
+    attempts: i64,
+    url: String,
+    secret: String,
+}
+
+/// Attempt every pending delivery that is due, returning how many were attempted.
+///
+/// A delivery succeeds when its endpoint answers with a 2xx status. Otherwise it is tried
+/// again after a delay that doubles each time, until `MAX_ATTEMPTS` have failed. Each
+/// endpoint's deliveries are sent one after another in the order they were queued, while
+/// different endpoints are sent to at the same time, so a slow endpoint does not hold up
+/// the others. A delivery that is retried still arrives after later ones.
+pub async fn deliver_due_webhooks(
+    pool: &Pool<Sqlite>,
+    client: &reqwest::Client,
+) -> Result<usize, sqlx::Error> {
+    let due = sqlx::query_as::<_, DueDelivery>(
+        "SELECT id, endpoint_id, event, payload, attempts, url, secret FROM (
+             SELECT d.id, d.endpoint_id, d.event, d.payload, d.attempts, e.url, e.secret,
+                    ROW_NUMBER() OVER (PARTITION BY d.endpoint_id ORDER BY d.id) AS endpoint_row
+             FROM webhook_deliveries d
+             JOIN webhook_endpoints e ON e.id = d.endpoint_id
+             WHERE d.status = 'pending' AND e.is_active = 1
+               AND (d.next_attempt_at IS NULL OR d.next_attempt_at <= ?)
+         )
+         WHERE endpoint_row <= ?
+         ORDER BY id
+         LIMIT ?",
+    )
+    .bind(chrono::Utc::now().to_rfc3339())
+    .bind(DELIVERIES_PER_ENDPOINT)
+    .bind(DELIVERY_BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+    let attempted = due.len();
+
+    let mut by_endpoint: BTreeMap<i64, Vec<DueDelivery>> = BTreeMap::new();
+    for delivery in due {
+        by_endpoint.entry(delivery.endpoint_id).or_default().push(delivery);
+    }
+
+    let mut sending = tokio::task::JoinSet::new();
+    for deliveries in by_endpoint.into_values() {
+        let client = client.clone();
+        sending.spawn(async move {
+            let mut sent = Vec::with_capacity(deliveries.len());
+            for delivery in deliveries {
+                let outcome = send_delivery(&client, &delivery).await;
+                sent.push((delivery, outcome));
+            }
+            sent
+        });
+    }
+
+    while let Some(sent) = sending.join_next().await {
+        // A send that panicked leaves its endpoint's deliveries pending for the next run
+        let Ok(sent) = sent else {
+            continue;
+        };
+
+        for (delivery, (response_status, response_body, delivered)) in sent {
+            let attempts = delivery.attempts + 1;
+            let now = chrono::Utc::now();
+            let (status, next_attempt_at) = if delivered {
+                (DeliveryStatus::Delivered, None)
+            } else if attempts >= MAX_ATTEMPTS {
+                (DeliveryStatus::Failed, None)
+            } else {
+                (
+                    DeliveryStatus::Pending,
+                    Some((now + retry_delay(attempts)).to_rfc3339()),
+                )
+            };
+
+            sqlx::query(
+                "UPDATE webhook_deliveries
+                 SET status = ?, attempts = ?, next_attempt_at = ?, last_attempt_at = ?,
+                     response_status = ?, response_body = ?
+                 WHERE id = ?",
+            )
+            .bind(status.as_str())
+            .bind(attempts)
+            .bind(next_attempt_at)
+            .bind(now.to_rfc3339())
+            .bind(response_status)
+            .bind(response_body)
+            .bind(delivery.id)
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(attempted)
+}
+
+/// Post one delivery, returning the response status, the start of the response (or the
+/// error) and whether it was delivered
+async fn send_delivery(
+    client: &reqwest::Client,
+    delivery: &DueDelivery,
+) -> (Option<i64>, String, bool) {
+    let timestamp = chrono::Utc::now().timestamp();
+    let result = client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            sign_payload(&delivery.secret, timestamp, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            let body = read_response_start(response).await;
+            (Some(status.as_u16() as i64), body, status.is_success())
+        }
+        Err(e) => (
+            None,
+            e.to_string().chars().take(RESPONSE_BODY_LIMIT).collect(),
+            false,
+        ),
+    }
+}
+
+/// The first `RESPONSE_BODY_LIMIT` characters of a response, without downloading the rest
+async fn read_response_start(mut response: reqwest::Response) -> String {
+    // No character takes more than 4 bytes
+    let max_bytes = RESPONSE_BODY_LIMIT * 4;
+    let mut bytes = Vec::new();
+    while bytes.len() < max_bytes {
+        match response.chunk().await {
+            Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    bytes.truncate(max_bytes);
+    String::from_utf8_lossy(&bytes)
+        .chars()
+        .take(RESPONSE_BODY_LIMIT)
+        .collect()
+}
+
+/// Send due webhook deliveries in the background every `interval`
+pub fn spawn_webhook_worker(pool: Pool<Sqlite>, interval: Duration) {
+    tokio::spawn(async move {
+        let client = webhook_client();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = deliver_due_webhooks(&pool, &client).await {
+                eprintln!("⚠️  Warning: Failed to send webhooks: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        extract::State, http::HeaderMap, http::StatusCode, response::Redirect, routing::post,
+        Router,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::{db::migrations::run_migrations, models::Money};
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// Start an HTTP server on a free local port that answers every POST with `status`
+    async fn start_stub(status: StatusCode) -> (String, Received) {
+        async fn receive(
+            State((status, received)): State<(StatusCode, Received)>,
+            headers: HeaderMap,
+            body: String,
+        ) -> StatusCode {
+            received.lock().unwrap().push((headers, body));
+            status
+        }
+
+        let received = Received::default();
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state((status, received.clone()));
+        (serve(app).await, received)
+    }
+
+    /// Serve `app` on a free local port, returning the URL of its `/hook`
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    async fn add_endpoint(pool: &Pool<Sqlite>, url: &str, events: &str) -> i64 {
+        sqlx::query(
+            "INSERT INTO webhook_endpoints (url, secret, events, created_at)
+             VALUES (?, 'whsec_test', ?, ?)",
+        )
+        .bind(url)
+        .bind(events)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_rowid()
+    }
+
+    async fn delivery_state(pool: &Pool<Sqlite>, id: i64) -> (String, i64, Option<i64>) {
+        sqlx::query_as("SELECT status, attempts, response_status FROM webhook_deliveries WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_payload_to_subscribers() {
+        let pool = test_pool().await;
+        let (url, received) = start_stub(StatusCode::OK).await;
+        add_endpoint(&pool, &url, "customer.created product.stock_low").await;
+        add_endpoint(&pool, &url, "transaction.created").await;
+
+        let customer = serde_json::json!({ "id": 7, "name": "مریم" });
+        queue_webhook(&pool, WebhookEvent::CustomerCreated, &customer).await.unwrap();
+
+        let client = webhook_client();
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 1);
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        assert_eq!(headers[EVENT_HEADER], "customer.created");
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER],
+            sign_payload("whsec_test", timestamp, body).as_str()
+        );
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["event"], "customer.created");
+        assert_eq!(payload["data"], customer);
+
+        let delivery_id: i64 = headers[DELIVERY_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            delivery_state(&pool, delivery_id).await,
+            ("delivered".to_string(), 1, Some(200))
+        );
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_waits_before_retry() {
+        let pool = test_pool().await;
+        let (url, received) = start_stub(StatusCode::INTERNAL_SERVER_ERROR).await;
+        add_endpoint(&pool, &url, "transaction.created").await;
+
+        queue_webhook(&pool, WebhookEvent::TransactionCreated, &serde_json::json!({ "id": 1 }))
+            .await
+            .unwrap();
+
+        let client = webhook_client();
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 1);
+        assert_eq!(received.lock().unwrap().len(), 1);
+        assert_eq!(
+            delivery_state(&pool, 1).await,
+            ("pending".to_string(), 1, Some(500))
+        );
+
+        // The retry is not due for another minute
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 0);
+
+        sqlx::query("UPDATE webhook_deliveries SET attempts = ?, next_attempt_at = NULL")
+            .bind(MAX_ATTEMPTS - 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 1);
+        assert_eq!(
+            delivery_state(&pool, 1).await,
+            ("failed".to_string(), MAX_ATTEMPTS, Some(500))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redeliver_sends_same_payload_again() {
+        let pool = test_pool().await;
+        let (url, received) = start_stub(StatusCode::NO_CONTENT).await;
+        add_endpoint(&pool, &url, "customer.deleted").await;
+
+        queue_webhook(&pool, WebhookEvent::CustomerDeleted, &serde_json::json!({ "id": 3 }))
+            .await
+            .unwrap();
+        let client = webhook_client();
+        deliver_due_webhooks(&pool, &client).await.unwrap();
+
+        let copy_id = redeliver_webhook(&pool, 1).await.unwrap().unwrap();
+        assert_ne!(copy_id, 1);
+        assert_eq!(redeliver_webhook(&pool, 999).await.unwrap(), None);
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 1);
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].1, received[1].1);
+        assert_ne!(received[0].0[DELIVERY_HEADER], received[1].0[DELIVERY_HEADER]);
+    }
+
+    #[tokio::test]
+    async fn test_only_start_of_response_is_kept() {
+        let pool = test_pool().await;
+        let app = Router::new().route("/hook", post(|| async { "پاسخ ".repeat(100_000) }));
+        add_endpoint(&pool, &serve(app).await, "customer.created").await;
+
+        queue_webhook(&pool, WebhookEvent::CustomerCreated, &serde_json::json!({ "id": 1 }))
+            .await
+            .unwrap();
+        deliver_due_webhooks(&pool, &webhook_client()).await.unwrap();
+
+        let body: String = sqlx::query_scalar("SELECT response_body FROM webhook_deliveries")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(body.chars().count(), RESPONSE_BODY_LIMIT);
+        assert!(body.starts_with("پاسخ پاسخ"));
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_not_followed() {
+        let pool = test_pool().await;
+        let (target, received) = start_stub(StatusCode::OK).await;
+        let app = Router::new().route(
+            "/hook",
+            post(move || async move { Redirect::temporary(&target) }),
+        );
+        add_endpoint(&pool, &serve(app).await, "customer.created").await;
+
+        queue_webhook(&pool, WebhookEvent::CustomerCreated, &serde_json::json!({ "id": 1 }))
+            .await
+            .unwrap();
+        deliver_due_webhooks(&pool, &webhook_client()).await.unwrap();
+
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(
+            delivery_state(&pool, 1).await,
+            ("pending".to_string(), 1, Some(307))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_backlog_leaves_room_for_others() {
+        let pool = test_pool().await;
+        let (busy_url, busy) = start_stub(StatusCode::OK).await;
+        let (quiet_url, quiet) = start_stub(StatusCode::OK).await;
+        add_endpoint(&pool, &busy_url, "customer.created").await;
+        add_endpoint(&pool, &quiet_url, "customer.deleted").await;
+
+        for id in 0..12 {
+            queue_webhook(&pool, WebhookEvent::CustomerCreated, &serde_json::json!({ "id": id }))
+                .await
+                .unwrap();
+        }
+        queue_webhook(&pool, WebhookEvent::CustomerDeleted, &serde_json::json!({ "id": 1 }))
+            .await
+            .unwrap();
+
+        let client = webhook_client();
+        assert_eq!(
+            deliver_due_webhooks(&pool, &client).await.unwrap() as i64,
+            DELIVERIES_PER_ENDPOINT + 1
+        );
+        assert_eq!(busy.lock().unwrap().len() as i64, DELIVERIES_PER_ENDPOINT);
+        assert_eq!(quiet.lock().unwrap().len(), 1);
+
+        // The rest of the backlog goes out on the next run
+        assert_eq!(deliver_due_webhooks(&pool, &client).await.unwrap(), 2);
+        assert_eq!(busy.lock().unwrap().len(), 12);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_receives_deliveries_in_order() {
+        let pool = test_pool().await;
+        let (url, received) = start_stub(StatusCode::OK).await;
+        add_endpoint(&pool, &url, "customer.updated").await;
+
+        for id in 0..5 {
+            queue_webhook(&pool, WebhookEvent::CustomerUpdated, &serde_json::json!({ "id": id }))
+                .await
+                .unwrap();
+        }
+        deliver_due_webhooks(&pool, &webhook_client()).await.unwrap();
+
+        let ids: Vec<i64> = received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, body)| {
+                let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+                payload["data"]["id"].as_i64().unwrap()
+            })
+            .collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_stock_low_only_when_crossing_threshold() {
+        let pool = test_pool().await;
+        let (url, _received) = start_stub(StatusCode::OK).await;
+        add_endpoint(&pool, &url, "product.stock_low").await;
+
+        let mut product = Product {
+            id: 1,
+            name: "عسل".to_string(),
+            description: String::new(),
+            price: Money::ZERO,
+            image_url: None,
+            stock: 5,
+            created_at: String::new(),
+        };
+        let stock = StockSettings {
+            low_stock_threshold: 5,
+        };
+        queue_stock_low(&pool, stock, 6, &product).await.unwrap();
+        product.stock = 4;
+        queue_stock_low(&pool, stock, 5, &product).await.unwrap();
+
+        let queued = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM webhook_deliveries")
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+        assert_eq!(queued().await, 1);
+
+        // The threshold is configurable
+        let stock = StockSettings {
+            low_stock_threshold: 10,
+        };
+        product.stock = 10;
+        queue_stock_low(&pool, stock, 11, &product).await.unwrap();
+        assert_eq!(queued().await, 2);
+    }
+}
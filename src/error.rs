@@ -1,9 +1,12 @@
 use askama::Template;
 use axum::{
-    http::StatusCode,
+    extract::rejection::{JsonRejection, PathRejection},
+    http::{header, StatusCode},
     response::{Html, IntoResponse, Response},
 };
+use serde::Serialize;
 use std::fmt;
+use utoipa::ToSchema;
 
 use crate::templates::errors::ErrorTemplate;
 
@@ -12,6 +15,8 @@ pub enum AppError {
     Database(sqlx::Error),
     Unauthorized,
     Forbidden,
+    /// A state-changing request without the session's CSRF token
+    InvalidCsrfToken,
     NotFound,
     BadRequest(String),
     Internal(String),
@@ -20,6 +25,8 @@ pub enum AppError {
     Validation(Vec<(String, String)>),
     DuplicateEntry(String),
     SessionExpired,
+    /// A feature that needs server setup that has not been done, e.g. a missing PDF font
+    Unavailable(String),
 }
 
 impl fmt::Display for AppError {
@@ -28,6 +35,7 @@ impl fmt::Display for AppError {
             AppError::Database(e) => write!(f, "Database error: {}", e),
             AppError::Unauthorized => write!(f, "Authentication required"),
             AppError::Forbidden => write!(f, "Access forbidden"),
+            AppError::InvalidCsrfToken => write!(f, "Missing or invalid CSRF token"),
             AppError::NotFound => write!(f, "Resource not found"),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
             AppError::Internal(msg) => write!(f, "Internal error: {}", msg),
@@ -42,6 +50,7 @@ impl fmt::Display for AppError {
             }
             AppError::DuplicateEntry(field) => write!(f, "Duplicate entry: {}", field),
             AppError::SessionExpired => write!(f, "Session expired"),
+            AppError::Unavailable(msg) => write!(f, "Unavailable: {}", msg),
         }
     }
 }
@@ -72,6 +81,10 @@ impl IntoResponse for AppError {
                 StatusCode::FORBIDDEN,
                 ErrorTemplate::for_forbidden()
             ),
+            AppError::InvalidCsrfToken => (
+                StatusCode::FORBIDDEN,
+                ErrorTemplate::for_invalid_csrf_token()
+            ),
             AppError::NotFound => (
                 StatusCode::NOT_FOUND,
                 ErrorTemplate::for_not_found()
@@ -84,6 +97,13 @@ impl IntoResponse for AppError {
                 StatusCode::CONFLICT,
                 ErrorTemplate::for_duplicate_entry(field)
             ),
+            AppError::Unavailable(ref msg) => {
+                eprintln!("Unavailable: {}", msg);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorTemplate::for_unavailable(msg.clone())
+                )
+            },
             AppError::Validation(ref errors) => {
                 let details = errors.iter()
                     .map(|(field, msg)| format!("• {}: {}", field, msg))
@@ -182,4 +202,192 @@ impl From<xlsxwriter::XlsxError> for AppError {
     }
 }
 
-pub type AppResult<T> = Result<T, AppError>;
\ No newline at end of file
+pub type AppResult<T> = Result<T, AppError>;
+
+/// An error from the JSON API, sent as an RFC 7807 problem document instead of an HTML page.
+///
+/// Every `AppError` converts into one with the same status code and message as its error page,
+/// so API handlers can use `?` on the helpers the HTML handlers share.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub detail: String,
+    /// Field name and message for each invalid field
+    pub errors: Vec<(String, String)>,
+}
+
+/// An error as the JSON API reports it, following RFC 9457
+#[derive(Serialize, ToSchema)]
+pub struct Problem<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: &'a str,
+    /// The invalid fields, for validation errors and duplicates
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldProblem<'a>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FieldProblem<'a> {
+    field: &'a str,
+    message: &'a str,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            detail: detail.into(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        let message = |template: ErrorTemplate| template.message;
+
+        match err {
+            AppError::Unauthorized => {
+                ApiError::new(StatusCode::UNAUTHORIZED, message(ErrorTemplate::for_unauthorized()))
+            }
+            AppError::SessionExpired => {
+                ApiError::new(StatusCode::UNAUTHORIZED, "نشست کاربری شما منقضی شده است.")
+            }
+            AppError::Forbidden => {
+                ApiError::new(StatusCode::FORBIDDEN, message(ErrorTemplate::for_forbidden()))
+            }
+            AppError::InvalidCsrfToken => ApiError::new(
+                StatusCode::FORBIDDEN,
+                "درخواست‌های تغییردهنده باید توکن CSRF نشست را در سرآیند X-CSRF-Token بفرستند.",
+            ),
+            AppError::NotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, message(ErrorTemplate::for_not_found()))
+            }
+            AppError::BadRequest(msg) => ApiError::new(StatusCode::BAD_REQUEST, msg),
+            AppError::Validation(errors) => ApiError {
+                status: StatusCode::BAD_REQUEST,
+                detail: "اطلاعات وارد شده معتبر نیست.".to_string(),
+                errors,
+            },
+            AppError::DuplicateEntry(field) => ApiError::new(
+                StatusCode::CONFLICT,
+                message(ErrorTemplate::for_duplicate_entry(&field)),
+            ),
+            AppError::Unavailable(msg) => ApiError::new(StatusCode::SERVICE_UNAVAILABLE, msg),
+            AppError::Database(ref e) if e.to_string().contains("UNIQUE constraint failed") => {
+                ApiError::new(
+                    StatusCode::CONFLICT,
+                    message(ErrorTemplate::for_duplicate_entry("unknown")),
+                )
+            }
+            AppError::Database(_)
+            | AppError::Internal(_)
+            | AppError::Template(_)
+            | AppError::Bcrypt(_) => {
+                // The cause is logged, not sent to the client
+                eprintln!("Internal error in API: {}", err);
+                ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "متأسفانه مشکلی در سیستم رخ داده است.",
+                )
+            }
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        AppError::from(err).into()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let problem = Problem {
+            problem_type: "about:blank",
+            title: self.status.canonical_reason().unwrap_or("Error"),
+            status: self.status.as_u16(),
+            detail: &self.detail,
+            errors: self
+                .errors
+                .iter()
+                .map(|(field, message)| FieldProblem { field, message })
+                .collect(),
+        };
+
+        let body = serde_json::to_string(&problem).unwrap_or_default();
+        (
+            self.status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            body,
+        )
+            .into_response()
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_from_app_error() {
+        assert_eq!(ApiError::from(AppError::NotFound).status, StatusCode::NOT_FOUND);
+        assert_eq!(ApiError::from(AppError::Unauthorized).status, StatusCode::UNAUTHORIZED);
+        assert_eq!(ApiError::from(AppError::Forbidden).status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            ApiError::from(AppError::DuplicateEntry("username".to_string())).status,
+            StatusCode::CONFLICT
+        );
+
+        let error = ApiError::from(AppError::BadRequest("مبلغ تراکنش معتبر نیست".to_string()));
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.detail, "مبلغ تراکنش معتبر نیست");
+
+        let error = ApiError::from(AppError::Validation(vec![(
+            "email".to_string(),
+            "ایمیل معتبر نیست".to_string(),
+        )]));
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.errors, vec![("email".to_string(), "ایمیل معتبر نیست".to_string())]);
+    }
+
+    #[test]
+    fn test_api_error_hides_internal_details() {
+        let error = ApiError::from(AppError::Internal("disk full at /var/lib".to_string()));
+        assert_eq!(error.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!error.detail.contains("disk"));
+    }
+
+    #[tokio::test]
+    async fn test_api_error_response_is_problem_json() {
+        let response = ApiError::from(AppError::NotFound).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["title"], "Not Found");
+        assert!(problem.get("errors").is_none());
+    }
+}
@@ -0,0 +1,167 @@
+use askama::Template;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_extra::extract::CookieJar;
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    db::{audit::record_audit, sessions::delete_user_sessions},
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{AuditEntity, ChangePasswordForm, User},
+    templates::account::ChangePasswordTemplate,
+    utils::password::{hash_password, verify_password, PasswordPolicy},
+};
+
+/// Show the form for changing one's own password
+pub async fn show_change_password(
+    State(pool): State<Pool<Sqlite>>,
+    State(policy): State<PasswordPolicy>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+
+    let template = change_password_template(user, &policy, csrf_token, None);
+    Ok(Html(template.render()?))
+}
+
+/// Change one's own password after checking the current one.
+///
+/// Every other session of the user ends, so a stolen session does not outlive the change.
+pub async fn change_password(
+    State(pool): State<Pool<Sqlite>>,
+    State(policy): State<PasswordPolicy>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Form(form): Form<ChangePasswordForm>,
+) -> AppResult<Response> {
+    let user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+
+    let error = if !verify_password(&form.current_password, &user.password_hash)? {
+        Some("رمز عبور فعلی اشتباه است".to_string())
+    } else if form.new_password != form.confirm_password {
+        Some("رمز عبور جدید و تکرار آن یکسان نیستند".to_string())
+    } else if form.new_password == form.current_password {
+        Some("رمز عبور جدید باید با رمز فعلی متفاوت باشد".to_string())
+    } else {
+        policy.check(&form.new_password).err()
+    };
+    if let Some(error) = error {
+        let template = change_password_template(user, &policy, csrf_token, Some(error));
+        return Ok(Html(template.render()?).into_response());
+    }
+
+    sqlx::query("UPDATE users SET password_hash = ?, must_change_password = 0 WHERE id = ?")
+        .bind(hash_password(&form.new_password)?)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+    let changed = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(user.id)
+        .fetch_one(&pool)
+        .await?;
+    record_audit(&pool, Some(user.id), AuditEntity::User, user.id, Some(&user), Some(&changed)).await?;
+
+    let current_session = jar.get("session_id").map(|cookie| cookie.value().to_string());
+    let ended = delete_user_sessions(&pool, user.id, current_session.as_deref()).await?;
+
+    println!(
+        "🔑 Password changed by {} ({} other sessions ended)",
+        user.username, ended
+    );
+
+    let jar = jar.add(flash_cookie("رمز عبور شما با موفقیت تغییر کرد 🔑"));
+
+    Ok((jar, Redirect::to("/")).into_response())
+}
+
+fn change_password_template(
+    user: User,
+    policy: &PasswordPolicy,
+    csrf_token: String,
+    error: Option<String>,
+) -> ChangePasswordTemplate {
+    ChangePasswordTemplate {
+        forced: user.must_change_password,
+        min_length: policy.min_length,
+        policy_description: policy.description(),
+        error,
+        current_user: Some(user),
+        csrf_token,
+        active_page: "account",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_extra::extract::cookie::Cookie;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    #[tokio::test]
+    async fn test_change_password_ends_other_sessions() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role) VALUES ('ali', ?, 'علی', 'user')",
+        )
+        .bind(bcrypt::hash("old-pass-1", 4).unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+        for id in ["ali-phone", "ali-laptop"] {
+            sqlx::query("INSERT INTO sessions (id, user_id, expires_at) VALUES (?, 1, ?)")
+                .bind(id)
+                .bind((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let policy = PasswordPolicy {
+            min_length: 8,
+            require_letter: true,
+            require_digit: true,
+        };
+        let form = ChangePasswordForm {
+            current_password: "old-pass-1".to_string(),
+            new_password: "new-pass-2".to_string(),
+            confirm_password: "new-pass-2".to_string(),
+        };
+        change_password(
+            State(pool.clone()),
+            State(policy),
+            CookieJar::new().add(Cookie::new("session_id", "ali-phone")),
+            CsrfToken(String::new()),
+            Form(form),
+        )
+        .await
+        .unwrap();
+
+        let sessions: Vec<String> = sqlx::query_scalar("SELECT id FROM sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(sessions, vec!["ali-phone"]);
+        let hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE id = 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(verify_password("new-pass-2", &hash).unwrap());
+    }
+}
@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::{Pool, QueryBuilder, Sqlite};
+
+use crate::{
+    error::{ApiError, ApiResult, AppError, Problem},
+    handlers::{
+        api::{ApiJson, ApiPath, CustomerPage, Page},
+        customers::{
+            create_customer_record, delete_customer_record, find_form_duplicates,
+            push_customer_order, push_customer_select, update_customer_record,
+            validate_customer_form,
+        },
+    },
+    models::{
+        tag::CUSTOMER_TAGS_SQL, transactions::CUSTOMER_BALANCE_SQL, CustomerForm, CustomerQuery,
+        CustomerSummary, DuplicateMatch, User,
+    },
+};
+
+/// Customers with their balances, searched, filtered, sorted and paged like the list page
+#[utoipa::path(
+    get,
+    path = "/customers",
+    tag = "customers",
+    params(CustomerQuery),
+    responses((status = 200, description = "One page of customers", body = CustomerPage))
+)]
+pub async fn list_customers(
+    State(pool): State<Pool<Sqlite>>,
+    Query(query): Query<CustomerQuery>,
+) -> ApiResult<Json<Page<CustomerSummary>>> {
+    Ok(Json(fetch_customer_page(&pool, &query).await?))
+}
+
+/// One page of the customers matching `query`, with the total across all pages
+pub async fn fetch_customer_page(
+    pool: &Pool<Sqlite>,
+    query: &CustomerQuery,
+) -> ApiResult<Page<CustomerSummary>> {
+    let mut count_query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM (");
+    push_customer_select(&mut count_query, query);
+    count_query.push(")");
+    let total: i64 = count_query.build_query_scalar().fetch_one(pool).await?;
+
+    let mut select_query = QueryBuilder::<Sqlite>::new("");
+    push_customer_select(&mut select_query, query);
+    push_customer_order(&mut select_query, query);
+    select_query
+        .push(" LIMIT ")
+        .push_bind(query.per_page())
+        .push(" OFFSET ")
+        .push_bind(query.offset());
+
+    let items = select_query
+        .build_query_as::<CustomerSummary>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(Page {
+        items,
+        total,
+        page: query.page(),
+        per_page: query.per_page(),
+    })
+}
+
+/// A customer with their balance
+#[utoipa::path(
+    get,
+    path = "/customers/{id}",
+    tag = "customers",
+    params(("id" = i64, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The customer", body = CustomerSummary),
+        (status = 404, description = "No such customer", body = Problem),
+    )
+)]
+pub async fn get_customer(
+    State(pool): State<Pool<Sqlite>>,
+    ApiPath(id): ApiPath<i64>,
+) -> ApiResult<Json<CustomerSummary>> {
+    Ok(Json(fetch_customer_summary(&pool, id).await?))
+}
+
+/// Create a customer.
+///
+/// One that looks like an existing customer is refused with 409 unless
+/// `confirm_duplicate` is true, as the add form asks for confirmation.
+/// Requires `customers.edit`.
+#[utoipa::path(
+    post,
+    path = "/customers",
+    tag = "customers",
+    request_body = CustomerForm,
+    responses(
+        (status = 201, description = "The new customer", body = CustomerSummary),
+        (status = 400, description = "Invalid fields", body = Problem),
+        (status = 409, description = "Likely duplicates, listed in `errors`", body = Problem),
+    )
+)]
+pub async fn create_customer(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiJson(mut form): ApiJson<CustomerForm>,
+) -> ApiResult<(StatusCode, Json<CustomerSummary>)> {
+    validate_customer_form(&mut form)?;
+
+    if !form.confirm_duplicate {
+        let duplicates = find_form_duplicates(&pool, &form).await?;
+        if !duplicates.is_empty() {
+            return Err(duplicates_problem(&duplicates));
+        }
+    }
+
+    let customer = create_customer_record(&pool, &form, Some(user.id)).await?;
+    println!("✅ New customer added via API: {}", customer.full_name);
+
+    let summary = fetch_customer_summary(&pool, customer.id).await?;
+    Ok((StatusCode::CREATED, Json(summary)))
+}
+
+/// Update a customer; requires `customers.edit`
+#[utoipa::path(
+    put,
+    path = "/customers/{id}",
+    tag = "customers",
+    params(("id" = i64, Path, description = "Customer id")),
+    request_body = CustomerForm,
+    responses(
+        (status = 200, description = "The updated customer", body = CustomerSummary),
+        (status = 400, description = "Invalid fields", body = Problem),
+        (status = 404, description = "No such customer", body = Problem),
+    )
+)]
+pub async fn update_customer(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiPath(id): ApiPath<i64>,
+    ApiJson(mut form): ApiJson<CustomerForm>,
+) -> ApiResult<Json<CustomerSummary>> {
+    validate_customer_form(&mut form)?;
+    update_customer_record(&pool, id, &form, Some(user.id)).await?;
+    println!("✏️ Updated customer via API: {} (ID: {})", form.full_name, id);
+
+    Ok(Json(fetch_customer_summary(&pool, id).await?))
+}
+
+/// Delete a customer with their transactions; requires `customers.delete`
+#[utoipa::path(
+    delete,
+    path = "/customers/{id}",
+    tag = "customers",
+    params(("id" = i64, Path, description = "Customer id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such customer", body = Problem),
+    )
+)]
+pub async fn delete_customer(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiPath(id): ApiPath<i64>,
+) -> ApiResult<StatusCode> {
+    delete_customer_record(&pool, id, Some(user.id)).await?;
+    println!("🗑️ Deleted customer via API, ID: {}", id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn fetch_customer_summary(pool: &Pool<Sqlite>, id: i64) -> ApiResult<CustomerSummary> {
+    Ok(sqlx::query_as::<_, CustomerSummary>(&format!(
+        "SELECT customers.*, {} AS balance, {} AS tags FROM customers WHERE id = ?",
+        CUSTOMER_BALANCE_SQL, CUSTOMER_TAGS_SQL
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound)?)
+}
+
+/// A conflict naming the existing customers the new one looks like, and why
+fn duplicates_problem(duplicates: &[DuplicateMatch]) -> ApiError {
+    ApiError {
+        status: StatusCode::CONFLICT,
+        detail: "مشتری مشابهی از قبل ثبت شده است؛ برای ثبت با این حال confirm_duplicate را true بفرستید."
+            .to_string(),
+        errors: duplicates
+            .iter()
+            .flat_map(|duplicate| {
+                duplicate.reasons.iter().map(move |reason| {
+                    (
+                        reason.field().to_string(),
+                        format!(
+                            "{} با مشتری {} «{}»",
+                            reason.display_name(),
+                            duplicate.customer.id,
+                            duplicate.customer.full_name
+                        ),
+                    )
+                })
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,174 @@
+//! JSON API under `/api/v1`, for the mobile app and scripts.
+//!
+//! Handlers reuse the validation and database helpers of the HTML handlers, and answer
+//! errors with `ApiError` problem documents instead of error pages. The OpenAPI document
+//! describing it is generated from the same types in [`openapi`].
+
+pub mod customers;
+pub mod openapi;
+pub mod products;
+pub mod segments;
+pub mod transactions;
+pub mod users;
+
+use axum::{
+    extract::{FromRequest, FromRequestParts},
+    routing::{delete, get, post, put, MethodRouter},
+    Router,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    error::{ApiError, AppError},
+    middleware::{api_auth_middleware, api_permission_middleware},
+    models::{CustomerSummary, Permission},
+    state::AppState,
+};
+
+/// A JSON request body; a malformed body is answered with a problem document
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// Path parameters; an id that is not a number is answered with a problem document
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(pub T);
+
+/// One page of a list, with the total across all pages
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(CustomerPage = Page<CustomerSummary>)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+/// Routes of the JSON API, to be nested under `/api/v1`
+pub fn routes(state: &AppState) -> Router<AppState> {
+    api_router()
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            api_auth_middleware,
+        ))
+        .fallback(not_found)
+}
+
+/// The API's routes with their permissions, before requests are signed in
+fn api_router() -> Router<AppState> {
+    API_ROUTES.iter().fold(Router::new(), |router, route| {
+        router.route(route.path, (route.methods)())
+    })
+}
+
+/// One path of the API and the handlers of its methods
+struct ApiRoute {
+    path: &'static str,
+    methods: fn() -> MethodRouter<AppState>,
+}
+
+/// Every path of the API. Methods without a permission are open to any signed-in user.
+///
+/// Every route here must be described in [`openapi::ApiDoc`]; a test probes each path
+/// and checks the two agree.
+const API_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        path: "/me",
+        methods: || get(users::show_me),
+    },
+    ApiRoute {
+        path: "/customers",
+        methods: || {
+            get(customers::list_customers).merge(needs(
+                Permission::CustomersEdit,
+                post(customers::create_customer),
+            ))
+        },
+    },
+    ApiRoute {
+        path: "/customers/:id",
+        methods: || {
+            get(customers::get_customer)
+                .merge(needs(
+                    Permission::CustomersEdit,
+                    put(customers::update_customer),
+                ))
+                .merge(needs(
+                    Permission::CustomersDelete,
+                    delete(customers::delete_customer),
+                ))
+        },
+    },
+    ApiRoute {
+        path: "/customers/:id/transactions",
+        methods: || {
+            get(transactions::list_transactions).merge(needs(
+                Permission::TransactionsEdit,
+                post(transactions::create_transaction),
+            ))
+        },
+    },
+    ApiRoute {
+        path: "/customers/:id/transactions/:transaction_id",
+        methods: || {
+            get(transactions::get_transaction)
+                .merge(needs(
+                    Permission::TransactionsEdit,
+                    put(transactions::update_transaction),
+                ))
+                .merge(needs(
+                    Permission::TransactionsDelete,
+                    delete(transactions::delete_transaction),
+                ))
+        },
+    },
+    ApiRoute {
+        path: "/products",
+        methods: || {
+            get(products::list_products).merge(needs(
+                Permission::CatalogManage,
+                post(products::create_product),
+            ))
+        },
+    },
+    ApiRoute {
+        path: "/products/:id",
+        methods: || {
+            get(products::get_product).merge(needs(
+                Permission::CatalogManage,
+                put(products::update_product).delete(products::delete_product),
+            ))
+        },
+    },
+    ApiRoute {
+        path: "/segments",
+        methods: || get(segments::list_segments),
+    },
+    ApiRoute {
+        path: "/segments/:id/customers",
+        methods: || get(segments::list_segment_customers),
+    },
+    ApiRoute {
+        path: "/users",
+        methods: || needs(Permission::UsersManage, get(users::list_users)),
+    },
+    ApiRoute {
+        path: "/users/:id",
+        methods: || needs(Permission::UsersManage, get(users::get_user)),
+    },
+];
+
+/// Only open `methods` to users whose role has `permission`
+fn needs(permission: Permission, methods: MethodRouter<AppState>) -> MethodRouter<AppState> {
+    methods.route_layer(axum::middleware::from_fn_with_state(
+        permission,
+        api_permission_middleware,
+    ))
+}
+
+/// Unknown API paths get a problem document rather than an empty 404
+async fn not_found() -> ApiError {
+    AppError::NotFound.into()
+}
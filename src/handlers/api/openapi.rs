@@ -0,0 +1,574 @@
+//! OpenAPI 3 document of the JSON API.
+//!
+//! Paths come from the `#[utoipa::path]` attribute on each handler and schemas from the
+//! `ToSchema` types they read and return, so the document follows the code. A test checks
+//! that it describes exactly the routes the API serves.
+
+use askama::Template;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde_json::Value;
+use sqlx::{Pool, Sqlite};
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ContentBuilder, Ref, RefOr, ResponseBuilder, SecurityRequirement,
+    },
+    Modify, OpenApi,
+};
+
+use crate::{
+    error::{AppError, AppResult, FieldProblem, Problem},
+    handlers::api::{customers, products, segments, transactions, users, CustomerPage},
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        Customer, CustomerForm, CustomerSummary, LedgerEntry, Money, Product, ProductForm,
+        ProductVariant, Segment, Transaction, TransactionForm, UserProfile,
+    },
+    templates::api_docs::{
+        ApiDocsTemplate, DocField, DocOperation, DocResponse, DocSchema, DocTag,
+    },
+};
+
+/// Content type of the API's error responses
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// HTTP methods the API uses, in the order the docs page lists them
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Rumiland CRM API",
+        version = "1",
+        description = "JSON API of Rumiland CRM. Requests sign in with an API token in the \
+            `Authorization: Bearer` header, or with the session cookie of the pages; with the \
+            cookie, `POST`, `PUT` and `DELETE` requests must also send the session's CSRF token \
+            in the `X-CSRF-Token` header. Amounts in responses are whole rials."
+    ),
+    servers((url = "/api/v1")),
+    paths(
+        users::show_me,
+        customers::list_customers,
+        customers::get_customer,
+        customers::create_customer,
+        customers::update_customer,
+        customers::delete_customer,
+        transactions::list_transactions,
+        transactions::get_transaction,
+        transactions::create_transaction,
+        transactions::update_transaction,
+        transactions::delete_transaction,
+        products::list_products,
+        products::get_product,
+        products::create_product,
+        products::update_product,
+        products::delete_product,
+        segments::list_segments,
+        segments::list_segment_customers,
+        users::list_users,
+        users::get_user,
+    ),
+    components(schemas(
+        Customer,
+        CustomerForm,
+        CustomerSummary,
+        CustomerPage,
+        Transaction,
+        TransactionForm,
+        LedgerEntry,
+        Product,
+        ProductForm,
+        ProductVariant,
+        products::ProductDetail,
+        Segment,
+        UserProfile,
+        Money,
+        Problem,
+        FieldProblem,
+    )),
+    modifiers(&SignInAndErrors),
+    tags(
+        (name = "customers", description = "Customers and their balances"),
+        (name = "transactions", description = "A customer's transactions"),
+        (name = "products", description = "The product catalog"),
+        (name = "segments", description = "Saved customer filters, for exports and campaigns"),
+        (name = "users", description = "Users and the signed-in user"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Adds the two ways of signing in, and the error responses every operation shares
+struct SignInAndErrors;
+
+impl Modify for SignInAndErrors {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("An API token created on the tokens page"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "session_id",
+                "The session cookie set when signing in to the pages",
+            ))),
+        );
+        for (name, description) in [
+            ("Unauthorized", "Not signed in, or the API token is unknown, revoked or expired"),
+            ("Forbidden", "The user or API token lacks the permission this needs"),
+        ] {
+            components.responses.insert(
+                name.to_string(),
+                ResponseBuilder::new()
+                    .description(description)
+                    .content(
+                        PROBLEM_CONTENT_TYPE,
+                        ContentBuilder::new()
+                            .schema(Ref::from_schema_name("Problem"))
+                            .build(),
+                    )
+                    .build()
+                    .into(),
+            );
+        }
+
+        openapi.security = Some(vec![
+            SecurityRequirement::new("bearer", Vec::<String>::new()),
+            SecurityRequirement::new("session", Vec::<String>::new()),
+        ]);
+
+        for path in openapi.paths.paths.values_mut() {
+            for operation in path.operations.values_mut() {
+                let responses = &mut operation.responses.responses;
+
+                // Errors are problem documents, which have their own content type
+                for response in responses.values_mut() {
+                    if let RefOr::T(response) = response {
+                        if let Some(content) = response.content.remove("application/json") {
+                            let is_problem = matches!(
+                                &content.schema,
+                                RefOr::Ref(schema) if schema.ref_location.ends_with("/Problem")
+                            );
+                            let content_type = if is_problem {
+                                PROBLEM_CONTENT_TYPE
+                            } else {
+                                "application/json"
+                            };
+                            response.content.insert(content_type.to_string(), content);
+                        }
+                    }
+                }
+
+                responses
+                    .entry("401".to_string())
+                    .or_insert_with(|| Ref::from_response_name("Unauthorized").into());
+                responses
+                    .entry("403".to_string())
+                    .or_insert_with(|| Ref::from_response_name("Forbidden").into());
+            }
+        }
+    }
+}
+
+/// The OpenAPI document, for generating clients
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Readable docs of the API, rendered from its OpenAPI document
+pub async fn show_docs(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let spec = serde_json::to_value(ApiDoc::openapi())
+        .map_err(|e| AppError::Internal(format!("Failed to build the OpenAPI document: {}", e)))?;
+
+    let template = ApiDocsTemplate {
+        title: text(&spec["info"]["title"]),
+        version: text(&spec["info"]["version"]),
+        description: text(&spec["info"]["description"]),
+        tags: doc_tags(&spec),
+        schemas: doc_schemas(&spec),
+        current_user,
+        csrf_token,
+        active_page: "api",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// The document's operations grouped by tag, in the order the tags are declared
+fn doc_tags(spec: &Value) -> Vec<DocTag> {
+    let mut tags: Vec<DocTag> = spec["tags"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|tag| DocTag {
+            name: text(&tag["name"]),
+            description: text(&tag["description"]),
+            operations: Vec::new(),
+        })
+        .collect();
+
+    for operation in doc_operations(spec) {
+        match tags.iter_mut().find(|tag| tag.name == operation.tag) {
+            Some(tag) => tag.operations.push(operation),
+            None => tags.push(DocTag {
+                name: operation.tag.clone(),
+                description: String::new(),
+                operations: vec![operation],
+            }),
+        }
+    }
+
+    tags.retain(|tag| !tag.operations.is_empty());
+    tags
+}
+
+fn doc_operations(spec: &Value) -> Vec<DocOperation> {
+    let mut operations = Vec::new();
+    for (path, item) in spec["paths"].as_object().into_iter().flatten() {
+        for method in METHODS {
+            let operation = &item[method];
+            if operation.is_null() {
+                continue;
+            }
+
+            operations.push(DocOperation {
+                method: method.to_string(),
+                path: path.clone(),
+                tag: text(&operation["tags"][0]),
+                summary: text(&operation["summary"]),
+                description: text(&operation["description"]),
+                parameters: operation["parameters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|parameter| DocField {
+                        name: text(&parameter["name"]),
+                        location: text(&parameter["in"]),
+                        type_name: type_name(&parameter["schema"]),
+                        required: parameter["required"].as_bool().unwrap_or(false),
+                        description: text(&parameter["description"]),
+                    })
+                    .collect(),
+                request_body: content_schema(&operation["requestBody"]),
+                responses: operation["responses"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(status, response)| {
+                        let response = resolve(spec, response, "responses");
+                        DocResponse {
+                            status: status.clone(),
+                            description: text(&response["description"]),
+                            schema: content_schema(response),
+                        }
+                    })
+                    .collect(),
+            });
+        }
+    }
+    operations
+}
+
+fn doc_schemas(spec: &Value) -> Vec<DocSchema> {
+    spec["components"]["schemas"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| {
+            // A type with a flattened field is the combination of that field's schema and its own
+            let parts: Vec<&Value> = match schema["allOf"].as_array() {
+                Some(parts) => parts.iter().collect(),
+                None => vec![schema],
+            };
+            let is_object = schema["type"].is_null() || schema["type"] == "object";
+
+            DocSchema {
+                name: name.clone(),
+                description: text(&schema["description"]),
+                type_name: if is_object {
+                    String::new()
+                } else {
+                    type_name(schema)
+                },
+                includes: parts
+                    .iter()
+                    .filter_map(|part| part["$ref"].as_str())
+                    .map(|reference| ref_name(reference).to_string())
+                    .collect(),
+                properties: parts.iter().flat_map(|part| properties(part)).collect(),
+            }
+        })
+        .collect()
+}
+
+fn properties(schema: &Value) -> Vec<DocField> {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, property)| DocField {
+            name: name.clone(),
+            location: String::new(),
+            type_name: type_name(property),
+            required: required.contains(&name.as_str()),
+            description: text(&property["description"]),
+        })
+        .collect()
+}
+
+/// Schema of the first content type of a request body or response
+fn content_schema(body: &Value) -> Option<String> {
+    body["content"]
+        .as_object()
+        .and_then(|content| content.values().next())
+        .map(|content| type_name(&content["schema"]))
+}
+
+/// Short name of a schema, such as `Customer`, `integer (int64)` or `LedgerEntry[]`
+fn type_name(schema: &Value) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return ref_name(reference).to_string();
+    }
+    if let Some(parts) = schema["allOf"].as_array() {
+        return parts.iter().map(type_name).collect::<Vec<_>>().join(" + ");
+    }
+
+    let name = match schema["type"].as_str() {
+        Some("array") => format!("{}[]", type_name(&schema["items"])),
+        Some(name) => match schema["format"].as_str() {
+            Some(format) => format!("{} ({})", name, format),
+            None => name.to_string(),
+        },
+        None => "object".to_string(),
+    };
+    if schema["nullable"] == true {
+        format!("{} | null", name)
+    } else {
+        name
+    }
+}
+
+fn ref_name(reference: &str) -> &str {
+    reference.rsplit('/').next().unwrap_or(reference)
+}
+
+/// The object a `$ref` under `#/components/<kind>/` points to, or the value itself
+fn resolve<'a>(spec: &'a Value, value: &'a Value, kind: &str) -> &'a Value {
+    match value["$ref"].as_str() {
+        Some(reference) => &spec["components"][kind][ref_name(reference)],
+        None => value,
+    }
+}
+
+fn text(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        handlers::api::{api_router, API_ROUTES},
+        state::AppState,
+        utils::media::MediaStore,
+    };
+
+    /// A path as the document writes it (`/customers/{id}`)
+    fn spec_path(path: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Every method and path the router answers; any other method on a path gets 405
+    async fn routed_operations() -> BTreeSet<(String, String)> {
+        let config = Config::from_env();
+        let state = AppState {
+            pool: SqlitePoolOptions::new()
+                .connect_lazy("sqlite::memory:")
+                .unwrap(),
+            media: MediaStore::new(std::env::temp_dir().join("rumiland_openapi_test")),
+            documents: config.document_settings(),
+            login_limits: config.login_limits.clone(),
+            sessions: config.session_settings(),
+            password_policy: config.password_policy.clone(),
+            stock: config.stock,
+        };
+        let router = api_router().with_state(state);
+
+        let mut operations = BTreeSet::new();
+        for path in API_ROUTES.iter().map(|route| route.path) {
+            let uri = path
+                .split('/')
+                .map(|segment| if segment.starts_with(':') { "1" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in METHODS {
+                let request = Request::builder()
+                    .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                    operations.insert((method.to_string(), spec_path(path)));
+                }
+            }
+        }
+        operations
+    }
+
+    fn documented_operations() -> BTreeSet<(String, String)> {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                METHODS
+                    .into_iter()
+                    .filter(|method| !item[*method].is_null())
+                    .map(|method| (method.to_string(), path.clone()))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_spec_matches_routes() {
+        let routed = routed_operations().await;
+        assert!(!routed.is_empty());
+
+        let documented = documented_operations();
+        assert!(
+            routed == documented,
+            "routes and OpenAPI document differ\nnot documented: {:?}\nnot routed: {:?}",
+            routed.difference(&documented).collect::<Vec<_>>(),
+            documented.difference(&routed).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_references_resolve() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        fn references<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+            match value {
+                Value::Object(object) => {
+                    if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                        found.push(reference);
+                    }
+                    object.values().for_each(|value| references(value, found));
+                }
+                Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+                _ => {}
+            }
+        }
+
+        let mut found = Vec::new();
+        references(&spec, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let pointer = reference.trim_start_matches('#');
+            assert!(
+                spec.pointer(pointer).is_some(),
+                "{} is not in the document",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_schemas_of_requested_types() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &spec["components"]["schemas"];
+        for name in [
+            "Customer",
+            "CustomerForm",
+            "Transaction",
+            "TransactionForm",
+            "Product",
+            "ProductForm",
+        ] {
+            assert!(schemas[name].is_object(), "{} has no schema", name);
+        }
+
+        let names: Vec<String> = properties(&schemas["CustomerForm"])
+            .into_iter()
+            .map(|field| field.name)
+            .collect();
+        assert!(names.contains(&"full_name".to_string()));
+        assert!(names.contains(&"confirm_duplicate".to_string()));
+    }
+
+    #[test]
+    fn test_type_name() {
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": { "$ref": "#/components/schemas/LedgerEntry" }
+        });
+        assert_eq!(type_name(&schema), "LedgerEntry[]");
+
+        let schema = serde_json::json!({ "type": "integer", "format": "int64" });
+        assert_eq!(type_name(&schema), "integer (int64)");
+
+        let schema = serde_json::json!({ "type": "string", "nullable": true });
+        assert_eq!(type_name(&schema), "string | null");
+    }
+
+    #[test]
+    fn test_doc_tags() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let tags = doc_tags(&spec);
+        assert_eq!(tags[0].name, "customers");
+
+        let create = tags[0]
+            .operations
+            .iter()
+            .find(|operation| operation.method == "post")
+            .unwrap();
+        assert_eq!(create.path, "/customers");
+        assert_eq!(create.request_body.as_deref(), Some("CustomerForm"));
+        let unauthorized = create
+            .responses
+            .iter()
+            .find(|response| response.status == "401")
+            .unwrap();
+        assert_eq!(unauthorized.schema.as_deref(), Some("Problem"));
+    }
+}
@@ -0,0 +1,179 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+use utoipa::ToSchema;
+
+use crate::{
+    db::{audit::record_audit, webhooks::queue_stock_low},
+    error::{ApiResult, AppError, Problem},
+    handlers::{
+        api::{ApiJson, ApiPath},
+        catalog::{delete_product_record, fetch_product, validate_product_form},
+    },
+    models::{AuditEntity, Product, ProductForm, ProductVariant, StockSettings, User},
+    utils::media::MediaStore,
+};
+
+/// A product together with its variants
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProductDetail {
+    #[serde(flatten)]
+    pub product: Product,
+    pub variants: Vec<ProductVariant>,
+}
+
+/// All products, newest first
+#[utoipa::path(
+    get,
+    path = "/products",
+    tag = "products",
+    responses((status = 200, description = "The products", body = Vec<Product>))
+)]
+pub async fn list_products(State(pool): State<Pool<Sqlite>>) -> ApiResult<Json<Vec<Product>>> {
+    let products = sqlx::query_as::<_, Product>("SELECT * FROM products ORDER BY created_at DESC")
+        .fetch_all(&pool)
+        .await?;
+    Ok(Json(products))
+}
+
+/// A product with its variants
+#[utoipa::path(
+    get,
+    path = "/products/{id}",
+    tag = "products",
+    params(("id" = i64, Path, description = "Product id")),
+    responses(
+        (status = 200, description = "The product", body = ProductDetail),
+        (status = 404, description = "No such product", body = Problem),
+    )
+)]
+pub async fn get_product(
+    State(pool): State<Pool<Sqlite>>,
+    ApiPath(id): ApiPath<i64>,
+) -> ApiResult<Json<ProductDetail>> {
+    let product = fetch_product(&pool, id).await?;
+    let variants = sqlx::query_as::<_, ProductVariant>(
+        "SELECT * FROM product_variants WHERE product_id = ? ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(ProductDetail { product, variants }))
+}
+
+/// Create a product; images are only uploaded through the catalog pages.
+///
+/// Requires `catalog.manage`.
+#[utoipa::path(
+    post,
+    path = "/products",
+    tag = "products",
+    request_body = ProductForm,
+    responses(
+        (status = 201, description = "The new product", body = Product),
+        (status = 400, description = "Invalid fields", body = Problem),
+    )
+)]
+pub async fn create_product(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiJson(form): ApiJson<ProductForm>,
+) -> ApiResult<(StatusCode, Json<Product>)> {
+    let price = validate_product_form(&form)?;
+
+    let id = sqlx::query("INSERT INTO products (name, description, price, stock) VALUES (?, ?, ?, ?)")
+        .bind(&form.name)
+        .bind(&form.description)
+        .bind(price)
+        .bind(form.stock)
+        .execute(&pool)
+        .await?
+        .last_insert_rowid();
+
+    let product = fetch_product(&pool, id).await?;
+    record_audit(&pool, Some(user.id), AuditEntity::Product, id, None, Some(&product)).await?;
+
+    println!("📦 New product added via API: {}", form.name);
+
+    Ok((StatusCode::CREATED, Json(product)))
+}
+
+/// Update a product, keeping its image; the stock of a product with variants is left as their total.
+///
+/// Requires `catalog.manage`.
+#[utoipa::path(
+    put,
+    path = "/products/{id}",
+    tag = "products",
+    params(("id" = i64, Path, description = "Product id")),
+    request_body = ProductForm,
+    responses(
+        (status = 200, description = "The updated product", body = Product),
+        (status = 400, description = "Invalid fields", body = Problem),
+        (status = 404, description = "No such product", body = Problem),
+    )
+)]
+pub async fn update_product(
+    State(pool): State<Pool<Sqlite>>,
+    State(stock): State<StockSettings>,
+    Extension(user): Extension<User>,
+    ApiPath(id): ApiPath<i64>,
+    ApiJson(form): ApiJson<ProductForm>,
+) -> ApiResult<Json<Product>> {
+    let price = validate_product_form(&form)?;
+    let old = fetch_product(&pool, id).await?;
+
+    let mut tx = pool.begin().await?;
+    let new = sqlx::query_as::<_, Product>(
+        r#"
+        UPDATE products
+        SET name = ?, description = ?, price = ?,
+            stock = CASE
+                WHEN EXISTS (SELECT 1 FROM product_variants WHERE product_id = products.id) THEN stock
+                ELSE ?
+            END
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(&form.name)
+    .bind(&form.description)
+    .bind(price)
+    .bind(form.stock)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    record_audit(&mut *tx, Some(user.id), AuditEntity::Product, id, Some(&old), Some(&new)).await?;
+    queue_stock_low(&mut *tx, stock, old.stock, &new).await?;
+    tx.commit().await?;
+
+    println!("✏️ Product updated via API: {} (ID: {})", form.name, id);
+
+    Ok(Json(new))
+}
+
+/// Delete a product, its variants and its image; requires `catalog.manage`
+#[utoipa::path(
+    delete,
+    path = "/products/{id}",
+    tag = "products",
+    params(("id" = i64, Path, description = "Product id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such product", body = Problem),
+    )
+)]
+pub async fn delete_product(
+    State(pool): State<Pool<Sqlite>>,
+    State(media): State<MediaStore>,
+    Extension(user): Extension<User>,
+    ApiPath(id): ApiPath<i64>,
+) -> ApiResult<StatusCode> {
+    let product = delete_product_record(&pool, &media, id, Some(user.id)).await?;
+    println!("🗑️ Product deleted via API: {} (ID: {})", product.name, id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
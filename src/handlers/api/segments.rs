@@ -0,0 +1,68 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+use utoipa::IntoParams;
+
+use crate::{
+    error::{ApiResult, Problem},
+    handlers::{
+        api::{customers::fetch_customer_page, ApiPath, CustomerPage, Page},
+        segments::{fetch_segment, fetch_segments},
+    },
+    models::{CustomerQuery, CustomerSummary, Segment},
+};
+
+/// Sorting and paging of a segment's customers; the filters come from the segment
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct SegmentCustomersQuery {
+    /// `name`, `company`, `sales`, `city`, `job_title` or `balance`; newest first otherwise
+    pub sort: String,
+    /// `asc` or `desc`
+    pub dir: String,
+    pub page: String,
+    /// 10, 25, 50 or 100
+    pub per_page: String,
+}
+
+/// Saved customer segments, with the filters each one applies
+#[utoipa::path(
+    get,
+    path = "/segments",
+    tag = "segments",
+    responses((status = 200, description = "Every saved segment", body = [Segment]))
+)]
+pub async fn list_segments(State(pool): State<Pool<Sqlite>>) -> ApiResult<Json<Vec<Segment>>> {
+    Ok(Json(fetch_segments(&pool).await?))
+}
+
+/// The customers in a segment now, sorted and paged like the customer list
+#[utoipa::path(
+    get,
+    path = "/segments/{id}/customers",
+    tag = "segments",
+    params(("id" = i64, Path, description = "Segment id"), SegmentCustomersQuery),
+    responses(
+        (status = 200, description = "One page of the segment's customers", body = CustomerPage),
+        (status = 404, description = "No such segment", body = Problem),
+    )
+)]
+pub async fn list_segment_customers(
+    State(pool): State<Pool<Sqlite>>,
+    ApiPath(id): ApiPath<i64>,
+    Query(paging): Query<SegmentCustomersQuery>,
+) -> ApiResult<Json<Page<CustomerSummary>>> {
+    let segment = fetch_segment(&pool, id).await?;
+    let query = CustomerQuery {
+        sort: paging.sort,
+        dir: paging.dir,
+        page: paging.page,
+        per_page: paging.per_page,
+        ..segment.customer_query()
+    };
+    Ok(Json(fetch_customer_page(&pool, &query).await?))
+}
@@ -0,0 +1,139 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    error::{ApiResult, Problem},
+    handlers::{
+        api::{ApiJson, ApiPath},
+        customers::{fetch_customer, fetch_ledger},
+        transactions::{
+            create_transaction_record, delete_transaction_record, fetch_transaction,
+            update_transaction_record,
+        },
+    },
+    models::{LedgerEntry, Transaction, TransactionForm, User},
+};
+
+/// A customer's transactions, newest first, with the running balance after each
+#[utoipa::path(
+    get,
+    path = "/customers/{id}/transactions",
+    tag = "transactions",
+    params(("id" = i64, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The customer's ledger", body = Vec<LedgerEntry>),
+        (status = 404, description = "No such customer", body = Problem),
+    )
+)]
+pub async fn list_transactions(
+    State(pool): State<Pool<Sqlite>>,
+    ApiPath(customer_id): ApiPath<i64>,
+) -> ApiResult<Json<Vec<LedgerEntry>>> {
+    fetch_customer(&mut *pool.acquire().await?, customer_id).await?;
+    Ok(Json(fetch_ledger(&pool, customer_id).await?))
+}
+
+/// One of a customer's transactions
+#[utoipa::path(
+    get,
+    path = "/customers/{id}/transactions/{transaction_id}",
+    tag = "transactions",
+    params(
+        ("id" = i64, Path, description = "Customer id"),
+        ("transaction_id" = i64, Path, description = "Transaction id"),
+    ),
+    responses(
+        (status = 200, description = "The transaction", body = Transaction),
+        (status = 404, description = "No such transaction for this customer", body = Problem),
+    )
+)]
+pub async fn get_transaction(
+    State(pool): State<Pool<Sqlite>>,
+    ApiPath((customer_id, transaction_id)): ApiPath<(i64, i64)>,
+) -> ApiResult<Json<Transaction>> {
+    let transaction =
+        fetch_transaction(&mut *pool.acquire().await?, customer_id, transaction_id).await?;
+    Ok(Json(transaction))
+}
+
+/// Record a transaction; the amount is in tomans and the date is Shamsi, as on the form.
+///
+/// Requires `transactions.edit`.
+#[utoipa::path(
+    post,
+    path = "/customers/{id}/transactions",
+    tag = "transactions",
+    params(("id" = i64, Path, description = "Customer id")),
+    request_body = TransactionForm,
+    responses(
+        (status = 201, description = "The new transaction", body = Transaction),
+        (status = 400, description = "Invalid fields", body = Problem),
+        (status = 404, description = "No such customer", body = Problem),
+    )
+)]
+pub async fn create_transaction(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiPath(customer_id): ApiPath<i64>,
+    ApiJson(form): ApiJson<TransactionForm>,
+) -> ApiResult<(StatusCode, Json<Transaction>)> {
+    let transaction = create_transaction_record(&pool, customer_id, &form, Some(user.id)).await?;
+    println!("✅ New transaction recorded via API (ID: {})", transaction.id);
+
+    Ok((StatusCode::CREATED, Json(transaction)))
+}
+
+/// Update a transaction; requires `transactions.edit`
+#[utoipa::path(
+    put,
+    path = "/customers/{id}/transactions/{transaction_id}",
+    tag = "transactions",
+    params(
+        ("id" = i64, Path, description = "Customer id"),
+        ("transaction_id" = i64, Path, description = "Transaction id"),
+    ),
+    request_body = TransactionForm,
+    responses(
+        (status = 200, description = "The updated transaction", body = Transaction),
+        (status = 400, description = "Invalid fields", body = Problem),
+        (status = 404, description = "No such transaction for this customer", body = Problem),
+    )
+)]
+pub async fn update_transaction(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiPath((customer_id, transaction_id)): ApiPath<(i64, i64)>,
+    ApiJson(form): ApiJson<TransactionForm>,
+) -> ApiResult<Json<Transaction>> {
+    let transaction =
+        update_transaction_record(&pool, customer_id, transaction_id, &form, Some(user.id))
+            .await?;
+    println!("✏️ Updated transaction via API: (ID: {})", transaction_id);
+
+    Ok(Json(transaction))
+}
+
+/// Delete a transaction; requires `transactions.delete`
+#[utoipa::path(
+    delete,
+    path = "/customers/{id}/transactions/{transaction_id}",
+    tag = "transactions",
+    params(
+        ("id" = i64, Path, description = "Customer id"),
+        ("transaction_id" = i64, Path, description = "Transaction id"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such transaction for this customer", body = Problem),
+    )
+)]
+pub async fn delete_transaction(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(user): Extension<User>,
+    ApiPath((customer_id, transaction_id)): ApiPath<(i64, i64)>,
+) -> ApiResult<StatusCode> {
+    delete_transaction_record(&pool, customer_id, transaction_id, Some(user.id)).await?;
+    println!("🗑️ Deleted transaction via API, ID: {}", transaction_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,54 @@
+use axum::{extract::State, Extension, Json};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    error::{ApiResult, Problem},
+    handlers::{api::ApiPath, users::fetch_user},
+    models::{User, UserProfile},
+};
+
+/// The signed-in user, with the names of their permissions
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "users",
+    responses((status = 200, description = "The signed-in user", body = UserProfile))
+)]
+pub async fn show_me(Extension(user): Extension<User>) -> Json<UserProfile> {
+    Json(user.into())
+}
+
+/// All users, active ones first; requires `users.manage`
+#[utoipa::path(
+    get,
+    path = "/users",
+    tag = "users",
+    responses((status = 200, description = "The users", body = Vec<UserProfile>))
+)]
+pub async fn list_users(State(pool): State<Pool<Sqlite>>) -> ApiResult<Json<Vec<UserProfile>>> {
+    let users = sqlx::query_as::<_, User>(
+        "SELECT * FROM users ORDER BY is_active DESC, created_at DESC",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(users.into_iter().map(UserProfile::from).collect()))
+}
+
+/// One user; requires `users.manage`
+#[utoipa::path(
+    get,
+    path = "/users/{id}",
+    tag = "users",
+    params(("id" = i64, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user", body = UserProfile),
+        (status = 404, description = "No such user", body = Problem),
+    )
+)]
+pub async fn get_user(
+    State(pool): State<Pool<Sqlite>>,
+    ApiPath(id): ApiPath<i64>,
+) -> ApiResult<Json<UserProfile>> {
+    Ok(Json(fetch_user(&pool, id).await?.into()))
+}
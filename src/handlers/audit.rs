@@ -0,0 +1,81 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::CookieJar;
+use sqlx::{Pool, QueryBuilder, Sqlite};
+
+use crate::{
+    error::AppResult,
+    middleware::{csrf::CsrfToken, permissions::require_permission},
+    models::{audit::AUDIT_PAGE_SIZE, AuditEntry, AuditQuery, Permission, User},
+    templates::audit::AuditTemplate,
+};
+
+/// Recorded changes, newest first, filtered by record, user and action (needs audit.view)
+pub async fn list_audit(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<AuditQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::AuditView).await?;
+
+    let mut count_query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM audit_log WHERE 1 = 1");
+    push_audit_filters(&mut count_query, &query);
+    let total: i64 = count_query.build_query_scalar().fetch_one(&pool).await?;
+    let total_pages = ((total + AUDIT_PAGE_SIZE - 1) / AUDIT_PAGE_SIZE).max(1);
+
+    let mut select_query = QueryBuilder::<Sqlite>::new(
+        "SELECT audit_log.*, users.username
+         FROM audit_log
+         LEFT JOIN users ON users.id = audit_log.user_id
+         WHERE 1 = 1",
+    );
+    push_audit_filters(&mut select_query, &query);
+    select_query
+        .push(" ORDER BY audit_log.id DESC LIMIT ")
+        .push_bind(AUDIT_PAGE_SIZE)
+        .push(" OFFSET ")
+        .push_bind(query.offset());
+    let entries = select_query
+        .build_query_as::<AuditEntry>()
+        .fetch_all(&pool)
+        .await?;
+
+    let users = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY username")
+        .fetch_all(&pool)
+        .await?;
+
+    let template = AuditTemplate {
+        entries,
+        total,
+        page: query.page(),
+        total_pages,
+        query,
+        users,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "audit",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+fn push_audit_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &AuditQuery) {
+    if let Some(entity) = query.entity_filter() {
+        builder
+            .push(" AND audit_log.entity_type = ")
+            .push_bind(entity.as_str());
+    }
+    if let Some(entity_id) = query.entity_id_filter() {
+        builder.push(" AND audit_log.entity_id = ").push_bind(entity_id);
+    }
+    if let Some(user_id) = query.user_id_filter() {
+        builder.push(" AND audit_log.user_id = ").push_bind(user_id);
+    }
+    if let Some(action) = query.action_filter() {
+        builder.push(" AND audit_log.action = ").push_bind(action.as_str());
+    }
+}
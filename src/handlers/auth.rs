@@ -1,7 +1,10 @@
+use std::net::SocketAddr;
+
 use askama::Template;
 use axum::{
-    extract::State,
-    response::{Html, IntoResponse, Redirect},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
     Form,
 };
 use axum_extra::extract::{
@@ -10,10 +13,18 @@ use axum_extra::extract::{
 };
 use sqlx::{Pool, Sqlite};
 
+use chrono::{DateTime, Utc};
+
 use crate::{
-    models::{LoginForm, Session, User},
+    error::AppResult,
+    handlers::flash_cookie,
+    middleware::{
+        auth::{session_cookie, CHANGE_PASSWORD_PATH},
+        csrf::{login_csrf_token, LOGIN_CSRF_COOKIE},
+    },
+    models::{LoginFailure, LoginForm, LoginLimits, LoginScope, Session, SessionSettings, User},
     templates::auth::LoginTemplate,
-    utils::password::verify_password,
+    utils::{localization::to_persian_digits, password::verify_password},
 };
 
 /// Show login page
@@ -37,9 +48,12 @@ pub async fn show_login(jar: CookieJar) -> impl IntoResponse {
         jar
     };
 
+    let (jar, csrf_token) = login_csrf_token(jar);
+
     let mut template = LoginTemplate {
         error: None,
         flash_message,
+        csrf_token,
     };
 
     // Add help message if login failed
@@ -50,12 +64,34 @@ pub async fn show_login(jar: CookieJar) -> impl IntoResponse {
     (jar, Html(template.render().unwrap())).into_response()
 }
 
-/// Handle login form submission
+/// Handle login form submission.
+///
+/// Failed attempts are counted per username and per client address; each one makes the
+/// next attempt wait longer, and enough of them lock the login out for a while.
 pub async fn do_login(
     State(pool): State<Pool<Sqlite>>,
+    State(limits): State<LoginLimits>,
+    State(sessions): State<SessionSettings>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     jar: CookieJar,
     Form(form): Form<LoginForm>,
-) -> Result<(CookieJar, Redirect), (CookieJar, impl IntoResponse)> {
+) -> AppResult<Response> {
+    let username = form.username.trim().to_lowercase();
+    let ip = client_ip(&headers, connect_info.map(|ConnectInfo(addr)| addr), &limits);
+    let keys = [(LoginScope::Username, username.as_str()), (LoginScope::Ip, ip.as_str())];
+
+    // Refuse before checking the password, so throttled attempts cost no bcrypt work
+    let now = Utc::now();
+    for (scope, key) in keys {
+        let Some(failure) = fetch_failure(&pool, scope, key).await? else {
+            continue;
+        };
+        if let Some(retry_at) = failure.retry_at(now, &limits) {
+            return throttled_response(jar, &failure, retry_at, now);
+        }
+    }
+
     // Find user by username
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
         .bind(&form.username)
@@ -66,45 +102,84 @@ pub async fn do_login(
     if let Some(user) = user {
         // Verify password
         if verify_password(&form.password, &user.password_hash).unwrap_or(false) {
+            // The password was right, so this is not guessing; do not count it as a failure
+            if !user.is_active {
+                println!("⛔ Login refused for deactivated user: {}", user.username);
+                let (jar, csrf_token) = login_csrf_token(jar);
+                let template = LoginTemplate {
+                    error: Some(
+                        "حساب کاربری شما غیرفعال شده است. با مدیر سیستم تماس بگیرید.".to_string(),
+                    ),
+                    flash_message: None,
+                    csrf_token,
+                };
+                return Ok((StatusCode::FORBIDDEN, jar, Html(template.render()?)).into_response());
+            }
+
+            // The address keeps its count, so one valid account cannot cover guessing at others
+            sqlx::query("DELETE FROM login_failures WHERE scope = ? AND key = ?")
+                .bind(LoginScope::Username.as_str())
+                .bind(&username)
+                .execute(&pool)
+                .await?;
+
             // Create session
             let session_id = Session::generate_id();
-            let expires_at = Session::generate_expiry();
+            let now = Utc::now();
+            let expires_at = sessions.expiry(form.remember_me, now);
+            let user_agent = headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
 
-            sqlx::query("INSERT INTO sessions (id, user_id, expires_at) VALUES (?, ?, ?)")
-                .bind(&session_id)
-                .bind(user.id)
-                .bind(&expires_at)
-                .execute(&pool)
-                .await
-                .unwrap();
-
-            // Set session cookie
-            let cookie = Cookie::build(("session_id", session_id))
-                .path("/")
-                .same_site(SameSite::Lax)
-                .http_only(true)
-                .build();
-
-            // Set welcome flash message
-            let flash_cookie =
-                Cookie::build(("flash_message", format!("خوش آمدید، {} 👋", user.full_name)))
-                    .path("/")
-                    .same_site(SameSite::Lax)
-                    .http_only(true)
-                    .max_age(cookie::time::Duration::seconds(60))
-                    .build();
-
-            let jar = jar.add(cookie).add(flash_cookie);
+            sqlx::query(
+                "INSERT INTO sessions (id, user_id, expires_at, csrf_token, remember_me,
+                     public_id, ip_address, user_agent, created_at, last_seen_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&session_id)
+            .bind(user.id)
+            .bind(expires_at.to_rfc3339())
+            .bind(Session::generate_csrf_token())
+            .bind(form.remember_me)
+            .bind(Session::generate_public_id())
+            .bind(&ip)
+            .bind(user_agent)
+            .bind(now.to_rfc3339())
+            .bind(now.to_rfc3339())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+            // A remembered session survives closing the browser; others end with it
+            let cookie = session_cookie(
+                session_id,
+                form.remember_me.then(|| sessions.lifetime(true)),
+            );
+
+            // The session has its own token from here on
+            let jar = jar
+                .add(cookie)
+                .add(flash_cookie(format!("خوش آمدید، {} 👋", user.full_name)))
+                .remove(Cookie::build(LOGIN_CSRF_COOKIE).path("/login"));
 
             println!("✅ User logged in: {}", user.username);
-            return Ok((jar, Redirect::to("/")));
+            // A temporary password has to be replaced before anything else
+            let landing = if user.must_change_password { CHANGE_PASSWORD_PATH } else { "/" };
+            return Ok((jar, Redirect::to(landing)).into_response());
         }
     }
 
     // Login failed
+    for (scope, key) in keys {
+        record_failure(&pool, &limits, scope, key).await?;
+    }
+
+    let (jar, csrf_token) = login_csrf_token(jar);
     let template = LoginTemplate {
         error: Some("نام کاربری یا رمز عبور اشتباه است".to_string()),
         flash_message: None,
+        csrf_token,
     };
 
     // Add helpful cookie with suggestions
@@ -117,7 +192,7 @@ pub async fn do_login(
 
     let jar = jar.add(help_cookie);
 
-    Err((jar, Html(template.render().unwrap())))
+    Ok((jar, Html(template.render()?)).into_response())
 }
 
 /// Handle logout
@@ -131,16 +206,303 @@ pub async fn logout(State(pool): State<Pool<Sqlite>>, jar: CookieJar) -> (Cookie
             .ok();
     }
 
-    // Set logout flash message
-    let flash_cookie = Cookie::build(("flash_message", "با موفقیت از سیستم خارج شدید 👋"))
-        .path("/")
-        .same_site(SameSite::Lax)
-        .http_only(true)
-        .max_age(cookie::time::Duration::seconds(60))
-        .build();
-
     // Remove session cookie and add flash message
-    let jar = jar.remove(Cookie::from("session_id")).add(flash_cookie);
+    let jar = jar
+        .remove(Cookie::from("session_id"))
+        .add(flash_cookie("با موفقیت از سیستم خارج شدید 👋"));
 
     (jar, Redirect::to("/login"))
 }
+
+/// The address a login attempt came from
+fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>, limits: &LoginLimits) -> String {
+    if limits.trusted_proxy_hops > 0 {
+        // Each proxy appends the address it saw, so only the entries our own proxies added
+        // can be trusted; the client can put anything it likes before them
+        let entries: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        let index = entries.len().saturating_sub(limits.trusted_proxy_hops);
+        if let Some(forwarded) = entries.get(index).filter(|value| !value.is_empty()) {
+            return forwarded.to_string();
+        }
+    }
+
+    peer.map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+async fn fetch_failure(
+    pool: &Pool<Sqlite>,
+    scope: LoginScope,
+    key: &str,
+) -> AppResult<Option<LoginFailure>> {
+    Ok(
+        sqlx::query_as::<_, LoginFailure>(
+            "SELECT * FROM login_failures WHERE scope = ? AND key = ?",
+        )
+        .bind(scope.as_str())
+        .bind(key)
+        .fetch_optional(pool)
+        .await?,
+    )
+}
+
+/// Count a failed attempt, locking the username or address once it reaches its limit
+async fn record_failure(
+    pool: &Pool<Sqlite>,
+    limits: &LoginLimits,
+    scope: LoginScope,
+    key: &str,
+) -> AppResult<()> {
+    let now = Utc::now();
+    let forgotten_before = (now - limits.lockout()).to_rfc3339();
+
+    // Counted in the one statement, so failures arriving together cannot overwrite each
+    // other's count. It starts over once a lockout has ended or the last failure is old.
+    let failures: i64 = sqlx::query_scalar(
+        "INSERT INTO login_failures (scope, key, failures, last_failure_at)
+         VALUES (?, ?, 1, ?)
+         ON CONFLICT(scope, key) DO UPDATE SET
+             failures = CASE
+                 WHEN COALESCE(locked_until <= excluded.last_failure_at, last_failure_at <= ?)
+                 THEN 1 ELSE failures + 1
+             END,
+             locked_until = CASE
+                 WHEN COALESCE(locked_until <= excluded.last_failure_at, last_failure_at <= ?)
+                 THEN NULL ELSE locked_until
+             END,
+             last_failure_at = excluded.last_failure_at
+         RETURNING failures",
+    )
+    .bind(scope.as_str())
+    .bind(key)
+    .bind(now.to_rfc3339())
+    .bind(&forgotten_before)
+    .bind(&forgotten_before)
+    .fetch_one(pool)
+    .await?;
+
+    let locked_until =
+        (failures >= limits.max_failures(scope)).then(|| (now + limits.lockout()).to_rfc3339());
+
+    if let Some(locked_until) = locked_until {
+        sqlx::query("UPDATE login_failures SET locked_until = ? WHERE scope = ? AND key = ?")
+            .bind(&locked_until)
+            .bind(scope.as_str())
+            .bind(key)
+            .execute(pool)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO lockout_events (scope, key, event, locked_until) VALUES (?, ?, 'locked', ?)",
+        )
+        .bind(scope.as_str())
+        .bind(key)
+        .bind(&locked_until)
+        .execute(pool)
+        .await?;
+
+        println!(
+            "🔒 Login locked for {} {} after {} failed attempts",
+            scope.as_str(),
+            key,
+            failures
+        );
+    }
+
+    Ok(())
+}
+
+/// The login page with a message saying how long to wait
+fn throttled_response(
+    jar: CookieJar,
+    failure: &LoginFailure,
+    retry_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> AppResult<Response> {
+    let seconds = (retry_at - now).num_seconds().max(1);
+    let error = if failure.is_locked(now) {
+        let minutes = (seconds + 59) / 60;
+        format!(
+            "به دلیل تلاش‌های ناموفق پی‌درپی، ورود به‌طور موقت قفل شده است. {} دقیقه دیگر دوباره تلاش کنید یا با مدیر سیستم تماس بگیرید.",
+            to_persian_digits(&minutes.to_string())
+        )
+    } else {
+        format!(
+            "تلاش‌های ناموفق زیادی انجام شده است. لطفاً {} ثانیه دیگر دوباره تلاش کنید.",
+            to_persian_digits(&seconds.to_string())
+        )
+    };
+
+    let (jar, csrf_token) = login_csrf_token(jar);
+    let template = LoginTemplate {
+        error: Some(error),
+        flash_message: None,
+        csrf_token,
+    };
+
+    Ok((StatusCode::TOO_MANY_REQUESTS, jar, Html(template.render()?)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    fn limits(trusted_proxy_hops: usize) -> LoginLimits {
+        LoginLimits {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 20,
+            lockout_minutes: 15,
+            backoff_base_seconds: 1,
+            trusted_proxy_hops,
+        }
+    }
+
+    #[test]
+    fn test_client_ip_ignores_client_supplied_forwarded_for() {
+        let peer = Some(SocketAddr::from(([10, 0, 0, 2], 41000)));
+        let mut headers = HeaderMap::new();
+
+        // The client made up the first entry; the proxy added the real address after it
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("198.51.100.66, 203.0.113.7"),
+        );
+        assert_eq!(client_ip(&headers, peer, &limits(1)), "203.0.113.7");
+        // Not behind a proxy, the header is not trusted at all
+        assert_eq!(client_ip(&headers, peer, &limits(0)), "10.0.0.2");
+
+        // Behind two proxies, the outer one saw the client
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("198.51.100.66, 203.0.113.7, 10.0.0.9"),
+        );
+        assert_eq!(client_ip(&headers, peer, &limits(2)), "203.0.113.7");
+
+        // Proxies may also add a header of their own rather than extend the first
+        headers.insert("x-forwarded-for", HeaderValue::from_static("198.51.100.66"));
+        headers.append("x-forwarded-for", HeaderValue::from_static("203.0.113.7"));
+        assert_eq!(client_ip(&headers, peer, &limits(1)), "203.0.113.7");
+
+        assert_eq!(client_ip(&HeaderMap::new(), peer, &limits(1)), "10.0.0.2");
+        assert_eq!(client_ip(&HeaderMap::new(), None, &limits(1)), "unknown");
+    }
+
+    async fn login(pool: &Pool<Sqlite>, username: &str) -> Response {
+        let sessions = SessionSettings {
+            idle_timeout_hours: 8,
+            remember_me_days: 30,
+        };
+        let form = LoginForm {
+            username: username.to_string(),
+            password: "pass-1234".to_string(),
+            remember_me: false,
+        };
+        do_login(
+            State(pool.clone()),
+            State(limits(0)),
+            State(sessions),
+            None,
+            HeaderMap::new(),
+            CookieJar::new(),
+            Form(form),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_deactivated_user_cannot_log_in() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        let hash = bcrypt::hash("pass-1234", 4).unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role, is_active)
+             VALUES ('ali', ?, 'علی', 'user', 1), ('sara', ?, 'سارا', 'user', 0)",
+        )
+        .bind(&hash)
+        .bind(&hash)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let session_count = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM sessions")
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+
+        // The right password is not counted as a failed attempt either
+        assert_eq!(login(&pool, "sara").await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(session_count().await, 0);
+        let failures: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM login_failures")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(failures, 0);
+
+        assert_eq!(login(&pool, "ali").await.status(), StatusCode::SEE_OTHER);
+        assert_eq!(session_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_failures_are_all_counted() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        let limits = limits(0);
+
+        // Each attempt waits for the one connection in between its statements, so any
+        // count read and written back separately would be lost to the others
+        let mut attempts = tokio::task::JoinSet::new();
+        for _ in 0..limits.max_failures_per_username {
+            let pool = pool.clone();
+            let limits = limits.clone();
+            attempts.spawn(async move {
+                record_failure(&pool, &limits, LoginScope::Username, "ali").await
+            });
+        }
+        while let Some(result) = attempts.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        let failure = fetch_failure(&pool, LoginScope::Username, "ali")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failure.failures, limits.max_failures_per_username);
+        assert!(failure.is_locked(Utc::now()));
+
+        // Once the lockout has ended, the count starts over
+        sqlx::query("UPDATE login_failures SET locked_until = ?")
+            .bind((Utc::now() - chrono::Duration::seconds(1)).to_rfc3339())
+            .execute(&pool)
+            .await
+            .unwrap();
+        record_failure(&pool, &limits, LoginScope::Username, "ali")
+            .await
+            .unwrap();
+        let failure = fetch_failure(&pool, LoginScope::Username, "ali")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failure.failures, 1);
+        assert!(!failure.is_locked(Utc::now()));
+    }
+}
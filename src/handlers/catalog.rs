@@ -1,26 +1,36 @@
 use askama::Template;
 use axum::{
-    extract::{Path, State},
+    extract::{multipart::MultipartError, Multipart, Path, State},
+    http::StatusCode,
     response::{Html, IntoResponse, Redirect},
     Form,
 };
-use axum_extra::extract::{
-    cookie::{Cookie, SameSite},
-    CookieJar,
-};
-use sqlx::{Pool, Sqlite};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Executor, Pool, Sqlite, SqliteConnection};
 
 use crate::{
+    db::{audit::record_audit, webhooks::queue_stock_low},
     error::{AppError, AppResult},
-    middleware::auth::get_current_user,
-    models::{Product, ProductForm},
-    templates::catalog::{AddProductTemplate, CatalogTemplate, ProductDetailTemplate, EditProductTemplate},
+    handlers::flash_cookie,
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        AuditEntity, Money, Product, ProductForm, ProductVariant, ProductVariantForm, StockSettings,
+    },
+    templates::catalog::{
+        AddProductTemplate, AddVariantTemplate, CatalogTemplate, EditProductTemplate,
+        EditVariantTemplate, ProductDetailTemplate,
+    },
+    utils::{
+        localization::persian_to_english_numbers,
+        media::{image_too_large, ImageUpload, MediaStore},
+    },
 };
 
 /// Show the product catalog
 pub async fn show_catalog(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
 
@@ -42,6 +52,7 @@ pub async fn show_catalog(
     let template = CatalogTemplate {
         products,
         current_user,
+        csrf_token,
         active_page: "catalog",
         flash_message,
     };
@@ -53,10 +64,12 @@ pub async fn show_catalog(
 pub async fn show_add_product_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
     let template = AddProductTemplate {
         current_user,
+        csrf_token,
         active_page: "catalog",
     };
     Ok(Html(template.render()?))
@@ -65,32 +78,59 @@ pub async fn show_add_product_form(
 /// Handle the submission of the new product form
 pub async fn add_product(
     State(pool): State<Pool<Sqlite>>,
+    State(media): State<MediaStore>,
     jar: CookieJar,
-    Form(form): Form<ProductForm>,
+    multipart: Multipart,
 ) -> AppResult<impl IntoResponse> {
+    let (form, image) = read_product_form(multipart).await?;
+    let price = validate_product_form(&form)?;
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let image_url = match image {
+        Some(image) => Some(media.save_product_image(image).await?),
+        None => None,
+    };
+
     // Insert the new product into the database
-    sqlx::query("INSERT INTO products (name, description, price, stock) VALUES (?, ?, ?, ?)")
-        .bind(&form.name)
-        .bind(&form.description)
-        .bind(form.price)
-        .bind(form.stock)
-        .execute(&pool)
-        .await?;
+    let result = sqlx::query(
+        "INSERT INTO products (name, description, price, stock, image_url) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(&form.name)
+    .bind(&form.description)
+    .bind(price)
+    .bind(form.stock)
+    .bind(&image_url)
+    .execute(&pool)
+    .await;
+
+    let id = match result {
+        Ok(done) => done.last_insert_rowid(),
+        Err(e) => {
+            if let Some(url) = &image_url {
+                media.delete_image(url).await;
+            }
+            return Err(e.into());
+        }
+    };
+
+    let product = fetch_product(&pool, id).await?;
+    record_audit(
+        &pool,
+        current_user.map(|user| user.id),
+        AuditEntity::Product,
+        id,
+        None,
+        Some(&product),
+    )
+    .await?;
 
     println!("📦 New product added to database: {}", form.name);
 
     // Set a flash message to confirm the action
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("محصول «{}» با موفقیت اضافه شد ✅", form.name),
-    ))
-    .path("/")
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
-
-    let jar = jar.add(flash_cookie);
+    let jar = jar.add(flash_cookie(format!(
+        "محصول «{}» با موفقیت اضافه شد ✅",
+        form.name
+    )));
 
     Ok((jar, Redirect::to("/catalog")))
 }
@@ -98,43 +138,56 @@ pub async fn add_product(
 pub async fn view_product(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path(id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
 
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
     // Fetch the specific product from the database by its ID
-    let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
-        .await?
-        // If the product is not found, return a NotFound error
-        .ok_or(AppError::NotFound)?;
+    let product = fetch_product(&pool, id).await?;
+
+    let variants = sqlx::query_as::<_, ProductVariant>(
+        "SELECT * FROM product_variants WHERE product_id = ? ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(&pool)
+    .await?;
 
     let template = ProductDetailTemplate {
         product,
+        variants,
         current_user,
+        csrf_token,
         active_page: "catalog",
+        flash_message,
     };
 
-    Ok(Html(template.render()?))
+    Ok((jar, Html(template.render()?)))
 }
 
 pub async fn show_edit_product_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path(id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
 
-    let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
-        .await?
-        .ok_or(AppError::NotFound)?;
+    let product = fetch_product(&pool, id).await?;
+    let has_variants = has_variants(&pool, id).await?;
 
     let template = EditProductTemplate {
         product,
+        has_variants,
         current_user,
+        csrf_token,
         active_page: "catalog",
     };
 
@@ -143,84 +196,463 @@ pub async fn show_edit_product_form(
 
 pub async fn update_product(
     State(pool): State<Pool<Sqlite>>,
+    State(media): State<MediaStore>,
+    State(stock): State<StockSettings>,
     jar: CookieJar,
     Path(id): Path<i64>,
-    Form(form): Form<ProductForm>,
+    multipart: Multipart,
 ) -> AppResult<impl IntoResponse> {
-    if form.name.trim().is_empty() {
-        return Err(AppError::BadRequest("نام محصول نمی‌تواند خالی باشد".to_string()));
-    }
-    if form.price < 0.0 {
-        return Err(AppError::BadRequest("قیمت نمی‌تواند منفی باشد".to_string()));
+    let (form, image) = read_product_form(multipart).await?;
+    let price = validate_product_form(&form)?;
+
+    let current_user = get_current_user(&pool, &jar).await;
+    let old = fetch_product(&pool, id).await?;
+    let old_image_url = old.image_url.clone();
+    let image_url = match image {
+        Some(image) => Some(media.save_product_image(image).await?),
+        None if form.remove_image => None,
+        None => old_image_url.clone(),
+    };
+
+    let result: AppResult<Product> = async {
+        let mut tx = pool.begin().await?;
+
+        // A product with variants keeps the stock computed from them
+        let new = sqlx::query_as::<_, Product>(
+            r#"
+            UPDATE products
+            SET name = ?, description = ?, price = ?, image_url = ?,
+                stock = CASE
+                    WHEN EXISTS (SELECT 1 FROM product_variants WHERE product_id = products.id) THEN stock
+                    ELSE ?
+                END
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&form.name)
+        .bind(&form.description)
+        .bind(price)
+        .bind(&image_url)
+        .bind(form.stock)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+        record_audit(
+            &mut *tx,
+            current_user.map(|user| user.id),
+            AuditEntity::Product,
+            id,
+            Some(&old),
+            Some(&new),
+        )
+        .await?;
+        queue_stock_low(&mut *tx, stock, old.stock, &new).await?;
+
+        tx.commit().await?;
+        Ok(new)
     }
-    if form.stock < 0 {
-        return Err(AppError::BadRequest("موجودی انبار نمی‌تواند منفی باشد".to_string()));
+    .await;
+
+    // Keep whichever image the product row ends up pointing at, so a product deleted
+    // meanwhile does not leave the new upload behind
+    let unused_image = match result {
+        Ok(_) => old_image_url.filter(|old| image_url.as_ref() != Some(old)),
+        Err(_) => image_url.filter(|new| old_image_url.as_ref() != Some(new)),
+    };
+    if let Some(url) = unused_image {
+        media.delete_image(&url).await;
     }
+    result?;
+
+    println!("✏️ Product updated in database: {} (ID: {})", form.name, id);
+
+    let jar = jar.add(flash_cookie(format!(
+        "محصول «{}» با موفقیت به‌روزرسانی شد ✏️",
+        form.name
+    )));
+
+    Ok((jar, Redirect::to("/catalog")))
+}
+
+pub async fn delete_product(
+    State(pool): State<Pool<Sqlite>>,
+    State(media): State<MediaStore>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let product =
+        delete_product_record(&pool, &media, id, current_user.map(|user| user.id)).await?;
+
+    let product_name = product.name;
+
+    println!("🗑️ Product deleted from database: {} (ID: {})", product_name, id);
+
+    // Set a flash message to confirm the deletion
+    let jar = jar.add(flash_cookie(format!(
+        "محصول «{}» با موفقیت حذف شد 🗑️",
+        product_name
+    )));
+
+    Ok((jar, Redirect::to("/catalog")))
+}
+
+/// Show the form to add a variant to a product
+pub async fn show_add_variant_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(product_id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let product = fetch_product(&pool, product_id).await?;
+
+    let template = AddVariantTemplate {
+        product,
+        current_user,
+        csrf_token,
+        active_page: "catalog",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// Handle the submission of the new variant form
+pub async fn add_variant(
+    State(pool): State<Pool<Sqlite>>,
+    State(stock): State<StockSettings>,
+    jar: CookieJar,
+    Path(product_id): Path<i64>,
+    Form(form): Form<ProductVariantForm>,
+) -> AppResult<impl IntoResponse> {
+    let product = fetch_product(&pool, product_id).await?;
+    let price = validate_variant_form(&form)?;
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let mut tx = pool.begin().await?;
+
+    let variant_id = sqlx::query(
+        "INSERT INTO product_variants (product_id, variant_name, description, price, stock) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(product_id)
+    .bind(form.variant_name.trim())
+    .bind(&form.description)
+    .bind(price)
+    .bind(form.stock)
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    let variant = fetch_variant(&mut *tx, product_id, variant_id).await?;
+    record_audit(
+        &mut *tx,
+        current_user.map(|user| user.id),
+        AuditEntity::Variant,
+        variant_id,
+        None,
+        Some(&variant),
+    )
+    .await?;
+
+    refresh_product_stock(&mut tx, stock, product_id).await?;
+    tx.commit().await?;
+
+    println!(
+        "📦 New variant added to product {}: {}",
+        product.name, form.variant_name
+    );
+
+    let jar = jar.add(flash_cookie(format!(
+        "نوع «{}» با موفقیت اضافه شد ✅",
+        form.variant_name.trim()
+    )));
+
+    Ok((jar, Redirect::to(&format!("/catalog/product/{}", product_id))))
+}
+
+/// Show the form to edit one of a product's variants
+pub async fn show_edit_variant_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path((product_id, variant_id)): Path<(i64, i64)>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let product = fetch_product(&pool, product_id).await?;
+    let variant = fetch_variant(&pool, product_id, variant_id).await?;
+
+    let template = EditVariantTemplate {
+        product,
+        variant,
+        current_user,
+        csrf_token,
+        active_page: "catalog",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// Handle the submission of the edit variant form
+pub async fn update_variant(
+    State(pool): State<Pool<Sqlite>>,
+    State(stock): State<StockSettings>,
+    jar: CookieJar,
+    Path((product_id, variant_id)): Path<(i64, i64)>,
+    Form(form): Form<ProductVariantForm>,
+) -> AppResult<impl IntoResponse> {
+    let old = fetch_variant(&pool, product_id, variant_id).await?;
+    let price = validate_variant_form(&form)?;
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let mut tx = pool.begin().await?;
 
     sqlx::query(
-        "UPDATE products SET name = ?, description = ?, price = ?, stock = ? WHERE id = ?",
+        "UPDATE product_variants SET variant_name = ?, description = ?, price = ?, stock = ? WHERE id = ? AND product_id = ?",
     )
-    .bind(&form.name)
+    .bind(form.variant_name.trim())
     .bind(&form.description)
-    .bind(form.price)
+    .bind(price)
     .bind(form.stock)
-    .bind(id)
-    .execute(&pool)
+    .bind(variant_id)
+    .bind(product_id)
+    .execute(&mut *tx)
     .await?;
 
-    println!("✏️ Product updated in database: {} (ID: {})", form.name, id);
+    let new = fetch_variant(&mut *tx, product_id, variant_id).await?;
+    record_audit(
+        &mut *tx,
+        current_user.map(|user| user.id),
+        AuditEntity::Variant,
+        variant_id,
+        Some(&old),
+        Some(&new),
+    )
+    .await?;
 
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("محصول «{}» با موفقیت به‌روزرسانی شد ✏️", form.name),
-    ))
-    .path("/")
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
-    
-    let jar = jar.add(flash_cookie);
+    refresh_product_stock(&mut tx, stock, product_id).await?;
+    tx.commit().await?;
 
-    Ok((jar, Redirect::to("/catalog")))
+    println!(
+        "✏️ Variant updated in database: {} (ID: {})",
+        form.variant_name, variant_id
+    );
+
+    let jar = jar.add(flash_cookie(format!(
+        "نوع «{}» با موفقیت به‌روزرسانی شد ✏️",
+        form.variant_name.trim()
+    )));
+
+    Ok((jar, Redirect::to(&format!("/catalog/product/{}", product_id))))
 }
 
-pub async fn delete_product(
+pub async fn delete_variant(
     State(pool): State<Pool<Sqlite>>,
+    State(stock): State<StockSettings>,
     jar: CookieJar,
-    Path(id): Path<i64>,
+    Path((product_id, variant_id)): Path<(i64, i64)>,
 ) -> AppResult<impl IntoResponse> {
-    let product = sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
+    let variant = fetch_variant(&pool, product_id, variant_id).await?;
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM product_variants WHERE id = ? AND product_id = ?")
+        .bind(variant_id)
+        .bind(product_id)
+        .execute(&mut *tx)
         .await?;
 
-    let product_name = product.map(|p| p.name).unwrap_or_else(|| "محصول".to_string());
+    record_audit(
+        &mut *tx,
+        current_user.map(|user| user.id),
+        AuditEntity::Variant,
+        variant_id,
+        Some(&variant),
+        None,
+    )
+    .await?;
+
+    refresh_product_stock(&mut tx, stock, product_id).await?;
+    tx.commit().await?;
+
+    println!(
+        "🗑️ Variant deleted from database: {} (ID: {})",
+        variant.variant_name, variant_id
+    );
+
+    let jar = jar.add(flash_cookie(format!(
+        "نوع «{}» با موفقیت حذف شد 🗑️",
+        variant.variant_name
+    )));
+
+    Ok((jar, Redirect::to(&format!("/catalog/product/{}", product_id))))
+}
+
+/// Read the multipart product form, returning the fields and the chosen image, if any
+async fn read_product_form(
+    mut multipart: Multipart,
+) -> AppResult<(ProductForm, Option<ImageUpload>)> {
+    let mut form = ProductForm::default();
+    let mut image = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+
+        if name == "image" {
+            let content_type = field.content_type().unwrap_or_default().to_string();
+            let bytes = field.bytes().await.map_err(multipart_error)?;
+            // Browsers send an empty part when no file was chosen
+            if !bytes.is_empty() {
+                image = Some(ImageUpload {
+                    content_type,
+                    bytes,
+                });
+            }
+            continue;
+        }
+
+        let value = field.text().await.map_err(multipart_error)?;
+        match name.as_str() {
+            "name" => form.name = value,
+            "description" => form.description = value,
+            "price" => form.price = value,
+            "stock" => {
+                form.stock = persian_to_english_numbers(value.trim())
+                    .parse()
+                    .map_err(|_| AppError::BadRequest("موجودی انبار معتبر نیست".to_string()))?
+            }
+            "remove_image" => form.remove_image = true,
+            _ => {}
+        }
+    }
+
+    Ok((form, image))
+}
+
+fn multipart_error(err: MultipartError) -> AppError {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        image_too_large()
+    } else {
+        AppError::BadRequest("فرم ارسال‌شده معتبر نیست".to_string())
+    }
+}
+
+pub async fn fetch_product(pool: &Pool<Sqlite>, id: i64) -> AppResult<Product> {
+    sqlx::query_as::<_, Product>("SELECT * FROM products WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+/// Delete a product and its image, recording it in the audit log, and return it as it was
+pub async fn delete_product_record(
+    pool: &Pool<Sqlite>,
+    media: &MediaStore,
+    id: i64,
+    user_id: Option<i64>,
+) -> AppResult<Product> {
+    let product = fetch_product(pool, id).await?;
 
     let result = sqlx::query("DELETE FROM products WHERE id = ?")
         .bind(id)
-        .execute(&pool)
+        .execute(pool)
         .await?;
-    
+
     // If no rows were affected, it means the product was already deleted.
     if result.rows_affected() == 0 {
         return Err(AppError::NotFound);
     }
 
-    println!("🗑️ Product deleted from database: {} (ID: {})", product_name, id);
+    record_audit(pool, user_id, AuditEntity::Product, id, Some(&product), None).await?;
 
-    // Set a flash message to confirm the deletion
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("محصول «{}» با موفقیت حذف شد 🗑️", product_name),
-    ))
-    .path("/")
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
-
-    let jar = jar.add(flash_cookie);
+    if let Some(url) = &product.image_url {
+        media.delete_image(url).await;
+    }
 
-    Ok((jar, Redirect::to("/catalog")))
-}
\ No newline at end of file
+    Ok(product)
+}
+
+/// Fetch a variant, making sure it belongs to the product in the URL
+async fn fetch_variant<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    product_id: i64,
+    variant_id: i64,
+) -> AppResult<ProductVariant> {
+    sqlx::query_as::<_, ProductVariant>(
+        "SELECT * FROM product_variants WHERE id = ? AND product_id = ?",
+    )
+    .bind(variant_id)
+    .bind(product_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or(AppError::NotFound)
+}
+
+async fn has_variants(pool: &Pool<Sqlite>, product_id: i64) -> AppResult<bool> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM product_variants WHERE product_id = ?)",
+    )
+    .bind(product_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(exists)
+}
+
+/// Set a product's stock to the total stock of its variants
+pub async fn refresh_product_stock(
+    conn: &mut SqliteConnection,
+    stock: StockSettings,
+    product_id: i64,
+) -> AppResult<()> {
+    let stock_before: Option<i32> = sqlx::query_scalar("SELECT stock FROM products WHERE id = ?")
+        .bind(product_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let product = sqlx::query_as::<_, Product>(
+        "UPDATE products SET stock = (SELECT COALESCE(SUM(stock), 0) FROM product_variants WHERE product_id = ?) WHERE id = ? RETURNING *",
+    )
+    .bind(product_id)
+    .bind(product_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let (Some(stock_before), Some(product)) = (stock_before, product) {
+        queue_stock_low(conn, stock, stock_before, &product).await?;
+    }
+    Ok(())
+}
+
+/// Check a product form and return its parsed price
+pub fn validate_product_form(form: &ProductForm) -> AppResult<Money> {
+    if form.name.trim().is_empty() {
+        return Err(AppError::BadRequest("نام محصول نمی‌تواند خالی باشد".to_string()));
+    }
+    if form.stock < 0 {
+        return Err(AppError::BadRequest("موجودی انبار نمی‌تواند منفی باشد".to_string()));
+    }
+    parse_price(&form.price)
+}
+
+/// Check a variant form and return its parsed price
+fn validate_variant_form(form: &ProductVariantForm) -> AppResult<Money> {
+    if form.variant_name.trim().is_empty() {
+        return Err(AppError::BadRequest("نام نوع محصول نمی‌تواند خالی باشد".to_string()));
+    }
+    if form.stock < 0 {
+        return Err(AppError::BadRequest("موجودی انبار نمی‌تواند منفی باشد".to_string()));
+    }
+    parse_price(&form.price)
+}
+
+/// Parse a price typed in tomans, which may use Persian digits and separators
+pub fn parse_price(input: &str) -> AppResult<Money> {
+    let price = Money::parse(input)
+        .ok_or_else(|| AppError::BadRequest("قیمت وارد شده معتبر نیست".to_string()))?;
+    if price.is_negative() {
+        return Err(AppError::BadRequest("قیمت نمی‌تواند منفی باشد".to_string()));
+    }
+    Ok(price)
+}
@@ -0,0 +1,302 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect},
+    Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Pool, QueryBuilder, Sqlite};
+
+use crate::{
+    db::{audit::record_audit, webhooks::queue_webhook},
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        AuditEntity, Cheque, ChequeDetail, ChequeListQuery, ChequeStatus, ChequeStatusChange,
+        ChequeStatusForm, Money, WebhookEvent,
+    },
+    templates::cheques::{ChequeDetailTemplate, ChequeListTemplate},
+    utils::date::{to_shamsi, today, week_bounds, ISO_FORMAT},
+};
+
+/// Cheques with their transaction amount and customer
+const CHEQUE_DETAIL_SQL: &str = "SELECT cheques.*,
+        transactions.customer_id,
+        customers.full_name AS customer_name,
+        transactions.amount,
+        transactions.direction
+     FROM cheques
+     JOIN transactions ON transactions.id = cheques.transaction_id
+     JOIN customers ON customers.id = transactions.customer_id";
+
+/// List open cheques due by the end of this week, overdue ones included
+pub async fn list_cheques(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<ChequeListQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let today = today();
+    let (week_start, week_end) = week_bounds(today);
+
+    let mut select_query = QueryBuilder::<Sqlite>::new(CHEQUE_DETAIL_SQL);
+    if !query.show_all() {
+        select_query
+            .push(" WHERE cheques.due_date <= ")
+            .push_bind(week_end.format(ISO_FORMAT).to_string());
+        select_query.push(" AND cheques.status IN (");
+        let mut statuses = select_query.separated(", ");
+        for status in ChequeStatus::open() {
+            statuses.push_bind(status.as_str());
+        }
+        select_query.push(")");
+    }
+    select_query.push(" ORDER BY cheques.due_date, cheques.id");
+
+    let cheques = select_query
+        .build_query_as::<ChequeDetail>()
+        .fetch_all(&pool)
+        .await?;
+
+    let total_amount: Money = cheques.iter().map(|cheque| cheque.amount).sum();
+
+    let template = ChequeListTemplate {
+        cheques,
+        show_all: query.show_all(),
+        today: today.format(ISO_FORMAT).to_string(),
+        week_start: to_shamsi(week_start),
+        week_end: to_shamsi(week_end),
+        total_amount: total_amount.format(),
+        current_user,
+        csrf_token,
+        active_page: "cheques",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Show a cheque with its status history
+pub async fn view_cheque(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let cheque = fetch_cheque(&pool, id).await?;
+
+    let history = sqlx::query_as::<_, ChequeStatusChange>(
+        "SELECT cheque_status_history.id, cheque_id, status, note, changed_at,
+                users.full_name AS changed_by
+         FROM cheque_status_history
+         LEFT JOIN users ON users.id = cheque_status_history.changed_by
+         WHERE cheque_id = ?
+         ORDER BY cheque_status_history.id DESC",
+    )
+    .bind(id)
+    .fetch_all(&pool)
+    .await?;
+
+    let template = ChequeDetailTemplate {
+        next_statuses: cheque.cheque.status_enum().next_statuses(),
+        cheque,
+        history,
+        current_user,
+        csrf_token,
+        active_page: "cheques",
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Move a cheque to a new status and record the change
+pub async fn change_cheque_status(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+    Form(form): Form<ChequeStatusForm>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let cheque = fetch_cheque(&pool, id).await?;
+
+    let status = ChequeStatus::parse(&form.status)
+        .ok_or_else(|| AppError::BadRequest("وضعیت چک معتبر نیست".to_string()))?;
+
+    let current_status = cheque.cheque.status_enum();
+    if !current_status.can_change_to(status) {
+        return Err(AppError::BadRequest(format!(
+            "وضعیت چک «{}» را نمی‌توان به «{}» تغییر داد",
+            current_status.display_name(),
+            status.display_name()
+        )));
+    }
+
+    let user_id = current_user.map(|user| user.id);
+    let mut tx = pool.begin().await?;
+
+    // Only moves on from the status checked above, so two changes at once cannot make
+    // a transition the checks would refuse
+    let changed = sqlx::query_as::<_, Cheque>(
+        "UPDATE cheques SET status = ? WHERE id = ? AND status = ? RETURNING *",
+    )
+    .bind(status.as_str())
+    .bind(id)
+    .bind(current_status.as_str())
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("وضعیت چک تغییر کرده است".to_string()))?;
+
+    sqlx::query(
+        "INSERT INTO cheque_status_history (cheque_id, status, note, changed_by) VALUES (?, ?, ?, ?)",
+    )
+    .bind(id)
+    .bind(status.as_str())
+    .bind(form.note.trim())
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // Bounced and returned cheques stop counting toward the customer's balance
+    record_audit(
+        &mut *tx,
+        user_id,
+        AuditEntity::Cheque,
+        id,
+        Some(&cheque.cheque),
+        Some(&changed),
+    )
+    .await?;
+    queue_webhook(&mut *tx, WebhookEvent::ChequeStatusChanged, &changed).await?;
+
+    tx.commit().await?;
+
+    println!(
+        "🧾 Cheque {} changed from {} to {}",
+        cheque.cheque.cheque_number,
+        current_status.as_str(),
+        status.as_str()
+    );
+
+    let jar = jar.add(flash_cookie(format!(
+        "وضعیت چک به «{}» تغییر کرد",
+        status.display_name()
+    )));
+
+    Ok((jar, Redirect::to(&format!("/cheques/{}", id))))
+}
+
+async fn fetch_cheque(pool: &Pool<Sqlite>, id: i64) -> AppResult<ChequeDetail> {
+    sqlx::query_as::<_, ChequeDetail>(&format!("{} WHERE cheques.id = ?", CHEQUE_DETAIL_SQL))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    /// A received cheque from a customer and a webhook endpoint for cheque changes
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        sqlx::query(
+            "INSERT INTO customers (full_name, company, phone_number, notes)
+             VALUES ('علی', 'شرکت', '09121234567', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO transactions (customer_id, amount, transaction_type, transaction_date, direction)
+             VALUES (1, 5000000, 'Cheque', '2024-03-29', 'credit')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO cheques (transaction_id, cheque_number, bank_name, due_date)
+             VALUES (1, '123456', 'ملت', '2024-04-20')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO webhook_endpoints (url, secret, events, created_at)
+             VALUES ('https://example.com/hook', 'secret', 'cheque.status_changed', '2024-03-29')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    fn status_form(status: ChequeStatus) -> Form<ChequeStatusForm> {
+        Form(ChequeStatusForm {
+            status: status.as_str().to_string(),
+            note: String::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_status_change_is_audited_and_queued() {
+        let pool = test_pool().await;
+
+        change_cheque_status(
+            State(pool.clone()),
+            CookieJar::new(),
+            Path(1),
+            status_form(ChequeStatus::Deposited),
+        )
+        .await
+        .unwrap();
+
+        let (entity_type, action): (String, String) =
+            sqlx::query_as("SELECT entity_type, action FROM audit_log WHERE entity_id = 1")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(entity_type, "cheque");
+        assert_eq!(action, "update");
+
+        let event: String = sqlx::query_scalar("SELECT event FROM webhook_deliveries")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(event, "cheque.status_changed");
+
+        // The cheque is no longer received, so the same change is refused
+        let result = change_cheque_status(
+            State(pool.clone()),
+            CookieJar::new(),
+            Path(1),
+            status_form(ChequeStatus::Deposited),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+}
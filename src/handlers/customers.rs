@@ -4,7 +4,7 @@ use serde_json;
 use askama::Template;
 use axum::{
     body::Body,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::header,
     response::{Html, IntoResponse, Redirect, Response},
     Form,
@@ -12,16 +12,27 @@ use axum::{
 
 use xlsxwriter::Workbook;
 
-use axum_extra::extract::{
-    cookie::{Cookie, SameSite},
-    CookieJar,
-};
-use sqlx::{Pool, Sqlite};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Pool, QueryBuilder, Sqlite, SqliteConnection};
 
 use crate::{
+    db::{audit::record_audit, webhooks::queue_webhook},
     error::{AppError, AppResult},
-    middleware::auth::get_current_user,
-    models::{Customer, CustomerForm, Transaction},
+    handlers::{
+        flash_cookie,
+        tags::{fetch_customer_tags, fetch_tag_names},
+    },
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        customer::PAGE_SIZES,
+        duplicate::{
+            find_duplicates, name_key, name_start, name_words, DuplicateKey, DuplicateMatch,
+        },
+        tag::CUSTOMER_TAGS_SQL,
+        transactions::{CUSTOMER_BALANCE_SQL, SIGNED_AMOUNT_SQL},
+        AuditEntity, Customer, CustomerForm, CustomerQuery, CustomerSummary, LedgerEntry,
+        LedgerSummary, Money, WebhookEvent,
+    },
     templates::customers::{AddTemplate, DetailTemplate, EditTemplate, ListTemplate},
     utils::{
         email::{normalize_email, validate_email},
@@ -32,6 +43,8 @@ use crate::{
 pub async fn list_customers(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<CustomerQuery>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
 
@@ -44,141 +57,245 @@ pub async fn list_customers(
         jar
     };
 
-    let customers = sqlx::query_as::<_, Customer>("SELECT * FROM customers ORDER BY id DESC")
+    // Count and total outstanding over the whole filtered list, not just this page
+    let mut totals_query = QueryBuilder::<Sqlite>::new(
+        "SELECT COUNT(*), COALESCE(SUM(MAX(balance, 0)), 0) FROM (",
+    );
+    push_customer_select(&mut totals_query, &query);
+    totals_query.push(")");
+    let (total, total_outstanding): (i64, Money) =
+        totals_query.build_query_as().fetch_one(&pool).await?;
+
+    let per_page = query.per_page();
+    let total_pages = ((total + per_page - 1) / per_page).max(1);
+
+    let mut select_query = QueryBuilder::<Sqlite>::new("");
+    push_customer_select(&mut select_query, &query);
+    push_customer_order(&mut select_query, &query);
+    select_query
+        .push(" LIMIT ")
+        .push_bind(per_page)
+        .push(" OFFSET ")
+        .push_bind(query.offset());
+
+    let customers = select_query
+        .build_query_as::<CustomerSummary>()
         .fetch_all(&pool)
         .await?;
 
+    let job_titles = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT job_title FROM customers WHERE job_title != '' ORDER BY job_title",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let tag_names = fetch_tag_names(&pool).await?;
+
     let template = ListTemplate {
         customers,
+        total,
+        total_outstanding: total_outstanding.format(),
+        page: query.page(),
+        total_pages,
+        query,
+        cities: crate::models::City::all_cities(),
+        job_titles,
+        tag_names,
+        page_sizes: PAGE_SIZES.to_vec(),
         active_page: "list",
         current_user,
+        csrf_token,
         flash_message,
     };
 
     Ok((jar, Html(template.render()?)))
 }
 
+/// Append a SELECT of the customers matching `query`, with their balances and tags
+pub fn push_customer_select(builder: &mut QueryBuilder<'_, Sqlite>, query: &CustomerQuery) {
+    builder
+        .push("SELECT customers.*, ")
+        .push(CUSTOMER_BALANCE_SQL)
+        .push(" AS balance, ")
+        .push(CUSTOMER_TAGS_SQL)
+        .push(" AS tags FROM customers");
+    push_customer_filters(builder, query);
+}
+
+/// Start of a test that a customer has a tag, finished with the name or names to look for
+const HAS_TAG_SQL: &str = "EXISTS (SELECT 1 FROM customer_tags \
+     JOIN tags ON tags.id = customer_tags.tag_id \
+     WHERE customer_tags.customer_id = customers.id AND tags.name";
+
+/// Append the WHERE clause for the search and filters in `query`
+fn push_customer_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &CustomerQuery) {
+    builder.push(" WHERE 1 = 1");
+
+    if let Some(term) = query.search_term() {
+        let pattern = format!("%{}%", escape_like(&term));
+        builder.push(" AND (");
+        for (i, column) in ["full_name", "company", "email", "phone_number"]
+            .iter()
+            .enumerate()
+        {
+            if i > 0 {
+                builder.push(" OR ");
+            }
+            builder
+                .push(*column)
+                .push(" LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\'");
+        }
+        // Phone numbers are stored as bare digits, so "0912 345" should still match
+        if let Some(digits) = query.search_digits() {
+            builder
+                .push(" OR phone_number LIKE ")
+                .push_bind(format!("%{}%", digits));
+        }
+        builder.push(")");
+    }
+
+    if let Some(city) = query.city_filter() {
+        builder.push(" AND city = ").push_bind(city.to_string());
+    }
+
+    if let Some(job_title) = query.job_title_filter() {
+        builder.push(" AND job_title = ").push_bind(job_title.to_string());
+    }
+
+    if let Some(min_sales) = query.min_sales_value() {
+        builder.push(" AND sales_count >= ").push_bind(min_sales);
+    }
+
+    if let Some(max_sales) = query.max_sales_value() {
+        builder.push(" AND sales_count <= ").push_bind(max_sales);
+    }
+
+    // Tag names compare without case, as the tags table stores them
+    let tags = query.tag_filters();
+    if query.matches_any_tag() && !tags.is_empty() {
+        builder.push(" AND ").push(HAS_TAG_SQL).push(" IN (");
+        let mut names = builder.separated(", ");
+        for tag in tags {
+            names.push_bind(tag);
+        }
+        builder.push("))");
+    } else {
+        for tag in tags {
+            builder
+                .push(" AND ")
+                .push(HAS_TAG_SQL)
+                .push(" = ")
+                .push_bind(tag)
+                .push(")");
+        }
+    }
+
+    if let Some(min_balance) = query.min_balance_value() {
+        builder
+            .push(" AND ")
+            .push(CUSTOMER_BALANCE_SQL)
+            .push(" >= ")
+            .push_bind(min_balance);
+    }
+
+    if let Some(max_balance) = query.max_balance_value() {
+        builder
+            .push(" AND ")
+            .push(CUSTOMER_BALANCE_SQL)
+            .push(" <= ")
+            .push_bind(max_balance);
+    }
+}
+
+/// Append the ORDER BY clause; the column comes from a fixed whitelist
+pub fn push_customer_order(builder: &mut QueryBuilder<'_, Sqlite>, query: &CustomerQuery) {
+    builder
+        .push(" ORDER BY ")
+        .push(query.sort_column())
+        .push(" ")
+        .push(query.sort_direction());
+
+    if query.sort_column() != "id" {
+        builder.push(", id DESC");
+    }
+}
+
+/// Escape LIKE wildcards so user input is matched literally
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 /// Show add customer form
 pub async fn show_add_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
 
     let template = AddTemplate {
         active_page: "add",
         current_user,
+        csrf_token,
         cities: crate::models::City::all_cities(),
+        form: CustomerForm::default(),
+        duplicates: Vec::new(),
     };
 
     Ok(Html(template.render()?))
 }
 
 /// Add new customer
+///
+/// A customer that looks like an existing one is not saved until the user confirms it.
 pub async fn add_customer(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Form(mut form): Form<CustomerForm>,
-) -> AppResult<impl IntoResponse> {
-    // Trim all fields
-    form.full_name = form.full_name.trim().to_string();
-    form.company = form.company.trim().to_string();
-    form.notes = form.notes.trim().to_string();
-    form.job_title = form.job_title.trim().to_string();
-    form.address = form.address.trim().to_string();
-    form.city = form.city.trim().to_string();
-    form.coordinates = form.coordinates.trim().to_string();
-
-    // Validate required fields
-    if form.full_name.trim().is_empty() {
-        return Err(AppError::BadRequest(
-            "نام کامل نمی‌تواند خالی باشد".to_string(),
-        ));
-    }
+) -> AppResult<Response> {
+    validate_customer_form(&mut form)?;
 
-    if form.company.trim().is_empty() {
-        return Err(AppError::BadRequest(
-            "نام شرکت نمی‌تواند خالی باشد".to_string(),
-        ));
-    }
-
-    // Validate and normalize phone number
-    form.phone_number = normalize_phone_number(&form.phone_number).map_err(|e| match e {
-        AppError::BadRequest(msg) => AppError::Validation(vec![("phone_number".to_string(), msg)]),
-        _ => e,
-    })?;
-
-    // Validate and normalize email
-    form.email = validate_email(&form.email).map_err(|e| match e {
-        AppError::BadRequest(msg) => AppError::Validation(vec![("email".to_string(), msg)]),
-        _ => e,
-    })?;
-    form.email = normalize_email(&form.email);
+    let current_user = get_current_user(&pool, &jar).await;
 
-    // Validate sales_count
-    if form.sales_count < 0 {
-        return Err(AppError::BadRequest(
-            "تعداد فروش نمیتواند منفی باشد".to_string(),
-        ));
+    if !form.confirm_duplicate {
+        let duplicates = find_form_duplicates(&pool, &form).await?;
+
+        if !duplicates.is_empty() {
+            let template = AddTemplate {
+                active_page: "add",
+                current_user,
+                csrf_token,
+                cities: crate::models::City::all_cities(),
+                form,
+                duplicates,
+            };
+            return Ok(Html(template.render()?).into_response());
+        }
     }
 
-    // Validate city
-    let city_str = form.city;
-
-    let valid_cities: Vec<String> = crate::models::City::all_cities()
-        .into_iter()
-        .map(|city| city.as_str().to_string())
-        .collect();
-
-    if !valid_cities.contains(&city_str.to_string()) && !city_str.is_empty() {
-        return Err(AppError::BadRequest(
-            "شهر انتخاب شده معتبر نیست".to_string(),
-        ));
-    }
-
-    sqlx::query(
-        "INSERT INTO customers (full_name, company, email, phone_number, sales_count, job_title, city, address, notes, coordinates)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-    )
-    .bind(&form.full_name)
-    .bind(&form.company)
-    .bind(&form.email)
-    .bind(&form.phone_number)
-    .bind(&form.sales_count)
-    .bind(&form.job_title)
-    .bind(city_str)
-    .bind(&form.address)
-    .bind(&form.notes)
-    .bind(&form.coordinates)
-    .execute(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Database error while adding customer: {:?}", e);
-        AppError::from(e)
-    })?;
+    create_customer_record(&pool, &form, current_user.map(|user| user.id)).await?;
 
     println!("✅ New customer added: {}", form.full_name);
 
     // Set flash message cookie
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("مشتری «{}» با موفقیت اضافه شد ✅", form.full_name),
-    ))
-    .path("/")
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
+    let jar = jar.add(flash_cookie(format!(
+        "مشتری «{}» با موفقیت اضافه شد ✅",
+        form.full_name
+    )));
 
-    let jar = jar.add(flash_cookie);
-
-    Ok((jar, Redirect::to("/")))
+    Ok((jar, Redirect::to("/")).into_response())
 }
 
 /// View customer details
 pub async fn view_customer(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path(id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
@@ -199,21 +316,23 @@ pub async fn view_customer(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let transactions = sqlx::query_as::<_, Transaction>(
-        "SELECT * FROM transactions WHERE customer_id = ? ORDER BY transaction_date DESC",
-    )
-    .bind(id)
-    .fetch_all(&pool)
-    .await?;
-    
-    let transactions_json = serde_json::to_string(&transactions).unwrap_or_else(|_| "[]".to_string());
+    let ledger = fetch_ledger(&pool, id).await?;
+    let tags = fetch_customer_tags(&pool, id).await?;
+    let tag_names = fetch_tag_names(&pool).await?;
+
+    let summary = LedgerSummary::from_entries(&ledger);
+    let transactions_json = serde_json::to_string(&ledger).unwrap_or_else(|_| "[]".to_string());
 
     let template = DetailTemplate {
         customer,
-        transactions,
+        tags,
+        tag_names,
+        ledger,
+        summary,
         transactions_json,
         active_page: "",
         current_user,
+        csrf_token,
         flash_message,
     };
 
@@ -224,6 +343,7 @@ pub async fn view_customer(
 pub async fn show_edit_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path(id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
@@ -238,6 +358,7 @@ pub async fn show_edit_form(
         customer,
         active_page: "",
         current_user,
+        csrf_token,
         cities: crate::models::City::all_cities(),
     };
 
@@ -251,71 +372,18 @@ pub async fn update_customer(
     Path(id): Path<i64>,
     Form(mut form): Form<CustomerForm>,
 ) -> AppResult<impl IntoResponse> {
-    // Validate and normalize phone number
-    form.phone_number = normalize_phone_number(&form.phone_number)?;
-
-    // Validate cities
-    let valid_cities: Vec<String> = crate::models::City::all_cities()
-        .into_iter()
-        .map(|city| city.as_str().to_string())
-        .collect();
-
-    let city_str = form.city.trim();
-
-    if !valid_cities.contains(&city_str.to_string()) && !city_str.is_empty() {
-        return Err(AppError::BadRequest(
-            "شهر انتخاب شده معتبر نیست".to_string(),
-        ));
-    }
+    validate_customer_form(&mut form)?;
 
-    // Validate sales_count
-    if form.sales_count < 0 {
-        return Err(AppError::BadRequest(
-            "تعداد فروش نمیتواند منفی باشد".to_string(),
-        ));
-    }
-
-    // Validate and normalize email
-    form.email = validate_email(&form.email)?;
-    form.email = normalize_email(&form.email);
-
-    let result = sqlx::query(
-        "UPDATE customers
-         SET full_name = ?, company = ?, email = ?, phone_number = ?, sales_count = ?, job_title = ?, city = ?, address = ?, notes = ?, coordinates = ?
-         WHERE id = ?"
-    )
-    .bind(&form.full_name)
-    .bind(&form.company)
-    .bind(&form.email)
-    .bind(&form.phone_number)
-    .bind(&form.sales_count)
-    .bind(&form.job_title)
-    .bind(city_str)
-    .bind(&form.address)
-    .bind(&form.notes)
-    .bind(&form.coordinates)
-    .bind(id)
-    .execute(&pool)
-    .await?;
-
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound);
-    }
+    let current_user = get_current_user(&pool, &jar).await;
+    update_customer_record(&pool, id, &form, current_user.map(|user| user.id)).await?;
 
     println!("✏️ Updated customer: {} (ID: {})", form.full_name, id);
 
     // Set flash message for update
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("اطلاعات «{}» با موفقیت به‌روزرسانی شد ✏️", form.full_name),
-    ))
-    .path("/")
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
-
-    let jar = jar.add(flash_cookie);
+    let jar = jar.add(flash_cookie(format!(
+        "اطلاعات «{}» با موفقیت به‌روزرسانی شد ✏️",
+        form.full_name
+    )));
 
     Ok((jar, Redirect::to(&format!("/customer/{}", id))))
 }
@@ -326,41 +394,33 @@ pub async fn delete_customer(
     jar: CookieJar,
     Path(id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
-    // Get customer name before deletion for flash message
-    let customer = sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
-        .await?;
-
-    let customer_name = customer
-        .map(|c| c.full_name)
-        .unwrap_or_else(|| "مشتری".to_string());
+    let current_user = get_current_user(&pool, &jar).await;
+    let customer = delete_customer_record(&pool, id, current_user.map(|user| user.id)).await?;
 
-    sqlx::query("DELETE FROM customers WHERE id = ?")
-        .bind(id)
-        .execute(&pool)
-        .await?;
+    let customer_name = customer.full_name;
 
     println!("🗑️ Deleted customer ID: {}", id);
 
     // Set flash message for deletion
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("مشتری «{}» با موفقیت حذف شد 🗑️", customer_name),
-    ))
-    .path("/")
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
-
-    let jar = jar.add(flash_cookie);
+    let jar = jar.add(flash_cookie(format!(
+        "مشتری «{}» با موفقیت حذف شد 🗑️",
+        customer_name
+    )));
 
     Ok((jar, Redirect::to("/")))
 }
 
-pub async fn export_customer(State(pool): State<Pool<Sqlite>>) -> AppResult<Response> {
-    let customers = sqlx::query_as::<_, Customer>("SELECT * FROM customers ORDER BY id DESC")
+pub async fn export_customer(
+    State(pool): State<Pool<Sqlite>>,
+    Query(query): Query<CustomerQuery>,
+) -> AppResult<Response> {
+    // Export what the list shows, without paging
+    let mut select_query = QueryBuilder::<Sqlite>::new("");
+    push_customer_select(&mut select_query, &query);
+    push_customer_order(&mut select_query, &query);
+
+    let customers = select_query
+        .build_query_as::<CustomerSummary>()
         .fetch_all(&pool)
         .await?;
 
@@ -381,15 +441,18 @@ pub async fn export_customer(State(pool): State<Pool<Sqlite>>) -> AppResult<Resp
         "شهر",
         "آدرس",
         "یادداشت‌ها",
-        "مختصات"
+        "مختصات",
+        "مانده حساب",
+        "برچسب‌ها",
     ];
 
     for (i, header) in headers.iter().enumerate() {
         sheet.write_string(0, i as u16, header, None)?;
     }
 
-    for (row_num, customer) in customers.iter().enumerate() {
+    for (row_num, summary) in customers.iter().enumerate() {
         let row = (row_num + 1) as u32;
+        let customer = &summary.customer;
         sheet.write_number(row, 0, customer.id as f64, None)?;
         sheet.write_string(row, 1, &customer.full_name, None)?;
         sheet.write_string(row, 2, &customer.company, None)?;
@@ -401,6 +464,8 @@ pub async fn export_customer(State(pool): State<Pool<Sqlite>>) -> AppResult<Resp
         sheet.write_string(row, 9, &customer.address, None)?;
         sheet.write_string(row, 10, &customer.notes, None)?;
         sheet.write_string(row, 11, &customer.coordinates, None)?;
+        sheet.write_number(row, 12, summary.balance.tomans(), None)?;
+        sheet.write_string(row, 13, &summary.tags.joined(), None)?;
     }
 
     workbook.close()?;
@@ -421,3 +486,290 @@ pub async fn export_customer(State(pool): State<Pool<Sqlite>>) -> AppResult<Resp
 
     Ok((headers, Body::from(buffer)).into_response())
 }
+
+/// Fetch a customer inside a transaction
+pub async fn fetch_customer(conn: &mut SqliteConnection, id: i64) -> AppResult<Customer> {
+    sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+/// Trim and check a submitted customer, normalizing its phone number and email
+pub fn validate_customer_form(form: &mut CustomerForm) -> AppResult<()> {
+    form.full_name = form.full_name.trim().to_string();
+    form.company = form.company.trim().to_string();
+    form.notes = form.notes.trim().to_string();
+    form.job_title = form.job_title.trim().to_string();
+    form.address = form.address.trim().to_string();
+    form.city = form.city.trim().to_string();
+    form.coordinates = form.coordinates.trim().to_string();
+
+    if form.full_name.is_empty() {
+        return Err(AppError::BadRequest(
+            "نام کامل نمی‌تواند خالی باشد".to_string(),
+        ));
+    }
+
+    if form.company.is_empty() {
+        return Err(AppError::BadRequest(
+            "نام شرکت نمی‌تواند خالی باشد".to_string(),
+        ));
+    }
+
+    form.phone_number = normalize_phone_number(&form.phone_number).map_err(|e| match e {
+        AppError::BadRequest(msg) => AppError::Validation(vec![("phone_number".to_string(), msg)]),
+        _ => e,
+    })?;
+
+    form.email = validate_email(&form.email).map_err(|e| match e {
+        AppError::BadRequest(msg) => AppError::Validation(vec![("email".to_string(), msg)]),
+        _ => e,
+    })?;
+    form.email = normalize_email(&form.email);
+
+    if form.sales_count < 0 {
+        return Err(AppError::BadRequest(
+            "تعداد فروش نمیتواند منفی باشد".to_string(),
+        ));
+    }
+
+    let valid_city = crate::models::City::all_cities()
+        .iter()
+        .any(|city| city.as_str() == form.city);
+    if !valid_city && !form.city.is_empty() {
+        return Err(AppError::BadRequest(
+            "شهر انتخاب شده معتبر نیست".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Existing customers that a validated form looks like.
+///
+/// Only customers with the same phone number or email, or sharing a word or the first
+/// letters of the folded name, are read to be compared.
+pub async fn find_form_duplicates(
+    pool: &Pool<Sqlite>,
+    form: &CustomerForm,
+) -> AppResult<Vec<DuplicateMatch>> {
+    let folded = name_key(&form.full_name);
+
+    let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM customers WHERE 1 = 0");
+    if !form.phone_number.is_empty() {
+        query
+            .push(" OR phone_number = ")
+            .push_bind(form.phone_number.clone());
+    }
+    if !form.email.is_empty() {
+        query.push(" OR email = ").push_bind(form.email.clone());
+    }
+    for word in name_words(&folded) {
+        query
+            .push(" OR ' ' || name_key || ' ' LIKE ")
+            .push_bind(format!("% {} %", escape_like(word)))
+            .push(" ESCAPE '\\'");
+    }
+    let start = name_start(&folded);
+    if !start.is_empty() {
+        query
+            .push(" OR REPLACE(name_key, ' ', '') LIKE ")
+            .push_bind(format!("{}%", escape_like(&start)))
+            .push(" ESCAPE '\\'");
+    }
+    let customers = query.build_query_as::<Customer>().fetch_all(pool).await?;
+
+    let key = DuplicateKey {
+        full_name: &form.full_name,
+        phone_number: &form.phone_number,
+        email: &form.email,
+    };
+    Ok(find_duplicates(&key, &customers))
+}
+
+/// Insert a validated customer, recording it in the audit log and queueing `customer.created`
+pub async fn create_customer_record(
+    pool: &Pool<Sqlite>,
+    form: &CustomerForm,
+    user_id: Option<i64>,
+) -> AppResult<Customer> {
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query(
+        "INSERT INTO customers (full_name, name_key, company, email, phone_number, sales_count, job_title, city, address, notes, coordinates)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&form.full_name)
+    .bind(name_key(&form.full_name))
+    .bind(&form.company)
+    .bind(&form.email)
+    .bind(&form.phone_number)
+    .bind(form.sales_count)
+    .bind(&form.job_title)
+    .bind(&form.city)
+    .bind(&form.address)
+    .bind(&form.notes)
+    .bind(&form.coordinates)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Database error while adding customer: {:?}", e);
+        AppError::from(e)
+    })?
+    .last_insert_rowid();
+
+    let customer = fetch_customer(&mut tx, id).await?;
+    record_audit(&mut *tx, user_id, AuditEntity::Customer, id, None, Some(&customer)).await?;
+    queue_webhook(&mut *tx, WebhookEvent::CustomerCreated, &customer).await?;
+    tx.commit().await?;
+
+    Ok(customer)
+}
+
+/// Save a validated form over an existing customer, recording the change in the audit log
+/// and queueing `customer.updated`
+pub async fn update_customer_record(
+    pool: &Pool<Sqlite>,
+    id: i64,
+    form: &CustomerForm,
+    user_id: Option<i64>,
+) -> AppResult<Customer> {
+    let mut tx = pool.begin().await?;
+    let old = fetch_customer(&mut tx, id).await?;
+
+    sqlx::query(
+        "UPDATE customers
+         SET full_name = ?, name_key = ?, company = ?, email = ?, phone_number = ?, sales_count = ?, job_title = ?, city = ?, address = ?, notes = ?, coordinates = ?
+         WHERE id = ?"
+    )
+    .bind(&form.full_name)
+    .bind(name_key(&form.full_name))
+    .bind(&form.company)
+    .bind(&form.email)
+    .bind(&form.phone_number)
+    .bind(form.sales_count)
+    .bind(&form.job_title)
+    .bind(&form.city)
+    .bind(&form.address)
+    .bind(&form.notes)
+    .bind(&form.coordinates)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    let new = fetch_customer(&mut tx, id).await?;
+    record_audit(&mut *tx, user_id, AuditEntity::Customer, id, Some(&old), Some(&new)).await?;
+    queue_webhook(&mut *tx, WebhookEvent::CustomerUpdated, &new).await?;
+    tx.commit().await?;
+
+    Ok(new)
+}
+
+/// Delete a customer, recording it in the audit log and queueing `customer.deleted`,
+/// and return it as it was
+pub async fn delete_customer_record(
+    pool: &Pool<Sqlite>,
+    id: i64,
+    user_id: Option<i64>,
+) -> AppResult<Customer> {
+    let mut tx = pool.begin().await?;
+    let customer = fetch_customer(&mut tx, id).await?;
+
+    sqlx::query("DELETE FROM customers WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit(&mut *tx, user_id, AuditEntity::Customer, id, Some(&customer), None).await?;
+    queue_webhook(&mut *tx, WebhookEvent::CustomerDeleted, &customer).await?;
+    tx.commit().await?;
+
+    Ok(customer)
+}
+
+/// A customer's transactions with the running balance after each, listed newest first
+pub async fn fetch_ledger(pool: &Pool<Sqlite>, customer_id: i64) -> AppResult<Vec<LedgerEntry>> {
+    // The balance is summed in date order
+    Ok(sqlx::query_as::<_, LedgerEntry>(&format!(
+        "SELECT transactions.*,
+                SUM({}) OVER (ORDER BY transaction_date, transactions.id) AS balance,
+                cheques.id AS cheque_id,
+                cheques.status AS cheque_status
+         FROM transactions
+         LEFT JOIN cheques ON cheques.transaction_id = transactions.id
+         WHERE customer_id = ?
+         ORDER BY transaction_date DESC, transactions.id DESC",
+        SIGNED_AMOUNT_SQL
+    ))
+    .bind(customer_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    fn form(full_name: &str, phone_number: &str, email: &str) -> CustomerForm {
+        CustomerForm {
+            full_name: full_name.to_string(),
+            company: "رومی".to_string(),
+            email: email.to_string(),
+            phone_number: phone_number.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_form_duplicates() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        for (full_name, phone_number, email) in [
+            ("علی کریمی", "09120000001", ""),
+            ("مریم حسینی", "09120000002", "maryam@example.com"),
+            ("علیرضا محمدی", "09120000003", ""),
+            ("رضا احمدی", "09120000004", ""),
+        ] {
+            create_customer_record(&pool, &form(full_name, phone_number, email), None)
+                .await
+                .unwrap();
+        }
+
+        let ids = |matches: Vec<DuplicateMatch>| {
+            let mut ids: Vec<i64> = matches.iter().map(|m| m.customer.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        // Folded spelling and an honorific, a shared email, the same phone number
+        let found = find_form_duplicates(&pool, &form("آقای علي كريمي", "09350000000", ""));
+        assert_eq!(ids(found.await.unwrap()), vec![1]);
+        let found = find_form_duplicates(&pool, &form("سارا", "09350000000", "maryam@example.com"));
+        assert_eq!(ids(found.await.unwrap()), vec![2]);
+        let found = find_form_duplicates(&pool, &form("سارا", "09120000004", ""));
+        assert_eq!(ids(found.await.unwrap()), vec![4]);
+
+        // A typo in one word, found through the other
+        let found = find_form_duplicates(&pool, &form("علیرضا محمودی", "09350000000", ""));
+        assert_eq!(ids(found.await.unwrap()), vec![3]);
+
+        // Names are kept folded when a customer is renamed
+        update_customer_record(&pool, 4, &form("خانم سارا رضایی", "09120000004", ""), None)
+            .await
+            .unwrap();
+        let name_key: String = sqlx::query_scalar("SELECT name_key FROM customers WHERE id = 4")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(name_key, "سارا رضایی");
+    }
+}
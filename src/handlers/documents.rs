@@ -0,0 +1,275 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{Html, IntoResponse, Response},
+};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{
+        invoice::INVOICE_SUMMARY_SQL, Cheque, Customer, DocumentKind, DocumentLine,
+        DocumentSettings, InvoiceItem, InvoiceStatus, InvoiceSummary, PrintDocument, Transaction,
+    },
+    templates::documents::PrintDocumentTemplate,
+    utils::{localization::to_persian_digits, pdf::render_pdf},
+};
+
+/// Printable view of a confirmed or cancelled invoice
+pub async fn print_invoice(
+    State(pool): State<Pool<Sqlite>>,
+    State(settings): State<DocumentSettings>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let document = invoice_document(&pool, &settings, id).await?;
+
+    let template = PrintDocumentTemplate {
+        document,
+        pdf_url: format!("/invoices/{}/pdf", id),
+        back_url: format!("/invoices/{}", id),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Download an invoice as a PDF
+pub async fn invoice_pdf(
+    State(pool): State<Pool<Sqlite>>,
+    State(settings): State<DocumentSettings>,
+    Path(id): Path<i64>,
+) -> AppResult<Response> {
+    let document = invoice_document(&pool, &settings, id).await?;
+    pdf_response(&settings, document).await
+}
+
+/// Printable receipt for a payment, or debit note for a charge, on a customer's ledger
+pub async fn print_receipt(
+    State(pool): State<Pool<Sqlite>>,
+    State(settings): State<DocumentSettings>,
+    Path((customer_id, transaction_id)): Path<(i64, i64)>,
+) -> AppResult<impl IntoResponse> {
+    let document = receipt_document(&pool, &settings, customer_id, transaction_id).await?;
+
+    let template = PrintDocumentTemplate {
+        document,
+        pdf_url: format!("/customer/{}/receipt/{}/pdf", customer_id, transaction_id),
+        back_url: format!("/customer/{}", customer_id),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Download a ledger transaction's receipt as a PDF
+pub async fn receipt_pdf(
+    State(pool): State<Pool<Sqlite>>,
+    State(settings): State<DocumentSettings>,
+    Path((customer_id, transaction_id)): Path<(i64, i64)>,
+) -> AppResult<Response> {
+    let document = receipt_document(&pool, &settings, customer_id, transaction_id).await?;
+    pdf_response(&settings, document).await
+}
+
+async fn invoice_document(
+    pool: &Pool<Sqlite>,
+    settings: &DocumentSettings,
+    id: i64,
+) -> AppResult<PrintDocument> {
+    let invoice = sqlx::query_as::<_, InvoiceSummary>(&format!(
+        "{} WHERE invoices.id = ?",
+        INVOICE_SUMMARY_SQL
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let status = invoice.invoice.status_enum();
+    if status == InvoiceStatus::Draft {
+        return Err(AppError::BadRequest(
+            "فاکتور پیش‌نویس قابل چاپ نیست؛ ابتدا آن را تأیید کنید".to_string(),
+        ));
+    }
+
+    let customer = fetch_customer(pool, invoice.invoice.customer_id).await?;
+    let items = sqlx::query_as::<_, InvoiceItem>(
+        "SELECT * FROM invoice_items WHERE invoice_id = ? ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+    let number = document_number(pool, DocumentKind::Invoice, id).await?;
+
+    Ok(PrintDocument {
+        kind: DocumentKind::Invoice,
+        number,
+        title: "فاکتور فروش".to_string(),
+        date: invoice.invoice.invoice_date.clone(),
+        company: settings.company.clone(),
+        customer_name: customer.full_name.clone(),
+        customer_phone: customer.formatted_phone(),
+        customer_address: customer.address.clone(),
+        details: vec![(
+            "شماره فاکتور".to_string(),
+            to_persian_digits(&id.to_string()),
+        )],
+        lines: items
+            .iter()
+            .map(|item| DocumentLine {
+                description: item.description.clone(),
+                quantity: to_persian_digits(&item.quantity.to_string()),
+                unit_price: item.formatted_unit_price(),
+                discount: item.formatted_discount(),
+                total: item.formatted_total(),
+            })
+            .collect(),
+        total: invoice.total,
+        notes: invoice.invoice.notes.clone(),
+        stamp: (status == InvoiceStatus::Cancelled).then(|| status.display_name().to_string()),
+    })
+}
+
+async fn receipt_document(
+    pool: &Pool<Sqlite>,
+    settings: &DocumentSettings,
+    customer_id: i64,
+    transaction_id: i64,
+) -> AppResult<PrintDocument> {
+    let transaction = sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE id = ? AND customer_id = ?",
+    )
+    .bind(transaction_id)
+    .bind(customer_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let customer = fetch_customer(pool, customer_id).await?;
+    let cheque = sqlx::query_as::<_, Cheque>("SELECT * FROM cheques WHERE transaction_id = ?")
+        .bind(transaction_id)
+        .fetch_optional(pool)
+        .await?;
+
+    let mut details = vec![(
+        "نوع تراکنش".to_string(),
+        transaction.transaction_type_display_name(),
+    )];
+    if let Some(cheque) = &cheque {
+        details.push((
+            "شماره چک".to_string(),
+            to_persian_digits(&cheque.cheque_number),
+        ));
+        details.push(("بانک".to_string(), cheque.bank_name.clone()));
+        details.push((
+            "تاریخ سررسید".to_string(),
+            to_persian_digits(&cheque.formatted_due_date()),
+        ));
+    }
+
+    let description = transaction
+        .description
+        .clone()
+        .filter(|description| !description.trim().is_empty())
+        .unwrap_or_else(|| transaction.transaction_type_display_name());
+
+    let number = document_number(pool, DocumentKind::Receipt, transaction_id).await?;
+
+    Ok(PrintDocument {
+        kind: DocumentKind::Receipt,
+        number,
+        title: if transaction.is_debit() {
+            "سند بدهکاری"
+        } else {
+            "رسید دریافت"
+        }
+        .to_string(),
+        date: transaction.transaction_date.clone(),
+        company: settings.company.clone(),
+        customer_name: customer.full_name.clone(),
+        customer_phone: customer.formatted_phone(),
+        customer_address: customer.address.clone(),
+        details,
+        lines: vec![DocumentLine {
+            description,
+            quantity: String::new(),
+            unit_price: String::new(),
+            discount: String::new(),
+            total: transaction.formatted_amount(),
+        }],
+        total: transaction.amount,
+        notes: String::new(),
+        stamp: cheque
+            .map(|cheque| cheque.status_enum())
+            .filter(|status| !status.counts_toward_balance())
+            .map(|status| format!("چک {}", status.display_name())),
+    })
+}
+
+/// The sequential number of a source's document, assigned the first time it is printed
+async fn document_number(pool: &Pool<Sqlite>, kind: DocumentKind, source_id: i64) -> AppResult<i64> {
+    // A conditional insert rather than INSERT OR IGNORE, which would use up a number on every reprint
+    sqlx::query(
+        "INSERT INTO documents (kind, source_id)
+         SELECT ?, ? WHERE NOT EXISTS (SELECT 1 FROM documents WHERE kind = ? AND source_id = ?)",
+    )
+    .bind(kind.as_str())
+    .bind(source_id)
+    .bind(kind.as_str())
+    .bind(source_id)
+    .execute(pool)
+    .await?;
+
+    Ok(
+        sqlx::query_scalar("SELECT id FROM documents WHERE kind = ? AND source_id = ?")
+            .bind(kind.as_str())
+            .bind(source_id)
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+async fn fetch_customer(pool: &Pool<Sqlite>, id: i64) -> AppResult<Customer> {
+    sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+/// Render a document with the configured font and send it as a download
+async fn pdf_response(settings: &DocumentSettings, document: PrintDocument) -> AppResult<Response> {
+    let font = tokio::fs::read(&settings.font_path).await.map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            // The font is not bundled, so a fresh install has none until one is added
+            AppError::Unavailable(format!(
+                "فونت PDF در {} پیدا نشد. یک فونت TrueType فارسی مانند وزیرمتن را آنجا قرار دهید یا مسیر آن را در PDF_FONT_PATH تنظیم کنید. تا آن زمان نسخه چاپی سند در دسترس است.",
+                settings.font_path.display()
+            ))
+        } else {
+            AppError::Internal(format!(
+                "Failed to read PDF font {} (set PDF_FONT_PATH): {}",
+                settings.font_path.display(),
+                e
+            ))
+        }
+    })?;
+
+    let file_name = document.file_name();
+    let pdf = tokio::task::spawn_blocking(move || render_pdf(&document, &font))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    println!("🖨️ PDF document generated: {}", file_name);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        pdf,
+    )
+        .into_response())
+}
@@ -0,0 +1,241 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect},
+    Form,
+};
+use axum_extra::extract::CookieJar;
+use serde_json::{json, Map};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    db::{
+        audit::{record_audit, record_changes},
+        webhooks::queue_webhook,
+    },
+    error::{AppError, AppResult},
+    handlers::{
+        customers::fetch_customer,
+        flash_cookie,
+        tags::{fetch_customer_tags, record_tag_change},
+        transactions::fetch_transaction,
+    },
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        duplicate::{find_duplicate_pairs, name_key},
+        AuditAction, AuditEntity, Customer, MergeFieldRow, MergeForm, MergeQuery, WebhookEvent,
+    },
+    templates::customers::{DuplicatesTemplate, MergeTemplate},
+};
+
+/// Most pairs listed on the duplicates page, strongest first
+const MAX_LISTED_PAIRS: usize = 200;
+
+/// List pairs of customers that look like the same person (needs customers.merge)
+pub async fn list_duplicates(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let customers = sqlx::query_as::<_, Customer>("SELECT * FROM customers ORDER BY id")
+        .fetch_all(&pool)
+        .await?;
+
+    // Comparing names is CPU work, so it runs off the async threads
+    let (pairs, total) = tokio::task::spawn_blocking(move || {
+        let mut pairs = find_duplicate_pairs(&customers);
+        let total = pairs.len();
+        pairs.truncate(MAX_LISTED_PAIRS);
+        (pairs, total)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let template = DuplicatesTemplate {
+        pairs,
+        total,
+        current_user,
+        csrf_token,
+        active_page: "list",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// Show two customers side by side to choose the merged value of each field
+pub async fn show_merge_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<MergeQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    if query.keep == query.remove {
+        return Err(same_customer());
+    }
+
+    let mut conn = pool.acquire().await?;
+    let keep = fetch_customer(&mut conn, query.keep).await?;
+    let remove = fetch_customer(&mut conn, query.remove).await?;
+
+    let transaction_count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE customer_id = ?")
+            .bind(remove.id)
+            .fetch_one(&mut *conn)
+            .await?;
+    let invoice_count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM invoices WHERE customer_id = ?")
+            .bind(remove.id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let template = MergeTemplate {
+        fields: MergeFieldRow::rows(&keep, &remove),
+        keep,
+        remove,
+        transaction_count,
+        invoice_count,
+        current_user,
+        csrf_token,
+        active_page: "list",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// Merge one customer into another.
+///
+/// The surviving customer takes the chosen value of each field, the other customer's
+/// transactions and invoices move to it, and the other customer is deleted.
+pub async fn merge_customers(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    let form = MergeForm::from_fields(&fields)
+        .ok_or_else(|| AppError::BadRequest("فرم ادغام معتبر نیست".to_string()))?;
+    if form.keep == form.remove {
+        return Err(same_customer());
+    }
+    let user_id = get_current_user(&pool, &jar).await.map(|user| user.id);
+
+    let mut tx = pool.begin().await?;
+    let keep = fetch_customer(&mut tx, form.keep).await?;
+    let remove = fetch_customer(&mut tx, form.remove).await?;
+    let merged = form.merged(&keep, &remove);
+
+    sqlx::query(
+        "UPDATE customers
+         SET full_name = ?, name_key = ?, company = ?, email = ?, phone_number = ?, sales_count = ?, job_title = ?, city = ?, address = ?, notes = ?, coordinates = ?
+         WHERE id = ?",
+    )
+    .bind(&merged.full_name)
+    .bind(name_key(&merged.full_name))
+    .bind(&merged.company)
+    .bind(&merged.email)
+    .bind(&merged.phone_number)
+    .bind(merged.sales_count)
+    .bind(&merged.job_title)
+    .bind(&merged.city)
+    .bind(&merged.address)
+    .bind(&merged.notes)
+    .bind(&merged.coordinates)
+    .bind(keep.id)
+    .execute(&mut *tx)
+    .await?;
+    record_audit(
+        &mut *tx,
+        user_id,
+        AuditEntity::Customer,
+        keep.id,
+        Some(&keep),
+        Some(&merged),
+    )
+    .await?;
+    queue_webhook(&mut *tx, WebhookEvent::CustomerUpdated, &merged).await?;
+
+    let moved: Vec<i64> = sqlx::query_scalar("SELECT id FROM transactions WHERE customer_id = ?")
+        .bind(remove.id)
+        .fetch_all(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE transactions SET customer_id = ? WHERE customer_id = ?")
+        .bind(keep.id)
+        .bind(remove.id)
+        .execute(&mut *tx)
+        .await?;
+    for transaction_id in &moved {
+        let mut changes = Map::new();
+        changes.insert(
+            "customer_id".to_string(),
+            json!({ "old": remove.id, "new": keep.id }),
+        );
+        record_changes(
+            &mut *tx,
+            user_id,
+            AuditEntity::Transaction,
+            *transaction_id,
+            AuditAction::Update,
+            changes,
+        )
+        .await?;
+        let transaction = fetch_transaction(&mut tx, keep.id, *transaction_id).await?;
+        queue_webhook(&mut *tx, WebhookEvent::TransactionUpdated, &transaction).await?;
+    }
+
+    // The kept customer takes on the tags of the removed one
+    let old_tags = fetch_customer_tags(&mut *tx, keep.id).await?;
+    let added_tags = sqlx::query(
+        "INSERT OR IGNORE INTO customer_tags (customer_id, tag_id, created_at)
+         SELECT ?, tag_id, created_at FROM customer_tags WHERE customer_id = ?",
+    )
+    .bind(keep.id)
+    .bind(remove.id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if added_tags > 0 {
+        let new_tags = fetch_customer_tags(&mut *tx, keep.id).await?;
+        record_tag_change(&mut *tx, user_id, keep.id, &old_tags, &new_tags).await?;
+    }
+
+    sqlx::query("UPDATE invoices SET customer_id = ? WHERE customer_id = ?")
+        .bind(keep.id)
+        .bind(remove.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM customers WHERE id = ?")
+        .bind(remove.id)
+        .execute(&mut *tx)
+        .await?;
+    record_audit(
+        &mut *tx,
+        user_id,
+        AuditEntity::Customer,
+        remove.id,
+        Some(&remove),
+        None,
+    )
+    .await?;
+    queue_webhook(&mut *tx, WebhookEvent::CustomerDeleted, &remove).await?;
+
+    tx.commit().await?;
+
+    println!(
+        "🔗 Merged customer ID {} into ID {} ({} transactions moved)",
+        remove.id,
+        keep.id,
+        moved.len()
+    );
+
+    let jar = jar.add(flash_cookie(format!(
+        "مشتری «{}» در «{}» ادغام شد ✅",
+        remove.full_name, merged.full_name
+    )));
+
+    Ok((jar, Redirect::to(&format!("/customer/{}", keep.id))))
+}
+
+fn same_customer() -> AppError {
+    AppError::BadRequest("یک مشتری را نمی‌توان با خودش ادغام کرد".to_string())
+}
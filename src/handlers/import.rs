@@ -0,0 +1,279 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use askama::Template;
+use axum::{
+    extract::{multipart::MultipartError, Multipart, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    Form,
+};
+use axum_extra::extract::CookieJar;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use uuid::Uuid;
+
+use crate::{
+    db::{audit::record_audit, webhooks::queue_webhook},
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        duplicate::name_key,
+        import::{validate_rows, MAX_IMPORT_ROWS},
+        AuditEntity, Customer, ImportColumn, ImportField, ImportMappingForm, ImportSheet,
+        ImportSummary, WebhookEvent,
+    },
+    templates::customers::{ImportPreviewTemplate, ImportTemplate},
+    utils::spreadsheet::{file_too_large, read_rows},
+};
+
+/// Uploaded files waiting to be imported are deleted after this long
+const IMPORT_FILE_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Show the form for uploading a customer spreadsheet
+pub async fn show_import_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let template = ImportTemplate {
+        current_user,
+        csrf_token,
+        active_page: "customers",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// Read an uploaded CSV or XLSX file and send the user to its preview
+pub async fn upload_import(mut multipart: Multipart) -> AppResult<impl IntoResponse> {
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let bytes = field.bytes().await.map_err(multipart_error)?;
+            upload = Some((file_name, bytes));
+        }
+    }
+
+    let (file_name, bytes) = upload
+        .filter(|(_, bytes)| !bytes.is_empty())
+        .ok_or_else(|| AppError::BadRequest("فایلی انتخاب نشده است".to_string()))?;
+
+    let sheet = ImportSheet::from_rows(file_name.clone(), read_rows(&file_name, &bytes)?);
+    if sheet.rows.is_empty() {
+        return Err(AppError::BadRequest(
+            "فایل هیچ ردیفی زیر سطر عنوان ندارد".to_string(),
+        ));
+    }
+    if sheet.rows.len() > MAX_IMPORT_ROWS {
+        return Err(AppError::BadRequest(format!(
+            "هر بار حداکثر {} ردیف را می‌توان وارد کرد",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    let token = Uuid::new_v4();
+    save_sheet(token, &sheet).await?;
+
+    println!(
+        "📥 Customer import uploaded: {} ({} rows)",
+        file_name,
+        sheet.rows.len()
+    );
+
+    Ok(Redirect::to(&format!("/import/{}", token)))
+}
+
+/// Show how each row would be imported under the detected or chosen column mapping
+pub async fn preview_import(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(token): Path<String>,
+    Query(fields): Query<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let token = parse_token(&token)?;
+    let sheet = load_sheet(token).await?;
+
+    let mapping = column_mapping(&sheet, &fields);
+    let existing_phones = existing_phones(&mut *pool.acquire().await?).await?;
+    let rows = validate_rows(&sheet, &mapping, &existing_phones);
+    let summary = ImportSummary::of(&rows);
+
+    let template = ImportPreviewTemplate {
+        token: token.to_string(),
+        file_name: sheet.file_name.clone(),
+        columns: sheet
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| ImportColumn {
+                header: header.clone(),
+                sample: sheet
+                    .rows
+                    .iter()
+                    .filter_map(|row| row.get(index))
+                    .find(|cell| !cell.trim().is_empty())
+                    .cloned()
+                    .unwrap_or_default(),
+                field: mapping.get(index).copied().flatten(),
+            })
+            .collect(),
+        fields: ImportField::ALL.to_vec(),
+        rows,
+        summary,
+        current_user,
+        csrf_token,
+        active_page: "customers",
+    };
+    Ok(Html(template.render()?))
+}
+
+/// Insert the new rows in one transaction, skipping invalid and duplicate ones
+pub async fn commit_import(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(token): Path<String>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+    let token = parse_token(&token)?;
+    let sheet = load_sheet(token).await?;
+    let mapping = column_mapping(&sheet, &fields);
+
+    let mut tx = pool.begin().await?;
+
+    // Checked again inside the transaction, since customers may have been added since the preview
+    let existing_phones = existing_phones(&mut tx).await?;
+    let rows = validate_rows(&sheet, &mapping, &existing_phones);
+    let summary = ImportSummary::of(&rows);
+
+    for row in rows.iter().filter(|row| row.is_new()) {
+        let form = &row.form;
+        let id = sqlx::query(
+            "INSERT INTO customers (full_name, name_key, company, email, phone_number, sales_count, job_title, city, address, notes, coordinates)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&form.full_name)
+        .bind(name_key(&form.full_name))
+        .bind(&form.company)
+        .bind(&form.email)
+        .bind(&form.phone_number)
+        .bind(form.sales_count)
+        .bind(&form.job_title)
+        .bind(&form.city)
+        .bind(&form.address)
+        .bind(&form.notes)
+        .bind(&form.coordinates)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        let customer = sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        record_audit(
+            &mut *tx,
+            current_user.as_ref().map(|user| user.id),
+            AuditEntity::Customer,
+            id,
+            None,
+            Some(&customer),
+        )
+        .await?;
+        queue_webhook(&mut *tx, WebhookEvent::CustomerCreated, &customer).await?;
+    }
+
+    tx.commit().await?;
+    let _ = tokio::fs::remove_file(sheet_path(token)).await;
+
+    println!(
+        "📥 Customer import from {}: {} created, {} duplicates, {} skipped",
+        sheet.file_name, summary.created, summary.duplicates, summary.skipped
+    );
+
+    let jar = jar.add(flash_cookie(format!(
+        "ورود از فایل انجام شد ✅ {} مشتری جدید، {} تکراری، {} ردیف نامعتبر رد شد",
+        summary.created, summary.duplicates, summary.skipped
+    )));
+
+    Ok((jar, Redirect::to("/")))
+}
+
+/// The mapping submitted with the request, or the one detected from the headers
+fn column_mapping(sheet: &ImportSheet, fields: &[(String, String)]) -> Vec<Option<ImportField>> {
+    let form = ImportMappingForm::from_fields(fields);
+    if form.columns.is_empty() {
+        sheet.detect_mapping()
+    } else {
+        form.columns
+    }
+}
+
+async fn existing_phones(conn: &mut SqliteConnection) -> AppResult<HashSet<String>> {
+    let phones: Vec<String> = sqlx::query_scalar("SELECT phone_number FROM customers")
+        .fetch_all(conn)
+        .await?;
+    Ok(phones.into_iter().collect())
+}
+
+fn parse_token(token: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(token).map_err(|_| AppError::NotFound)
+}
+
+fn import_dir() -> PathBuf {
+    std::env::temp_dir().join("rumiland_imports")
+}
+
+fn sheet_path(token: Uuid) -> PathBuf {
+    import_dir().join(format!("{}.json", token))
+}
+
+/// Keep the rows of an upload until it is imported, clearing out abandoned uploads
+async fn save_sheet(token: Uuid, sheet: &ImportSheet) -> AppResult<()> {
+    let dir = import_dir();
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to create import directory: {}", e)))?;
+
+    if let Ok(mut entries) = tokio::fs::read_dir(&dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let expired = entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > IMPORT_FILE_LIFETIME);
+            if expired {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+            }
+        }
+    }
+
+    let json = serde_json::to_vec(sheet).map_err(|e| AppError::Internal(e.to_string()))?;
+    tokio::fs::write(sheet_path(token), json)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to save import: {}", e)))
+}
+
+async fn load_sheet(token: Uuid) -> AppResult<ImportSheet> {
+    let json = tokio::fs::read(sheet_path(token))
+        .await
+        .map_err(|_| AppError::NotFound)?;
+    serde_json::from_slice(&json).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn multipart_error(err: MultipartError) -> AppError {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        file_too_large()
+    } else {
+        AppError::BadRequest("فرم ارسال‌شده معتبر نیست".to_string())
+    }
+}
@@ -0,0 +1,793 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect},
+    Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Executor, Pool, QueryBuilder, Sqlite, SqliteConnection};
+
+use crate::{
+    db::webhooks::{queue_stock_low, queue_webhook},
+    error::{AppError, AppResult},
+    handlers::{
+        catalog::{parse_price, refresh_product_stock},
+        flash_cookie,
+        transactions::fetch_transaction,
+    },
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        invoice::INVOICE_SUMMARY_SQL, Invoice, InvoiceForm, InvoiceItem, InvoiceItemForm,
+        InvoiceListQuery, InvoiceStatus, InvoiceSummary, Money, Product, SaleItemOption,
+        StockSettings, Transaction, TransactionDirection, TransactionType, WebhookEvent,
+    },
+    templates::invoices::{InvoiceDetailTemplate, InvoiceListTemplate, NewInvoiceTemplate},
+    utils::date::{shamsi_to_iso, to_shamsi, today},
+};
+
+/// Everything that can be sold: products without variants, and every variant
+const SALE_ITEM_OPTIONS_SQL: &str = "SELECT * FROM (
+        SELECT 'product:' || products.id AS value, products.name AS label,
+               products.id AS product_id, NULL AS variant_id,
+               products.price, products.stock
+        FROM products
+        WHERE NOT EXISTS (SELECT 1 FROM product_variants WHERE product_id = products.id)
+        UNION ALL
+        SELECT 'variant:' || product_variants.id,
+               products.name || ' - ' || product_variants.variant_name,
+               product_variants.product_id, product_variants.id,
+               product_variants.price, product_variants.stock
+        FROM product_variants
+        JOIN products ON products.id = product_variants.product_id
+     )";
+
+/// List invoices, optionally by status or customer
+pub async fn list_invoices(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<InvoiceListQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let status = InvoiceStatus::parse(&query.status);
+
+    let mut select_query = QueryBuilder::<Sqlite>::new(INVOICE_SUMMARY_SQL);
+    select_query.push(" WHERE 1 = 1");
+    if let Some(status) = status {
+        select_query
+            .push(" AND invoices.status = ")
+            .push_bind(status.as_str());
+    }
+    if let Some(customer_id) = query.customer_id {
+        select_query
+            .push(" AND invoices.customer_id = ")
+            .push_bind(customer_id);
+    }
+    select_query.push(" ORDER BY invoices.invoice_date DESC, invoices.id DESC");
+
+    let invoices = select_query
+        .build_query_as::<InvoiceSummary>()
+        .fetch_all(&pool)
+        .await?;
+
+    let template = InvoiceListTemplate {
+        invoices,
+        status,
+        statuses: InvoiceStatus::all(),
+        current_user,
+        csrf_token,
+        active_page: "invoices",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Show the form to start a new invoice
+pub async fn show_new_invoice_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<InvoiceListQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let customers = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, full_name FROM customers ORDER BY full_name",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let template = NewInvoiceTemplate {
+        customers,
+        selected_customer_id: query.customer_id.unwrap_or_default(),
+        today: to_shamsi(today()),
+        current_user,
+        csrf_token,
+        active_page: "invoices",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Create a draft invoice and open it to add line items
+pub async fn create_invoice(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Form(form): Form<InvoiceForm>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    let customer_exists: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM customers WHERE id = ?)")
+            .bind(form.customer_id)
+            .fetch_one(&pool)
+            .await?;
+    if !customer_exists {
+        return Err(AppError::BadRequest("مشتری انتخاب‌شده وجود ندارد".to_string()));
+    }
+
+    let invoice_date = shamsi_to_iso(&form.invoice_date).ok_or_else(|| {
+        AppError::BadRequest(
+            "تاریخ فاکتور معتبر نیست. لطفا از فرمت YYYY/MM/DD استفاده کنید".to_string(),
+        )
+    })?;
+
+    let result = sqlx::query(
+        "INSERT INTO invoices (customer_id, invoice_date, notes, created_by) VALUES (?, ?, ?, ?)",
+    )
+    .bind(form.customer_id)
+    .bind(&invoice_date)
+    .bind(form.notes.trim())
+    .bind(current_user.map(|user| user.id))
+    .execute(&pool)
+    .await?;
+
+    let id = result.last_insert_rowid();
+    println!("🧾 New draft invoice {} for customer {}", id, form.customer_id);
+
+    Ok(Redirect::to(&format!("/invoices/{}", id)))
+}
+
+/// Show an invoice with its line items
+pub async fn view_invoice(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let invoice = sqlx::query_as::<_, InvoiceSummary>(&format!(
+        "{} WHERE invoices.id = ?",
+        INVOICE_SUMMARY_SQL
+    ))
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let items = fetch_items(&pool, id).await?;
+
+    // Items can only be added while the invoice is a draft
+    let options = if invoice.invoice.status_enum() == InvoiceStatus::Draft {
+        sqlx::query_as::<_, SaleItemOption>(&format!("{} ORDER BY label", SALE_ITEM_OPTIONS_SQL))
+            .fetch_all(&pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let template = InvoiceDetailTemplate {
+        invoice,
+        items,
+        options,
+        current_user,
+        csrf_token,
+        active_page: "invoices",
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Add a product or variant to a draft invoice
+pub async fn add_invoice_item(
+    State(pool): State<Pool<Sqlite>>,
+    Path(id): Path<i64>,
+    Form(form): Form<InvoiceItemForm>,
+) -> AppResult<impl IntoResponse> {
+    let invoice = fetch_invoice(&pool, id).await?;
+    ensure_draft(&invoice)?;
+
+    let option = sqlx::query_as::<_, SaleItemOption>(&format!(
+        "{} WHERE value = ?",
+        SALE_ITEM_OPTIONS_SQL
+    ))
+    .bind(&form.item)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("کالای انتخاب‌شده معتبر نیست".to_string()))?;
+
+    if form.quantity <= 0 {
+        return Err(AppError::BadRequest("تعداد باید بیشتر از صفر باشد".to_string()));
+    }
+
+    let unit_price = if form.unit_price.trim().is_empty() {
+        option.price
+    } else {
+        parse_price(&form.unit_price)?
+    };
+    let discount = if form.discount.trim().is_empty() {
+        Money::ZERO
+    } else {
+        parse_price(&form.discount)?
+    };
+    let line_amount = unit_price
+        .checked_mul(form.quantity)
+        .ok_or_else(|| AppError::BadRequest("مبلغ این ردیف بیش از حد بزرگ است".to_string()))?;
+    if discount > line_amount {
+        return Err(AppError::BadRequest(
+            "تخفیف نمی‌تواند بیشتر از مبلغ ردیف باشد".to_string(),
+        ));
+    }
+
+    // Only added if the invoice is still a draft, in case it was confirmed meanwhile
+    let result = sqlx::query(
+        "INSERT INTO invoice_items (invoice_id, product_id, variant_id, description, quantity, unit_price, discount)
+         SELECT ?, ?, ?, ?, ?, ?, ?
+         WHERE EXISTS (SELECT 1 FROM invoices WHERE id = ? AND status = ?)",
+    )
+    .bind(id)
+    .bind(option.product_id)
+    .bind(option.variant_id)
+    .bind(&option.label)
+    .bind(form.quantity)
+    .bind(unit_price)
+    .bind(discount)
+    .bind(id)
+    .bind(InvoiceStatus::Draft.as_str())
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(status_changed());
+    }
+
+    Ok(Redirect::to(&format!("/invoices/{}", id)))
+}
+
+/// Remove a line from a draft invoice
+pub async fn delete_invoice_item(
+    State(pool): State<Pool<Sqlite>>,
+    Path((id, item_id)): Path<(i64, i64)>,
+) -> AppResult<impl IntoResponse> {
+    let invoice = fetch_invoice(&pool, id).await?;
+    ensure_draft(&invoice)?;
+
+    let result = sqlx::query(
+        "DELETE FROM invoice_items
+         WHERE id = ? AND invoice_id = ?
+           AND EXISTS (SELECT 1 FROM invoices WHERE id = ? AND status = ?)",
+    )
+    .bind(item_id)
+    .bind(id)
+    .bind(id)
+    .bind(InvoiceStatus::Draft.as_str())
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        // Either there is no such line, or the invoice stopped being a draft meanwhile
+        ensure_draft(&fetch_invoice(&pool, id).await?)?;
+        return Err(AppError::NotFound);
+    }
+
+    Ok(Redirect::to(&format!("/invoices/{}", id)))
+}
+
+/// Confirm a draft invoice: take its items out of stock and record the receivable
+pub async fn confirm_invoice(
+    State(pool): State<Pool<Sqlite>>,
+    State(stock): State<StockSettings>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    // Read inside the transaction, so the stock taken and the receivable recorded cover
+    // exactly the lines the invoice is confirmed with
+    let mut tx = pool.begin().await?;
+
+    let invoice = fetch_invoice(&mut *tx, id).await?;
+    ensure_draft(&invoice)?;
+
+    let items = fetch_items(&mut *tx, id).await?;
+    if items.is_empty() {
+        return Err(AppError::BadRequest("فاکتور بدون ردیف را نمی‌توان تأیید کرد".to_string()));
+    }
+    let total: Money = items.iter().map(InvoiceItem::total).sum();
+    if total <= Money::ZERO {
+        return Err(AppError::BadRequest("مبلغ فاکتور باید بیشتر از صفر باشد".to_string()));
+    }
+
+    for item in &items {
+        take_from_stock(&mut tx, stock, item).await?;
+    }
+
+    let transaction_id = sqlx::query(
+        "INSERT INTO transactions (customer_id, amount, transaction_type, description, transaction_date, direction)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(invoice.customer_id)
+    .bind(total)
+    .bind(TransactionType::Credit.as_str())
+    .bind(format!("فاکتور فروش شماره {}", id))
+    .bind(&invoice.invoice_date)
+    .bind(TransactionDirection::Debit.as_str())
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+    let transaction = fetch_transaction(&mut tx, invoice.customer_id, transaction_id).await?;
+    queue_webhook(&mut *tx, WebhookEvent::TransactionCreated, &transaction).await?;
+
+    let result = sqlx::query(
+        "UPDATE invoices SET status = ?, transaction_id = ? WHERE id = ? AND status = ?",
+    )
+    .bind(InvoiceStatus::Confirmed.as_str())
+    .bind(transaction_id)
+    .bind(id)
+    .bind(InvoiceStatus::Draft.as_str())
+    .execute(&mut *tx)
+    .await?;
+    // Someone else confirmed or cancelled it in the meantime
+    if result.rows_affected() == 0 {
+        return Err(status_changed());
+    }
+
+    tx.commit().await?;
+
+    println!("✅ Invoice {} confirmed for {}", id, total);
+
+    Ok((
+        jar.add(flash_cookie(
+            "فاکتور تأیید شد و مبلغ آن به حساب مشتری ثبت شد",
+        )),
+        Redirect::to(&format!("/invoices/{}", id)),
+    ))
+}
+
+/// Cancel an invoice, putting a confirmed invoice's items back in stock and removing its receivable
+pub async fn cancel_invoice(
+    State(pool): State<Pool<Sqlite>>,
+    State(stock): State<StockSettings>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let mut tx = pool.begin().await?;
+
+    let invoice = fetch_invoice(&mut *tx, id).await?;
+    let status = invoice.status_enum();
+    if status == InvoiceStatus::Cancelled {
+        return Err(AppError::BadRequest("این فاکتور قبلا لغو شده است".to_string()));
+    }
+
+    let items = fetch_items(&mut *tx, id).await?;
+
+    if status == InvoiceStatus::Confirmed {
+        for item in &items {
+            return_to_stock(&mut tx, stock, item).await?;
+        }
+        if let Some(transaction_id) = invoice.transaction_id {
+            // It may already be gone if it was deleted from the customer's ledger
+            let transaction =
+                sqlx::query_as::<_, Transaction>("DELETE FROM transactions WHERE id = ? RETURNING *")
+                    .bind(transaction_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if let Some(transaction) = transaction {
+                queue_webhook(&mut *tx, WebhookEvent::TransactionDeleted, &transaction).await?;
+            }
+        }
+    }
+
+    let result = sqlx::query(
+        "UPDATE invoices SET status = ?, transaction_id = NULL WHERE id = ? AND status = ?",
+    )
+    .bind(InvoiceStatus::Cancelled.as_str())
+    .bind(id)
+    .bind(status.as_str())
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(status_changed());
+    }
+
+    tx.commit().await?;
+
+    println!("🚫 Invoice {} cancelled (was {})", id, status.as_str());
+
+    Ok((
+        jar.add(flash_cookie("فاکتور لغو شد")),
+        Redirect::to(&format!("/invoices/{}", id)),
+    ))
+}
+
+async fn fetch_invoice<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    id: i64,
+) -> AppResult<Invoice> {
+    sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = ?")
+        .bind(id)
+        .fetch_optional(executor)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+async fn fetch_items<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    invoice_id: i64,
+) -> AppResult<Vec<InvoiceItem>> {
+    Ok(
+        sqlx::query_as::<_, InvoiceItem>(
+            "SELECT * FROM invoice_items WHERE invoice_id = ? ORDER BY id",
+        )
+        .bind(invoice_id)
+        .fetch_all(executor)
+        .await?,
+    )
+}
+
+/// The invoice was confirmed or cancelled by someone else while this request ran
+fn status_changed() -> AppError {
+    AppError::BadRequest("وضعیت فاکتور تغییر کرده است".to_string())
+}
+
+fn ensure_draft(invoice: &Invoice) -> AppResult<()> {
+    if invoice.status_enum() != InvoiceStatus::Draft {
+        return Err(AppError::BadRequest(
+            "فقط فاکتورهای پیش‌نویس قابل تغییر هستند".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Take a line's quantity out of its variant's or product's stock, refusing to go below zero
+async fn take_from_stock(
+    conn: &mut SqliteConnection,
+    stock: StockSettings,
+    item: &InvoiceItem,
+) -> AppResult<()> {
+    let out_of_stock = || AppError::BadRequest(format!("موجودی «{}» کافی نیست", item.description));
+
+    match (item.variant_id, item.product_id) {
+        (Some(variant_id), _) => {
+            let product_id: i64 = sqlx::query_scalar(
+                "UPDATE product_variants SET stock = stock - ? WHERE id = ? AND stock >= ? RETURNING product_id",
+            )
+            .bind(item.quantity)
+            .bind(variant_id)
+            .bind(item.quantity)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or_else(out_of_stock)?;
+            refresh_product_stock(conn, stock, product_id).await
+        }
+        (None, Some(product_id)) => {
+            // Products with variants keep their stock on the variants
+            let product = sqlx::query_as::<_, Product>(
+                "UPDATE products SET stock = stock - ?
+                 WHERE id = ? AND stock >= ?
+                   AND NOT EXISTS (SELECT 1 FROM product_variants WHERE product_id = products.id)
+                 RETURNING *",
+            )
+            .bind(item.quantity)
+            .bind(product_id)
+            .bind(item.quantity)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or_else(out_of_stock)?;
+            queue_stock_low(conn, stock, product.stock + item.quantity as i32, &product).await?;
+            Ok(())
+        }
+        (None, None) => Err(AppError::BadRequest(format!(
+            "«{}» دیگر در کاتالوگ نیست؛ این ردیف را حذف کنید",
+            item.description
+        ))),
+    }
+}
+
+/// Put a line's quantity back into stock. Items removed from the catalog since are skipped.
+async fn return_to_stock(
+    conn: &mut SqliteConnection,
+    stock: StockSettings,
+    item: &InvoiceItem,
+) -> AppResult<()> {
+    match (item.variant_id, item.product_id) {
+        (Some(variant_id), _) => {
+            let product_id: Option<i64> = sqlx::query_scalar(
+                "UPDATE product_variants SET stock = stock + ? WHERE id = ? RETURNING product_id",
+            )
+            .bind(item.quantity)
+            .bind(variant_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+            if let Some(product_id) = product_id {
+                refresh_product_stock(conn, stock, product_id).await?;
+            }
+        }
+        (None, Some(product_id)) => {
+            sqlx::query(
+                "UPDATE products SET stock = stock + ?
+                 WHERE id = ?
+                   AND NOT EXISTS (SELECT 1 FROM product_variants WHERE product_id = products.id)",
+            )
+            .bind(item.quantity)
+            .bind(product_id)
+            .execute(conn)
+            .await?;
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    const STOCK: StockSettings = StockSettings {
+        low_stock_threshold: 5,
+    };
+
+    /// A customer, a product sold as is with 5 in stock, and a product whose one variant
+    /// has 4 in stock
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        sqlx::query(
+            "INSERT INTO customers (full_name, company, phone_number, notes)
+             VALUES ('علی', 'شرکت', '09121234567', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO products (name, description, price, stock)
+             VALUES ('چای', '', 1000000, 5), ('قهوه', '', 1500000, 4)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO product_variants (product_id, variant_name, price, stock)
+             VALUES (2, '۲۵۰ گرمی', 1500000, 4)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    /// A draft invoice selling some of the plain product and some of the variant
+    async fn add_draft(pool: &Pool<Sqlite>, product_quantity: i64, variant_quantity: i64) -> i64 {
+        let id = sqlx::query(
+            "INSERT INTO invoices (customer_id, invoice_date) VALUES (1, '2024-03-29')",
+        )
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_rowid();
+        sqlx::query(
+            "INSERT INTO invoice_items (invoice_id, product_id, variant_id, description, quantity, unit_price, discount)
+             VALUES (?, 1, NULL, 'چای', ?, 1000000, 0), (?, 2, 1, 'قهوه - ۲۵۰ گرمی', ?, 1500000, 500000)",
+        )
+        .bind(id)
+        .bind(product_quantity)
+        .bind(id)
+        .bind(variant_quantity)
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn confirm(pool: &Pool<Sqlite>, id: i64) -> AppResult<()> {
+        confirm_invoice(
+            State(pool.clone()),
+            State(STOCK),
+            CookieJar::new(),
+            Path(id),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn cancel(pool: &Pool<Sqlite>, id: i64) -> AppResult<()> {
+        cancel_invoice(
+            State(pool.clone()),
+            State(STOCK),
+            CookieJar::new(),
+            Path(id),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Stock of the plain product, the variant, and the variant's product
+    async fn stock(pool: &Pool<Sqlite>) -> (i64, i64, i64) {
+        sqlx::query_as(
+            "SELECT (SELECT stock FROM products WHERE id = 1),
+                    (SELECT stock FROM product_variants WHERE id = 1),
+                    (SELECT stock FROM products WHERE id = 2)",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn invoice_state(pool: &Pool<Sqlite>, id: i64) -> (String, Option<i64>) {
+        sqlx::query_as("SELECT status, transaction_id FROM invoices WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_confirm_takes_stock_and_cancel_returns_it() {
+        let pool = test_pool().await;
+        let id = add_draft(&pool, 2, 3).await;
+
+        confirm(&pool, id).await.unwrap();
+        assert_eq!(stock(&pool).await, (3, 1, 1));
+
+        let (status, transaction_id) = invoice_state(&pool, id).await;
+        assert_eq!(status, "confirmed");
+        let transaction_id = transaction_id.expect("a confirmed invoice has a receivable");
+        let (amount, direction): (Money, String) =
+            sqlx::query_as("SELECT amount, direction FROM transactions WHERE id = ?")
+                .bind(transaction_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(amount, Money::parse("600000").unwrap());
+        assert_eq!(direction, "debit");
+
+        // A confirmed invoice cannot be confirmed again
+        assert!(matches!(
+            confirm(&pool, id).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(stock(&pool).await, (3, 1, 1));
+
+        cancel(&pool, id).await.unwrap();
+        assert_eq!(stock(&pool).await, (5, 4, 4));
+        assert_eq!(
+            invoice_state(&pool, id).await,
+            ("cancelled".to_string(), None)
+        );
+        let transactions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(transactions, 0);
+
+        assert!(matches!(
+            cancel(&pool, id).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(stock(&pool).await, (5, 4, 4));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_refuses_when_out_of_stock() {
+        let pool = test_pool().await;
+        // The first line fits, the second does not; neither may be taken
+        let id = add_draft(&pool, 2, 5).await;
+
+        assert!(matches!(
+            confirm(&pool, id).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(stock(&pool).await, (5, 4, 4));
+        assert_eq!(invoice_state(&pool, id).await, ("draft".to_string(), None));
+        let transactions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(transactions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_draft_leaves_stock_alone() {
+        let pool = test_pool().await;
+        let id = add_draft(&pool, 2, 3).await;
+
+        cancel(&pool, id).await.unwrap();
+        assert_eq!(stock(&pool).await, (5, 4, 4));
+        assert_eq!(
+            invoice_state(&pool, id).await,
+            ("cancelled".to_string(), None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_item_refuses_overflowing_quantity() {
+        let pool = test_pool().await;
+        let id = add_draft(&pool, 1, 1).await;
+
+        let form = InvoiceItemForm {
+            item: "product:1".to_string(),
+            quantity: i64::MAX,
+            unit_price: String::new(),
+            discount: String::new(),
+        };
+        let result = add_invoice_item(State(pool.clone()), Path(id), Form(form)).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_lines_added_while_confirming_are_refused_or_confirmed() {
+        let pool = test_pool().await;
+        let id = add_draft(&pool, 1, 1).await;
+
+        let form = InvoiceItemForm {
+            item: "product:1".to_string(),
+            quantity: 2,
+            unit_price: String::new(),
+            discount: String::new(),
+        };
+        let (added, confirmed) = tokio::join!(
+            add_invoice_item(State(pool.clone()), Path(id), Form(form)),
+            confirm(&pool, id),
+        );
+        confirmed.unwrap();
+
+        // Whichever ran first, every line on the confirmed invoice was taken from stock
+        // and charged to the customer
+        let items = fetch_items(&pool, id).await.unwrap();
+        assert_eq!(items.len(), if added.is_ok() { 3 } else { 2 });
+        let sold: i64 = items
+            .iter()
+            .filter(|item| item.product_id == Some(1))
+            .map(|item| item.quantity)
+            .sum();
+        assert_eq!(stock(&pool).await.0, 5 - sold);
+        let amount: Money = sqlx::query_scalar(
+            "SELECT amount FROM transactions WHERE id = (SELECT transaction_id FROM invoices WHERE id = ?)",
+        )
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(amount, items.iter().map(InvoiceItem::total).sum::<Money>());
+
+        // Lines cannot be added to or removed from it afterwards
+        let form = InvoiceItemForm {
+            item: "product:1".to_string(),
+            quantity: 1,
+            unit_price: String::new(),
+            discount: String::new(),
+        };
+        let result = add_invoice_item(State(pool.clone()), Path(id), Form(form)).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        let result = delete_invoice_item(State(pool.clone()), Path((id, items[0].id))).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(fetch_items(&pool, id).await.unwrap().len(), items.len());
+    }
+}
@@ -1,70 +1,420 @@
+pub mod account;
+pub mod api;
+pub mod audit;
 pub mod auth;
 pub mod catalog;
+pub mod cheques;
 pub mod customers;
+pub mod documents;
+pub mod duplicates;
+pub mod import;
+pub mod invoices;
+pub mod roles;
+pub mod segments;
+pub mod sessions;
+pub mod tags;
+pub mod tokens;
 pub mod transactions;
 pub mod users;
+pub mod webhooks;
 
-use axum::Router;
-use sqlx::{Pool, Sqlite};
+use axum::{extract::DefaultBodyLimit, Router};
+use axum_extra::extract::cookie::{Cookie, SameSite};
+
+use crate::{
+    models::Permission,
+    state::AppState,
+    utils::{
+        media::{MAX_UPLOAD_FORM_BYTES, MEDIA_URL_PREFIX},
+        spreadsheet::MAX_SPREADSHEET_FORM_BYTES,
+    },
+};
 
 /// Configure all routes for the application
-pub fn configure_routes(pool: Pool<Sqlite>) -> Router {
+pub fn configure_routes(state: AppState) -> Router {
     use axum::routing::{get, post};
     use tower_http::services::ServeDir;
 
+    // Product forms carry an image, so allow it plus the other fields
+    let product_form_limit = DefaultBodyLimit::max(MAX_UPLOAD_FORM_BYTES);
+    let import_form_limit = DefaultBodyLimit::max(MAX_SPREADSHEET_FORM_BYTES);
+
     // Public routes (no auth required)
     let public_routes = Router::new()
         .route("/login", get(auth::show_login).post(auth::do_login))
-        .nest_service("/static", ServeDir::new("static"));
+        .nest_service("/static", ServeDir::new("static"))
+        .nest_service(MEDIA_URL_PREFIX, ServeDir::new(state.media.root()));
 
-    // Protected routes (auth required)
-    let protected_routes = Router::new()
+    // Routes needing only a signed-in user: viewing, printing and one's own account
+    let signed_in_routes = Router::new()
         .route("/", get(customers::list_customers))
+        .route("/customer/:id", get(customers::view_customer))
+        .route(
+            "/customer/:customer_id/receipt/:transaction_id",
+            get(documents::print_receipt),
+        )
+        .route(
+            "/customer/:customer_id/receipt/:transaction_id/pdf",
+            get(documents::receipt_pdf),
+        )
+        // Cheque routes
+        .route("/cheques", get(cheques::list_cheques))
+        .route("/cheques/:id", get(cheques::view_cheque))
+        // Invoice routes
+        .route("/invoices", get(invoices::list_invoices))
+        .route("/invoices/:id", get(invoices::view_invoice))
+        .route("/invoices/:id/print", get(documents::print_invoice))
+        .route("/invoices/:id/pdf", get(documents::invoice_pdf))
+        .route("/logout", post(auth::logout))
+        // Customer tags and saved segments
+        .route("/tags", get(tags::list_tags))
+        .route("/segments", get(segments::list_segments))
+        // Catalog routes
+        .route("/catalog", get(catalog::show_catalog))
+        .route("/catalog/product/:id", get(catalog::view_product))
+        .route(
+            "/account/password",
+            get(account::show_change_password).post(account::change_password),
+        )
+        .route("/sessions", get(sessions::list_sessions))
+        .route("/sessions/revoke/:public_id", post(sessions::revoke_session))
+        .route("/sessions/revoke-others", post(sessions::revoke_other_sessions))
+        .route("/tokens", get(tokens::list_tokens).post(tokens::create_token))
+        .route("/tokens/revoke/:id", post(tokens::revoke_token))
+        // Description of the JSON API
+        .route("/api/openapi.json", get(api::openapi::openapi_json))
+        .route("/api/docs", get(api::openapi::show_docs));
+
+    // Each group below is only open to users whose role has its permission
+    let permission = |permission: Permission| {
+        axum::middleware::from_fn_with_state(
+            (state.pool.clone(), permission),
+            crate::middleware::permission_middleware,
+        )
+    };
+
+    let customer_edit_routes = Router::new()
         .route(
             "/add",
             get(customers::show_add_form).post(customers::add_customer),
         )
-        .route("/customer/:id", get(customers::view_customer))
+        .route(
+            "/edit/:id",
+            get(customers::show_edit_form).post(customers::update_customer),
+        )
+        // Customer import routes
+        .route(
+            "/import",
+            get(import::show_import_form)
+                .post(import::upload_import)
+                .layer(import_form_limit),
+        )
+        .route(
+            "/import/:token",
+            get(import::preview_import).post(import::commit_import),
+        )
+        // Tagging and segment routes
+        .route("/customers/tags", post(tags::tag_customers))
+        .route("/customer/:id/tags", post(tags::tag_customer))
+        .route("/tags/delete/:id", post(tags::delete_tag))
+        .route("/segments", post(segments::save_segment))
+        .route("/segments/delete/:id", post(segments::delete_segment))
+        .route_layer(permission(Permission::CustomersEdit));
+
+    let customer_merge_routes = Router::new()
+        .route("/duplicates", get(duplicates::list_duplicates))
+        .route(
+            "/merge",
+            get(duplicates::show_merge_form).post(duplicates::merge_customers),
+        )
+        .route_layer(permission(Permission::CustomersMerge));
+
+    let customer_delete_routes = Router::new()
+        .route("/delete/:id", post(customers::delete_customer))
+        .route_layer(permission(Permission::CustomersDelete));
+
+    let transaction_edit_routes = Router::new()
         .route(
             "/customer/:id/add-transaction",
             get(transactions::show_add_transaction_form).post(transactions::add_transaction),
         )
-        .route("/customer/:customer_id/delete-transaction/:transaction_id", post(transactions::delete_transaction))
         .route(
             "/customer/:customer_id/edit-transaction/:transaction_id",
             get(transactions::show_edit_transaction_form).post(transactions::edit_transaction)
         )
-        .route("/delete/:id", post(customers::delete_customer))
+        .route_layer(permission(Permission::TransactionsEdit));
+
+    let transaction_delete_routes = Router::new()
+        .route("/customer/:customer_id/delete-transaction/:transaction_id", post(transactions::delete_transaction))
+        .route_layer(permission(Permission::TransactionsDelete));
+
+    let cheque_routes = Router::new()
+        .route("/cheques/:id/status", post(cheques::change_cheque_status))
+        .route_layer(permission(Permission::ChequesManage));
+
+    let invoice_routes = Router::new()
         .route(
-            "/edit/:id",
-            get(customers::show_edit_form).post(customers::update_customer),
+            "/invoices/new",
+            get(invoices::show_new_invoice_form).post(invoices::create_invoice),
         )
-        .route("/export/customers", get(customers::export_customer))
-        .route("/logout", post(auth::logout))
-        // Catalog routes
-        .route("/catalog", get(catalog::show_catalog))
+        .route("/invoices/:id/items", post(invoices::add_invoice_item))
+        .route(
+            "/invoices/:id/items/:item_id/delete",
+            post(invoices::delete_invoice_item),
+        )
+        .route("/invoices/:id/confirm", post(invoices::confirm_invoice))
+        .route("/invoices/:id/cancel", post(invoices::cancel_invoice))
+        .route_layer(permission(Permission::InvoicesManage));
+
+    let catalog_routes = Router::new()
         .route(
             "/catalog/add",
-            get(catalog::show_add_product_form).post(catalog::add_product),
+            get(catalog::show_add_product_form)
+                .post(catalog::add_product)
+                .layer(product_form_limit),
         )
-        .route("/catalog/product/:id", get(catalog::view_product))
         .route(
             "/catalog/edit/:id",
-            get(catalog::show_edit_product_form).post(catalog::update_product),
+            get(catalog::show_edit_product_form)
+                .post(catalog::update_product)
+                .layer(product_form_limit),
         )
         .route("/catalog/delete/:id", post(catalog::delete_product))
-        // Admin only routes
+        .route(
+            "/catalog/product/:id/variants/add",
+            get(catalog::show_add_variant_form).post(catalog::add_variant),
+        )
+        .route(
+            "/catalog/product/:id/variants/edit/:variant_id",
+            get(catalog::show_edit_variant_form).post(catalog::update_variant),
+        )
+        .route(
+            "/catalog/product/:id/variants/delete/:variant_id",
+            post(catalog::delete_variant),
+        )
+        .route_layer(permission(Permission::CatalogManage));
+
+    let export_routes = Router::new()
+        .route("/export/customers", get(customers::export_customer))
+        .route_layer(permission(Permission::Export));
+
+    let user_routes = Router::new()
         .route("/users", get(users::list_users))
         .route(
             "/users/add",
             get(users::show_add_user_form).post(users::add_user),
         )
-        .route("/users/delete/:id", post(users::delete_user))
+        .route(
+            "/users/edit/:id",
+            get(users::show_edit_user_form).post(users::update_user),
+        )
+        .route("/users/deactivate/:id", post(users::deactivate_user))
+        .route("/users/activate/:id", post(users::activate_user))
+        .route("/users/logout/:id", post(users::logout_user))
+        .route(
+            "/users/reset-password/:id",
+            get(users::show_reset_password).post(users::reset_password),
+        )
+        .route("/users/lockouts/unlock", post(users::unlock_login))
+        // Role routes
+        .route("/roles", get(roles::list_roles))
+        .route("/roles/add", get(roles::show_add_role_form).post(roles::add_role))
+        .route(
+            "/roles/edit/:id",
+            get(roles::show_edit_role_form).post(roles::update_role),
+        )
+        .route("/roles/delete/:id", post(roles::delete_role))
+        .route("/tokens/all", get(tokens::list_all_tokens))
+        .route_layer(permission(Permission::UsersManage));
+
+    let audit_routes = Router::new()
+        .route("/audit", get(audit::list_audit))
+        .route_layer(permission(Permission::AuditView));
+
+    let webhook_routes = Router::new()
+        .route("/webhooks", get(webhooks::list_webhooks))
+        .route(
+            "/webhooks/add",
+            get(webhooks::show_add_webhook_form).post(webhooks::add_webhook),
+        )
+        .route("/webhooks/deliveries", get(webhooks::list_deliveries))
+        .route("/webhooks/:id", get(webhooks::view_webhook))
+        .route(
+            "/webhooks/edit/:id",
+            get(webhooks::show_edit_webhook_form).post(webhooks::update_webhook),
+        )
+        .route("/webhooks/delete/:id", post(webhooks::delete_webhook))
+        .route("/webhooks/redeliver/:id", post(webhooks::redeliver))
+        .route_layer(permission(Permission::WebhooksManage));
+
+    // Protected routes (auth required)
+    let protected_routes = signed_in_routes
+        .merge(customer_edit_routes)
+        .merge(customer_delete_routes)
+        .merge(customer_merge_routes)
+        .merge(transaction_edit_routes)
+        .merge(transaction_delete_routes)
+        .merge(cheque_routes)
+        .merge(invoice_routes)
+        .merge(catalog_routes)
+        .merge(export_routes)
+        .merge(user_routes)
+        .merge(audit_routes)
+        .merge(webhook_routes)
         .layer(axum::middleware::from_fn_with_state(
-            pool.clone(),
+            state.clone(),
             crate::middleware::auth_middleware,
         ));
 
-    // Combine routes
-    public_routes.merge(protected_routes).with_state(pool)
+    // JSON API; it checks the CSRF header itself, so it is added outside the form check
+    let api_routes = api::routes(&state);
+
+    // Combine routes; every POST must carry the CSRF token, checked before any handler runs
+    public_routes
+        .merge(protected_routes)
+        .layer(axum::middleware::from_fn_with_state(
+            state.pool.clone(),
+            crate::middleware::csrf_middleware,
+        ))
+        .nest("/api/v1", api_routes)
+        .with_state(state)
+}
+
+/// A message shown once on the next page, which reads the cookie and removes it
+pub fn flash_cookie(message: impl Into<String>) -> Cookie<'static> {
+    Cookie::build(("flash_message", message.into()))
+        .path("/")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .max_age(cookie::time::Duration::seconds(60))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        db::migrations::run_migrations,
+        models::api_token::{generate_token, hash_token},
+        utils::media::MediaStore,
+    };
+
+    /// The app over a database with customers 1 and 2 and a token of user 1 that may delete them
+    async fn app_with_token() -> (Router, Pool<Sqlite>, String) {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role)
+             VALUES ('sync', '', 'همگام‌سازی', 'admin')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO customers (full_name, company, phone_number, notes)
+             VALUES ('رضا', '', '09120000001', ''), ('مینا', '', '09120000002', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let token = generate_token();
+        let now = chrono::Utc::now();
+        sqlx::query(
+            "INSERT INTO api_tokens
+             (user_id, name, token_prefix, token_hash, scopes, created_at, expires_at)
+             VALUES (1, 'sync', '', ?, 'customers.delete', ?, ?)",
+        )
+        .bind(hash_token(&token))
+        .bind(now.to_rfc3339())
+        .bind((now + chrono::Duration::days(1)).to_rfc3339())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let config = Config::from_env();
+        let state = AppState {
+            pool: pool.clone(),
+            media: MediaStore::new(std::env::temp_dir().join("rumiland_routes_test")),
+            documents: config.document_settings(),
+            login_limits: config.login_limits.clone(),
+            sessions: config.session_settings(),
+            password_policy: config.password_policy.clone(),
+            stock: config.stock,
+        };
+        (configure_routes(state), pool, token)
+    }
+
+    fn bearer_request(method: Method, uri: &str, token: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn customer_exists(pool: &Pool<Sqlite>, id: i64) -> bool {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM customers WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+            == 1
+    }
+
+    #[tokio::test]
+    async fn test_token_changes_are_recorded_against_its_owner() {
+        let (app, pool, token) = app_with_token().await;
+
+        let response = app
+            .oneshot(bearer_request(
+                Method::DELETE,
+                "/api/v1/customers/1",
+                &token,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!customer_exists(&pool, 1).await);
+
+        let actor: Option<i64> = sqlx::query_scalar(
+            "SELECT user_id FROM audit_log WHERE entity_id = 1 AND action = 'delete'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(actor, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_pages_refuse_api_tokens() {
+        let (app, pool, token) = app_with_token().await;
+
+        let response = app
+            .clone()
+            .oneshot(bearer_request(Method::POST, "/delete/2", &token))
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error());
+        assert!(customer_exists(&pool, 2).await);
+
+        let response = app
+            .oneshot(bearer_request(Method::GET, "/", &token))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
 }
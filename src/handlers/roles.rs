@@ -0,0 +1,302 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{csrf::CsrfToken, permissions::require_permission},
+    models::{Permission, Role, RoleForm, RoleSummary},
+    templates::roles::{RoleFormTemplate, RolesTemplate},
+};
+
+/// Roles with what each one allows (needs users.manage)
+pub async fn list_roles(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::UsersManage).await?;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let mut roles = Vec::new();
+    for role in sqlx::query_as::<_, Role>("SELECT * FROM roles ORDER BY id")
+        .fetch_all(&pool)
+        .await?
+    {
+        let permissions = role_permissions(&pool, &role).await?;
+        let user_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = ?")
+            .bind(&role.name)
+            .fetch_one(&pool)
+            .await?;
+        roles.push(RoleSummary {
+            role,
+            permissions,
+            user_count,
+        });
+    }
+
+    let template = RolesTemplate {
+        roles,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "users",
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Show the form for a new role (needs users.manage)
+pub async fn show_add_role_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::UsersManage).await?;
+
+    let template = RoleFormTemplate {
+        role_id: None,
+        name: String::new(),
+        display_name: String::new(),
+        permissions: Vec::new(),
+        error: None,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "users",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Create a role (needs users.manage)
+pub async fn add_role(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    let current_user = require_permission(&pool, &jar, Permission::UsersManage).await?;
+    let form = RoleForm::from_fields(fields);
+
+    let mut error = validate_role_form(&form);
+    if error.is_none() {
+        let taken: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM roles WHERE name = ?")
+            .bind(&form.name)
+            .fetch_one(&pool)
+            .await?;
+        if taken > 0 {
+            error = Some("نقشی با این شناسه وجود دارد".to_string());
+        }
+    }
+    if let Some(error) = error {
+        let template = RoleFormTemplate {
+            role_id: None,
+            name: form.name,
+            display_name: form.display_name,
+            permissions: form.permissions,
+            error: Some(error),
+            current_user: Some(current_user),
+            csrf_token,
+            active_page: "users",
+        };
+        return Ok(Html(template.render()?).into_response());
+    }
+
+    let mut tx = pool.begin().await?;
+    let role_id = sqlx::query("INSERT INTO roles (name, display_name) VALUES (?, ?)")
+        .bind(&form.name)
+        .bind(&form.display_name)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+    for permission in &form.permissions {
+        sqlx::query("INSERT INTO role_permissions (role_id, permission) VALUES (?, ?)")
+            .bind(role_id)
+            .bind(permission.as_str())
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    println!("🛡️ Role {} created by {}", form.name, current_user.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "نقش «{}» با موفقیت ایجاد شد 🛡️",
+        form.display_name
+    )));
+    Ok((jar, Redirect::to("/roles")).into_response())
+}
+
+/// Show the form for changing a role's name and permissions (needs users.manage)
+pub async fn show_edit_role_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::UsersManage).await?;
+    let role = fetch_editable_role(&pool, id).await?;
+    let permissions = role_permissions(&pool, &role).await?;
+
+    let template = RoleFormTemplate {
+        role_id: Some(role.id),
+        name: role.name,
+        display_name: role.display_name,
+        permissions,
+        error: None,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "users",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Change a role's display name and permissions; its key stays the same (needs users.manage)
+pub async fn update_role(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    let current_user = require_permission(&pool, &jar, Permission::UsersManage).await?;
+    let role = fetch_editable_role(&pool, id).await?;
+    let form = RoleForm {
+        name: role.name.clone(),
+        ..RoleForm::from_fields(fields)
+    };
+
+    if let Some(error) = validate_role_form(&form) {
+        let template = RoleFormTemplate {
+            role_id: Some(role.id),
+            name: form.name,
+            display_name: form.display_name,
+            permissions: form.permissions,
+            error: Some(error),
+            current_user: Some(current_user),
+            csrf_token,
+            active_page: "users",
+        };
+        return Ok(Html(template.render()?).into_response());
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE roles SET display_name = ? WHERE id = ?")
+        .bind(&form.display_name)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM role_permissions WHERE role_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    for permission in &form.permissions {
+        sqlx::query("INSERT INTO role_permissions (role_id, permission) VALUES (?, ?)")
+            .bind(id)
+            .bind(permission.as_str())
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    println!("🛡️ Role {} updated by {}", role.name, current_user.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "نقش «{}» با موفقیت ویرایش شد 🛡️",
+        form.display_name
+    )));
+    Ok((jar, Redirect::to("/roles")).into_response())
+}
+
+/// Delete a role no user has (needs users.manage)
+pub async fn delete_role(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::UsersManage).await?;
+    let role = fetch_editable_role(&pool, id).await?;
+
+    let user_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = ?")
+        .bind(&role.name)
+        .fetch_one(&pool)
+        .await?;
+    if user_count > 0 {
+        return Err(AppError::BadRequest(
+            "این نقش به کاربرانی داده شده است؛ ابتدا نقش آن‌ها را تغییر دهید".to_string(),
+        ));
+    }
+
+    sqlx::query("DELETE FROM roles WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+
+    println!("🗑️ Role {} deleted by {}", role.name, current_user.username);
+
+    let jar = jar.add(flash_cookie(format!("نقش «{}» حذف شد", role.display_name)));
+    Ok((jar, Redirect::to("/roles")))
+}
+
+/// A role that may be changed; the admin role always keeps every permission
+async fn fetch_editable_role(pool: &Pool<Sqlite>, id: i64) -> AppResult<Role> {
+    let role = sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if role.is_admin() {
+        return Err(AppError::BadRequest(
+            "نقش مدیر همه دسترسی‌ها را دارد و قابل تغییر نیست".to_string(),
+        ));
+    }
+    Ok(role)
+}
+
+async fn role_permissions(pool: &Pool<Sqlite>, role: &Role) -> AppResult<Vec<Permission>> {
+    if role.is_admin() {
+        return Ok(Permission::ALL.to_vec());
+    }
+
+    Ok(sqlx::query_scalar::<_, String>(
+        "SELECT permission FROM role_permissions WHERE role_id = ?",
+    )
+    .bind(role.id)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .filter_map(|permission| Permission::parse(permission))
+    .collect())
+}
+
+fn validate_role_form(form: &RoleForm) -> Option<String> {
+    if form.name.is_empty()
+        || !form
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Some("شناسه نقش فقط می‌تواند شامل حروف کوچک انگلیسی، رقم و _ باشد".to_string());
+    }
+    if form.display_name.is_empty() {
+        return Some("نام نقش نمی‌تواند خالی باشد".to_string());
+    }
+    None
+}
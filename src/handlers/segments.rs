@@ -0,0 +1,179 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Pool, QueryBuilder, Sqlite};
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::{customers::push_customer_select, flash_cookie},
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{customer::City, CustomerQuery, Money, Segment, SegmentForm, SegmentSummary, User},
+    templates::segments::SegmentsTemplate,
+    utils::localization::persian_to_english_numbers,
+};
+
+/// Saved segments with how many customers each has now
+pub async fn list_segments(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let mut segments = Vec::new();
+    for segment in fetch_segments(&pool).await? {
+        let customer_count = count_customers(&pool, &segment.customer_query()).await?;
+        segments.push(SegmentSummary {
+            segment,
+            customer_count,
+        });
+    }
+
+    let template = SegmentsTemplate {
+        segments,
+        active_page: "segments",
+        current_user,
+        csrf_token,
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Save the customer list's current filters as a named segment (needs customers.edit)
+pub async fn save_segment(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(current_user): Extension<User>,
+    jar: CookieJar,
+    Form(form): Form<SegmentForm>,
+) -> AppResult<impl IntoResponse> {
+    if let Some(error) = validate_segment_form(&form) {
+        return Err(AppError::BadRequest(error));
+    }
+
+    let name = form.name.trim();
+    let taken: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM segments WHERE name = ?)")
+        .bind(name)
+        .fetch_one(&pool)
+        .await?;
+    if taken {
+        return Err(AppError::BadRequest(format!(
+            "گروهی با نام «{}» از قبل وجود دارد",
+            name
+        )));
+    }
+
+    // Filters are stored as the list reads them, with the tag list tidied up
+    let query = form.customer_query();
+    sqlx::query(
+        "INSERT INTO segments (name, description, tags, tag_mode, city, min_sales, max_sales, min_balance, max_balance, created_by, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(name)
+    .bind(form.description.trim())
+    .bind(query.tag_filters().join(", "))
+    .bind(if query.matches_any_tag() { "any" } else { "" })
+    .bind(query.city.trim())
+    .bind(query.min_sales.trim())
+    .bind(query.max_sales.trim())
+    .bind(query.min_balance.trim())
+    .bind(query.max_balance.trim())
+    .bind(current_user.id)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&pool)
+    .await?;
+
+    println!("📌 Segment {:?} saved by {}", name, current_user.username);
+
+    let jar = jar.add(flash_cookie(format!("گروه «{}» ذخیره شد 📌", name)));
+    Ok((jar, Redirect::to("/segments")))
+}
+
+/// Delete a saved segment; its customers are not touched (needs customers.edit)
+pub async fn delete_segment(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(current_user): Extension<User>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let segment = fetch_segment(&pool, id).await?;
+
+    sqlx::query("DELETE FROM segments WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+
+    println!("🗑️ Segment {:?} deleted by {}", segment.name, current_user.username);
+
+    let jar = jar.add(flash_cookie(format!("گروه «{}» حذف شد", segment.name)));
+    Ok((jar, Redirect::to("/segments")))
+}
+
+/// Every saved segment, in name order
+pub async fn fetch_segments(pool: &Pool<Sqlite>) -> AppResult<Vec<Segment>> {
+    Ok(
+        sqlx::query_as::<_, Segment>("SELECT * FROM segments ORDER BY name")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn fetch_segment(pool: &Pool<Sqlite>, id: i64) -> AppResult<Segment> {
+    sqlx::query_as::<_, Segment>("SELECT * FROM segments WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+/// How many customers the customer list would show for `query`
+async fn count_customers(pool: &Pool<Sqlite>, query: &CustomerQuery) -> AppResult<i64> {
+    let mut count_query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM (");
+    push_customer_select(&mut count_query, query);
+    count_query.push(")");
+    Ok(count_query.build_query_scalar().fetch_one(pool).await?)
+}
+
+fn validate_segment_form(form: &SegmentForm) -> Option<String> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Some("نام گروه را وارد کنید".to_string());
+    }
+    if name.chars().count() > 100 {
+        return Some("نام گروه نمی‌تواند بیشتر از ۱۰۰ کاراکتر باشد".to_string());
+    }
+
+    let query = form.customer_query();
+    if !query.is_filtered() {
+        return Some("گروه دست‌کم به یک فیلتر نیاز دارد".to_string());
+    }
+    if !query.city.trim().is_empty() && City::from_str(query.city.trim()) == City::None {
+        return Some("شهر انتخاب شده معتبر نیست".to_string());
+    }
+    // The list skips a filter it cannot read; a saved one should not silently match everyone
+    let is_count = |value: &str| {
+        value.trim().is_empty() || persian_to_english_numbers(value.trim()).parse::<i64>().is_ok()
+    };
+    if !is_count(&form.min_sales) || !is_count(&form.max_sales) {
+        return Some("تعداد فروش باید عدد باشد".to_string());
+    }
+    let is_amount = |value: &str| value.trim().is_empty() || Money::parse(value).is_some();
+    if !is_amount(&form.min_balance) || !is_amount(&form.max_balance) {
+        return Some("مانده حساب باید مبلغ معتبری باشد".to_string());
+    }
+    None
+}
@@ -0,0 +1,228 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    db::sessions::delete_user_sessions,
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::Session,
+    templates::sessions::SessionsTemplate,
+};
+
+/// The signed-in user's open sessions on all their devices
+pub async fn list_sessions(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+    let current_session_id = current_session_id(&jar)?;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let sessions = sqlx::query_as::<_, Session>(
+        "SELECT * FROM sessions WHERE user_id = ? AND expires_at > ? ORDER BY last_seen_at DESC",
+    )
+    .bind(current_user.id)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .fetch_all(&pool)
+    .await?;
+
+    let current_public_id = sessions
+        .iter()
+        .find(|session| session.id == current_session_id)
+        .map(|session| session.public_id.clone())
+        .unwrap_or_default();
+
+    let template = SessionsTemplate {
+        sessions,
+        current_public_id,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "sessions",
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Sign one of the user's other devices out
+pub async fn revoke_session(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(public_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+    let current_session_id = current_session_id(&jar)?;
+
+    // The current session is ended by logging out, not from here
+    let result = sqlx::query(
+        "DELETE FROM sessions WHERE public_id = ? AND user_id = ? AND id != ?",
+    )
+    .bind(&public_id)
+    .bind(user.id)
+    .bind(&current_session_id)
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    println!("🔌 Session revoked by {}", user.username);
+
+    Ok((
+        jar.add(flash_cookie("نشست انتخاب‌شده بسته شد 🔌")),
+        Redirect::to("/sessions"),
+    ))
+}
+
+/// Sign the user out everywhere except the current device
+pub async fn revoke_other_sessions(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+) -> AppResult<impl IntoResponse> {
+    let user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+    let current_session_id = current_session_id(&jar)?;
+
+    let count = delete_user_sessions(&pool, user.id, Some(&current_session_id)).await?;
+
+    println!("🔌 {} other sessions revoked by {}", count, user.username);
+
+    Ok((
+        jar.add(flash_cookie("از همه دستگاه‌های دیگر خارج شدید 🔌")),
+        Redirect::to("/sessions"),
+    ))
+}
+
+fn current_session_id(jar: &CookieJar) -> AppResult<String> {
+    jar.get("session_id")
+        .map(|cookie| cookie.value().to_string())
+        .ok_or(AppError::Unauthorized)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    /// Ali signed in on a phone and a laptop, and Sara on her phone
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role)
+             VALUES ('ali', '', 'علی', 'user'), ('sara', '', 'سارا', 'user')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (id, user_id, public_id) in [
+            ("ali-phone", 1, "p1"),
+            ("ali-laptop", 1, "p2"),
+            ("sara-phone", 2, "p3"),
+        ] {
+            add_session(&pool, id, user_id, public_id).await;
+        }
+        pool
+    }
+
+    async fn add_session(pool: &Pool<Sqlite>, id: &str, user_id: i64, public_id: &str) {
+        sqlx::query(
+            "INSERT INTO sessions (id, user_id, expires_at, public_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339())
+        .bind(public_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn session_ids(pool: &Pool<Sqlite>) -> Vec<String> {
+        sqlx::query_scalar("SELECT id FROM sessions ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    fn jar(session_id: &str) -> CookieJar {
+        CookieJar::new().add(Cookie::new("session_id", session_id.to_string()))
+    }
+
+    async fn revoke(pool: &Pool<Sqlite>, session_id: &str, public_id: &str) -> AppResult<()> {
+        revoke_session(
+            State(pool.clone()),
+            jar(session_id),
+            Path(public_id.to_string()),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_session_only_ends_own_other_sessions() {
+        let pool = test_pool().await;
+
+        // Someone else's session is not found, let alone ended
+        assert!(matches!(
+            revoke(&pool, "ali-phone", "p3").await,
+            Err(AppError::NotFound)
+        ));
+        // The session making the request is ended by logging out instead
+        assert!(matches!(
+            revoke(&pool, "ali-phone", "p1").await,
+            Err(AppError::NotFound)
+        ));
+        assert_eq!(
+            session_ids(&pool).await,
+            vec!["ali-laptop", "ali-phone", "sara-phone"]
+        );
+
+        revoke(&pool, "ali-phone", "p2").await.unwrap();
+        assert_eq!(session_ids(&pool).await, vec!["ali-phone", "sara-phone"]);
+
+        // Without a session there is nobody to revoke for
+        assert!(matches!(
+            revoke(&pool, "ali-laptop", "p1").await,
+            Err(AppError::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_other_sessions_keeps_current_one() {
+        let pool = test_pool().await;
+        add_session(&pool, "ali-tablet", 1, "p4").await;
+
+        revoke_other_sessions(State(pool.clone()), jar("ali-phone"))
+            .await
+            .unwrap();
+        assert_eq!(session_ids(&pool).await, vec!["ali-phone", "sara-phone"]);
+    }
+}
@@ -0,0 +1,316 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde_json::{json, Map};
+use sqlx::{Executor, Pool, Sqlite, SqliteConnection};
+
+use crate::{
+    db::audit::record_changes,
+    error::{AppError, AppResult},
+    handlers::{customers::fetch_customer, flash_cookie},
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        tag::MAX_TAG_NAME_CHARS, AuditAction, AuditEntity, Tag, TagAction, TagForm, TagSummary,
+        User,
+    },
+    templates::tags::TagsTemplate,
+    utils::localization::to_persian_digits,
+};
+
+/// Every tag with how many customers have it
+pub async fn list_tags(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let tags = sqlx::query_as::<_, TagSummary>(
+        "SELECT tags.*,
+                (SELECT COUNT(*) FROM customer_tags WHERE tag_id = tags.id) AS customer_count
+         FROM tags
+         ORDER BY tags.name",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let template = TagsTemplate {
+        tags,
+        active_page: "tags",
+        current_user,
+        csrf_token,
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Put a tag on, or take it off, the customers chosen on the list (needs customers.edit)
+pub async fn tag_customers(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(current_user): Extension<User>,
+    jar: CookieJar,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    let form = TagForm::from_fields(fields);
+
+    if form.customer_ids.is_empty() {
+        return Err(AppError::BadRequest(
+            "دست‌کم یک مشتری را انتخاب کنید".to_string(),
+        ));
+    }
+    if let Some(error) = validate_tag_name(&form.tag) {
+        return Err(AppError::BadRequest(error));
+    }
+
+    let changed = apply_tag(
+        &pool,
+        &form.customer_ids,
+        &form.tag,
+        form.action,
+        Some(current_user.id),
+    )
+    .await?;
+
+    println!(
+        "🏷️ Tag {:?} {} {} customers by {}",
+        form.tag,
+        if form.action == TagAction::Add { "added to" } else { "removed from" },
+        changed,
+        current_user.username
+    );
+
+    let changed = to_persian_digits(&changed.to_string());
+    let message = match form.action {
+        TagAction::Add => format!("برچسب «{}» به {} مشتری اضافه شد 🏷️", form.tag, changed),
+        TagAction::Remove => format!("برچسب «{}» از {} مشتری برداشته شد 🏷️", form.tag, changed),
+    };
+    let jar = jar.add(flash_cookie(message));
+    Ok((jar, Redirect::to(&form.return_url())))
+}
+
+/// Put a tag on, or take it off, one customer from their page (needs customers.edit)
+pub async fn tag_customer(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(current_user): Extension<User>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    let form = TagForm::from_fields(fields);
+
+    if let Some(error) = validate_tag_name(&form.tag) {
+        return Err(AppError::BadRequest(error));
+    }
+
+    apply_tag(&pool, &[id], &form.tag, form.action, Some(current_user.id)).await?;
+
+    let message = match form.action {
+        TagAction::Add => format!("برچسب «{}» اضافه شد 🏷️", form.tag),
+        TagAction::Remove => format!("برچسب «{}» برداشته شد 🏷️", form.tag),
+    };
+    let jar = jar.add(flash_cookie(message));
+    Ok((jar, Redirect::to(&format!("/customer/{}", id))))
+}
+
+/// Delete a tag, taking it off every customer that has it (needs customers.edit)
+pub async fn delete_tag(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(current_user): Extension<User>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let mut tx = pool.begin().await?;
+    let tag = sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let customer_ids: Vec<i64> =
+        sqlx::query_scalar("SELECT customer_id FROM customer_tags WHERE tag_id = ?")
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .await?;
+    for customer_id in &customer_ids {
+        let old = fetch_customer_tags(&mut *tx, *customer_id).await?;
+        let new: Vec<String> = old.iter().filter(|name| **name != tag.name).cloned().collect();
+        record_tag_change(&mut *tx, Some(current_user.id), *customer_id, &old, &new).await?;
+    }
+
+    // Removing the tag removes it from its customers too
+    sqlx::query("DELETE FROM tags WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    println!(
+        "🗑️ Tag {:?} deleted from {} customers by {}",
+        tag.name,
+        customer_ids.len(),
+        current_user.username
+    );
+
+    let jar = jar.add(flash_cookie(format!("برچسب «{}» حذف شد", tag.name)));
+    Ok((jar, Redirect::to("/tags")))
+}
+
+/// Put a tag on, or take it off, each of `customer_ids`, recording each change in the
+/// audit log, and return how many customers changed.
+///
+/// A tag that does not exist yet is created when first put on a customer.
+pub async fn apply_tag(
+    pool: &Pool<Sqlite>,
+    customer_ids: &[i64],
+    name: &str,
+    action: TagAction,
+    user_id: Option<i64>,
+) -> AppResult<usize> {
+    let mut tx = pool.begin().await?;
+
+    let tag_id = match action {
+        TagAction::Add => Some(find_or_create_tag(&mut tx, name).await?),
+        TagAction::Remove => {
+            sqlx::query_scalar::<_, i64>("SELECT id FROM tags WHERE name = ?")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?
+        }
+    };
+    // No customer has a tag that does not exist
+    let Some(tag_id) = tag_id else {
+        return Ok(0);
+    };
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut changed = 0;
+    for &customer_id in customer_ids {
+        fetch_customer(&mut tx, customer_id).await?;
+        let old = fetch_customer_tags(&mut *tx, customer_id).await?;
+
+        let result = match action {
+            TagAction::Add => {
+                sqlx::query(
+                    "INSERT OR IGNORE INTO customer_tags (customer_id, tag_id, created_at)
+                     VALUES (?, ?, ?)",
+                )
+                .bind(customer_id)
+                .bind(tag_id)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?
+            }
+            TagAction::Remove => {
+                sqlx::query("DELETE FROM customer_tags WHERE customer_id = ? AND tag_id = ?")
+                    .bind(customer_id)
+                    .bind(tag_id)
+                    .execute(&mut *tx)
+                    .await?
+            }
+        };
+
+        if result.rows_affected() > 0 {
+            let new = fetch_customer_tags(&mut *tx, customer_id).await?;
+            record_tag_change(&mut *tx, user_id, customer_id, &old, &new).await?;
+            changed += 1;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(changed)
+}
+
+/// Id of the tag called `name`, ignoring case, adding it if there is none
+async fn find_or_create_tag(conn: &mut SqliteConnection, name: &str) -> AppResult<i64> {
+    sqlx::query("INSERT OR IGNORE INTO tags (name, created_at) VALUES (?, ?)")
+        .bind(name)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?)
+}
+
+/// Names of a customer's tags, in name order
+pub async fn fetch_customer_tags<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    customer_id: i64,
+) -> AppResult<Vec<String>> {
+    Ok(sqlx::query_scalar::<_, String>(
+        "SELECT tags.name FROM customer_tags
+         JOIN tags ON tags.id = customer_tags.tag_id
+         WHERE customer_tags.customer_id = ?
+         ORDER BY tags.name",
+    )
+    .bind(customer_id)
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Every tag's name, offered as suggestions wherever a tag is typed
+pub async fn fetch_tag_names(pool: &Pool<Sqlite>) -> AppResult<Vec<String>> {
+    Ok(
+        sqlx::query_scalar::<_, String>("SELECT name FROM tags ORDER BY name")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+/// Record a change to a customer's tags in the audit log, as a change of a `tags` field
+pub async fn record_tag_change<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    user_id: Option<i64>,
+    customer_id: i64,
+    old: &[String],
+    new: &[String],
+) -> AppResult<()> {
+    let mut changes = Map::new();
+    changes.insert(
+        "tags".to_string(),
+        json!({ "old": old.join("، "), "new": new.join("، ") }),
+    );
+    record_changes(
+        executor,
+        user_id,
+        AuditEntity::Customer,
+        customer_id,
+        AuditAction::Update,
+        changes,
+    )
+    .await?;
+    Ok(())
+}
+
+fn validate_tag_name(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return Some("نام برچسب را وارد کنید".to_string());
+    }
+    if name.chars().count() > MAX_TAG_NAME_CHARS {
+        return Some(format!(
+            "نام برچسب نمی‌تواند بیشتر از {} کاراکتر باشد",
+            to_persian_digits(&MAX_TAG_NAME_CHARS.to_string())
+        ));
+    }
+    if name.contains([',', '،']) {
+        return Some("نام برچسب نمی‌تواند ویرگول داشته باشد".to_string());
+    }
+    None
+}
@@ -0,0 +1,214 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+    Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::{Duration, Utc};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        api_token::{display_prefix, generate_token, hash_token, TOKEN_LIFETIMES},
+        ApiToken, ApiTokenForm, Permission, User,
+    },
+    templates::tokens::TokensTemplate,
+};
+
+/// The signed-in user's API tokens
+pub async fn list_tokens(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let template = tokens_page(&pool, current_user, csrf_token, None, flash_message).await?;
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Every user's API tokens, for admins
+pub async fn list_all_tokens(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar).await;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let tokens = sqlx::query_as::<_, ApiToken>(
+        "SELECT api_tokens.*, users.username FROM api_tokens
+         JOIN users ON users.id = api_tokens.user_id
+         ORDER BY api_tokens.id DESC",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let template = TokensTemplate {
+        tokens,
+        show_all: true,
+        new_token: None,
+        lifetimes: TOKEN_LIFETIMES.to_vec(),
+        current_user,
+        csrf_token,
+        active_page: "tokens",
+        flash_message,
+    };
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Create a token and show it once; only its hash is kept
+pub async fn create_token(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+    let form = ApiTokenForm::from_fields(fields);
+
+    if form.name.is_empty() {
+        return Err(AppError::BadRequest(
+            "نام توکن نمی‌تواند خالی باشد".to_string(),
+        ));
+    }
+    if form.name.chars().count() > 100 {
+        return Err(AppError::BadRequest(
+            "نام توکن نمی‌تواند بیشتر از ۱۰۰ کاراکتر باشد".to_string(),
+        ));
+    }
+    if !TOKEN_LIFETIMES.contains(&form.lifetime_days) {
+        return Err(AppError::BadRequest("مدت اعتبار نامعتبر است".to_string()));
+    }
+    // A token never carries more than its owner's role
+    if form
+        .scopes
+        .iter()
+        .any(|scope| !current_user.has_permission(*scope))
+    {
+        return Err(AppError::Forbidden);
+    }
+
+    let token = generate_token();
+    let now = Utc::now();
+    sqlx::query(
+        "INSERT INTO api_tokens (user_id, name, token_prefix, token_hash, scopes, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(current_user.id)
+    .bind(&form.name)
+    .bind(display_prefix(&token))
+    .bind(hash_token(&token))
+    .bind(form.scopes_value())
+    .bind(now.to_rfc3339())
+    .bind((now + Duration::days(form.lifetime_days)).to_rfc3339())
+    .execute(&pool)
+    .await?;
+
+    println!(
+        "🗝️ API token '{}' created by {}",
+        form.name, current_user.username
+    );
+
+    let template = tokens_page(&pool, current_user, csrf_token, Some(token), None).await?;
+    Ok(Html(template.render()?))
+}
+
+/// Revoke a token; users revoke their own, admins anyone's
+pub async fn revoke_token(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let user = get_current_user(&pool, &jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+
+    let token = sqlx::query_as::<_, ApiToken>(
+        "SELECT api_tokens.*, users.username FROM api_tokens
+         JOIN users ON users.id = api_tokens.user_id
+         WHERE api_tokens.id = ?",
+    )
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let own_token = token.user_id == user.id;
+    if !own_token && !user.has_permission(Permission::UsersManage) {
+        return Err(AppError::Forbidden);
+    }
+
+    sqlx::query("UPDATE api_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&pool)
+        .await?;
+
+    println!(
+        "🗝️ API token '{}' of {} revoked by {}",
+        token.name, token.username, user.username
+    );
+
+    let redirect_to = if own_token { "/tokens" } else { "/tokens/all" };
+    Ok((
+        jar.add(flash_cookie(format!("توکن «{}» لغو شد 🗝️", token.name))),
+        Redirect::to(redirect_to),
+    ))
+}
+
+async fn tokens_page(
+    pool: &Pool<Sqlite>,
+    current_user: User,
+    csrf_token: String,
+    new_token: Option<String>,
+    flash_message: Option<String>,
+) -> AppResult<TokensTemplate> {
+    let tokens = sqlx::query_as::<_, ApiToken>(
+        "SELECT api_tokens.*, users.username FROM api_tokens
+         JOIN users ON users.id = api_tokens.user_id
+         WHERE api_tokens.user_id = ?
+         ORDER BY api_tokens.id DESC",
+    )
+    .bind(current_user.id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(TokensTemplate {
+        tokens,
+        show_all: false,
+        new_token,
+        lifetimes: TOKEN_LIFETIMES.to_vec(),
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "tokens",
+        flash_message,
+    })
+}
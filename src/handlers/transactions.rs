@@ -1,9 +1,14 @@
 use crate::{
+    db::{audit::record_audit, webhooks::queue_webhook},
     error::{AppError, AppResult},
-    middleware::auth::get_current_user,
-    models::{Customer, Transaction, TransactionForm, TransactionType},
+    handlers::{customers::fetch_customer, flash_cookie},
+    middleware::{auth::get_current_user, csrf::CsrfToken},
+    models::{
+        AuditEntity, Cheque, ChequeStatus, Customer, Money, Transaction, TransactionDirection, TransactionForm,
+        TransactionType, WebhookEvent,
+    },
     templates::transactions::{AddTransactionTemplate, EditTransactionTemplate},
-    utils::localization::persian_to_english_numbers,
+    utils::date::shamsi_to_iso,
 };
 use askama::Template;
 use axum::{
@@ -11,16 +16,14 @@ use axum::{
     response::{Html, IntoResponse, Redirect},
     Form,
 };
-use axum_extra::extract::{
-    cookie::{Cookie, SameSite},
-    CookieJar,
-};
-use sqlx::{Pool, Sqlite};
+use axum_extra::extract::CookieJar;
+use sqlx::{Pool, Sqlite, SqliteConnection};
 
 #[axum::debug_handler]
 pub async fn show_add_transaction_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path(customer_id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
@@ -34,8 +37,10 @@ pub async fn show_add_transaction_form(
     let template = AddTransactionTemplate {
         customer,
         current_user,
+        csrf_token,
         active_page: "",
         transaction_types: TransactionType::all(),
+        directions: TransactionDirection::all(),
     };
 
     Ok(Html(template.render()?))
@@ -45,6 +50,7 @@ pub async fn show_add_transaction_form(
 pub async fn show_edit_transaction_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path((customer_id, transaction_id)): Path<(i64, i64)>,
 ) -> AppResult<impl IntoResponse> {
     let current_user = get_current_user(&pool, &jar).await;
@@ -65,12 +71,20 @@ pub async fn show_edit_transaction_form(
         .fetch_one(&pool)
         .await?;
 
+    let cheque = sqlx::query_as::<_, Cheque>("SELECT * FROM cheques WHERE transaction_id = ?")
+        .bind(transaction_id)
+        .fetch_optional(&pool)
+        .await?;
+
     let template = EditTransactionTemplate {
         customer,
         transaction,
+        cheque,
         current_user,
+        csrf_token,
         active_page: "",
         transaction_types: TransactionType::all(),
+        directions: TransactionDirection::all(),
     };
 
     Ok(Html(template.render()?))
@@ -83,41 +97,10 @@ pub async fn add_transaction(
     Path(customer_id): Path<i64>,
     Form(form): Form<TransactionForm>,
 ) -> AppResult<impl IntoResponse> {
-    let shamsi_date = persian_to_english_numbers(&form.transaction_date);
-
-    if shamsi_date.trim().is_empty() {
-        return Err(AppError::BadRequest("تاریخ تراکنش الزامی است".to_string()));
-    }
-
-    let parts: Vec<&str> = shamsi_date.split('/').collect();
-    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
-        return Err(AppError::BadRequest(
-            "فرمت تاریخ تراکنش معتبر نیست. لطفا از فرمت YYYY/MM/DD استفاده کنید".to_string(),
-        ));
-    }
-
-    sqlx::query(
-        "INSERT INTO transactions (customer_id, amount, transaction_type, description, transaction_date)
-         VALUES (?, ?, ?, ?, ?)",
-    )
-    .bind(customer_id)
-    .bind(form.amount)
-    .bind(form.transaction_type)
-    .bind(form.description)
-    .bind(form.transaction_date)
-    .execute(&pool)
-    .await?;
-
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        "تراکنش جدید با موفقیت ثبت شد ✅".to_string(),
-    ))
-    .path(format!("/customer/{}", customer_id))
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .build();
+    let current_user = get_current_user(&pool, &jar).await;
+    create_transaction_record(&pool, customer_id, &form, current_user.map(|user| user.id)).await?;
 
-    let jar = jar.add(flash_cookie);
+    let jar = jar.add(flash_cookie("تراکنش جدید با موفقیت ثبت شد ✅"));
 
     Ok((jar, Redirect::to(&format!("/customer/{}", customer_id))))
 }
@@ -127,25 +110,22 @@ pub async fn delete_transaction(
     jar: CookieJar,
     Path((customer_id, transaction_id)): Path<(i64, i64)>,
 ) -> AppResult<impl IntoResponse> {
-    sqlx::query("DELETE FROM transactions WHERE id = ?")
-        .bind(transaction_id)
-        .execute(&pool)
-        .await?;
+    let current_user = get_current_user(&pool, &jar).await;
+    delete_transaction_record(
+        &pool,
+        customer_id,
+        transaction_id,
+        current_user.map(|user| user.id),
+    )
+    .await?;
 
     println!("🗑️ Deleted transaction ID: {}", transaction_id);
 
     // Set flash message for deletion
-    let flash_cookie = Cookie::build((
-        "flash_message",
-        format!("تراکنش {} با موفقیت حذف شد 🗑️", transaction_id),
-    ))
-    .path(format!("/customer/{}", customer_id))
-    .same_site(SameSite::Lax)
-    .http_only(true)
-    .max_age(cookie::time::Duration::seconds(60))
-    .build();
-
-    let jar = jar.add(flash_cookie);
+    let jar = jar.add(flash_cookie(format!(
+        "تراکنش {} با موفقیت حذف شد 🗑️",
+        transaction_id
+    )));
 
     Ok((jar, Redirect::to(&format!("/customer/{}", customer_id))))
 }
@@ -156,47 +136,287 @@ pub async fn edit_transaction(
     Path((customer_id, transaction_id)): Path<(i64, i64)>,
     Form(form): Form<TransactionForm>,
 ) -> AppResult<impl IntoResponse> {
-    let shamsi_date = persian_to_english_numbers(&form.transaction_date);
+    let current_user = get_current_user(&pool, &jar).await;
+    update_transaction_record(
+        &pool,
+        customer_id,
+        transaction_id,
+        &form,
+        current_user.map(|user| user.id),
+    )
+    .await?;
 
-    if shamsi_date.trim().is_empty() {
-        return Err(AppError::BadRequest("تاریخ تراکنش الزامی است".to_string()));
-    }
+    println!("✏️ Updated transaction: (ID: {})", transaction_id);
+
+    // Set flash message for update
+    let jar = jar.add(flash_cookie("تراکنش با موفقیت ویرایش شد"));
+
+    Ok((jar, Redirect::to(&format!("/customer/{}", customer_id))))
+}
+
+/// Check a transaction form and record it for a customer, with its cheque if it is one.
+///
+/// The new transaction is recorded in the audit log and queued as `transaction.created`.
+pub async fn create_transaction_record(
+    pool: &Pool<Sqlite>,
+    customer_id: i64,
+    form: &TransactionForm,
+    user_id: Option<i64>,
+) -> AppResult<Transaction> {
+    let amount = parse_amount(&form.amount)?;
+    let transaction_date = validate_transaction_date(&form.transaction_date)?;
+
+    let direction = TransactionDirection::parse(&form.direction)
+        .ok_or_else(|| AppError::BadRequest("نوع بدهکار/بستانکار تراکنش معتبر نیست".to_string()))?;
 
-    let parts: Vec<&str> = shamsi_date.split('/').collect();
-    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
-        return Err(AppError::BadRequest(
-            "فرمت تاریخ تراکنش معتبر نیست. لطفا از فرمت YYYY/MM/DD استفاده کنید".to_string(),
-        ));
+    let due_date = validate_cheque_fields(form)?;
+
+    let mut tx = pool.begin().await?;
+    fetch_customer(&mut tx, customer_id).await?;
+
+    let transaction_id = sqlx::query(
+        "INSERT INTO transactions (customer_id, amount, transaction_type, description, transaction_date, direction)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(customer_id)
+    .bind(amount)
+    .bind(&form.transaction_type)
+    .bind(&form.description)
+    .bind(&transaction_date)
+    .bind(direction.as_str())
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    if let Some(due_date) = due_date {
+        save_cheque(&mut tx, transaction_id, form, &due_date, user_id).await?;
     }
 
-    let result = sqlx::query(
+    let transaction = fetch_transaction(&mut tx, customer_id, transaction_id).await?;
+    record_audit(
+        &mut *tx,
+        user_id,
+        AuditEntity::Transaction,
+        transaction_id,
+        None,
+        Some(&transaction),
+    )
+    .await?;
+    queue_webhook(&mut *tx, WebhookEvent::TransactionCreated, &transaction).await?;
+
+    tx.commit().await?;
+    Ok(transaction)
+}
+
+/// Check a transaction form and save it over one of a customer's transactions.
+///
+/// A transaction that is no longer a cheque payment loses its cheque. The change is
+/// queued as `transaction.updated`.
+pub async fn update_transaction_record(
+    pool: &Pool<Sqlite>,
+    customer_id: i64,
+    transaction_id: i64,
+    form: &TransactionForm,
+    user_id: Option<i64>,
+) -> AppResult<Transaction> {
+    let amount = parse_amount(&form.amount)?;
+    let transaction_date = validate_transaction_date(&form.transaction_date)?;
+
+    let direction = TransactionDirection::parse(&form.direction)
+        .ok_or_else(|| AppError::BadRequest("نوع بدهکار/بستانکار تراکنش معتبر نیست".to_string()))?;
+
+    let due_date = validate_cheque_fields(form)?;
+
+    let mut tx = pool.begin().await?;
+    let old = fetch_transaction(&mut tx, customer_id, transaction_id).await?;
+
+    sqlx::query(
         "UPDATE transactions
-         SET amount = ?, transaction_type = ?, description = ?, transaction_date = ?
+         SET amount = ?, transaction_type = ?, description = ?, transaction_date = ?, direction = ?
          WHERE id = ?",
     )
-    .bind(&form.amount)
+    .bind(amount)
     .bind(&form.transaction_type)
     .bind(&form.description)
-    .bind(&form.transaction_date)
+    .bind(&transaction_date)
+    .bind(direction.as_str())
     .bind(transaction_id)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await?;
 
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound);
+    match due_date {
+        Some(due_date) => save_cheque(&mut tx, transaction_id, form, &due_date, user_id).await?,
+        // No longer a cheque payment, so it has no cheque to track
+        None => {
+            sqlx::query("DELETE FROM cheques WHERE transaction_id = ?")
+                .bind(transaction_id)
+                .execute(&mut *tx)
+                .await?;
+        }
     }
 
-    println!("✏️ Updated transaction: (ID: {})", transaction_id);
+    let new = fetch_transaction(&mut tx, customer_id, transaction_id).await?;
+    record_audit(
+        &mut *tx,
+        user_id,
+        AuditEntity::Transaction,
+        transaction_id,
+        Some(&old),
+        Some(&new),
+    )
+    .await?;
+    queue_webhook(&mut *tx, WebhookEvent::TransactionUpdated, &new).await?;
+    tx.commit().await?;
 
-    // Set flash message for update
-    let flash_cookie = Cookie::build(("flash_message", "تراکنش با موفقیت ویرایش شد"))
-        .path(format!("/customer/{}", customer_id))
-        .same_site(SameSite::Lax)
-        .http_only(true)
-        .max_age(cookie::time::Duration::seconds(60))
-        .build();
+    Ok(new)
+}
 
-    let jar = jar.add(flash_cookie);
+/// Delete one of a customer's transactions, recording it in the audit log and queueing
+/// `transaction.deleted`
+pub async fn delete_transaction_record(
+    pool: &Pool<Sqlite>,
+    customer_id: i64,
+    transaction_id: i64,
+    user_id: Option<i64>,
+) -> AppResult<Transaction> {
+    let mut tx = pool.begin().await?;
+    let transaction = fetch_transaction(&mut tx, customer_id, transaction_id).await?;
 
-    Ok((jar, Redirect::to(&format!("/customer/{}", customer_id))))
+    sqlx::query("DELETE FROM transactions WHERE id = ?")
+        .bind(transaction_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit(
+        &mut *tx,
+        user_id,
+        AuditEntity::Transaction,
+        transaction_id,
+        Some(&transaction),
+        None,
+    )
+    .await?;
+    queue_webhook(&mut *tx, WebhookEvent::TransactionDeleted, &transaction).await?;
+    tx.commit().await?;
+
+    Ok(transaction)
+}
+
+/// Parse a transaction amount typed in tomans; it must be positive, since the
+/// direction says which way the money went
+fn parse_amount(input: &str) -> AppResult<Money> {
+    let amount = Money::parse(input)
+        .ok_or_else(|| AppError::BadRequest("مبلغ تراکنش معتبر نیست".to_string()))?;
+    if amount <= Money::ZERO {
+        return Err(AppError::BadRequest("مبلغ تراکنش باید بیشتر از صفر باشد".to_string()));
+    }
+    Ok(amount)
+}
+
+/// Check a Shamsi transaction date and convert it to the ISO date that is stored
+fn validate_transaction_date(input: &str) -> AppResult<String> {
+    if input.trim().is_empty() {
+        return Err(AppError::BadRequest("تاریخ تراکنش الزامی است".to_string()));
+    }
+
+    shamsi_to_iso(input).ok_or_else(|| {
+        AppError::BadRequest(
+            "تاریخ تراکنش معتبر نیست. لطفا از فرمت YYYY/MM/DD استفاده کنید".to_string(),
+        )
+    })
+}
+
+/// Check the cheque details of a cheque transaction and return its ISO due date.
+///
+/// Returns `None` for other transaction types, whose cheque fields are ignored.
+fn validate_cheque_fields(form: &TransactionForm) -> AppResult<Option<String>> {
+    if !form.is_cheque() {
+        return Ok(None);
+    }
+
+    if form.cheque_number.trim().is_empty() {
+        return Err(AppError::BadRequest("شماره چک الزامی است".to_string()));
+    }
+
+    if form.bank_name.trim().is_empty() {
+        return Err(AppError::BadRequest("نام بانک الزامی است".to_string()));
+    }
+
+    let due_date = shamsi_to_iso(&form.due_date).ok_or_else(|| {
+        AppError::BadRequest(
+            "تاریخ سررسید چک معتبر نیست. لطفا از فرمت YYYY/MM/DD استفاده کنید".to_string(),
+        )
+    })?;
+
+    Ok(Some(due_date))
+}
+
+/// Fetch a transaction, making sure it belongs to the customer in the URL
+pub async fn fetch_transaction(
+    conn: &mut SqliteConnection,
+    customer_id: i64,
+    transaction_id: i64,
+) -> AppResult<Transaction> {
+    sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = ? AND customer_id = ?")
+        .bind(transaction_id)
+        .bind(customer_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+/// Create or update the cheque attached to a transaction.
+///
+/// A new cheque starts as received, which is also recorded as the first history entry.
+async fn save_cheque(
+    conn: &mut SqliteConnection,
+    transaction_id: i64,
+    form: &TransactionForm,
+    due_date: &str,
+    user_id: Option<i64>,
+) -> AppResult<()> {
+    let existing: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM cheques WHERE transaction_id = ?")
+            .bind(transaction_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    match existing {
+        Some(cheque_id) => {
+            sqlx::query(
+                "UPDATE cheques SET cheque_number = ?, bank_name = ?, due_date = ? WHERE id = ?",
+            )
+            .bind(form.cheque_number.trim())
+            .bind(form.bank_name.trim())
+            .bind(due_date)
+            .bind(cheque_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        None => {
+            let cheque_id = sqlx::query(
+                "INSERT INTO cheques (transaction_id, cheque_number, bank_name, due_date, status)
+                 VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(transaction_id)
+            .bind(form.cheque_number.trim())
+            .bind(form.bank_name.trim())
+            .bind(due_date)
+            .bind(ChequeStatus::Received.as_str())
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+
+            sqlx::query(
+                "INSERT INTO cheque_status_history (cheque_id, status, changed_by) VALUES (?, ?, ?)",
+            )
+            .bind(cheque_id)
+            .bind(ChequeStatus::Received.as_str())
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(())
 }
@@ -1,30 +1,36 @@
 use askama::Template;
 use axum::{
     extract::{Path, State},
-    response::{Html, IntoResponse, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
     Form,
 };
-use axum_extra::extract::{
-    cookie::{Cookie, SameSite},
-    CookieJar,
-};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 use sqlx::{Pool, Sqlite};
 
 use crate::{
+    db::{audit::record_audit, sessions::delete_user_sessions},
     error::{AppError, AppResult},
-    middleware::auth::{get_current_user, require_admin},
-    models::{User, UserForm},
-    templates::users::{AddUserTemplate, UsersTemplate},
-    utils::password::hash_password,
+    handlers::flash_cookie,
+    middleware::{
+        auth::get_current_user,
+        csrf::CsrfToken,
+        permissions::require_permission,
+    },
+    models::{
+        AuditEntity, EditUserForm, LockoutEvent, LoginFailure, LoginScope, Permission, ResetPasswordForm, Role,
+        UnlockForm, User, UserForm,
+    },
+    templates::users::{AddUserTemplate, EditUserTemplate, ResetPasswordTemplate, UsersTemplate},
+    utils::password::{hash_password, PasswordPolicy},
 };
 
-/// List all users (admin only)
+/// List all users (needs users.manage)
 pub async fn list_users(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
 ) -> AppResult<impl IntoResponse> {
-    // Require admin role
-    let _ = require_admin(&pool, &jar).await?;
+    let _ = require_permission(&pool, &jar, Permission::UsersManage).await?;
     
     let current_user = get_current_user(&pool, &jar).await;
     
@@ -38,7 +44,7 @@ pub async fn list_users(
         jar
     };
     
-    let users = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY created_at DESC")
+    let users = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY is_active DESC, created_at DESC")
         .fetch_all(&pool)
         .await
         .map_err(|e| {
@@ -46,9 +52,46 @@ pub async fn list_users(
             AppError::Internal("خطا در دریافت لیست کاربران".to_string())
         })?;
     
+    // Expired lockouts are left in the table until the next failure resets them
+    let now = chrono::Utc::now();
+    let lockouts: Vec<LoginFailure> = sqlx::query_as::<_, LoginFailure>(
+        "SELECT * FROM login_failures WHERE locked_until IS NOT NULL ORDER BY locked_until DESC",
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .filter(|failure| failure.is_locked(now))
+    .collect();
+
+    let lockout_events = sqlx::query_as::<_, LockoutEvent>(
+        "SELECT lockout_events.*, users.username AS unlocked_by
+         FROM lockout_events
+         LEFT JOIN users ON users.id = lockout_events.user_id
+         ORDER BY lockout_events.id DESC
+         LIMIT 20",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let session_counts = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT user_id, COUNT(*) FROM sessions WHERE expires_at > ? GROUP BY user_id",
+    )
+    .bind(now.to_rfc3339())
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let roles = fetch_roles(&pool).await?;
+
     let template = UsersTemplate {
         users,
+        roles,
+        session_counts,
+        lockouts,
+        lockout_events,
         current_user,
+        csrf_token,
         active_page: "users",
         flash_message,
     };
@@ -56,43 +99,48 @@ pub async fn list_users(
     Ok((jar, Html(template.render()?)))
 }
 
-/// Show add user form (admin only)
+/// Show add user form (needs users.manage)
 pub async fn show_add_user_form(
     State(pool): State<Pool<Sqlite>>,
+    State(policy): State<PasswordPolicy>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
 ) -> AppResult<impl IntoResponse> {
-    let _ = require_admin(&pool, &jar).await?;
-    
-    let current_user = get_current_user(&pool, &jar).await;
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await?;
     
     let template = AddUserTemplate {
-        current_user,
+        roles: fetch_assignable_roles(&pool, &admin).await?,
+        min_length: policy.min_length,
+        policy_description: policy.description(),
+        current_user: Some(admin),
+        csrf_token,
         active_page: "users",
     };
     
     Ok(Html(template.render()?))
 }
 
-/// Add new user (admin only)
+/// Add new user (needs users.manage)
 pub async fn add_user(
     State(pool): State<Pool<Sqlite>>,
+    State(policy): State<PasswordPolicy>,
     jar: CookieJar,
     Form(form): Form<UserForm>,
 ) -> AppResult<impl IntoResponse> {
-    let admin = require_admin(&pool, &jar).await
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
         .map_err(|_| AppError::Forbidden)?;
     
-    // Validate role
-    if form.role != "admin" && form.role != "user" {
-        return Err(AppError::BadRequest("Invalid role".to_string()));
-    }
+    let role = fetch_role_by_name(&pool, &form.role).await?;
+    ensure_can_grant(&admin, &role)?;
+
+    policy.check(&form.password).map_err(AppError::BadRequest)?;
     
     // Hash password
     let password_hash = hash_password(&form.password)?;
     
     // Insert user
-    sqlx::query(
-        "INSERT INTO users (username, password_hash, full_name, role) VALUES (?, ?, ?, ?)"
+    let id = sqlx::query(
+        "INSERT INTO users (username, password_hash, full_name, role, must_change_password) VALUES (?, ?, ?, ?, 1)"
     )
     .bind(&form.username)
     .bind(password_hash)
@@ -106,62 +154,613 @@ pub async fn add_user(
         } else {
             AppError::from(e)
         }
-    })?;
+    })?
+    .last_insert_rowid();
+
+    let user = fetch_user(&pool, id).await?;
+    record_audit(&pool, Some(admin.id), AuditEntity::User, id, None, Some(&user)).await?;
     
     println!("👤 New user created: {} by {}", form.username, admin.username);
     
     // Set flash message
-    let role_text = if form.role == "admin" { "مدیر" } else { "کاربر" };
-    let flash_cookie = Cookie::build(("flash_message", format!("کاربر «{}» با نقش {} با موفقیت ایجاد شد 👤", form.full_name, role_text)))
-        .path("/")
-        .same_site(SameSite::Lax)
-        .http_only(true)
-        .max_age(cookie::time::Duration::seconds(60))
-        .build();
-    
-    let jar = jar.add(flash_cookie);
-    
+    let jar = jar.add(flash_cookie(format!(
+        "کاربر «{}» با نقش {} با موفقیت ایجاد شد 👤",
+        form.full_name, role.display_name
+    )));
+
     Ok((jar, Redirect::to("/users")))
 }
 
-/// Delete user (admin only)
-pub async fn delete_user(
+/// Show the form for editing a user's name and role (needs users.manage)
+pub async fn show_edit_user_form(
     State(pool): State<Pool<Sqlite>>,
     jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
     Path(id): Path<i64>,
 ) -> AppResult<impl IntoResponse> {
-    let admin = require_admin(&pool, &jar).await
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await?;
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    let template = EditUserTemplate {
+        user,
+        roles: fetch_assignable_roles(&pool, &admin).await?,
+        current_user: Some(admin),
+        csrf_token,
+        active_page: "users",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Update a user's name and role (needs users.manage)
+pub async fn update_user(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+    Form(form): Form<EditUserForm>,
+) -> AppResult<impl IntoResponse> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
         .map_err(|_| AppError::Forbidden)?;
-    
-    // Don't allow deleting yourself
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    let role = fetch_role_by_name(&pool, &form.role).await?;
+    ensure_can_grant(&admin, &role)?;
+    let full_name = form.full_name.trim();
+    if full_name.is_empty() {
+        return Err(AppError::BadRequest("نام کامل نمی‌تواند خالی باشد".to_string()));
+    }
+
+    // Taking the admin role away must leave another active admin
+    let guard = if role.is_admin() { "" } else { KEEPS_AN_ACTIVE_ADMIN };
+    let result = sqlx::query(&format!("UPDATE users SET full_name = ?, role = ? WHERE id = ?{}", guard))
+        .bind(full_name)
+        .bind(&role.name)
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(last_admin_error());
+    }
+    audit_user_change(&pool, &admin, &user).await?;
+
+    println!("✏️ User {} updated by {}", user.username, admin.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "کاربر «{}» با موفقیت ویرایش شد ✏️",
+        full_name
+    )));
+
+    Ok((jar, Redirect::to("/users")))
+}
+
+/// Deactivate a user, keeping their history (needs users.manage).
+///
+/// A deactivated user cannot log in and is signed out everywhere.
+pub async fn deactivate_user(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
+        .map_err(|_| AppError::Forbidden)?;
+
+    // Don't allow deactivating yourself
     if admin.id == id {
-        return Err(AppError::BadRequest("Cannot delete yourself".to_string()));
+        return Err(AppError::BadRequest("نمی‌توانید حساب خودتان را غیرفعال کنید".to_string()));
     }
-    
-    // Get user name before deletion
-    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    let result = sqlx::query(&format!("UPDATE users SET is_active = 0 WHERE id = ?{}", KEEPS_AN_ACTIVE_ADMIN))
         .bind(id)
-        .fetch_optional(&pool)
+        .execute(&pool)
         .await?;
-    
-    let user_name = user.map(|u| u.full_name).unwrap_or_else(|| "کاربر".to_string());
-    
-    sqlx::query("DELETE FROM users WHERE id = ?")
+    if result.rows_affected() == 0 {
+        return Err(last_admin_error());
+    }
+    audit_user_change(&pool, &admin, &user).await?;
+    delete_user_sessions(&pool, id, None).await?;
+
+    println!("⛔ User {} deactivated by {}", user.username, admin.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "کاربر «{}» غیرفعال شد ⛔",
+        user.full_name
+    )));
+
+    Ok((jar, Redirect::to("/users")))
+}
+
+/// Let a deactivated user log in again (needs users.manage)
+pub async fn activate_user(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
+        .map_err(|_| AppError::Forbidden)?;
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    sqlx::query("UPDATE users SET is_active = 1 WHERE id = ?")
         .bind(id)
         .execute(&pool)
         .await?;
-    
-    println!("🗑️ User deleted by admin: {}", admin.username);
-    
-    // Set flash message
-    let flash_cookie = Cookie::build(("flash_message", format!("کاربر «{}» با موفقیت حذف شد 🗑️", user_name)))
-        .path("/")
-        .same_site(SameSite::Lax)
-        .http_only(true)
-        .max_age(cookie::time::Duration::seconds(60))
-        .build();
-    
-    let jar = jar.add(flash_cookie);
-    
+    audit_user_change(&pool, &admin, &user).await?;
+
+    println!("✅ User {} reactivated by {}", user.username, admin.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "کاربر «{}» دوباره فعال شد ✅",
+        user.full_name
+    )));
+
+    Ok((jar, Redirect::to("/users")))
+}
+
+/// Show the form for setting a user's password (needs users.manage)
+pub async fn show_reset_password(
+    State(pool): State<Pool<Sqlite>>,
+    State(policy): State<PasswordPolicy>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await?;
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    let template = ResetPasswordTemplate {
+        user,
+        min_length: policy.min_length,
+        policy_description: policy.description(),
+        error: None,
+        current_user: Some(admin),
+        csrf_token,
+        active_page: "users",
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Give a user a temporary password they must change at next login (needs users.manage)
+pub async fn reset_password(
+    State(pool): State<Pool<Sqlite>>,
+    State(policy): State<PasswordPolicy>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+    Form(form): Form<ResetPasswordForm>,
+) -> AppResult<Response> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
+        .map_err(|_| AppError::Forbidden)?;
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    let error = if form.new_password != form.confirm_password {
+        Some("رمز عبور و تکرار آن یکسان نیستند".to_string())
+    } else {
+        policy.check(&form.new_password).err()
+    };
+    if let Some(error) = error {
+        let template = ResetPasswordTemplate {
+            user,
+            min_length: policy.min_length,
+            policy_description: policy.description(),
+            error: Some(error),
+            current_user: Some(admin),
+            csrf_token,
+            active_page: "users",
+        };
+        return Ok(Html(template.render()?).into_response());
+    }
+
+    sqlx::query("UPDATE users SET password_hash = ?, must_change_password = 1 WHERE id = ?")
+        .bind(hash_password(&form.new_password)?)
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    audit_user_change(&pool, &admin, &user).await?;
+
+    // Whoever was using the old password is signed out
+    let keep = (admin.id == id)
+        .then(|| jar.get("session_id").map(|cookie| cookie.value().to_string()))
+        .flatten();
+    delete_user_sessions(&pool, id, keep.as_deref()).await?;
+
+    println!("🔑 Password of {} reset by admin {}", user.username, admin.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "رمز عبور «{}» بازنشانی شد؛ کاربر در ورود بعدی باید آن را تغییر دهد 🔑",
+        user.full_name
+    )));
+
+    Ok((jar, Redirect::to("/users")).into_response())
+}
+
+/// Sign a user out on every device (needs users.manage)
+pub async fn logout_user(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
+        .map_err(|_| AppError::Forbidden)?;
+
+    let user = fetch_user(&pool, id).await?;
+    ensure_can_manage(&admin, &user)?;
+
+    // Admins signing themselves out keep the session they are using
+    let keep = (admin.id == id)
+        .then(|| jar.get("session_id").map(|cookie| cookie.value().to_string()))
+        .flatten();
+    let count = delete_user_sessions(&pool, id, keep.as_deref()).await?;
+
+    println!("🔌 {} sessions of {} ended by admin {}", count, user.username, admin.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "کاربر «{}» از همه دستگاه‌ها خارج شد 🔌",
+        user.full_name
+    )));
+
     Ok((jar, Redirect::to("/users")))
-}
\ No newline at end of file
+}
+
+/// Lift a login lockout on a username or client address (needs users.manage)
+pub async fn unlock_login(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Form(form): Form<UnlockForm>,
+) -> AppResult<impl IntoResponse> {
+    let admin = require_permission(&pool, &jar, Permission::UsersManage).await
+        .map_err(|_| AppError::Forbidden)?;
+
+    let scope = LoginScope::parse(&form.scope)
+        .ok_or_else(|| AppError::BadRequest("نوع قفل نامعتبر است".to_string()))?;
+
+    let result = sqlx::query("DELETE FROM login_failures WHERE scope = ? AND key = ?")
+        .bind(scope.as_str())
+        .bind(&form.key)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    sqlx::query(
+        "INSERT INTO lockout_events (scope, key, event, user_id) VALUES (?, ?, 'unlocked', ?)",
+    )
+    .bind(scope.as_str())
+    .bind(&form.key)
+    .bind(admin.id)
+    .execute(&pool)
+    .await?;
+
+    println!("🔓 Login unlocked for {} {} by {}", scope.as_str(), form.key, admin.username);
+
+    let jar = jar.add(flash_cookie(format!(
+        "قفل ورود {} «{}» برداشته شد 🔓",
+        scope.display_name(),
+        form.key
+    )));
+
+    Ok((jar, Redirect::to("/users")))
+}
+
+pub async fn fetch_user(pool: &Pool<Sqlite>, id: i64) -> AppResult<User> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+/// Record what an admin changed on a user, given the user as it was before
+async fn audit_user_change(pool: &Pool<Sqlite>, admin: &User, old: &User) -> AppResult<()> {
+    let new = fetch_user(pool, old.id).await?;
+    record_audit(pool, Some(admin.id), AuditEntity::User, old.id, Some(old), Some(&new)).await?;
+    Ok(())
+}
+
+async fn fetch_roles(pool: &Pool<Sqlite>) -> AppResult<Vec<Role>> {
+    Ok(sqlx::query_as::<_, Role>("SELECT * FROM roles ORDER BY id")
+        .fetch_all(pool)
+        .await?)
+}
+
+/// The roles `actor` may give to users; only admins may make others admins
+async fn fetch_assignable_roles(pool: &Pool<Sqlite>, actor: &User) -> AppResult<Vec<Role>> {
+    let mut roles = fetch_roles(pool).await?;
+    roles.retain(|role| actor.is_admin() || !role.is_admin());
+    Ok(roles)
+}
+
+async fn fetch_role_by_name(pool: &Pool<Sqlite>, name: &str) -> AppResult<Role> {
+    sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE name = ?")
+        .bind(name)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("نقش نامعتبر است".to_string()))
+}
+
+/// Refuse to let `actor` change an admin's account unless they are an admin themselves
+fn ensure_can_manage(actor: &User, user: &User) -> AppResult<()> {
+    if actor.can_manage(user) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
+
+/// Refuse to let `actor` give out the admin role unless they are an admin themselves
+fn ensure_can_grant(actor: &User, role: &Role) -> AppResult<()> {
+    if role.is_admin() && !actor.is_admin() {
+        Err(AppError::Forbidden)
+    } else {
+        Ok(())
+    }
+}
+
+/// Appended to an UPDATE of one user so that it only applies if the user is not
+/// an active admin, or another active admin remains. Checking and writing in
+/// one statement keeps two admins from demoting each other at the same time.
+const KEEPS_AN_ACTIVE_ADMIN: &str = " AND (role != 'admin' OR is_active = 0 OR EXISTS (
+    SELECT 1 FROM users AS other
+    WHERE other.role = 'admin' AND other.is_active = 1 AND other.id != users.id
+))";
+
+/// Why demoting or deactivating the last active admin was refused
+fn last_admin_error() -> AppError {
+    AppError::BadRequest("این کاربر تنها مدیر فعال سیستم است؛ ابتدا مدیر دیگری تعیین کنید".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    /// An admin (1) signed in on their PC, and Ali (2) on a phone and a laptop
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role)
+             VALUES ('boss', '', 'مدیر', 'admin'), ('ali', '', 'علی', 'user')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (id, user_id) in [("admin-pc", 1), ("ali-phone", 2), ("ali-laptop", 2)] {
+            add_session(&pool, id, user_id).await;
+        }
+        pool
+    }
+
+    async fn add_session(pool: &Pool<Sqlite>, id: &str, user_id: i64) {
+        sqlx::query("INSERT INTO sessions (id, user_id, expires_at) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(user_id)
+            .bind((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339())
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn session_ids(pool: &Pool<Sqlite>) -> Vec<String> {
+        sqlx::query_scalar("SELECT id FROM sessions ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    fn jar(session_id: &str) -> CookieJar {
+        CookieJar::new().add(Cookie::new("session_id", session_id.to_string()))
+    }
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            require_letter: true,
+            require_digit: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reset_password_ends_users_sessions() {
+        let pool = test_pool().await;
+        let form = ResetPasswordForm {
+            new_password: "temp-pass-1".to_string(),
+            confirm_password: "temp-pass-1".to_string(),
+        };
+        reset_password(
+            State(pool.clone()),
+            State(policy()),
+            jar("admin-pc"),
+            CsrfToken(String::new()),
+            Path(2),
+            Form(form),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(session_ids(&pool).await, vec!["admin-pc"]);
+        let ali = fetch_user(&pool, 2).await.unwrap();
+        assert!(ali.must_change_password);
+    }
+
+    #[tokio::test]
+    async fn test_deactivate_user_ends_users_sessions() {
+        let pool = test_pool().await;
+        deactivate_user(State(pool.clone()), jar("admin-pc"), Path(2))
+            .await
+            .unwrap();
+
+        assert_eq!(session_ids(&pool).await, vec!["admin-pc"]);
+        assert!(!fetch_user(&pool, 2).await.unwrap().is_active);
+    }
+
+    #[tokio::test]
+    async fn test_last_active_admin_is_kept() {
+        let pool = test_pool().await;
+        let demote = |id| EditUserForm {
+            full_name: format!("کاربر {}", id),
+            role: "user".to_string(),
+        };
+
+        // The only admin cannot give up the role
+        let result = update_user(
+            State(pool.clone()),
+            jar("admin-pc"),
+            Path(1),
+            Form(demote(1)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert!(fetch_user(&pool, 1).await.unwrap().is_admin());
+
+        // The guard is part of the UPDATE itself, not a separate check beforehand
+        let result = sqlx::query(&format!(
+            "UPDATE users SET is_active = 0 WHERE id = 1{}",
+            KEEPS_AN_ACTIVE_ADMIN
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(result.rows_affected(), 0);
+
+        // With a second admin either one may step down, but not both
+        sqlx::query("UPDATE users SET role = 'admin' WHERE id = 2")
+            .execute(&pool)
+            .await
+            .unwrap();
+        update_user(
+            State(pool.clone()),
+            jar("admin-pc"),
+            Path(1),
+            Form(demote(1)),
+        )
+        .await
+        .unwrap();
+        assert!(!fetch_user(&pool, 1).await.unwrap().is_admin());
+        let result = update_user(
+            State(pool.clone()),
+            jar("ali-phone"),
+            Path(2),
+            Form(demote(2)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert!(fetch_user(&pool, 2).await.unwrap().is_admin());
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_manage_admins() {
+        let pool = test_pool().await;
+        // Mina's custom role may manage users, but she is no admin
+        sqlx::query("INSERT INTO roles (name, display_name) VALUES ('clerk', 'کارمند')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO role_permissions (role_id, permission)
+             SELECT id, 'users.manage' FROM roles WHERE name = 'clerk'",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role)
+             VALUES ('mina', '', 'مینا', 'clerk')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        add_session(&pool, "mina-pc", 3).await;
+        let edit = |role: &str| EditUserForm {
+            full_name: "نام تازه".to_string(),
+            role: role.to_string(),
+        };
+
+        // She cannot make anyone an admin
+        let form = UserForm {
+            username: "reza".to_string(),
+            password: "temp-pass-1".to_string(),
+            full_name: "رضا".to_string(),
+            role: "admin".to_string(),
+        };
+        let result = add_user(
+            State(pool.clone()),
+            State(policy()),
+            jar("mina-pc"),
+            Form(form),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        let result = update_user(
+            State(pool.clone()),
+            jar("mina-pc"),
+            Path(2),
+            Form(edit("admin")),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        assert!(!fetch_user(&pool, 2).await.unwrap().is_admin());
+
+        // Nor touch the admin's account
+        let result = update_user(
+            State(pool.clone()),
+            jar("mina-pc"),
+            Path(1),
+            Form(edit("user")),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        let form = ResetPasswordForm {
+            new_password: "temp-pass-1".to_string(),
+            confirm_password: "temp-pass-1".to_string(),
+        };
+        let result = reset_password(
+            State(pool.clone()),
+            State(policy()),
+            jar("mina-pc"),
+            CsrfToken(String::new()),
+            Path(1),
+            Form(form),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        let result = deactivate_user(State(pool.clone()), jar("mina-pc"), Path(1)).await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        let result = logout_user(State(pool.clone()), jar("mina-pc"), Path(1)).await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        let boss = fetch_user(&pool, 1).await.unwrap();
+        assert!(boss.is_admin() && boss.is_active && !boss.must_change_password);
+        assert_eq!(
+            session_ids(&pool).await,
+            vec!["admin-pc", "ali-laptop", "ali-phone", "mina-pc"]
+        );
+
+        // Other users are still hers to manage
+        update_user(
+            State(pool.clone()),
+            jar("mina-pc"),
+            Path(2),
+            Form(edit("user")),
+        )
+        .await
+        .unwrap();
+        deactivate_user(State(pool.clone()), jar("mina-pc"), Path(2))
+            .await
+            .unwrap();
+        assert!(!fetch_user(&pool, 2).await.unwrap().is_active);
+    }
+}
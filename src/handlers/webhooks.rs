@@ -0,0 +1,346 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    db::webhooks::redeliver_webhook,
+    error::{AppError, AppResult},
+    handlers::flash_cookie,
+    middleware::{csrf::CsrfToken, permissions::require_permission},
+    models::{
+        webhook::generate_secret, DeliveryStatus, Permission, User, WebhookDelivery,
+        WebhookDeliveryQuery, WebhookEndpoint, WebhookEndpointForm, WebhookEndpointSummary,
+    },
+    templates::webhooks::{WebhookDeliveriesTemplate, WebhookFormTemplate, WebhooksTemplate},
+};
+
+/// Deliveries listed on the delivery log, newest first
+const DELIVERY_LOG_SIZE: i64 = 100;
+
+/// Webhook endpoints with their waiting and failed deliveries (needs webhooks.manage)
+pub async fn list_webhooks(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let endpoints = sqlx::query_as::<_, WebhookEndpointSummary>(
+        "SELECT webhook_endpoints.*,
+                (SELECT COUNT(*) FROM webhook_deliveries
+                 WHERE endpoint_id = webhook_endpoints.id AND status = 'pending') AS pending_count,
+                (SELECT COUNT(*) FROM webhook_deliveries
+                 WHERE endpoint_id = webhook_endpoints.id AND status = 'failed') AS failed_count
+         FROM webhook_endpoints
+         ORDER BY id",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let template = WebhooksTemplate {
+        endpoints,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "webhooks",
+        flash_message,
+    };
+
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Show the form for a new endpoint (needs webhooks.manage)
+pub async fn show_add_webhook_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+
+    let form = WebhookEndpointForm {
+        is_active: true,
+        ..WebhookEndpointForm::default()
+    };
+    let template = form_page(None, form, None, current_user, csrf_token);
+    Ok(Html(template.render()?))
+}
+
+/// Add an endpoint with a new signing secret (needs webhooks.manage)
+pub async fn add_webhook(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+    let form = WebhookEndpointForm::from_fields(fields);
+
+    if let Some(error) = validate_webhook_form(&form) {
+        let template = form_page(None, form, Some(error), current_user, csrf_token);
+        return Ok(Html(template.render()?).into_response());
+    }
+
+    let id = sqlx::query(
+        "INSERT INTO webhook_endpoints (url, description, secret, events, is_active, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&form.url)
+    .bind(&form.description)
+    .bind(generate_secret())
+    .bind(form.events_value())
+    .bind(form.is_active)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&pool)
+    .await?
+    .last_insert_rowid();
+
+    println!("🪝 Webhook endpoint {} added by {}", form.url, current_user.username);
+
+    // The endpoint's page shows the secret the receiver needs to check signatures
+    let jar = jar.add(flash_cookie(
+        "وبهوک اضافه شد؛ کلید امضا را در سامانه گیرنده وارد کنید 🪝",
+    ));
+    Ok((jar, Redirect::to(&format!("/webhooks/{}", id))).into_response())
+}
+
+/// An endpoint with its signing secret and recent deliveries (needs webhooks.manage)
+pub async fn view_webhook(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+    Query(query): Query<WebhookDeliveryQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+    let endpoint = fetch_endpoint(&pool, id).await?;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let template =
+        deliveries_page(&pool, Some(endpoint), query, current_user, csrf_token, flash_message)
+            .await?;
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Recent deliveries to every endpoint (needs webhooks.manage)
+pub async fn list_deliveries(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Query(query): Query<WebhookDeliveryQuery>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+
+    // Check for flash message
+    let flash_message = jar.get("flash_message").map(|c| c.value().to_string());
+
+    // Remove flash message cookie after reading
+    let jar = if flash_message.is_some() {
+        jar.remove(Cookie::from("flash_message"))
+    } else {
+        jar
+    };
+
+    let template =
+        deliveries_page(&pool, None, query, current_user, csrf_token, flash_message).await?;
+    Ok((jar, Html(template.render()?)))
+}
+
+/// Show the form for changing an endpoint (needs webhooks.manage)
+pub async fn show_edit_webhook_form(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+    let endpoint = fetch_endpoint(&pool, id).await?;
+
+    let form = WebhookEndpointForm {
+        events: endpoint.event_list(),
+        url: endpoint.url,
+        description: endpoint.description,
+        is_active: endpoint.is_active,
+    };
+    let template = form_page(Some(id), form, None, current_user, csrf_token);
+    Ok(Html(template.render()?))
+}
+
+/// Change an endpoint's address, events or whether it is active (needs webhooks.manage)
+pub async fn update_webhook(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(id): Path<i64>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+    fetch_endpoint(&pool, id).await?;
+    let form = WebhookEndpointForm::from_fields(fields);
+
+    if let Some(error) = validate_webhook_form(&form) {
+        let template = form_page(Some(id), form, Some(error), current_user, csrf_token);
+        return Ok(Html(template.render()?).into_response());
+    }
+
+    sqlx::query(
+        "UPDATE webhook_endpoints SET url = ?, description = ?, events = ?, is_active = ? WHERE id = ?",
+    )
+    .bind(&form.url)
+    .bind(&form.description)
+    .bind(form.events_value())
+    .bind(form.is_active)
+    .bind(id)
+    .execute(&pool)
+    .await?;
+
+    println!("🪝 Webhook endpoint {} updated by {}", form.url, current_user.username);
+
+    let jar = jar.add(flash_cookie("وبهوک با موفقیت ویرایش شد 🪝"));
+    Ok((jar, Redirect::to(&format!("/webhooks/{}", id))).into_response())
+}
+
+/// Delete an endpoint and its delivery log (needs webhooks.manage)
+pub async fn delete_webhook(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+    let endpoint = fetch_endpoint(&pool, id).await?;
+
+    sqlx::query("DELETE FROM webhook_endpoints WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+
+    println!("🗑️ Webhook endpoint {} deleted by {}", endpoint.url, current_user.username);
+
+    let jar = jar.add(flash_cookie(format!("وبهوک {} حذف شد", endpoint.url)));
+    Ok((jar, Redirect::to("/webhooks")))
+}
+
+/// Send a delivery's payload again as a new delivery (needs webhooks.manage)
+pub async fn redeliver(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let current_user = require_permission(&pool, &jar, Permission::WebhooksManage).await?;
+    let endpoint_id: i64 =
+        sqlx::query_scalar("SELECT endpoint_id FROM webhook_deliveries WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+    redeliver_webhook(&pool, id).await?.ok_or(AppError::NotFound)?;
+
+    println!("🔁 Webhook delivery {} queued again by {}", id, current_user.username);
+
+    let jar = jar.add(flash_cookie(
+        "ارسال دوباره در صف قرار گرفت و تا چند لحظه دیگر انجام می‌شود 🔁",
+    ));
+    Ok((jar, Redirect::to(&format!("/webhooks/{}", endpoint_id))))
+}
+
+async fn fetch_endpoint(pool: &Pool<Sqlite>, id: i64) -> AppResult<WebhookEndpoint> {
+    sqlx::query_as::<_, WebhookEndpoint>("SELECT * FROM webhook_endpoints WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+async fn deliveries_page(
+    pool: &Pool<Sqlite>,
+    endpoint: Option<WebhookEndpoint>,
+    query: WebhookDeliveryQuery,
+    current_user: User,
+    csrf_token: String,
+    flash_message: Option<String>,
+) -> AppResult<WebhookDeliveriesTemplate> {
+    let status = DeliveryStatus::parse(&query.status);
+
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT webhook_deliveries.*, webhook_endpoints.url FROM webhook_deliveries
+         JOIN webhook_endpoints ON webhook_endpoints.id = webhook_deliveries.endpoint_id
+         WHERE (? IS NULL OR webhook_deliveries.endpoint_id = ?)
+           AND (? IS NULL OR webhook_deliveries.status = ?)
+         ORDER BY webhook_deliveries.id DESC
+         LIMIT ?",
+    )
+    .bind(endpoint.as_ref().map(|endpoint| endpoint.id))
+    .bind(endpoint.as_ref().map(|endpoint| endpoint.id))
+    .bind(status.map(|status| status.as_str()))
+    .bind(status.map(|status| status.as_str()))
+    .bind(DELIVERY_LOG_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(WebhookDeliveriesTemplate {
+        endpoint,
+        deliveries,
+        status,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "webhooks",
+        flash_message,
+    })
+}
+
+fn form_page(
+    endpoint_id: Option<i64>,
+    form: WebhookEndpointForm,
+    error: Option<String>,
+    current_user: User,
+    csrf_token: String,
+) -> WebhookFormTemplate {
+    WebhookFormTemplate {
+        endpoint_id,
+        url: form.url,
+        description: form.description,
+        events: form.events,
+        is_active: form.is_active,
+        error,
+        current_user: Some(current_user),
+        csrf_token,
+        active_page: "webhooks",
+    }
+}
+
+fn validate_webhook_form(form: &WebhookEndpointForm) -> Option<String> {
+    let is_http = form.url.starts_with("http://") || form.url.starts_with("https://");
+    if !is_http || form.url.contains(char::is_whitespace) {
+        return Some("آدرس وبهوک باید با http:// یا https:// شروع شود".to_string());
+    }
+    if form.url.chars().count() > 500 {
+        return Some("آدرس وبهوک نمی‌تواند بیشتر از ۵۰۰ کاراکتر باشد".to_string());
+    }
+    if form.events.is_empty() {
+        return Some("دست‌کم یک رویداد را انتخاب کنید".to_string());
+    }
+    None
+}
@@ -8,6 +8,7 @@ pub mod error;
 pub mod handlers;
 pub mod middleware;
 pub mod models;
+pub mod state;
 pub mod templates;
 pub mod utils;
 
@@ -9,17 +9,22 @@ mod error;
 mod handlers;
 mod middleware;
 mod models;
+mod state;
 mod templates;
 mod utils;
 
-use std::env;
+use std::{env, net::SocketAddr};
 
 use crate::{
     config::Config,
     db::{
         connection::create_pool,
-        migrations::{create_default_admin, run_migrations},
+        migrations::{create_default_admin, migration_status, run_migrations, schema_columns},
+        sessions::spawn_session_purger,
+        webhooks::spawn_webhook_worker,
     },
+    state::AppState,
+    utils::media::MediaStore,
 };
 
 #[tokio::main]
@@ -30,6 +35,10 @@ async fn main() {
         create_admin_cli().await;
         return;
     }
+    if args.len() > 1 && args[1] == "migrate" {
+        migrate_cli(args.get(2).map(String::as_str)).await;
+        return;
+    }
 
     // Load configuration
     let config = Config::from_env();
@@ -54,8 +63,44 @@ async fn main() {
         eprintln!("⚠️  Warning: Failed to create default admin: {}", e);
     }
 
+    // Prepare the directory for uploaded files
+    let media = MediaStore::new(&config.media_dir);
+    if let Err(e) = media.create_dirs().await {
+        eprintln!("❌ Failed to create media directory {}: {}", config.media_dir, e);
+        std::process::exit(1);
+    }
+
+    // Printed documents need a font with Persian glyphs for their PDFs
+    let documents = config.document_settings();
+    if !documents.font_path.is_file() {
+        eprintln!(
+            "⚠️  Warning: PDF font {} not found; set PDF_FONT_PATH to enable PDF export",
+            documents.font_path.display()
+        );
+    }
+
+    // Expired sessions are deleted periodically, not only when they are next used
+    spawn_session_purger(
+        pool.clone(),
+        std::time::Duration::from_secs(config.session_purge_minutes.max(1) as u64 * 60),
+    );
+
+    // Webhooks are queued with the changes they report and sent from here, retrying failures
+    spawn_webhook_worker(
+        pool.clone(),
+        std::time::Duration::from_secs(config.webhook_poll_seconds.max(1) as u64),
+    );
+
     // Configure routes
-    let app = handlers::configure_routes(pool);
+    let app = handlers::configure_routes(AppState {
+        pool,
+        media,
+        documents,
+        login_limits: config.login_limits.clone(),
+        sessions: config.session_settings(),
+        password_policy: config.password_policy.clone(),
+        stock: config.stock,
+    });
 
     // Start server
     let addr = config.server_address();
@@ -65,11 +110,16 @@ async fn main() {
 
     println!("🚀 Server running at http://{}", addr);
     println!("📊 Database: {}", config.database_url);
+    println!("🖼️ Media directory: {}", config.media_dir);
     println!("🔐 Authentication enabled");
     println!("📝 Visit http://{}/login to get started", addr);
 
-    axum::serve(listener, app)
-        .await
+    // Login throttling counts failures per client address
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
         .expect("Server failed to start");
 }
 
@@ -151,3 +201,57 @@ async fn create_admin_cli() {
         }
     }
 }
+
+/// CLI command to inspect or apply database migrations
+async fn migrate_cli(subcommand: Option<&str>) {
+    let pool = match create_pool().await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("❌ Failed to connect to database: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match subcommand {
+        Some("up") => {
+            if let Err(e) = run_migrations(&pool).await {
+                eprintln!("❌ Failed to run migrations: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("status") => {
+            let statuses = match migration_status(&pool).await {
+                Ok(statuses) => statuses,
+                Err(e) => {
+                    eprintln!("❌ Failed to read migration status: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            println!("=== Migrations ===\n");
+            for status in &statuses {
+                match &status.applied_at {
+                    Some(applied_at) => println!(
+                        "  ✅ {:04}_{:<40} applied {}",
+                        status.version, status.name, applied_at
+                    ),
+                    None => println!("  ⏳ {:04}_{:<40} pending", status.version, status.name),
+                }
+            }
+
+            let pending = statuses.iter().filter(|s| s.applied_at.is_none()).count();
+            println!("\n{} applied, {} pending", statuses.len() - pending, pending);
+
+            if let Ok(schema) = schema_columns(&pool).await {
+                println!("\n=== Schema ===\n");
+                for (table, columns) in schema {
+                    println!("  {}: {}", table, columns.join(", "));
+                }
+            }
+        }
+        _ => {
+            eprintln!("Usage: rumiland_crm migrate <status|up>");
+            std::process::exit(2);
+        }
+    }
+}
@@ -1,93 +1,373 @@
 use axum::{
     extract::State,
+    http::{header, HeaderMap},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Redirect, Response},
 };
-use axum_extra::extract::CookieJar;
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use chrono::Duration;
 use sqlx::{Pool, Sqlite};
 
-use crate::{error::AppError, models::{Session, User}};
+use crate::{
+    error::AppError,
+    middleware::permissions::load_access,
+    models::{
+        api_token::{hash_token, parse_bearer},
+        ApiToken, Session, SessionSettings, User,
+    },
+};
+
+/// Sessions are extended, and their last-seen time updated, at most this often,
+/// so not every request writes to the database
+const SLIDE_INTERVAL_SECONDS: i64 = 60;
+
+/// Page where users change their own password
+pub const CHANGE_PASSWORD_PATH: &str = "/account/password";
+
+/// Paths open to a user who must change their password first
+const PASSWORD_CHANGE_PATHS: &[&str] = &[CHANGE_PASSWORD_PATH, "/logout"];
 
-/// Middleware to check if user is authenticated
+/// Middleware to check if user is authenticated.
+///
+/// Each request pushes the session's expiry forward, so it only ends after a stretch of inactivity.
 pub async fn auth_middleware(
     State(pool): State<Pool<Sqlite>>,
+    State(settings): State<SessionSettings>,
     jar: CookieJar,
     request: axum::extract::Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    // Check for session cookie
-    if let Some(session_cookie) = jar.get("session_id") {
-        let session_id = session_cookie.value();
-        
-        // Verify session in database
-        let session = sqlx::query_as::<_, Session>(
-            "SELECT * FROM sessions WHERE id = ?"
-        )
+    // Pages only take the session cookie; API tokens are for `/api/v1`, whose handlers
+    // act as the token's user
+    let signed_in = check_session(&pool, &settings, &jar).await?;
+
+    // A user with a temporary password can only change it or log out
+    if signed_in.must_change_password && !PASSWORD_CHANGE_PATHS.contains(&request.uri().path()) {
+        return Ok((signed_in.refreshed, Redirect::to(CHANGE_PASSWORD_PATH)).into_response());
+    }
+
+    Ok((signed_in.refreshed, next.run(request).await).into_response())
+}
+
+/// A valid session found from the request's cookie
+pub struct SignedIn {
+    pub session: Session,
+    pub must_change_password: bool,
+    /// Cookies to send back; holds the renewed session cookie if it was extended
+    pub refreshed: CookieJar,
+}
+
+/// Check the session cookie, sliding the session's expiry forward.
+///
+/// Expired sessions, and sessions of deactivated users, are deleted.
+pub async fn check_session(
+    pool: &Pool<Sqlite>,
+    settings: &SessionSettings,
+    jar: &CookieJar,
+) -> Result<SignedIn, AppError> {
+    let Some(cookie) = jar.get("session_id") else {
+        return Err(AppError::Unauthorized);
+    };
+    let session_id = cookie.value();
+
+    // Verify session in database
+    let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = ?")
         .bind(session_id)
-        .fetch_optional(&pool)
+        .fetch_optional(pool)
         .await
         .map_err(|e| {
             eprintln!("Database error in auth middleware: {:?}", e);
             AppError::Internal("خطا در بررسی احراز هویت".to_string())
-        })?;
-        
-        if let Some(session) = session {
-            // Check if session is expired
-            let now = chrono::Utc::now();
-            let expires_at = chrono::DateTime::parse_from_rfc3339(&session.expires_at)
-                .map_err(|_| AppError::Internal("خطا در پردازش زمان نشست".to_string()))?;
-            
-            if expires_at > now {
-                // Valid session, allow request to proceed
-                return Ok(next.run(request).await);
-            } else {
-                // Session expired, clean it up
-                sqlx::query("DELETE FROM sessions WHERE id = ?")
-                    .bind(session_id)
-                    .execute(&pool)
-                    .await
-                    .ok();
-                
-                return Err(AppError::SessionExpired);
-            }
+        })?
+        .ok_or(AppError::Unauthorized)?;
+
+    // Check if session is expired
+    let now = chrono::Utc::now();
+    let expires_at = session
+        .expires_at_time()
+        .ok_or_else(|| AppError::Internal("خطا در پردازش زمان نشست".to_string()))?;
+
+    if expires_at <= now {
+        // Session expired, clean it up
+        sqlx::query("DELETE FROM sessions WHERE id = ?")
+            .bind(session_id)
+            .execute(pool)
+            .await
+            .ok();
+
+        return Err(AppError::SessionExpired);
+    }
+
+    // Valid session; slide its expiry before letting the request proceed
+    let new_expiry = settings.expiry(session.remember_me, now);
+    let mut refreshed = CookieJar::new();
+    if new_expiry - expires_at >= Duration::seconds(SLIDE_INTERVAL_SECONDS) {
+        sqlx::query("UPDATE sessions SET expires_at = ?, last_seen_at = ? WHERE id = ?")
+            .bind(new_expiry.to_rfc3339())
+            .bind(now.to_rfc3339())
+            .bind(session_id)
+            .execute(pool)
+            .await?;
+
+        // A remembered session's cookie expires too, so refresh it alongside
+        if session.remember_me {
+            refreshed = refreshed.add(session_cookie(
+                session_id.to_string(),
+                Some(settings.lifetime(true)),
+            ));
         }
     }
-    
-    // No valid session
-    Err(AppError::Unauthorized)
+
+    let (must_change_password, is_active) = sqlx::query_as::<_, (bool, bool)>(
+        "SELECT must_change_password, is_active FROM users WHERE id = ?",
+    )
+    .bind(session.user_id)
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or((false, false));
+
+    // Deactivating a user ends their sessions, but do not rely on that alone
+    if !is_active {
+        sqlx::query("DELETE FROM sessions WHERE id = ?")
+            .bind(session_id)
+            .execute(pool)
+            .await
+            .ok();
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok(SignedIn {
+        session,
+        must_change_password,
+        refreshed,
+    })
+}
+
+/// The user of the request's `Authorization: Bearer` API token, if it has one.
+///
+/// The user's permissions are narrowed to the token's scopes. A token that is unknown,
+/// revoked or expired, or whose user is deactivated, is refused rather than ignored.
+pub async fn check_bearer_token(
+    pool: &Pool<Sqlite>,
+    headers: &HeaderMap,
+) -> Result<Option<User>, AppError> {
+    let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_bearer)
+    else {
+        return Ok(None);
+    };
+
+    let api_token = sqlx::query_as::<_, ApiToken>(
+        "SELECT api_tokens.*, users.username
+         FROM api_tokens
+         JOIN users ON users.id = api_tokens.user_id
+         WHERE token_hash = ? AND revoked_at IS NULL",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::Unauthorized)?;
+
+    let now = chrono::Utc::now();
+    if api_token.is_expired(now) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let mut user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ? AND is_active = 1")
+        .bind(api_token.user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    // A temporary password has to be replaced in the browser first
+    if user.must_change_password {
+        return Err(AppError::Forbidden);
+    }
+
+    load_access(pool, &mut user).await?;
+    user.permissions = api_token.granted(&user.permissions);
+
+    // Like session activity, the last use is written at most once a minute
+    let stale = api_token.last_used_at_time().map_or(true, |last_used_at| {
+        now - last_used_at >= Duration::seconds(SLIDE_INTERVAL_SECONDS)
+    });
+    if stale {
+        sqlx::query("UPDATE api_tokens SET last_used_at = ? WHERE id = ?")
+            .bind(now.to_rfc3339())
+            .bind(api_token.id)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(Some(user))
+}
+
+/// The session cookie; without a max age it lasts until the browser closes
+pub fn session_cookie(session_id: String, max_age: Option<Duration>) -> Cookie<'static> {
+    let mut cookie = Cookie::build(("session_id", session_id))
+        .path("/")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .build();
+    if let Some(max_age) = max_age {
+        cookie.set_max_age(cookie::time::Duration::seconds(max_age.num_seconds()));
+    }
+    cookie
 }
 
 /// Get current user from session
 pub async fn get_current_user(pool: &Pool<Sqlite>, jar: &CookieJar) -> Option<User> {
     if let Some(session_cookie) = jar.get("session_id") {
         let result = sqlx::query_as::<_, (i64,)>(
-            "SELECT user_id FROM sessions WHERE id = ? AND expires_at > datetime('now')"
+            "SELECT user_id FROM sessions WHERE id = ? AND expires_at > ?"
         )
         .bind(session_cookie.value())
+        .bind(chrono::Utc::now().to_rfc3339())
         .fetch_optional(pool)
         .await
         .ok()?;
         
         if let Some((user_id,)) = result {
-            return sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+            let mut user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ? AND is_active = 1")
                 .bind(user_id)
                 .fetch_optional(pool)
                 .await
-                .ok()?;
+                .ok()??;
+            load_access(pool, &mut user).await.ok()?;
+            return Some(user);
         }
     }
     None
 }
 
-/// Require admin role
-pub async fn require_admin(pool: &Pool<Sqlite>, jar: &CookieJar) -> Result<User, AppError> {
-    let user = get_current_user(pool, jar)
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::run_migrations;
+
+    fn settings() -> SessionSettings {
+        SessionSettings {
+            idle_timeout_hours: 8,
+            remember_me_days: 30,
+        }
+    }
+
+    async fn test_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, password_hash, full_name, role)
+             VALUES ('ali', '', 'علی', 'user')",
+        )
+        .execute(&pool)
         .await
-        .ok_or(AppError::Unauthorized)?;
-    
-    if !user.is_admin() {
-        return Err(AppError::Forbidden);
+        .unwrap();
+        pool
+    }
+
+    async fn add_session(
+        pool: &Pool<Sqlite>,
+        id: &str,
+        expires_at: DateTime<Utc>,
+        remember_me: bool,
+    ) {
+        sqlx::query(
+            "INSERT INTO sessions (id, user_id, expires_at, remember_me) VALUES (?, 1, ?, ?)",
+        )
+        .bind(id)
+        .bind(expires_at.to_rfc3339())
+        .bind(remember_me)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn expires_at(pool: &Pool<Sqlite>, id: &str) -> Option<String> {
+        sqlx::query_scalar("SELECT expires_at FROM sessions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    fn jar(session_id: &str) -> CookieJar {
+        CookieJar::new().add(Cookie::new("session_id", session_id.to_string()))
     }
-    
-    Ok(user)
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_check_session_slides_expiry_at_most_once_a_minute() {
+        let pool = test_pool().await;
+        let settings = settings();
+        let now = Utc::now();
+
+        // Last slid half a minute ago: not written again yet
+        let recent = now + settings.lifetime(false) - Duration::seconds(30);
+        add_session(&pool, "recent", recent, false).await;
+        let signed_in = check_session(&pool, &settings, &jar("recent"))
+            .await
+            .unwrap();
+        assert_eq!(expires_at(&pool, "recent").await, Some(recent.to_rfc3339()));
+        assert!(signed_in.refreshed.get("session_id").is_none());
+
+        // Last slid two minutes ago: pushed forward
+        let stale = now + settings.lifetime(false) - Duration::seconds(120);
+        add_session(&pool, "stale", stale, false).await;
+        let signed_in = check_session(&pool, &settings, &jar("stale"))
+            .await
+            .unwrap();
+        let slid = DateTime::parse_from_rfc3339(&expires_at(&pool, "stale").await.unwrap())
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(slid >= stale + Duration::seconds(120));
+        // The cookie of a session that ends with the browser is left as it is
+        assert!(signed_in.refreshed.get("session_id").is_none());
+
+        // A remembered session's cookie is renewed along with it
+        let remembered = now + settings.lifetime(true) - Duration::seconds(120);
+        add_session(&pool, "remembered", remembered, true).await;
+        let signed_in = check_session(&pool, &settings, &jar("remembered"))
+            .await
+            .unwrap();
+        assert!(signed_in.refreshed.get("session_id").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_check_session_deletes_expired_session() {
+        let pool = test_pool().await;
+        add_session(&pool, "old", Utc::now() - Duration::seconds(1), false).await;
+
+        let result = check_session(&pool, &settings(), &jar("old")).await;
+        assert!(matches!(result, Err(AppError::SessionExpired)));
+        assert_eq!(expires_at(&pool, "old").await, None);
+
+        let result = check_session(&pool, &settings(), &CookieJar::new()).await;
+        assert!(matches!(result, Err(AppError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn test_check_session_drops_session_of_deactivated_user() {
+        let pool = test_pool().await;
+        let expires_at_later = Utc::now() + Duration::hours(1);
+        add_session(&pool, "current", expires_at_later, false).await;
+        sqlx::query("UPDATE users SET is_active = 0 WHERE id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let result = check_session(&pool, &settings(), &jar("current")).await;
+        assert!(matches!(result, Err(AppError::Unauthorized)));
+        assert_eq!(expires_at(&pool, "current").await, None);
+    }
+}
@@ -0,0 +1,190 @@
+use axum::{
+    async_trait,
+    body::{to_bytes, Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Multipart, Request, State},
+    http::{header, request::Parts, Method},
+    middleware::Next,
+    response::Response,
+    Form,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::{error::AppError, utils::media::MAX_UPLOAD_FORM_BYTES};
+
+/// Name of the hidden form field carrying the token
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// Header scripts can send the token in instead of a form field
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Cookie holding the token for the login form, before there is a session
+pub const LOGIN_CSRF_COOKIE: &str = "login_csrf";
+
+/// The CSRF token of the current session, or of the login form before signing in.
+///
+/// Put it in every form as a hidden `csrf_token` field.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CsrfToken {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CsrfToken>()
+            .cloned()
+            .ok_or(AppError::Unauthorized)
+    }
+}
+
+#[derive(Deserialize)]
+struct CsrfForm {
+    #[serde(default)]
+    csrf_token: String,
+}
+
+/// Middleware that rejects state-changing requests without the right CSRF token.
+///
+/// Safe requests pass through with the expected token available as a `CsrfToken`
+/// extractor, so handlers can render it into their forms.
+pub async fn csrf_middleware(
+    State(pool): State<Pool<Sqlite>>,
+    jar: CookieJar,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let expected = expected_token(&pool, &jar).await?;
+
+    let mut request = if is_safe(request.method()) {
+        request
+    } else {
+        let (parts, body) = request.into_parts();
+        let bytes = to_bytes(body, MAX_UPLOAD_FORM_BYTES).await.map_err(|_| {
+            AppError::BadRequest("حجم درخواست بیش از حد مجاز است".to_string())
+        })?;
+
+        let submitted = submitted_token(&parts, &bytes).await;
+        let valid = match (&expected, &submitted) {
+            (Some(expected), Some(submitted)) => tokens_match(expected, submitted),
+            _ => false,
+        };
+        if !valid {
+            eprintln!("⚠️  Rejected {} {} without a valid CSRF token", parts.method, parts.uri);
+            return Err(AppError::InvalidCsrfToken);
+        }
+
+        Request::from_parts(parts, Body::from(bytes))
+    };
+
+    if let Some(token) = expected {
+        request.extensions_mut().insert(CsrfToken(token));
+    }
+    Ok(next.run(request).await)
+}
+
+/// The token for the login form, reusing the one already in its cookie
+pub fn login_csrf_token(jar: CookieJar) -> (CookieJar, String) {
+    if let Some(cookie) = jar.get(LOGIN_CSRF_COOKIE) {
+        let token = cookie.value().to_string();
+        return (jar, token);
+    }
+
+    let token = crate::models::Session::generate_csrf_token();
+    let cookie = Cookie::build((LOGIN_CSRF_COOKIE, token.clone()))
+        .path("/login")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .build();
+    (jar.add(cookie), token)
+}
+
+/// The session's token when signed in, otherwise the login form's
+async fn expected_token(pool: &Pool<Sqlite>, jar: &CookieJar) -> Result<Option<String>, AppError> {
+    if let Some(session_cookie) = jar.get("session_id") {
+        let token = sqlx::query_scalar::<_, String>(
+            "SELECT csrf_token FROM sessions WHERE id = ? AND csrf_token != ''",
+        )
+        .bind(session_cookie.value())
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Database error in CSRF middleware: {:?}", e);
+            AppError::Internal("خطا در بررسی احراز هویت".to_string())
+        })?;
+        if token.is_some() {
+            return Ok(token);
+        }
+    }
+
+    Ok(jar
+        .get(LOGIN_CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty()))
+}
+
+/// Whether a method only reads, so needs no CSRF token
+pub fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Read the token from the header, or from an urlencoded or multipart form body
+async fn submitted_token(parts: &Parts, bytes: &Bytes) -> Option<String> {
+    if let Some(token) = parts.headers.get(CSRF_HEADER) {
+        return token.to_str().ok().map(str::to_string);
+    }
+
+    let content_type = parts.headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let form_request = || {
+        Request::builder()
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(bytes.clone()))
+            .ok()
+    };
+
+    if content_type.starts_with("application/x-www-form-urlencoded") {
+        let Form(form) = Form::<CsrfForm>::from_request(form_request()?, &()).await.ok()?;
+        return Some(form.csrf_token);
+    }
+
+    if content_type.starts_with("multipart/form-data") {
+        let mut multipart = Multipart::from_request(form_request()?, &()).await.ok()?;
+        while let Some(field) = multipart.next_field().await.ok()? {
+            if field.name() == Some(CSRF_FIELD) {
+                return field.text().await.ok();
+            }
+        }
+    }
+
+    None
+}
+
+/// Compare tokens in constant time, so the comparison does not leak how much matched
+pub fn tokens_match(expected: &str, submitted: &str) -> bool {
+    expected.len() == submitted.len()
+        && expected
+            .bytes()
+            .zip(submitted.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc123", "abc124"));
+        assert!(!tokens_match("abc123", "abc12"));
+        assert!(!tokens_match("abc123", ""));
+    }
+}
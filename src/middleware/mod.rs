@@ -1,3 +1,9 @@
+pub mod api;
 pub mod auth;
+pub mod csrf;
+pub mod permissions;
 
+pub use api::{api_auth_middleware, api_permission_middleware};
 pub use auth::auth_middleware;
+pub use csrf::csrf_middleware;
+pub use permissions::permission_middleware;
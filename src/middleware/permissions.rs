@@ -0,0 +1,82 @@
+use axum::{extract::State, middleware::Next, response::Response};
+use axum_extra::extract::CookieJar;
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    error::AppError,
+    middleware::auth::get_current_user,
+    models::{Permission, Role, User},
+};
+
+/// Middleware that lets a request through only if the signed-in user has a permission.
+///
+/// Layer it on a group of routes with the permission as part of its state:
+/// `from_fn_with_state((pool, Permission::Export), permission_middleware)`.
+/// Handlers behind it take the user it checked as `Extension<User>`.
+pub async fn permission_middleware(
+    State((pool, permission)): State<(Pool<Sqlite>, Permission)>,
+    jar: CookieJar,
+    mut request: axum::extract::Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let user = require_permission(&pool, &jar, permission).await?;
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// The signed-in user, if they have a permission
+pub async fn require_permission(
+    pool: &Pool<Sqlite>,
+    jar: &CookieJar,
+    permission: Permission,
+) -> Result<User, AppError> {
+    let user = get_current_user(pool, jar)
+        .await
+        .ok_or(AppError::Unauthorized)?;
+
+    check_permission(&user, permission)?;
+    Ok(user)
+}
+
+/// Refuse a user without a permission
+pub fn check_permission(user: &User, permission: Permission) -> Result<(), AppError> {
+    if !user.has_permission(permission) {
+        eprintln!(
+            "🚫 {} denied {}: missing permission",
+            user.username,
+            permission.as_str()
+        );
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+/// Fill in the display name and permissions of a user's role.
+///
+/// The admin role always has every permission, whatever is stored for it.
+pub async fn load_access(pool: &Pool<Sqlite>, user: &mut User) -> Result<(), sqlx::Error> {
+    user.role_display_name = sqlx::query_scalar("SELECT display_name FROM roles WHERE name = ?")
+        .bind(&user.role)
+        .fetch_optional(pool)
+        .await?
+        .unwrap_or_else(|| user.role.clone());
+
+    user.permissions = if user.role == Role::ADMIN {
+        Permission::ALL.to_vec()
+    } else {
+        sqlx::query_scalar::<_, String>(
+            "SELECT role_permissions.permission
+             FROM role_permissions
+             JOIN roles ON roles.id = role_permissions.role_id
+             WHERE roles.name = ?",
+        )
+        .bind(&user.role)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .filter_map(|permission| Permission::parse(permission))
+        .collect()
+    };
+
+    Ok(())
+}
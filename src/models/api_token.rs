@@ -0,0 +1,276 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+
+use crate::{models::role::Permission, utils::date::format_timestamp};
+
+/// Start of every API token, so a leaked token is easy to recognise
+pub const TOKEN_PREFIX: &str = "rlk_";
+
+/// Characters of a token kept in the clear, to tell a user's tokens apart
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// Lifetimes offered when creating a token, in days
+pub const TOKEN_LIFETIMES: [i64; 4] = [7, 30, 90, 365];
+
+/// A personal access token for scripts and integrations.
+///
+/// Only a hash of the token is stored; the token itself is shown once, when created.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    /// Space-separated names of the permissions the token may use
+    pub scopes: String,
+    pub created_at: String,
+    pub expires_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+    /// Username of the token's owner
+    pub username: String,
+}
+
+impl ApiToken {
+    pub fn scope_list(&self) -> Vec<Permission> {
+        self.scopes
+            .split_whitespace()
+            .filter_map(Permission::parse)
+            .collect()
+    }
+
+    /// The permissions a request with this token has: its scopes that the owner's role
+    /// still grants, so taking a permission from a role also takes it from their tokens
+    pub fn granted(&self, role_permissions: &[Permission]) -> Vec<Permission> {
+        self.scope_list()
+            .into_iter()
+            .filter(|permission| role_permissions.contains(permission))
+            .collect()
+    }
+
+    pub fn expires_at_time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.expires_at)
+            .ok()
+            .map(|expires_at| expires_at.with_timezone(&Utc))
+    }
+
+    pub fn last_used_at_time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(self.last_used_at.as_deref()?)
+            .ok()
+            .map(|last_used_at| last_used_at.with_timezone(&Utc))
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at_time()
+            .map_or(true, |expires_at| expires_at <= now)
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Whether the token can still be used, and so revoked
+    pub fn is_usable(&self) -> bool {
+        !self.is_revoked() && !self.is_expired(Utc::now())
+    }
+
+    pub fn status_display_name(&self) -> &'static str {
+        if self.is_revoked() {
+            "لغو شده"
+        } else if self.is_expired(Utc::now()) {
+            "منقضی"
+        } else {
+            "فعال"
+        }
+    }
+
+    pub fn status_badge_class(&self) -> &'static str {
+        if self.is_usable() {
+            "badge-success"
+        } else {
+            "badge-error"
+        }
+    }
+
+    pub fn scope_display_names(&self) -> Vec<&'static str> {
+        self.scope_list()
+            .iter()
+            .map(|permission| permission.display_name())
+            .collect()
+    }
+
+    pub fn formatted_created_at(&self) -> String {
+        format_timestamp(&self.created_at)
+    }
+
+    pub fn formatted_expires_at(&self) -> String {
+        format_timestamp(&self.expires_at)
+    }
+
+    pub fn formatted_last_used_at(&self) -> String {
+        self.last_used_at
+            .as_deref()
+            .map(format_timestamp)
+            .unwrap_or_else(|| "هرگز".to_string())
+    }
+}
+
+/// Form data for creating a token.
+///
+/// Chosen scopes arrive as repeated `scopes` fields, so the form is read as a list of pairs.
+#[derive(Debug, Default)]
+pub struct ApiTokenForm {
+    pub name: String,
+    pub lifetime_days: i64,
+    pub scopes: Vec<Permission>,
+}
+
+impl ApiTokenForm {
+    pub fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let mut form = ApiTokenForm::default();
+        for (key, value) in fields {
+            match key.as_str() {
+                "name" => form.name = value.trim().to_string(),
+                "lifetime_days" => form.lifetime_days = value.trim().parse().unwrap_or(0),
+                "scopes" => {
+                    if let Some(permission) = Permission::parse(&value) {
+                        if !form.scopes.contains(&permission) {
+                            form.scopes.push(permission);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        form
+    }
+
+    /// Scopes as stored in `api_tokens.scopes`
+    pub fn scopes_value(&self) -> String {
+        self.scopes
+            .iter()
+            .map(|permission| permission.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Create a new random token
+pub fn generate_token() -> String {
+    format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// The start of a token that is stored in the clear
+pub fn display_prefix(token: &str) -> String {
+    token.chars().take(DISPLAY_PREFIX_LEN).collect()
+}
+
+/// Hash of a token as stored and looked up.
+///
+/// Tokens are long and random, so a fast hash is enough; unlike passwords they
+/// cannot be guessed from a dictionary.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// The token from an `Authorization: Bearer <token>` header value
+pub fn parse_bearer(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(scopes: &str) -> ApiToken {
+        ApiToken {
+            id: 1,
+            user_id: 1,
+            name: "sync".to_string(),
+            token_prefix: "rlk_12345678".to_string(),
+            token_hash: String::new(),
+            scopes: scopes.to_string(),
+            created_at: "2024-03-01T10:00:00+00:00".to_string(),
+            expires_at: "2024-04-01T10:00:00+00:00".to_string(),
+            last_used_at: None,
+            revoked_at: None,
+            username: "ali".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_generate_and_hash_token() {
+        let first = generate_token();
+        let second = generate_token();
+        assert!(first.starts_with(TOKEN_PREFIX));
+        assert_eq!(first.len(), TOKEN_PREFIX.len() + 64);
+        assert_ne!(first, second);
+
+        assert_eq!(hash_token(&first), hash_token(&first));
+        assert_ne!(hash_token(&first), hash_token(&second));
+        assert_eq!(hash_token(&first).len(), 64);
+        assert_eq!(display_prefix(&first).len(), DISPLAY_PREFIX_LEN);
+    }
+
+    #[test]
+    fn test_parse_bearer() {
+        assert_eq!(parse_bearer("Bearer rlk_abc"), Some("rlk_abc"));
+        assert_eq!(parse_bearer("bearer  rlk_abc "), Some("rlk_abc"));
+        assert_eq!(parse_bearer("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(parse_bearer("Bearer "), None);
+        assert_eq!(parse_bearer("rlk_abc"), None);
+    }
+
+    #[test]
+    fn test_granted_is_limited_to_role() {
+        let token = token("customers.edit export bogus");
+        assert_eq!(
+            token.scope_list(),
+            vec![Permission::CustomersEdit, Permission::Export]
+        );
+        assert_eq!(
+            token.granted(&[Permission::CustomersEdit, Permission::UsersManage]),
+            vec![Permission::CustomersEdit]
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let token = token("");
+        let before = DateTime::parse_from_rfc3339("2024-03-15T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let after = DateTime::parse_from_rfc3339("2024-04-02T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(!token.is_expired(before));
+        assert!(token.is_expired(after));
+    }
+
+    #[test]
+    fn test_api_token_form_from_fields() {
+        let form = ApiTokenForm::from_fields(vec![
+            ("csrf_token".to_string(), "x".to_string()),
+            ("name".to_string(), " همگام‌سازی ".to_string()),
+            ("lifetime_days".to_string(), "30".to_string()),
+            ("scopes".to_string(), "transactions.edit".to_string()),
+            ("scopes".to_string(), "export".to_string()),
+            ("scopes".to_string(), "export".to_string()),
+            ("scopes".to_string(), "bogus".to_string()),
+        ]);
+        assert_eq!(form.name, "همگام‌سازی");
+        assert_eq!(form.lifetime_days, 30);
+        assert_eq!(form.scopes_value(), "transactions.edit export");
+    }
+}
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::FromRow;
+
+use crate::utils::{date::format_timestamp, localization::persian_to_english_numbers};
+
+/// Entries shown on one page of the audit log
+pub const AUDIT_PAGE_SIZE: i64 = 50;
+
+/// Placeholder recorded instead of secret values such as password hashes
+pub const REDACTED: &str = "***";
+
+/// What kind of record an audit entry is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AuditEntity {
+    Customer,
+    Transaction,
+    Product,
+    Variant,
+    User,
+    Cheque,
+}
+
+impl AuditEntity {
+    pub const ALL: [AuditEntity; 6] = [
+        AuditEntity::Customer,
+        AuditEntity::Transaction,
+        AuditEntity::Product,
+        AuditEntity::Variant,
+        AuditEntity::User,
+        AuditEntity::Cheque,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|entity| entity.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEntity::Customer => "customer",
+            AuditEntity::Transaction => "transaction",
+            AuditEntity::Product => "product",
+            AuditEntity::Variant => "variant",
+            AuditEntity::User => "user",
+            AuditEntity::Cheque => "cheque",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            AuditEntity::Customer => "مشتری",
+            AuditEntity::Transaction => "تراکنش",
+            AuditEntity::Product => "محصول",
+            AuditEntity::Variant => "نوع محصول",
+            AuditEntity::User => "کاربر",
+            AuditEntity::Cheque => "چک",
+        }
+    }
+}
+
+/// What happened to the record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 3] = [AuditAction::Create, AuditAction::Update, AuditAction::Delete];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "ایجاد",
+            AuditAction::Update => "ویرایش",
+            AuditAction::Delete => "حذف",
+        }
+    }
+
+    pub fn badge_class(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "badge-success",
+            AuditAction::Update => "badge-info",
+            AuditAction::Delete => "badge-error",
+        }
+    }
+}
+
+/// One recorded change, with the username of whoever made it
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub entity_type: String,
+    pub entity_id: i64,
+    pub action: String,
+    /// JSON object mapping each changed field to its `old` and `new` values
+    pub changes: String,
+    pub created_at: String,
+    pub username: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn entity_enum(&self) -> Option<AuditEntity> {
+        AuditEntity::parse(&self.entity_type)
+    }
+
+    pub fn entity_name(&self) -> &str {
+        self.entity_enum()
+            .map(|entity| entity.display_name())
+            .unwrap_or(&self.entity_type)
+    }
+
+    pub fn action_enum(&self) -> AuditAction {
+        AuditAction::parse(&self.action).unwrap_or(AuditAction::Update)
+    }
+
+    /// Link to the record, while it can still be viewed
+    pub fn entity_url(&self) -> Option<String> {
+        if self.action_enum() == AuditAction::Delete {
+            return None;
+        }
+        match self.entity_enum()? {
+            AuditEntity::Customer => Some(format!("/customer/{}", self.entity_id)),
+            AuditEntity::Product => Some(format!("/catalog/product/{}", self.entity_id)),
+            AuditEntity::User => Some(format!("/users/edit/{}", self.entity_id)),
+            AuditEntity::Cheque => Some(format!("/cheques/{}", self.entity_id)),
+            AuditEntity::Transaction | AuditEntity::Variant => None,
+        }
+    }
+
+    /// Changed fields with their old and new values, as shown on the audit page
+    pub fn field_changes(&self) -> Vec<FieldChange> {
+        let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(&self.changes) else {
+            return Vec::new();
+        };
+        fields
+            .iter()
+            .map(|(field, change)| FieldChange {
+                field: field.clone(),
+                old: display_value(change.get("old")),
+                new: display_value(change.get("new")),
+            })
+            .collect()
+    }
+
+    pub fn formatted_created_at(&self) -> String {
+        format_timestamp(&self.created_at)
+    }
+}
+
+/// A field's value before and after a change
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Filters for the audit log page, kept as strings like `CustomerQuery`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub entity: String,
+    pub entity_id: String,
+    pub user_id: String,
+    pub action: String,
+    pub page: String,
+}
+
+impl AuditQuery {
+    pub fn entity_filter(&self) -> Option<AuditEntity> {
+        AuditEntity::parse(self.entity.trim())
+    }
+
+    pub fn entity_id_filter(&self) -> Option<i64> {
+        parse_number(&self.entity_id)
+    }
+
+    pub fn user_id_filter(&self) -> Option<i64> {
+        parse_number(&self.user_id)
+    }
+
+    pub fn action_filter(&self) -> Option<AuditAction> {
+        AuditAction::parse(self.action.trim())
+    }
+
+    /// Current page, starting at 1
+    pub fn page(&self) -> i64 {
+        parse_number(&self.page).unwrap_or(1).max(1)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1).saturating_mul(AUDIT_PAGE_SIZE)
+    }
+
+    /// Query string for the same filters on another page
+    pub fn page_query(&self, page: i64) -> String {
+        let mut pairs = Vec::new();
+        if let Some(entity) = self.entity_filter() {
+            pairs.push(format!("entity={}", entity.as_str()));
+        }
+        if let Some(entity_id) = self.entity_id_filter() {
+            pairs.push(format!("entity_id={}", entity_id));
+        }
+        if let Some(user_id) = self.user_id_filter() {
+            pairs.push(format!("user_id={}", user_id));
+        }
+        if let Some(action) = self.action_filter() {
+            pairs.push(format!("action={}", action.as_str()));
+        }
+        pairs.push(format!("page={}", page));
+        pairs.join("&")
+    }
+}
+
+/// The fields that differ between two snapshots of a record, as `{field: {old, new}}`.
+///
+/// A created record has no old snapshot and a deleted one no new snapshot, so every
+/// field is listed. Values of `secret_fields` are replaced with [`REDACTED`].
+pub fn diff(old: Option<&Value>, new: Option<&Value>, secret_fields: &[&str]) -> Map<String, Value> {
+    let empty = Map::new();
+    let old_fields = old.and_then(Value::as_object).unwrap_or(&empty);
+    let new_fields = new.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut names: Vec<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let mut changes = Map::new();
+    for name in names {
+        let old_value = old_fields.get(name).unwrap_or(&Value::Null);
+        let new_value = new_fields.get(name).unwrap_or(&Value::Null);
+        if old.is_some() && new.is_some() && old_value == new_value {
+            continue;
+        }
+
+        let redact = |value: &Value| {
+            if secret_fields.contains(&name.as_str()) && !value.is_null() {
+                json!(REDACTED)
+            } else {
+                value.clone()
+            }
+        };
+        changes.insert(
+            name.clone(),
+            json!({ "old": redact(old_value), "new": redact(new_value) }),
+        );
+    }
+    changes
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "—".to_string(),
+        Some(Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn parse_number(value: &str) -> Option<i64> {
+    persian_to_english_numbers(value.trim()).parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = json!({ "id": 1, "phone": "09120000000", "name": "Ali", "password_hash": "a" });
+        let new = json!({ "id": 1, "phone": "09121111111", "name": "Ali", "password_hash": "b" });
+
+        let changes = diff(Some(&old), Some(&new), &["password_hash"]);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes["phone"], json!({ "old": "09120000000", "new": "09121111111" }));
+        assert_eq!(changes["password_hash"], json!({ "old": REDACTED, "new": REDACTED }));
+
+        let created = diff(None, Some(&new), &[]);
+        assert_eq!(created.len(), 4);
+        assert_eq!(created["name"], json!({ "old": null, "new": "Ali" }));
+
+        let deleted = diff(Some(&old), None, &[]);
+        assert_eq!(deleted["id"], json!({ "old": 1, "new": null }));
+    }
+}
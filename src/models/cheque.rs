@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{models::money::Money, utils::date::iso_to_shamsi};
+
+/// A cheque received from or given to a customer, attached to a `Cheque` transaction
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Cheque {
+    pub id: i64,
+    pub transaction_id: i64,
+    pub cheque_number: String,
+    pub bank_name: String,
+    pub due_date: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+impl Cheque {
+    pub fn status_enum(&self) -> ChequeStatus {
+        ChequeStatus::parse(&self.status).unwrap_or(ChequeStatus::Received)
+    }
+
+    pub fn formatted_due_date(&self) -> String {
+        iso_to_shamsi(&self.due_date)
+    }
+}
+
+/// A cheque with the transaction and customer it belongs to, for the cheque pages
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ChequeDetail {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub cheque: Cheque,
+    pub customer_id: i64,
+    pub customer_name: String,
+    pub amount: Money,
+    pub direction: String,
+}
+
+impl ChequeDetail {
+    pub fn formatted_amount(&self) -> String {
+        self.amount.format()
+    }
+
+    pub fn is_received(&self) -> bool {
+        self.direction == "credit"
+    }
+}
+
+/// One entry in a cheque's status history
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ChequeStatusChange {
+    pub id: i64,
+    pub cheque_id: i64,
+    pub status: String,
+    pub note: String,
+    pub changed_at: String,
+    pub changed_by: Option<String>,
+}
+
+impl ChequeStatusChange {
+    pub fn status_enum(&self) -> ChequeStatus {
+        ChequeStatus::parse(&self.status).unwrap_or(ChequeStatus::Received)
+    }
+}
+
+/// Form data for changing a cheque's status
+#[derive(Debug, Deserialize)]
+pub struct ChequeStatusForm {
+    pub status: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Query string for the cheque list
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ChequeListQuery {
+    /// `all` lists every cheque; anything else lists open cheques due by the end of this week
+    pub show: String,
+}
+
+impl ChequeListQuery {
+    pub fn show_all(&self) -> bool {
+        self.show == "all"
+    }
+}
+
+/// Where a cheque is in its lifecycle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ChequeStatus {
+    Received,
+    Deposited,
+    Cleared,
+    Bounced,
+    Returned,
+}
+
+impl ChequeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChequeStatus::Received => "received",
+            ChequeStatus::Deposited => "deposited",
+            ChequeStatus::Cleared => "cleared",
+            ChequeStatus::Bounced => "bounced",
+            ChequeStatus::Returned => "returned",
+        }
+    }
+
+    /// Parse a stored or submitted status, rejecting unknown values
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "received" => Some(ChequeStatus::Received),
+            "deposited" => Some(ChequeStatus::Deposited),
+            "cleared" => Some(ChequeStatus::Cleared),
+            "bounced" => Some(ChequeStatus::Bounced),
+            "returned" => Some(ChequeStatus::Returned),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ChequeStatus::Received => "دریافت شده",
+            ChequeStatus::Deposited => "واگذار شده به بانک",
+            ChequeStatus::Cleared => "پاس شده",
+            ChequeStatus::Bounced => "برگشتی",
+            ChequeStatus::Returned => "عودت داده شده",
+        }
+    }
+
+    pub fn badge_class(&self) -> &'static str {
+        match self {
+            ChequeStatus::Received | ChequeStatus::Deposited => "badge-info",
+            ChequeStatus::Cleared => "badge-success",
+            ChequeStatus::Bounced | ChequeStatus::Returned => "badge-error",
+        }
+    }
+
+    /// Whether the cheque's amount still counts toward the customer's balance.
+    ///
+    /// Bounced and returned cheques were never paid, so they are left out.
+    pub fn counts_toward_balance(&self) -> bool {
+        !matches!(self, ChequeStatus::Bounced | ChequeStatus::Returned)
+    }
+
+    /// Statuses a cheque can move to from this one
+    pub fn next_statuses(&self) -> Vec<ChequeStatus> {
+        match self {
+            ChequeStatus::Received => vec![ChequeStatus::Deposited, ChequeStatus::Returned],
+            ChequeStatus::Deposited => vec![ChequeStatus::Cleared, ChequeStatus::Bounced],
+            ChequeStatus::Bounced => vec![ChequeStatus::Deposited, ChequeStatus::Returned],
+            ChequeStatus::Cleared | ChequeStatus::Returned => vec![],
+        }
+    }
+
+    pub fn can_change_to(&self, next: ChequeStatus) -> bool {
+        self.next_statuses().contains(&next)
+    }
+
+    /// Statuses of cheques that are still waiting to be cashed
+    pub fn open() -> Vec<ChequeStatus> {
+        vec![ChequeStatus::Received, ChequeStatus::Deposited]
+    }
+
+    pub fn is_open(&self) -> bool {
+        Self::open().contains(self)
+    }
+}
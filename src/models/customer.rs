@@ -1,8 +1,18 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    models::{
+        money::Money,
+        tag::{split_tag_names, TagNames},
+        transactions::BalanceStatus,
+    },
+    utils::localization::persian_to_english_numbers,
+};
 
 /// Customer entity representing a CRM customer
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Customer {
     pub id: i64,
     pub full_name: String,
@@ -11,6 +21,7 @@ pub struct Customer {
     pub phone_number: String,
     pub sales_count: i64,
     pub job_title: String,
+    /// One of `Hidaj`, `Khorramdarreh`, `Abhar`, `Zanjan` and `Qazvin`, or empty
     pub city: String,
     pub address: String,
     pub notes: String,
@@ -18,7 +29,7 @@ pub struct Customer {
 }
 
 /// Form data for creating/updating a customer
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct CustomerForm {
     pub full_name: String,
     pub company: String,
@@ -30,6 +41,9 @@ pub struct CustomerForm {
     pub address: String,
     pub notes: String,
     pub coordinates: String,
+    /// Save even though the customer looks like an existing one
+    #[serde(default)]
+    pub confirm_duplicate: bool,
 }
 
 impl Customer {
@@ -45,6 +59,266 @@ impl Customer {
     
 }
 
+/// A customer on the list, with their current balance and tags
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct CustomerSummary {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub customer: Customer,
+    pub balance: Money,
+    /// Names of the customer's tags, sorted
+    #[schema(value_type = Vec<String>)]
+    pub tags: TagNames,
+}
+
+impl CustomerSummary {
+    pub fn balance_status(&self) -> BalanceStatus {
+        BalanceStatus::of(self.balance)
+    }
+
+    pub fn formatted_balance(&self) -> String {
+        self.balance.abs().format()
+    }
+}
+
+/// Default number of customers shown per page
+pub const DEFAULT_PAGE_SIZE: i64 = 25;
+
+/// Page sizes offered on the customer list
+pub const PAGE_SIZES: [i64; 4] = [10, 25, 50, 100];
+
+/// Query string for searching, filtering, sorting and paging the customer list.
+///
+/// Every field is kept as a raw string so that empty inputs from the filter
+/// form and Persian digits do not make the whole request fail to parse.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct CustomerQuery {
+    /// Text to find in the name, company, phone number or email
+    pub q: String,
+    pub city: String,
+    pub job_title: String,
+    pub min_sales: String,
+    pub max_sales: String,
+    /// Comma-separated tag names the customers must have
+    pub tags: String,
+    /// `any` for customers with any of the tags; all of them otherwise
+    pub tag_mode: String,
+    /// Lowest balance in tomans; a negative balance means the customer is in credit
+    pub min_balance: String,
+    /// Highest balance in tomans
+    pub max_balance: String,
+    /// `name`, `company`, `sales`, `city`, `job_title` or `balance`; newest first otherwise
+    pub sort: String,
+    /// `asc` or `desc`
+    pub dir: String,
+    pub page: String,
+    /// 10, 25, 50 or 100
+    pub per_page: String,
+}
+
+impl CustomerQuery {
+    /// Free-text search term with Persian digits converted to English
+    pub fn search_term(&self) -> Option<String> {
+        let term = persian_to_english_numbers(self.q.trim());
+        (!term.is_empty()).then_some(term)
+    }
+
+    /// Digits of the search term, used to match phone numbers typed with spaces
+    pub fn search_digits(&self) -> Option<String> {
+        let digits: String = self
+            .search_term()?
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .collect();
+        (!digits.is_empty()).then_some(digits)
+    }
+
+    pub fn city_filter(&self) -> Option<&str> {
+        let city = self.city.trim();
+        (!city.is_empty()).then_some(city)
+    }
+
+    pub fn job_title_filter(&self) -> Option<&str> {
+        let job_title = self.job_title.trim();
+        (!job_title.is_empty()).then_some(job_title)
+    }
+
+    pub fn min_sales_value(&self) -> Option<i64> {
+        parse_number(&self.min_sales)
+    }
+
+    pub fn max_sales_value(&self) -> Option<i64> {
+        parse_number(&self.max_sales)
+    }
+
+    pub fn tag_filters(&self) -> Vec<String> {
+        split_tag_names(&self.tags)
+    }
+
+    /// Whether customers with any one of the tags match, rather than only those with all
+    pub fn matches_any_tag(&self) -> bool {
+        self.tag_mode == "any"
+    }
+
+    pub fn min_balance_value(&self) -> Option<Money> {
+        Money::parse(&self.min_balance)
+    }
+
+    pub fn max_balance_value(&self) -> Option<Money> {
+        Money::parse(&self.max_balance)
+    }
+
+    /// Whether any search or filter is applied
+    pub fn is_filtered(&self) -> bool {
+        self.search_term().is_some()
+            || self.city_filter().is_some()
+            || self.job_title_filter().is_some()
+            || self.min_sales_value().is_some()
+            || self.max_sales_value().is_some()
+            || !self.tag_filters().is_empty()
+            || self.min_balance_value().is_some()
+            || self.max_balance_value().is_some()
+    }
+
+    /// Sort key after checking it against the sortable columns
+    pub fn sort_key(&self) -> &'static str {
+        match self.sort.as_str() {
+            "name" => "name",
+            "company" => "company",
+            "sales" => "sales",
+            "city" => "city",
+            "job_title" => "job_title",
+            "balance" => "balance",
+            _ => "id",
+        }
+    }
+
+    /// SQL column for the current sort key
+    pub fn sort_column(&self) -> &'static str {
+        match self.sort_key() {
+            "name" => "full_name",
+            "company" => "company",
+            "sales" => "sales_count",
+            "city" => "city",
+            "job_title" => "job_title",
+            "balance" => "balance",
+            _ => "id",
+        }
+    }
+
+    pub fn is_ascending(&self) -> bool {
+        match self.dir.as_str() {
+            "asc" => true,
+            "desc" => false,
+            // Newest customers first unless another column is chosen
+            _ => self.sort_key() != "id",
+        }
+    }
+
+    pub fn sort_direction(&self) -> &'static str {
+        if self.is_ascending() {
+            "ASC"
+        } else {
+            "DESC"
+        }
+    }
+
+    /// Current page, starting at 1
+    pub fn page(&self) -> i64 {
+        parse_number(&self.page).unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        parse_number(&self.per_page)
+            .filter(|size| PAGE_SIZES.contains(size))
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Rows to skip for the current page; a page far past the end just comes back empty
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1).saturating_mul(self.per_page())
+    }
+
+    /// Query string for the same search on another page
+    pub fn page_query(&self, page: i64) -> String {
+        self.to_query_string(self.sort_key(), self.is_ascending(), page)
+    }
+
+    /// Query string for sorting by `key`, toggling the direction if already sorted by it
+    pub fn sort_query(&self, key: &str) -> String {
+        let ascending = if self.sort_key() == key {
+            !self.is_ascending()
+        } else {
+            true
+        };
+        self.to_query_string(key, ascending, 1)
+    }
+
+    /// Arrow shown next to the column the list is sorted by
+    pub fn sort_indicator(&self, key: &str) -> &'static str {
+        if self.sort_key() != key {
+            ""
+        } else if self.is_ascending() {
+            "▲"
+        } else {
+            "▼"
+        }
+    }
+
+    /// Query string carrying only the filters, used by the export link
+    pub fn filter_query(&self) -> String {
+        self.pairs().join("&")
+    }
+
+    fn to_query_string(&self, sort: &str, ascending: bool, page: i64) -> String {
+        let mut pairs = self.pairs();
+        pairs.push(format!("sort={}", sort));
+        pairs.push(format!("dir={}", if ascending { "asc" } else { "desc" }));
+        pairs.push(format!("per_page={}", self.per_page()));
+        pairs.push(format!("page={}", page));
+        pairs.join("&")
+    }
+
+    fn pairs(&self) -> Vec<String> {
+        [
+            ("q", self.q.trim()),
+            ("city", self.city.trim()),
+            ("job_title", self.job_title.trim()),
+            ("min_sales", self.min_sales.trim()),
+            ("max_sales", self.max_sales.trim()),
+            ("tags", self.tags.trim()),
+            ("tag_mode", if self.matches_any_tag() { "any" } else { "" }),
+            ("min_balance", self.min_balance.trim()),
+            ("max_balance", self.max_balance.trim()),
+        ]
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("{}={}", key, encode_query_value(value)))
+        .collect()
+    }
+}
+
+fn parse_number(value: &str) -> Option<i64> {
+    persian_to_english_numbers(value.trim()).parse().ok()
+}
+
+/// Percent-encode a query string value
+fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub enum City {
     Hidaj,
@@ -99,3 +373,102 @@ impl City {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_customer_query_defaults() {
+        let query = CustomerQuery::default();
+        assert_eq!(query.page(), 1);
+        assert_eq!(query.per_page(), DEFAULT_PAGE_SIZE);
+        assert_eq!(query.sort_column(), "id");
+        assert_eq!(query.sort_direction(), "DESC");
+        assert!(!query.is_filtered());
+    }
+
+    #[test]
+    fn test_customer_query_persian_digits() {
+        let query = CustomerQuery {
+            q: " ۰۹۱۲ ۳۴۵ ".to_string(),
+            min_sales: "۵".to_string(),
+            page: "۳".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(query.search_term().as_deref(), Some("0912 345"));
+        assert_eq!(query.search_digits().as_deref(), Some("0912345"));
+        assert_eq!(query.min_sales_value(), Some(5));
+        assert_eq!(query.max_sales_value(), None);
+        assert_eq!(query.offset(), 2 * DEFAULT_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_customer_query_rejects_unknown_sort_and_page_size() {
+        let query = CustomerQuery {
+            sort: "id; DROP TABLE customers".to_string(),
+            per_page: "1000".to_string(),
+            page: "-4".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(query.sort_column(), "id");
+        assert_eq!(query.per_page(), DEFAULT_PAGE_SIZE);
+        assert_eq!(query.page(), 1);
+    }
+
+    #[test]
+    fn test_customer_query_huge_page_does_not_overflow() {
+        let query = CustomerQuery {
+            page: i64::MAX.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(query.page(), i64::MAX);
+        assert_eq!(query.offset(), i64::MAX);
+    }
+
+    #[test]
+    fn test_customer_query_strings() {
+        let query = CustomerQuery {
+            q: "علی & co".to_string(),
+            city: "Zanjan".to_string(),
+            sort: "name".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter_query(),
+            "q=%D8%B9%D9%84%DB%8C+%26+co&city=Zanjan"
+        );
+        assert!(query.sort_query("name").contains("dir=desc"));
+        assert!(query.sort_query("sales").contains("sort=sales&dir=asc"));
+        assert!(query.page_query(2).ends_with("page=2"));
+    }
+
+    #[test]
+    fn test_customer_query_tags_and_balance() {
+        let query = CustomerQuery {
+            tags: "vip، late payer".to_string(),
+            tag_mode: "any".to_string(),
+            min_balance: "۱۲٬۵۰۰".to_string(),
+            max_balance: "many".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(query.tag_filters(), vec!["vip", "late payer"]);
+        assert!(query.matches_any_tag());
+        assert_eq!(query.min_balance_value(), Money::parse("12500"));
+        assert_eq!(query.max_balance_value(), None);
+        assert!(query.is_filtered());
+        assert_eq!(
+            query.filter_query(),
+            "tags=vip%D8%8C+late+payer&tag_mode=any&min_balance=%DB%B1%DB%B2%D9%AC%DB%B5%DB%B0%DB%B0&max_balance=many"
+        );
+
+        // All of the tags is the default, so it is left out of links
+        let query = CustomerQuery {
+            tags: "vip".to_string(),
+            tag_mode: "all".to_string(),
+            ..Default::default()
+        };
+        assert!(!query.matches_any_tag());
+        assert_eq!(query.filter_query(), "tags=vip");
+    }
+}
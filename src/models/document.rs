@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{
+    models::money::Money,
+    utils::{date::iso_to_shamsi_long, localization::to_persian_digits},
+};
+
+/// What a printed document was issued for; each source gets one document number
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub enum DocumentKind {
+    Invoice,
+    Receipt,
+}
+
+impl DocumentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentKind::Invoice => "invoice",
+            DocumentKind::Receipt => "receipt",
+        }
+    }
+}
+
+/// The business details printed at the top of every document
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompanyHeader {
+    pub name: String,
+    pub address: String,
+    pub phone: String,
+    /// A closing line such as a thank-you note or bank account details
+    pub footer: String,
+}
+
+/// How printed documents look, from the configuration
+#[derive(Debug, Clone)]
+pub struct DocumentSettings {
+    pub company: CompanyHeader,
+    /// TrueType font the PDFs are set in; it needs Persian glyphs
+    pub font_path: PathBuf,
+}
+
+/// One row of a printed document, already formatted for display
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentLine {
+    pub description: String,
+    pub quantity: String,
+    pub unit_price: String,
+    pub discount: String,
+    pub total: String,
+}
+
+/// An invoice or receipt laid out for printing.
+///
+/// Both the HTML print view and the PDF are rendered from this, so they always agree.
+#[derive(Debug, Clone, Serialize)]
+pub struct PrintDocument {
+    pub kind: DocumentKind,
+    /// Sequential number shared by all printed documents
+    pub number: i64,
+    pub title: String,
+    /// ISO date the document is dated
+    pub date: String,
+    pub company: CompanyHeader,
+    pub customer_name: String,
+    pub customer_phone: String,
+    pub customer_address: String,
+    /// Extra label and value pairs, e.g. the cheque number on a receipt
+    pub details: Vec<(String, String)>,
+    pub lines: Vec<DocumentLine>,
+    pub total: Money,
+    pub notes: String,
+    /// Shown prominently when the document is no longer valid, e.g. a cancelled invoice
+    pub stamp: Option<String>,
+}
+
+impl PrintDocument {
+    pub fn formatted_number(&self) -> String {
+        to_persian_digits(&self.number.to_string())
+    }
+
+    pub fn formatted_date(&self) -> String {
+        iso_to_shamsi_long(&self.date)
+    }
+
+    pub fn formatted_total(&self) -> String {
+        self.total.format()
+    }
+
+    pub fn total_in_words(&self) -> String {
+        self.total.in_words()
+    }
+
+    /// Whether the rows have quantities and prices, as invoice items do
+    pub fn has_item_columns(&self) -> bool {
+        self.kind == DocumentKind::Invoice
+    }
+
+    /// File name offered when downloading the PDF
+    pub fn file_name(&self) -> String {
+        format!("{}-{}.pdf", self.kind.as_str(), self.number)
+    }
+}
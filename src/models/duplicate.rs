@@ -0,0 +1,512 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Deserialize;
+
+use crate::{models::Customer, utils::localization::fold_persian_text};
+
+/// Words dropped from names before comparing them
+const HONORIFICS: &[&str] = &["اقا", "اقای", "خانم", "جناب", "دکتر", "مهندس", "حاج", "حاجی"];
+
+/// Letters at the start of a name, spaces left out, that customers are also grouped by
+const NAME_START_CHARS: usize = 3;
+
+/// Why two customers look like the same person
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DuplicateReason {
+    Phone,
+    Email,
+    Name,
+}
+
+impl DuplicateReason {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            DuplicateReason::Phone => "شماره تلفن یکسان",
+            DuplicateReason::Email => "ایمیل یکسان",
+            DuplicateReason::Name => "نام مشابه",
+        }
+    }
+
+    /// The customer field that matched
+    pub fn field(&self) -> &'static str {
+        match self {
+            DuplicateReason::Phone => "phone_number",
+            DuplicateReason::Email => "email",
+            DuplicateReason::Name => "full_name",
+        }
+    }
+}
+
+/// An existing customer that a new or existing record may duplicate
+#[derive(Debug, Clone)]
+pub struct DuplicateMatch {
+    pub customer: Customer,
+    pub reasons: Vec<DuplicateReason>,
+}
+
+/// Two existing customers that look like the same person
+#[derive(Debug, Clone)]
+pub struct DuplicatePair {
+    pub first: Customer,
+    pub second: Customer,
+    pub reasons: Vec<DuplicateReason>,
+}
+
+/// The name, phone and email compared when looking for duplicates.
+///
+/// Phone and email must already be normalized, as they are when stored.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateKey<'a> {
+    pub full_name: &'a str,
+    pub phone_number: &'a str,
+    pub email: &'a str,
+}
+
+impl<'a> DuplicateKey<'a> {
+    pub fn of(customer: &'a Customer) -> Self {
+        Self {
+            full_name: &customer.full_name,
+            phone_number: &customer.phone_number,
+            email: &customer.email,
+        }
+    }
+
+    /// Every way in which two records look like the same person
+    pub fn reasons(&self, other: &DuplicateKey) -> Vec<DuplicateReason> {
+        let mut reasons = Vec::new();
+        if !self.phone_number.is_empty() && self.phone_number == other.phone_number {
+            reasons.push(DuplicateReason::Phone);
+        }
+        if !self.email.is_empty() && self.email == other.email {
+            reasons.push(DuplicateReason::Email);
+        }
+        if names_match(self.full_name, other.full_name) {
+            reasons.push(DuplicateReason::Name);
+        }
+        reasons
+    }
+}
+
+/// Customers that the given record may duplicate, strongest matches first
+pub fn find_duplicates(key: &DuplicateKey, customers: &[Customer]) -> Vec<DuplicateMatch> {
+    let mut matches: Vec<DuplicateMatch> = customers
+        .iter()
+        .filter_map(|customer| {
+            let reasons = key.reasons(&DuplicateKey::of(customer));
+            (!reasons.is_empty()).then(|| DuplicateMatch {
+                customer: customer.clone(),
+                reasons,
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.reasons.len().cmp(&a.reasons.len()).then(a.reasons.cmp(&b.reasons)));
+    matches
+}
+
+/// Pairs of customers that look like the same person, strongest matches first.
+///
+/// Customers are only compared with those sharing a phone number, an email, a word of
+/// the name or the first letters of the name, rather than every customer with every
+/// other. A typo in the first letters of every word of a name can hide a match.
+pub fn find_duplicate_pairs(customers: &[Customer]) -> Vec<DuplicatePair> {
+    // Names are folded once up front, since a customer is compared with many others
+    let names: Vec<String> = customers
+        .iter()
+        .map(|customer| name_key(&customer.full_name))
+        .collect();
+
+    let mut groups: HashMap<Group, Vec<usize>> = HashMap::new();
+    for (i, customer) in customers.iter().enumerate() {
+        let mut keys: Vec<Group> = name_words(&names[i]).map(Group::Word).collect();
+        let start = name_start(&names[i]);
+        if !start.is_empty() {
+            keys.push(Group::Start(start));
+        }
+        if !customer.phone_number.is_empty() {
+            keys.push(Group::Phone(&customer.phone_number));
+        }
+        if !customer.email.is_empty() {
+            keys.push(Group::Email(&customer.email));
+        }
+        for key in keys {
+            groups.entry(key).or_default().push(i);
+        }
+    }
+
+    // Each group lists its customers in order, so pairs come out as (earlier, later)
+    let candidates: BTreeSet<(usize, usize)> = groups
+        .values()
+        .flat_map(|members| {
+            members.iter().enumerate().flat_map(move |(n, &i)| {
+                members[n + 1..]
+                    .iter()
+                    .filter(move |&&j| j != i)
+                    .map(move |&j| (i, j))
+            })
+        })
+        .collect();
+
+    let mut pairs: Vec<DuplicatePair> = candidates
+        .into_iter()
+        .filter_map(|(i, j)| {
+            let (first, second) = (&customers[i], &customers[j]);
+            let mut reasons = Vec::new();
+            if !first.phone_number.is_empty() && first.phone_number == second.phone_number {
+                reasons.push(DuplicateReason::Phone);
+            }
+            if !first.email.is_empty() && first.email == second.email {
+                reasons.push(DuplicateReason::Email);
+            }
+            if comparable_names_match(&names[i], &names[j]) {
+                reasons.push(DuplicateReason::Name);
+            }
+            (!reasons.is_empty()).then(|| DuplicatePair {
+                first: first.clone(),
+                second: second.clone(),
+                reasons,
+            })
+        })
+        .collect();
+    pairs.sort_by(|a, b| b.reasons.len().cmp(&a.reasons.len()).then(a.reasons.cmp(&b.reasons)));
+    pairs
+}
+
+/// Something customers who may be the same person share, to group them by
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Group<'a> {
+    Phone(&'a str),
+    Email(&'a str),
+    Word(&'a str),
+    Start(String),
+}
+
+/// Whether two names probably belong to the same person.
+///
+/// Names match after folding Persian spelling variants and dropping honorifics,
+/// when their words are the same in another order, or when they differ by a
+/// typo or two (none for names under five letters, one under ten, two above).
+pub fn names_match(a: &str, b: &str) -> bool {
+    comparable_names_match(&name_key(a), &name_key(b))
+}
+
+fn comparable_names_match(a: &str, b: &str) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a == b || sorted_words(a) == sorted_words(b) {
+        return true;
+    }
+
+    // Compare without spaces, so "علی رضا" matches "علیرضا"
+    let a: Vec<char> = a.chars().filter(|c| *c != ' ').collect();
+    let b: Vec<char> = b.chars().filter(|c| *c != ' ').collect();
+    let length = a.len().max(b.len());
+    let allowed = match length {
+        0..=4 => 0,
+        5..=9 => 1,
+        _ => 2,
+    };
+    a.len().abs_diff(b.len()) <= allowed && edit_distance(&a, &b) <= allowed
+}
+
+/// A name folded to one spelling, without honorifics, as stored in `customers.name_key`
+pub fn name_key(name: &str) -> String {
+    fold_persian_text(name)
+        .split(' ')
+        .filter(|word| !HONORIFICS.contains(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Words of a name key long enough to group customers by
+pub fn name_words(name_key: &str) -> impl Iterator<Item = &str> {
+    name_key.split(' ').filter(|word| word.chars().count() > 1)
+}
+
+/// The first letters of a name key, spaces left out
+pub fn name_start(name_key: &str) -> String {
+    name_key
+        .chars()
+        .filter(|c| *c != ' ')
+        .take(NAME_START_CHARS)
+        .collect()
+}
+
+fn sorted_words(name: &str) -> Vec<&str> {
+    let mut words: Vec<&str> = name.split(' ').collect();
+    words.sort_unstable();
+    words
+}
+
+/// Levenshtein distance between two strings of characters
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Which of the two records a merged field is taken from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeSide {
+    /// The surviving record
+    #[default]
+    Keep,
+    /// The record being merged into it and deleted
+    Remove,
+}
+
+/// Choice of the surviving record and, field by field, which record's value it keeps
+#[derive(Debug, Clone, Default)]
+pub struct MergeForm {
+    pub keep: i64,
+    pub remove: i64,
+    pub fields: HashMap<String, MergeSide>,
+}
+
+/// The two customers to compare on the merge screen
+#[derive(Debug, Deserialize)]
+pub struct MergeQuery {
+    pub keep: i64,
+    pub remove: i64,
+}
+
+/// Customer fields offered on the merge screen, with their labels
+pub const MERGE_FIELDS: [(&str, &str); 10] = [
+    ("full_name", "نام کامل"),
+    ("company", "شرکت"),
+    ("email", "ایمیل"),
+    ("phone_number", "شماره تلفن"),
+    ("sales_count", "تعداد فروش"),
+    ("job_title", "سمت شغلی"),
+    ("city", "شهر"),
+    ("address", "آدرس"),
+    ("notes", "یادداشت‌ها"),
+    ("coordinates", "موقعیت مکانی"),
+];
+
+impl MergeForm {
+    /// Read the submitted fields: `keep` and `remove` ids, then `keep` or `remove` per field
+    pub fn from_fields(fields: &[(String, String)]) -> Option<Self> {
+        let mut form = Self::default();
+        for (name, value) in fields {
+            match name.as_str() {
+                "keep" => form.keep = value.parse().ok()?,
+                "remove" => form.remove = value.parse().ok()?,
+                field if MERGE_FIELDS.iter().any(|(name, _)| *name == field) => {
+                    let side = match value.as_str() {
+                        "keep" => MergeSide::Keep,
+                        "remove" => MergeSide::Remove,
+                        _ => return None,
+                    };
+                    form.fields.insert(field.to_string(), side);
+                }
+                _ => {}
+            }
+        }
+        (form.keep != 0 && form.remove != 0).then_some(form)
+    }
+
+    pub fn side(&self, field: &str) -> MergeSide {
+        self.fields.get(field).copied().unwrap_or_default()
+    }
+
+    /// The surviving record with each field taken from the chosen side
+    pub fn merged(&self, keep: &Customer, remove: &Customer) -> Customer {
+        let pick = |field: &str, kept: &String, removed: &String| match self.side(field) {
+            MergeSide::Keep => kept.clone(),
+            MergeSide::Remove => removed.clone(),
+        };
+        Customer {
+            id: keep.id,
+            full_name: pick("full_name", &keep.full_name, &remove.full_name),
+            company: pick("company", &keep.company, &remove.company),
+            email: pick("email", &keep.email, &remove.email),
+            phone_number: pick("phone_number", &keep.phone_number, &remove.phone_number),
+            sales_count: match self.side("sales_count") {
+                MergeSide::Keep => keep.sales_count,
+                MergeSide::Remove => remove.sales_count,
+            },
+            job_title: pick("job_title", &keep.job_title, &remove.job_title),
+            city: pick("city", &keep.city, &remove.city),
+            address: pick("address", &keep.address, &remove.address),
+            notes: pick("notes", &keep.notes, &remove.notes),
+            coordinates: pick("coordinates", &keep.coordinates, &remove.coordinates),
+        }
+    }
+}
+
+/// One row of the merge screen: a field and both records' values
+#[derive(Debug, Clone)]
+pub struct MergeFieldRow {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub keep_value: String,
+    pub remove_value: String,
+}
+
+impl MergeFieldRow {
+    /// Rows for every field, so the screen can offer a choice where the values differ
+    pub fn rows(keep: &Customer, remove: &Customer) -> Vec<Self> {
+        let value = |customer: &Customer, field: &str| match field {
+            "full_name" => customer.full_name.clone(),
+            "company" => customer.company.clone(),
+            "email" => customer.email.clone(),
+            "phone_number" => customer.formatted_phone(),
+            "sales_count" => customer.sales_count.to_string(),
+            "job_title" => customer.job_title.clone(),
+            "city" if customer.city.is_empty() => String::new(),
+            "city" => customer.city_display_name(),
+            "address" => customer.address.clone(),
+            "notes" => customer.notes.clone(),
+            "coordinates" => customer.coordinates.clone(),
+            _ => String::new(),
+        };
+        MERGE_FIELDS
+            .iter()
+            .map(|(name, label)| MergeFieldRow {
+                name,
+                label,
+                keep_value: value(keep, name),
+                remove_value: value(remove, name),
+            })
+            .collect()
+    }
+
+    pub fn differs(&self) -> bool {
+        self.keep_value != self.remove_value
+    }
+
+    /// Preselect the other record's value when the surviving one is empty
+    pub fn prefers_remove(&self) -> bool {
+        self.keep_value.trim().is_empty() && !self.remove_value.trim().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn customer(id: i64, full_name: &str, phone_number: &str, email: &str) -> Customer {
+        Customer {
+            id,
+            full_name: full_name.to_string(),
+            company: "رومی".to_string(),
+            email: email.to_string(),
+            phone_number: phone_number.to_string(),
+            sales_count: 0,
+            job_title: String::new(),
+            city: String::new(),
+            address: String::new(),
+            notes: String::new(),
+            coordinates: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_names_match() {
+        assert!(names_match("علی کریمی", "علي كريمي"));
+        assert!(names_match("آقای علی کریمی", "علی کریمی"));
+        assert!(names_match("کریمی علی", "علی کریمی"));
+        assert!(names_match("علیرضا محمدی", "علی رضا محمدی"));
+        assert!(names_match("علیرضا محمدی", "علیرضا محمودی"));
+        assert!(!names_match("علی", "ولی"));
+        assert!(!names_match("علی کریمی", "رضا کریمی"));
+        assert!(!names_match("", ""));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let customers = vec![
+            customer(1, "رضا احمدی", "09120000000", ""),
+            customer(2, "مریم حسینی", "09350000000", "maryam@example.com"),
+            customer(3, "علی کریمی", "09191111111", ""),
+        ];
+        let key = DuplicateKey {
+            full_name: "مریم حسيني",
+            phone_number: "09120000000",
+            email: "maryam@example.com",
+        };
+
+        let matches = find_duplicates(&key, &customers);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].customer.id, 2);
+        assert_eq!(
+            matches[0].reasons,
+            vec![DuplicateReason::Email, DuplicateReason::Name]
+        );
+        assert_eq!(matches[1].customer.id, 1);
+        assert_eq!(matches[1].reasons, vec![DuplicateReason::Phone]);
+    }
+
+    #[test]
+    fn test_find_duplicate_pairs() {
+        let customers = vec![
+            customer(1, "رضا احمدی", "09120000000", ""),
+            customer(2, "احمدی رضا", "09120000000", ""),
+            customer(3, "علی کریمی", "09191111111", ""),
+        ];
+        let pairs = find_duplicate_pairs(&customers);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].first.id, pairs[0].second.id), (1, 2));
+        assert_eq!(
+            pairs[0].reasons,
+            vec![DuplicateReason::Phone, DuplicateReason::Name]
+        );
+    }
+
+    #[test]
+    fn test_find_duplicate_pairs_groups_customers() {
+        let customers = vec![
+            customer(1, "علیرضا محمدی", "09120000001", ""),
+            customer(2, "علیرضا محمودی", "09120000002", ""),
+            customer(3, "شهرزاد", "09120000003", ""),
+            customer(4, "شهرزاذ", "09120000004", ""),
+            customer(5, "رضا", "09120000005", "reza@example.com"),
+            customer(6, "کاوه", "09120000006", "reza@example.com"),
+        ];
+        let pairs: Vec<(i64, i64)> = find_duplicate_pairs(&customers)
+            .iter()
+            .map(|pair| (pair.first.id, pair.second.id))
+            .collect();
+        // A shared word, the same first letters, the same email
+        assert_eq!(pairs, vec![(5, 6), (1, 2), (3, 4)]);
+
+        assert_eq!(name_key("آقای علي  كريمي"), "علی کریمی");
+        assert_eq!(
+            name_words("علی م کریمی").collect::<Vec<_>>(),
+            vec!["علی", "کریمی"]
+        );
+        assert_eq!(name_start("ما هان"), "ماه");
+    }
+
+    #[test]
+    fn test_merged() {
+        let keep = customer(1, "رضا احمدی", "09120000000", "");
+        let remove = customer(2, "رضا احمدی", "09350000000", "reza@example.com");
+        let fields = [
+            ("csrf_token", "token"),
+            ("keep", "1"),
+            ("remove", "2"),
+            ("email", "remove"),
+            ("phone_number", "keep"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let form = MergeForm::from_fields(&fields).unwrap();
+        assert_eq!(form.side("full_name"), MergeSide::Keep);
+
+        let merged = form.merged(&keep, &remove);
+        assert_eq!(merged.id, 1);
+        assert_eq!(merged.email, "reza@example.com");
+        assert_eq!(merged.phone_number, "09120000000");
+
+        assert!(MergeForm::from_fields(&fields[..2]).is_none());
+    }
+}
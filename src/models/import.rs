@@ -0,0 +1,430 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::AppError,
+    models::{City, CustomerForm},
+    utils::{
+        email::{normalize_email, validate_email},
+        localization::persian_to_english_numbers,
+        phone::normalize_phone_number,
+    },
+};
+
+/// Most data rows accepted in one import file
+pub const MAX_IMPORT_ROWS: usize = 5000;
+
+/// A `CustomerForm` field a spreadsheet column can be mapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportField {
+    FullName,
+    Company,
+    Email,
+    PhoneNumber,
+    SalesCount,
+    JobTitle,
+    City,
+    Address,
+    Notes,
+    Coordinates,
+}
+
+impl ImportField {
+    pub const ALL: [ImportField; 10] = [
+        ImportField::FullName,
+        ImportField::Company,
+        ImportField::Email,
+        ImportField::PhoneNumber,
+        ImportField::SalesCount,
+        ImportField::JobTitle,
+        ImportField::City,
+        ImportField::Address,
+        ImportField::Notes,
+        ImportField::Coordinates,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportField::FullName => "full_name",
+            ImportField::Company => "company",
+            ImportField::Email => "email",
+            ImportField::PhoneNumber => "phone_number",
+            ImportField::SalesCount => "sales_count",
+            ImportField::JobTitle => "job_title",
+            ImportField::City => "city",
+            ImportField::Address => "address",
+            ImportField::Notes => "notes",
+            ImportField::Coordinates => "coordinates",
+        }
+    }
+
+    /// Label used on the customer form and as the export header
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ImportField::FullName => "نام کامل",
+            ImportField::Company => "شرکت",
+            ImportField::Email => "ایمیل",
+            ImportField::PhoneNumber => "شماره تلفن",
+            ImportField::SalesCount => "تعداد فروش",
+            ImportField::JobTitle => "سمت شغلی",
+            ImportField::City => "شهر",
+            ImportField::Address => "آدرس",
+            ImportField::Notes => "یادداشت‌ها",
+            ImportField::Coordinates => "مختصات",
+        }
+    }
+
+    /// Other headers recognised for this field, besides its key and display name
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            ImportField::FullName => &["نام", "name"],
+            ImportField::Company => &["نام شرکت"],
+            ImportField::Email => &["پست الکترونیک", "e-mail"],
+            ImportField::PhoneNumber => &["تلفن", "موبایل", "شماره موبایل", "phone", "mobile"],
+            ImportField::SalesCount => &[],
+            ImportField::JobTitle => &["سمت"],
+            ImportField::City => &[],
+            ImportField::Address => &[],
+            ImportField::Notes => &["یادداشت", "توضیحات"],
+            ImportField::Coordinates => &["موقعیت مکانی"],
+        }
+    }
+
+    /// Guess the field from a header cell, ignoring case, spaces and a trailing `*`
+    pub fn from_header(header: &str) -> Option<Self> {
+        let header = normalize_header(header);
+        Self::ALL.into_iter().find(|field| {
+            normalize_header(field.as_str()) == header
+                || normalize_header(field.display_name()) == header
+                || field
+                    .aliases()
+                    .iter()
+                    .any(|alias| normalize_header(alias) == header)
+        })
+    }
+}
+
+fn normalize_header(header: &str) -> String {
+    header
+        .trim()
+        .trim_end_matches('*')
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\u{200c}' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The rows read from an uploaded file, kept between the preview and the import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSheet {
+    pub file_name: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl ImportSheet {
+    /// Split the first row off as headers, dropping rows with no values at all
+    pub fn from_rows(file_name: String, mut rows: Vec<Vec<String>>) -> Self {
+        rows.retain(|row| row.iter().any(|cell| !cell.trim().is_empty()));
+        let headers = if rows.is_empty() {
+            Vec::new()
+        } else {
+            rows.remove(0)
+        };
+        Self {
+            file_name,
+            headers,
+            rows,
+        }
+    }
+
+    /// Map each column to the field its header names, each field at most once
+    pub fn detect_mapping(&self) -> Vec<Option<ImportField>> {
+        let mut used = HashSet::new();
+        self.headers
+            .iter()
+            .map(|header| ImportField::from_header(header).filter(|field| used.insert(*field)))
+            .collect()
+    }
+}
+
+/// A column of the uploaded file as shown in the mapping form
+#[derive(Debug, Clone)]
+pub struct ImportColumn {
+    pub header: String,
+    /// First non-empty value in the column, to help recognise it
+    pub sample: String,
+    pub field: Option<ImportField>,
+}
+
+impl ImportColumn {
+    pub fn is_mapped_to(&self, field: &ImportField) -> bool {
+        self.field == Some(*field)
+    }
+}
+
+/// What will happen, or happened, to one row of the file
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowStatus {
+    /// Valid and not yet in the CRM
+    New,
+    /// Valid, but the phone number belongs to an existing customer or an earlier row
+    Duplicate,
+    /// Failed validation, with the reason for each failing field
+    Invalid(Vec<String>),
+}
+
+/// One data row after validation
+#[derive(Debug)]
+pub struct ImportRow {
+    /// Row number in the file, counting the header as row 1
+    pub line: usize,
+    pub form: CustomerForm,
+    pub status: RowStatus,
+}
+
+impl ImportRow {
+    pub fn is_new(&self) -> bool {
+        self.status == RowStatus::New
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        self.status == RowStatus::Duplicate
+    }
+
+    pub fn errors(&self) -> &[String] {
+        match &self.status {
+            RowStatus::Invalid(errors) => errors,
+            _ => &[],
+        }
+    }
+
+    pub fn city_display_name(&self) -> &'static str {
+        match City::from_str(&self.form.city) {
+            City::None => "",
+            city => city.display_name(),
+        }
+    }
+}
+
+/// Counts shown on the preview and after the import
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub duplicates: usize,
+    pub skipped: usize,
+}
+
+impl ImportSummary {
+    pub fn of(rows: &[ImportRow]) -> Self {
+        let mut summary = Self::default();
+        for row in rows {
+            match row.status {
+                RowStatus::New => summary.created += 1,
+                RowStatus::Duplicate => summary.duplicates += 1,
+                RowStatus::Invalid(_) => summary.skipped += 1,
+            }
+        }
+        summary
+    }
+}
+
+/// Column mapping chosen on the preview page, one `column` field per column in order
+#[derive(Debug, Clone, Default)]
+pub struct ImportMappingForm {
+    pub columns: Vec<Option<ImportField>>,
+}
+
+impl ImportMappingForm {
+    pub fn from_fields(fields: &[(String, String)]) -> Self {
+        let columns = fields
+            .iter()
+            .filter(|(name, _)| name == "column")
+            .map(|(_, value)| ImportField::parse(value))
+            .collect();
+        Self { columns }
+    }
+}
+
+/// Validate every row under a column mapping.
+///
+/// `existing_phones` holds the normalized phone numbers already in the CRM; a row
+/// repeating one of them, or a phone number from an earlier row, is a duplicate.
+pub fn validate_rows(
+    sheet: &ImportSheet,
+    mapping: &[Option<ImportField>],
+    existing_phones: &HashSet<String>,
+) -> Vec<ImportRow> {
+    let mut seen_phones = HashSet::new();
+    sheet
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(index, cells)| {
+            let (form, errors) = validate_row(cells, mapping);
+            let status = if !errors.is_empty() {
+                RowStatus::Invalid(errors)
+            } else if existing_phones.contains(&form.phone_number)
+                || !seen_phones.insert(form.phone_number.clone())
+            {
+                RowStatus::Duplicate
+            } else {
+                RowStatus::New
+            };
+            ImportRow {
+                line: index + 2,
+                form,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// Build a customer form from one row and check it like the add customer form does
+fn validate_row(cells: &[String], mapping: &[Option<ImportField>]) -> (CustomerForm, Vec<String>) {
+    let mut form = CustomerForm::default();
+    let mut sales_count = String::new();
+    for (cell, field) in cells.iter().zip(mapping) {
+        let value = cell.trim().to_string();
+        match field {
+            Some(ImportField::FullName) => form.full_name = value,
+            Some(ImportField::Company) => form.company = value,
+            Some(ImportField::Email) => form.email = value,
+            Some(ImportField::PhoneNumber) => form.phone_number = value,
+            Some(ImportField::SalesCount) => sales_count = value,
+            Some(ImportField::JobTitle) => form.job_title = value,
+            Some(ImportField::City) => form.city = value,
+            Some(ImportField::Address) => form.address = value,
+            Some(ImportField::Notes) => form.notes = value,
+            Some(ImportField::Coordinates) => form.coordinates = value,
+            None => {}
+        }
+    }
+
+    let mut errors = Vec::new();
+
+    if form.full_name.is_empty() {
+        errors.push("نام کامل نمی‌تواند خالی باشد".to_string());
+    }
+    if form.company.is_empty() {
+        errors.push("نام شرکت نمی‌تواند خالی باشد".to_string());
+    }
+
+    match normalize_phone_number(&persian_to_english_numbers(&form.phone_number)) {
+        Ok(phone) => form.phone_number = phone,
+        Err(e) => errors.push(error_message(e)),
+    }
+
+    match validate_email(&form.email) {
+        Ok(email) => form.email = normalize_email(&email),
+        Err(e) => errors.push(error_message(e)),
+    }
+
+    if !sales_count.is_empty() {
+        match persian_to_english_numbers(&sales_count).parse::<i64>() {
+            Ok(count) if count >= 0 => form.sales_count = count,
+            Ok(_) => errors.push("تعداد فروش نمیتواند منفی باشد".to_string()),
+            Err(_) => errors.push(format!("تعداد فروش «{}» عدد نیست", sales_count)),
+        }
+    }
+
+    // The export writes the Persian city name, so accept it as well as the stored key
+    if !form.city.is_empty() {
+        match City::all_cities()
+            .into_iter()
+            .find(|city| city.as_str() == form.city || city.display_name() == form.city)
+        {
+            Some(city) => form.city = city.as_str().to_string(),
+            None => errors.push(format!("شهر «{}» معتبر نیست", form.city)),
+        }
+    }
+
+    (form, errors)
+}
+
+fn error_message(error: AppError) -> String {
+    match error {
+        AppError::BadRequest(msg) => msg,
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(rows: &[&[&str]]) -> ImportSheet {
+        ImportSheet::from_rows(
+            "customers.csv".to_string(),
+            rows.iter()
+                .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_detect_mapping() {
+        let sheet = sheet(&[
+            &["ID", "نام کامل", "شرکت", "Phone Number", "شهر", "نام", "مانده حساب"],
+            &["1", "علی", "رومی", "09120000000", "زنجان", "", ""],
+        ]);
+        assert_eq!(
+            sheet.detect_mapping(),
+            vec![
+                None,
+                Some(ImportField::FullName),
+                Some(ImportField::Company),
+                Some(ImportField::PhoneNumber),
+                Some(ImportField::City),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_rows_skips_blank_rows() {
+        let sheet = sheet(&[&["", ""], &["name", "phone"], &[" ", ""], &["علی", "0912"]]);
+        assert_eq!(sheet.headers, vec!["name", "phone"]);
+        assert_eq!(sheet.rows.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_rows() {
+        let sheet = sheet(&[
+            &["full_name", "company", "phone_number", "email", "city", "sales_count"],
+            &["علی", "رومی", "۰۹۱۲ ۱۲۳ ۴۵۶۷", "ALI@Example.com", "زنجان", "۳"],
+            &["رضا", "رومی", "9121234567", "", "Abhar", ""],
+            &["", "رومی", "123", "bad", "Tehran", "-1"],
+            &["مریم", "رومی", "09350000000", "", "", ""],
+        ]);
+        let existing = HashSet::from(["09350000000".to_string()]);
+        let rows = validate_rows(&sheet, &sheet.detect_mapping(), &existing);
+
+        assert_eq!(rows[0].status, RowStatus::New);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].form.phone_number, "09121234567");
+        assert_eq!(rows[0].form.email, "ali@example.com");
+        assert_eq!(rows[0].form.city, "Zanjan");
+        assert_eq!(rows[0].form.sales_count, 3);
+
+        // Same number as the row above once normalized
+        assert_eq!(rows[1].status, RowStatus::Duplicate);
+        assert_eq!(rows[2].errors().len(), 5);
+        assert_eq!(rows[3].status, RowStatus::Duplicate);
+
+        assert_eq!(
+            ImportSummary::of(&rows),
+            ImportSummary {
+                created: 1,
+                duplicates: 2,
+                skipped: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{models::money::Money, utils::date::iso_to_shamsi};
+
+/// Invoices with their customer's name, item count and total, as read into `InvoiceSummary`
+pub const INVOICE_SUMMARY_SQL: &str = "SELECT invoices.*,
+        customers.full_name AS customer_name,
+        (SELECT COUNT(*) FROM invoice_items WHERE invoice_items.invoice_id = invoices.id) AS item_count,
+        COALESCE((SELECT SUM(quantity * unit_price - discount)
+            FROM invoice_items WHERE invoice_items.invoice_id = invoices.id), 0) AS total
+     FROM invoices
+     JOIN customers ON customers.id = invoices.customer_id";
+
+/// A sales invoice for a customer.
+///
+/// Confirming it takes its items out of stock and records the total as a
+/// receivable transaction; cancelling a confirmed invoice undoes both.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Invoice {
+    pub id: i64,
+    pub customer_id: i64,
+    pub invoice_date: String,
+    pub status: String,
+    pub notes: String,
+    /// The receivable created when the invoice was confirmed
+    pub transaction_id: Option<i64>,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+}
+
+impl Invoice {
+    pub fn status_enum(&self) -> InvoiceStatus {
+        InvoiceStatus::parse(&self.status).unwrap_or(InvoiceStatus::Draft)
+    }
+
+    pub fn formatted_date(&self) -> String {
+        iso_to_shamsi(&self.invoice_date)
+    }
+}
+
+/// An invoice with its customer's name and computed total, for lists and the invoice page
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct InvoiceSummary {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub invoice: Invoice,
+    pub customer_name: String,
+    pub item_count: i64,
+    pub total: Money,
+}
+
+impl InvoiceSummary {
+    pub fn formatted_total(&self) -> String {
+        self.total.format()
+    }
+}
+
+/// One line of an invoice.
+///
+/// The product name and price are copied onto the line, so the invoice still
+/// reads the same after the catalog changes.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct InvoiceItem {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub product_id: Option<i64>,
+    pub variant_id: Option<i64>,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: Money,
+    pub discount: Money,
+}
+
+impl InvoiceItem {
+    pub fn total(&self) -> Money {
+        self.unit_price * self.quantity - self.discount
+    }
+
+    pub fn formatted_unit_price(&self) -> String {
+        self.unit_price.format()
+    }
+
+    pub fn formatted_discount(&self) -> String {
+        self.discount.format()
+    }
+
+    pub fn formatted_total(&self) -> String {
+        self.total().format()
+    }
+}
+
+/// A product or variant that can be put on an invoice
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SaleItemOption {
+    /// `product:<id>` or `variant:<id>`
+    pub value: String,
+    pub label: String,
+    pub product_id: i64,
+    pub variant_id: Option<i64>,
+    pub price: Money,
+    pub stock: i32,
+}
+
+impl SaleItemOption {
+    pub fn formatted_price(&self) -> String {
+        self.price.format()
+    }
+}
+
+/// Form data for starting a new invoice
+#[derive(Debug, Deserialize)]
+pub struct InvoiceForm {
+    pub customer_id: i64,
+    pub invoice_date: String,
+    #[serde(default)]
+    pub notes: String,
+}
+
+/// Form data for adding a line to a draft invoice
+#[derive(Debug, Deserialize)]
+pub struct InvoiceItemForm {
+    /// A `SaleItemOption::value`
+    pub item: String,
+    pub quantity: i64,
+    /// Leave empty to use the catalog price
+    #[serde(default)]
+    pub unit_price: String,
+    #[serde(default)]
+    pub discount: String,
+}
+
+/// Query string for the invoice list and the new invoice form
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct InvoiceListQuery {
+    pub status: String,
+    pub customer_id: Option<i64>,
+}
+
+/// Where an invoice is in its lifecycle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum InvoiceStatus {
+    Draft,
+    Confirmed,
+    Cancelled,
+}
+
+impl InvoiceStatus {
+    pub fn all() -> Vec<InvoiceStatus> {
+        vec![
+            InvoiceStatus::Draft,
+            InvoiceStatus::Confirmed,
+            InvoiceStatus::Cancelled,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Draft => "draft",
+            InvoiceStatus::Confirmed => "confirmed",
+            InvoiceStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Parse a stored or submitted status, rejecting unknown values
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "draft" => Some(InvoiceStatus::Draft),
+            "confirmed" => Some(InvoiceStatus::Confirmed),
+            "cancelled" => Some(InvoiceStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Draft => "پیش‌نویس",
+            InvoiceStatus::Confirmed => "تأیید شده",
+            InvoiceStatus::Cancelled => "لغو شده",
+        }
+    }
+
+    pub fn badge_class(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Draft => "badge-info",
+            InvoiceStatus::Confirmed => "badge-success",
+            InvoiceStatus::Cancelled => "badge-error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_total() {
+        let item = InvoiceItem {
+            id: 1,
+            invoice_id: 1,
+            product_id: Some(1),
+            variant_id: None,
+            description: "قهوه".to_string(),
+            quantity: 3,
+            unit_price: Money::parse("150000").unwrap(),
+            discount: Money::parse("20000").unwrap(),
+        };
+        assert_eq!(item.total(), Money::parse("430000").unwrap());
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::utils::date::format_timestamp;
+
+/// What failed login attempts are counted against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LoginScope {
+    Username,
+    Ip,
+}
+
+impl LoginScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "username" => Some(LoginScope::Username),
+            "ip" => Some(LoginScope::Ip),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginScope::Username => "username",
+            LoginScope::Ip => "ip",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            LoginScope::Username => "نام کاربری",
+            LoginScope::Ip => "نشانی IP",
+        }
+    }
+}
+
+/// Limits on failed logins, from the configuration
+#[derive(Debug, Clone)]
+pub struct LoginLimits {
+    /// Failures on one username before it is locked
+    pub max_failures_per_username: i64,
+    /// Failures from one client address before it is locked
+    pub max_failures_per_ip: i64,
+    /// How long a lockout lasts; failures older than this are forgotten
+    pub lockout_minutes: i64,
+    /// Wait after the first failure, doubled after each one that follows
+    pub backoff_base_seconds: i64,
+    /// Reverse proxies in front of the app that each append to `X-Forwarded-For`.
+    ///
+    /// The client address is the entry the outermost of them added; anything further left
+    /// was sent by the client. With 0 the header is ignored.
+    pub trusted_proxy_hops: usize,
+}
+
+impl LoginLimits {
+    pub fn max_failures(&self, scope: LoginScope) -> i64 {
+        match scope {
+            LoginScope::Username => self.max_failures_per_username,
+            LoginScope::Ip => self.max_failures_per_ip,
+        }
+    }
+
+    pub fn lockout(&self) -> Duration {
+        Duration::minutes(self.lockout_minutes)
+    }
+
+    /// How long to wait after `failures` consecutive failures, never longer than a lockout
+    pub fn backoff(&self, failures: i64) -> Duration {
+        if failures <= 0 || self.backoff_base_seconds <= 0 {
+            return Duration::zero();
+        }
+        let doublings = (failures - 1).min(30) as u32;
+        let seconds = self.backoff_base_seconds.saturating_mul(1 << doublings);
+        Duration::seconds(seconds).min(self.lockout())
+    }
+}
+
+/// Failed login attempts against one username or client address
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct LoginFailure {
+    pub scope: String,
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: String,
+    pub locked_until: Option<String>,
+}
+
+impl LoginFailure {
+    pub fn scope_enum(&self) -> LoginScope {
+        LoginScope::parse(&self.scope).unwrap_or(LoginScope::Username)
+    }
+
+    pub fn last_failure(&self) -> Option<DateTime<Utc>> {
+        parse_timestamp(&self.last_failure_at)
+    }
+
+    pub fn locked_until_time(&self) -> Option<DateTime<Utc>> {
+        self.locked_until.as_deref().and_then(parse_timestamp)
+    }
+
+    pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+        self.locked_until_time().is_some_and(|until| until > now)
+    }
+
+    /// Whether the count should start over, because the lockout ended or the failures are old
+    pub fn is_stale(&self, now: DateTime<Utc>, limits: &LoginLimits) -> bool {
+        match (self.locked_until_time(), self.last_failure()) {
+            (Some(until), _) => until <= now,
+            (None, Some(last)) => now - last >= limits.lockout(),
+            (None, None) => true,
+        }
+    }
+
+    /// When the next attempt is allowed, if that is still in the future
+    pub fn retry_at(&self, now: DateTime<Utc>, limits: &LoginLimits) -> Option<DateTime<Utc>> {
+        let retry_at = match self.locked_until_time() {
+            Some(until) => until,
+            None => self.last_failure()? + limits.backoff(self.failures),
+        };
+        (retry_at > now).then_some(retry_at)
+    }
+
+    pub fn formatted_locked_until(&self) -> String {
+        self.locked_until
+            .as_deref()
+            .map(format_timestamp)
+            .unwrap_or_default()
+    }
+}
+
+/// A lockout starting or being lifted by an admin
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct LockoutEvent {
+    pub id: i64,
+    pub scope: String,
+    pub key: String,
+    /// `locked` or `unlocked`
+    pub event: String,
+    pub locked_until: Option<String>,
+    pub created_at: String,
+    /// Admin who lifted the lockout
+    pub unlocked_by: Option<String>,
+}
+
+impl LockoutEvent {
+    pub fn scope_enum(&self) -> LoginScope {
+        LoginScope::parse(&self.scope).unwrap_or(LoginScope::Username)
+    }
+
+    pub fn is_lock(&self) -> bool {
+        self.event == "locked"
+    }
+
+    pub fn formatted_created_at(&self) -> String {
+        format_timestamp(&self.created_at)
+    }
+
+    pub fn formatted_locked_until(&self) -> String {
+        self.locked_until
+            .as_deref()
+            .map(format_timestamp)
+            .unwrap_or_default()
+    }
+}
+
+/// Form data for lifting a lockout
+#[derive(Debug, Deserialize)]
+pub struct UnlockForm {
+    pub scope: String,
+    pub key: String,
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> LoginLimits {
+        LoginLimits {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 20,
+            lockout_minutes: 15,
+            backoff_base_seconds: 1,
+            trusted_proxy_hops: 0,
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_lockout() {
+        let limits = limits();
+        assert_eq!(limits.backoff(0), Duration::zero());
+        assert_eq!(limits.backoff(1), Duration::seconds(1));
+        assert_eq!(limits.backoff(2), Duration::seconds(2));
+        assert_eq!(limits.backoff(5), Duration::seconds(16));
+        assert_eq!(limits.backoff(20), Duration::minutes(15));
+        assert_eq!(limits.backoff(1000), Duration::minutes(15));
+    }
+
+    #[test]
+    fn test_retry_at() {
+        let limits = limits();
+        let now = Utc::now();
+        let mut failure = LoginFailure {
+            scope: "username".to_string(),
+            key: "admin".to_string(),
+            failures: 3,
+            last_failure_at: now.to_rfc3339(),
+            locked_until: None,
+        };
+        assert!(failure.retry_at(now, &limits).is_some());
+        assert!(failure.retry_at(now + Duration::seconds(5), &limits).is_none());
+
+        failure.locked_until = Some((now + Duration::minutes(15)).to_rfc3339());
+        assert!(failure.is_locked(now));
+        assert!(failure.retry_at(now + Duration::minutes(10), &limits).is_some());
+        assert!(failure.is_stale(now + Duration::minutes(16), &limits));
+    }
+}
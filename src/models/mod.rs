@@ -1,11 +1,53 @@
+pub mod api_token;
+pub mod audit;
+pub mod cheque;
 pub mod customer;
+pub mod document;
+pub mod duplicate;
+pub mod import;
+pub mod invoice;
+pub mod login_throttle;
+pub mod money;
 pub mod product;
+pub mod role;
+pub mod segment;
 pub mod session;
+pub mod tag;
 pub mod transactions;
 pub mod user;
+pub mod webhook;
 
-pub use customer::{City, Customer, CustomerForm};
-pub use product::{Product, ProductForm};
-pub use session::Session;
-pub use transactions::{Transaction, TransactionForm, TransactionType};
-pub use user::{LoginForm, User, UserForm};
+pub use api_token::{ApiToken, ApiTokenForm};
+pub use audit::{AuditAction, AuditEntity, AuditEntry, AuditQuery};
+pub use cheque::{
+    Cheque, ChequeDetail, ChequeListQuery, ChequeStatus, ChequeStatusChange, ChequeStatusForm,
+};
+pub use customer::{City, Customer, CustomerForm, CustomerQuery, CustomerSummary};
+pub use document::{
+    CompanyHeader, DocumentKind, DocumentLine, DocumentSettings, PrintDocument,
+};
+pub use duplicate::{DuplicateMatch, DuplicatePair, MergeFieldRow, MergeForm, MergeQuery};
+pub use import::{
+    ImportColumn, ImportField, ImportMappingForm, ImportRow, ImportSheet, ImportSummary,
+};
+pub use invoice::{
+    Invoice, InvoiceForm, InvoiceItem, InvoiceItemForm, InvoiceListQuery, InvoiceStatus,
+    InvoiceSummary, SaleItemOption,
+};
+pub use login_throttle::{LockoutEvent, LoginFailure, LoginLimits, LoginScope, UnlockForm};
+pub use money::Money;
+pub use product::{Product, ProductForm, ProductVariant, ProductVariantForm, StockSettings};
+pub use role::{Permission, Role, RoleForm, RoleSummary};
+pub use segment::{Segment, SegmentForm, SegmentSummary};
+pub use session::{Session, SessionSettings};
+pub use tag::{Tag, TagAction, TagForm, TagNames, TagSummary};
+pub use transactions::{
+    LedgerEntry, LedgerSummary, Transaction, TransactionDirection, TransactionForm, TransactionType,
+};
+pub use user::{
+    ChangePasswordForm, EditUserForm, LoginForm, ResetPasswordForm, User, UserForm, UserProfile,
+};
+pub use webhook::{
+    DeliveryStatus, WebhookDelivery, WebhookDeliveryQuery, WebhookEndpoint, WebhookEndpointForm,
+    WebhookEndpointSummary, WebhookEvent,
+};
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Mul, Neg, Sub},
+};
+use utoipa::ToSchema;
+
+use crate::utils::localization::{
+    number_to_persian_words, persian_to_english_numbers, to_persian_digits,
+};
+
+/// Rials in one toman
+const RIALS_PER_TOMAN: i64 = 10;
+
+/// An exact amount of money, stored as a whole number of rials.
+///
+/// Amounts are entered and shown in tomans; keeping rials as an integer means
+/// sums never drift the way floating point totals do.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    ToSchema,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    /// Parse an amount in tomans as typed into a form.
+    ///
+    /// Accepts Persian or English digits, thousand separators, and a single
+    /// decimal digit for rials (`۱۲٬۵۰۰٫۵`).
+    pub fn parse(input: &str) -> Option<Self> {
+        let normalized: String = persian_to_english_numbers(input.trim())
+            .chars()
+            .filter(|c| !matches!(c, ',' | '٬' | '،' | ' ' | '\u{200c}'))
+            .map(|c| if c == '٫' { '.' } else { c })
+            .collect();
+
+        let (negative, digits) = match normalized.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, normalized.as_str()),
+        };
+
+        let (tomans, rials) = match digits.split_once('.') {
+            Some((tomans, rials)) => (tomans, rials.trim_end_matches('0')),
+            None => (digits, ""),
+        };
+
+        if tomans.is_empty()
+            || !tomans.chars().all(|c| c.is_ascii_digit())
+            || !rials.chars().all(|c| c.is_ascii_digit())
+            || rials.len() > 1
+        {
+            return None;
+        }
+
+        let rials = tomans
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(RIALS_PER_TOMAN)?
+            .checked_add(rials.parse::<i64>().unwrap_or(0))?;
+
+        Some(Money(if negative { -rials } else { rials }))
+    }
+
+    /// A unit price times a quantity, or `None` if the result is too large to store
+    pub fn checked_mul(self, quantity: i64) -> Option<Money> {
+        self.0.checked_mul(quantity).map(Money)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn abs(&self) -> Self {
+        Money(self.0.abs())
+    }
+
+    /// The amount in tomans as a number, for spreadsheets and charts
+    pub fn tomans(&self) -> f64 {
+        self.0 as f64 / RIALS_PER_TOMAN as f64
+    }
+
+    /// The amount in tomans as plain English digits, for form inputs
+    pub fn input_value(&self) -> String {
+        self.toman_digits(false)
+    }
+
+    /// Tomans with Persian digits and thousand separators, e.g. `۱۵۰٬۰۰۰ تومان`
+    pub fn format(&self) -> String {
+        format!("{} تومان", to_persian_digits(&self.toman_digits(true)))
+    }
+
+    /// The amount spelled out in Persian words, e.g. `صد و پنجاه هزار تومان`
+    pub fn in_words(&self) -> String {
+        let tomans = number_to_persian_words(self.0 / RIALS_PER_TOMAN);
+        match self.0 % RIALS_PER_TOMAN {
+            0 => format!("{} تومان", tomans),
+            rials => format!(
+                "{} تومان و {} ریال",
+                tomans,
+                number_to_persian_words(rials.abs())
+            ),
+        }
+    }
+
+    fn toman_digits(&self, grouped: bool) -> String {
+        let tomans = (self.0 / RIALS_PER_TOMAN).unsigned_abs().to_string();
+        let rials = (self.0 % RIALS_PER_TOMAN).unsigned_abs();
+
+        let mut digits = if grouped {
+            group_thousands(&tomans)
+        } else {
+            tomans
+        };
+        if rials != 0 {
+            digits.push(if grouped { '٫' } else { '.' });
+            digits.push_str(&rials.to_string());
+        }
+        if self.is_negative() {
+            digits.insert(0, '-');
+        }
+        digits
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format())
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+/// Multiply a unit price by a quantity
+impl Mul<i64> for Money {
+    type Output = Money;
+
+    fn mul(self, quantity: i64) -> Money {
+        Money(self.0 * quantity)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+/// Insert the Persian thousands separator into a string of digits
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 * 2);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push('٬');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Money::parse("150000"), Some(Money(1_500_000)));
+        assert_eq!(Money::parse("۱۵۰٬۰۰۰"), Some(Money(1_500_000)));
+        assert_eq!(Money::parse(" 1,250,000 "), Some(Money(12_500_000)));
+        assert_eq!(Money::parse("12.5"), Some(Money(125)));
+        assert_eq!(Money::parse("۱۲٫۵۰"), Some(Money(125)));
+        assert_eq!(Money::parse("-20"), Some(Money(-200)));
+        assert_eq!(Money::parse("12.55"), None);
+        assert_eq!(Money::parse("12a"), None);
+        assert_eq!(Money::parse(""), None);
+        assert_eq!(Money::parse("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Money(1_500_000).format(), "۱۵۰٬۰۰۰ تومان");
+        assert_eq!(Money(125).format(), "۱۲٫۵ تومان");
+        assert_eq!(Money(-12_345_670).format(), "-۱٬۲۳۴٬۵۶۷ تومان");
+        assert_eq!(Money::ZERO.format(), "۰ تومان");
+        assert_eq!(Money(125).input_value(), "12.5");
+        assert_eq!(Money(-5).input_value(), "-0.5");
+        assert_eq!(Money(1_500_000).in_words(), "صد و پنجاه هزار تومان");
+        assert_eq!(Money(125).in_words(), "دوازده تومان و پنج ریال");
+    }
+
+    #[test]
+    fn test_sum_is_exact() {
+        let total: Money = std::iter::repeat(Money::parse("0.1").unwrap())
+            .take(1000)
+            .sum();
+        assert_eq!(total, Money::parse("100").unwrap());
+        assert_eq!(Money::parse("12.5").unwrap() * 3, Money(375));
+        assert_eq!(Money(125).checked_mul(3), Some(Money(375)));
+        assert_eq!(Money(125).checked_mul(i64::MAX), None);
+    }
+}
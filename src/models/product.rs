@@ -1,41 +1,103 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, FromRow)]
+use crate::{models::money::Money, utils::media::thumbnail_url};
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
 pub struct Product {
     pub id: i64,
     pub name: String,
     pub description: String,
-    pub price: f64,
+    pub price: Money,
     pub image_url: Option<String>,
     pub stock: i32,
     pub created_at: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// Product form fields; the image is uploaded alongside as a separate multipart field
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct ProductForm {
     pub name: String,
     pub description: String,
-    pub price: f64,
+    /// Price in tomans
+    pub price: String,
     pub stock: i32,
+    /// Drop the current image without uploading a new one
+    #[serde(default)]
+    pub remove_image: bool,
 }
 
 impl Product {
     pub fn formatted_price(&self) -> String {
-        let price_str = format!("{:.0}", self.price);
-        format!(
-            "{} تومان",
-            crate::utils::localization::to_persian_digits(&price_str)
-        )
+        self.price.format()
+    }
+
+    /// The small version of the product image, for the catalog grid and forms
+    pub fn thumbnail_url(&self) -> Option<String> {
+        self.image_url.as_deref().map(thumbnail_url)
     }
 
     pub fn stock_status_class(&self) -> &'static str {
-        if self.stock > 50 {
-            "badge-success"
-        } else if self.stock > 0 {
-            "badge-warning"
-        } else {
-            "badge-error"
-        }
+        stock_status_class(self.stock)
+    }
+}
+
+/// One size or packaging of a product, with its own price and stock.
+///
+/// A product with variants keeps its `stock` equal to the sum of theirs.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct ProductVariant {
+    pub id: i64,
+    pub variant_name: String,
+    pub product_id: i64,
+    pub description: String,
+    pub price: Money,
+    pub stock: i32,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductVariantForm {
+    pub variant_name: String,
+    #[serde(default)]
+    pub description: String,
+    pub price: String,
+    pub stock: i32,
+}
+
+impl ProductVariant {
+    pub fn formatted_price(&self) -> String {
+        self.price.format()
+    }
+
+    pub fn stock_status_class(&self) -> &'static str {
+        stock_status_class(self.stock)
+    }
+}
+
+/// When a product counts as running low, from the configuration
+#[derive(Debug, Clone, Copy)]
+pub struct StockSettings {
+    /// Stock at or below which a product is running low
+    pub low_stock_threshold: i32,
+}
+
+impl StockSettings {
+    /// Whether a change in stock just took a product down to the low-stock threshold.
+    ///
+    /// Only the crossing counts, so selling the last few items one at a time reports it once.
+    pub fn fell_to_low_stock(&self, before: i32, after: i32) -> bool {
+        before > self.low_stock_threshold && after <= self.low_stock_threshold
+    }
+}
+
+fn stock_status_class(stock: i32) -> &'static str {
+    if stock > 50 {
+        "badge-success"
+    } else if stock > 0 {
+        "badge-warning"
+    } else {
+        "badge-error"
     }
 }
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Something a user may be allowed to do. Viewing records needs no permission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+    CustomersEdit,
+    CustomersDelete,
+    CustomersMerge,
+    TransactionsEdit,
+    TransactionsDelete,
+    ChequesManage,
+    InvoicesManage,
+    CatalogManage,
+    Export,
+    UsersManage,
+    AuditView,
+    WebhooksManage,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 12] = [
+        Permission::CustomersEdit,
+        Permission::CustomersDelete,
+        Permission::CustomersMerge,
+        Permission::TransactionsEdit,
+        Permission::TransactionsDelete,
+        Permission::ChequesManage,
+        Permission::InvoicesManage,
+        Permission::CatalogManage,
+        Permission::Export,
+        Permission::UsersManage,
+        Permission::AuditView,
+        Permission::WebhooksManage,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|permission| permission.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::CustomersEdit => "customers.edit",
+            Permission::CustomersDelete => "customers.delete",
+            Permission::CustomersMerge => "customers.merge",
+            Permission::TransactionsEdit => "transactions.edit",
+            Permission::TransactionsDelete => "transactions.delete",
+            Permission::ChequesManage => "cheques.manage",
+            Permission::InvoicesManage => "invoices.manage",
+            Permission::CatalogManage => "catalog.manage",
+            Permission::Export => "export",
+            Permission::UsersManage => "users.manage",
+            Permission::AuditView => "audit.view",
+            Permission::WebhooksManage => "webhooks.manage",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Permission::CustomersEdit => "افزودن و ویرایش مشتری",
+            Permission::CustomersDelete => "حذف مشتری",
+            Permission::CustomersMerge => "ادغام مشتریان تکراری",
+            Permission::TransactionsEdit => "ثبت و ویرایش تراکنش",
+            Permission::TransactionsDelete => "حذف تراکنش",
+            Permission::ChequesManage => "تغییر وضعیت چک",
+            Permission::InvoicesManage => "صدور، تأیید و لغو فاکتور",
+            Permission::CatalogManage => "مدیریت کاتالوگ محصولات",
+            Permission::Export => "خروجی گرفتن از اطلاعات",
+            Permission::UsersManage => "مدیریت کاربران و نقش‌ها",
+            Permission::AuditView => "مشاهده گزارش تغییرات",
+            Permission::WebhooksManage => "مدیریت وبهوک‌ها",
+        }
+    }
+}
+
+/// A named set of permissions users are assigned
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Role {
+    pub id: i64,
+    /// Key stored in `users.role`
+    pub name: String,
+    pub display_name: String,
+    pub created_at: String,
+}
+
+impl Role {
+    /// Name of the built-in role that always has every permission
+    pub const ADMIN: &'static str = "admin";
+
+    pub fn is_admin(&self) -> bool {
+        self.name == Self::ADMIN
+    }
+}
+
+/// A role with its permissions and how many users have it, for the roles page
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleSummary {
+    pub role: Role,
+    pub permissions: Vec<Permission>,
+    pub user_count: i64,
+}
+
+impl RoleSummary {
+    pub fn has(&self, permission: &Permission) -> bool {
+        self.permissions.contains(permission)
+    }
+}
+
+/// Form data for creating or editing a role.
+///
+/// Checked permissions arrive as repeated `permissions` fields, which `Form` cannot
+/// put in a struct directly, so the form is read as a list of pairs.
+#[derive(Debug, Default)]
+pub struct RoleForm {
+    pub name: String,
+    pub display_name: String,
+    pub permissions: Vec<Permission>,
+}
+
+impl RoleForm {
+    pub fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let mut form = RoleForm::default();
+        for (key, value) in fields {
+            match key.as_str() {
+                "name" => form.name = value.trim().to_lowercase(),
+                "display_name" => form.display_name = value.trim().to_string(),
+                "permissions" => {
+                    if let Some(permission) = Permission::parse(&value) {
+                        if !form.permissions.contains(&permission) {
+                            form.permissions.push(permission);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        form
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_round_trip() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::parse(permission.as_str()), Some(permission));
+        }
+        assert_eq!(Permission::parse("customers.view"), None);
+    }
+
+    #[test]
+    fn test_role_form_from_fields() {
+        let form = RoleForm::from_fields(vec![
+            ("csrf_token".to_string(), "x".to_string()),
+            ("name".to_string(), " Cashier ".to_string()),
+            ("display_name".to_string(), "صندوق‌دار".to_string()),
+            ("permissions".to_string(), "transactions.edit".to_string()),
+            ("permissions".to_string(), "export".to_string()),
+            ("permissions".to_string(), "export".to_string()),
+            ("permissions".to_string(), "bogus".to_string()),
+        ]);
+        assert_eq!(form.name, "cashier");
+        assert_eq!(form.display_name, "صندوق‌دار");
+        assert_eq!(
+            form.permissions,
+            vec![Permission::TransactionsEdit, Permission::Export]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{
+    models::{customer::City, CustomerQuery},
+    utils::{date::format_timestamp, localization::to_persian_digits},
+};
+
+/// A named, saved set of customer filters over tags, city, sales count and balance.
+///
+/// Filters are kept as entered, like the customer list's query string, and opened as
+/// that query string for the list, the export and the API.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Segment {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    /// Comma-separated tag names the customers must have
+    pub tags: String,
+    /// `any` for customers with any of the tags; all of them otherwise
+    pub tag_mode: String,
+    /// One of `Hidaj`, `Khorramdarreh`, `Abhar`, `Zanjan` and `Qazvin`, or empty
+    pub city: String,
+    pub min_sales: String,
+    pub max_sales: String,
+    /// Lowest balance in tomans
+    pub min_balance: String,
+    /// Highest balance in tomans
+    pub max_balance: String,
+    #[serde(skip)]
+    pub created_by: Option<i64>,
+    pub created_at: String,
+}
+
+impl Segment {
+    /// The customer list query selecting the segment's customers
+    pub fn customer_query(&self) -> CustomerQuery {
+        CustomerQuery {
+            tags: self.tags.clone(),
+            tag_mode: self.tag_mode.clone(),
+            city: self.city.clone(),
+            min_sales: self.min_sales.clone(),
+            max_sales: self.max_sales.clone(),
+            min_balance: self.min_balance.clone(),
+            max_balance: self.max_balance.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Query string of the segment's filters, for the list and export links
+    pub fn filter_query(&self) -> String {
+        self.customer_query().filter_query()
+    }
+
+    /// The segment's filters in words, one per line of its card
+    pub fn criteria(&self) -> Vec<String> {
+        let query = self.customer_query();
+        let mut criteria = Vec::new();
+
+        let tags = query.tag_filters();
+        if !tags.is_empty() {
+            let joiner = if query.matches_any_tag() { " یا " } else { " و " };
+            criteria.push(format!("برچسب: {}", tags.join(joiner)));
+        }
+        if let Some(city) = query.city_filter() {
+            criteria.push(format!("شهر: {}", City::from_str(city).display_name()));
+        }
+        if let Some(min_sales) = query.min_sales_value() {
+            criteria.push(format!(
+                "دست‌کم {} فروش",
+                to_persian_digits(&min_sales.to_string())
+            ));
+        }
+        if let Some(max_sales) = query.max_sales_value() {
+            criteria.push(format!(
+                "حداکثر {} فروش",
+                to_persian_digits(&max_sales.to_string())
+            ));
+        }
+        if let Some(min_balance) = query.min_balance_value() {
+            criteria.push(format!("مانده حساب از {}", min_balance.format()));
+        }
+        if let Some(max_balance) = query.max_balance_value() {
+            criteria.push(format!("مانده حساب تا {}", max_balance.format()));
+        }
+        criteria
+    }
+
+    pub fn formatted_created_at(&self) -> String {
+        format_timestamp(&self.created_at)
+    }
+}
+
+/// A segment with how many customers are in it now, for the segments page
+#[derive(Debug, Clone)]
+pub struct SegmentSummary {
+    pub segment: Segment,
+    pub customer_count: i64,
+}
+
+/// Form for saving the customer list's current filters as a segment
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SegmentForm {
+    pub name: String,
+    pub description: String,
+    pub tags: String,
+    pub tag_mode: String,
+    pub city: String,
+    pub min_sales: String,
+    pub max_sales: String,
+    pub min_balance: String,
+    pub max_balance: String,
+}
+
+impl SegmentForm {
+    /// The form's filters as a customer list query
+    pub fn customer_query(&self) -> CustomerQuery {
+        CustomerQuery {
+            tags: self.tags.clone(),
+            tag_mode: self.tag_mode.clone(),
+            city: self.city.clone(),
+            min_sales: self.min_sales.clone(),
+            max_sales: self.max_sales.clone(),
+            min_balance: self.min_balance.clone(),
+            max_balance: self.max_balance.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment() -> Segment {
+        Segment {
+            id: 1,
+            name: "بدهکاران عمده".to_string(),
+            description: String::new(),
+            tags: "wholesale, vip".to_string(),
+            tag_mode: "any".to_string(),
+            city: "Zanjan".to_string(),
+            min_sales: "5".to_string(),
+            max_sales: String::new(),
+            min_balance: "1000".to_string(),
+            max_balance: String::new(),
+            created_by: None,
+            created_at: "2026-10-01T08:00:00+00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_segment_query() {
+        let segment = segment();
+        let query = segment.customer_query();
+        assert_eq!(query.tag_filters(), vec!["wholesale", "vip"]);
+        assert!(query.matches_any_tag());
+        assert_eq!(query.search_term(), None);
+        assert_eq!(
+            segment.filter_query(),
+            "city=Zanjan&min_sales=5&tags=wholesale%2C+vip&tag_mode=any&min_balance=1000"
+        );
+    }
+
+    #[test]
+    fn test_segment_criteria() {
+        let criteria = segment().criteria();
+        assert_eq!(criteria.len(), 4);
+        assert_eq!(criteria[0], "برچسب: wholesale یا vip");
+        assert_eq!(criteria[1], "شهر: زنجان");
+    }
+}
@@ -1,11 +1,24 @@
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::utils::date::format_timestamp;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Session {
     pub id: String,
     pub user_id: i64,
     pub expires_at: String,
+    /// Secret every form posted in this session must carry
+    pub csrf_token: String,
+    /// Signed in with "remember me", so the session outlives the browser
+    pub remember_me: bool,
+    /// Identifies the session on the sessions page; unlike `id` it is not a secret
+    pub public_id: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub created_at: String,
+    pub last_seen_at: String,
 }
 
 impl Session {
@@ -14,8 +27,148 @@ impl Session {
         uuid::Uuid::new_v4().to_string()
     }
 
-    pub fn generate_expiry() -> String {
-        use chrono::{Duration, Utc};
-        (Utc::now() + Duration::hours(24)).to_rfc3339()
+    /// Create a new CSRF token
+    pub fn generate_csrf_token() -> String {
+        uuid::Uuid::new_v4().simple().to_string()
+    }
+
+    /// Create a new public ID
+    pub fn generate_public_id() -> String {
+        uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+    }
+
+    pub fn formatted_created_at(&self) -> String {
+        format_timestamp(&self.created_at)
+    }
+
+    pub fn formatted_last_seen_at(&self) -> String {
+        format_timestamp(&self.last_seen_at)
+    }
+
+    /// Browser and operating system read from the user agent, e.g. `Firefox روی Windows`
+    pub fn device(&self) -> String {
+        let agent = self.user_agent.as_str();
+        if agent.is_empty() {
+            return "نامشخص".to_string();
+        }
+
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+            ("curl/", "curl"),
+        ]
+        .iter()
+        .find(|(marker, _)| agent.contains(marker))
+        .map(|(_, name)| *name);
+
+        let system = [
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iOS"),
+            ("Windows", "Windows"),
+            ("Mac OS", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .iter()
+        .find(|(marker, _)| agent.contains(marker))
+        .map(|(_, name)| *name);
+
+        match (browser, system) {
+            (Some(browser), Some(system)) => format!("{} روی {}", browser, system),
+            (Some(name), None) | (None, Some(name)) => name.to_string(),
+            (None, None) => agent.chars().take(60).collect(),
+        }
+    }
+
+    pub fn expires_at_time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.expires_at)
+            .ok()
+            .map(|expires_at| expires_at.with_timezone(&Utc))
+    }
+}
+
+/// How long sessions last, from the configuration
+#[derive(Debug, Clone)]
+pub struct SessionSettings {
+    /// A session ends after this many hours without a request
+    pub idle_timeout_hours: i64,
+    /// Idle timeout of sessions signed in with "remember me"
+    pub remember_me_days: i64,
+}
+
+impl SessionSettings {
+    pub fn lifetime(&self, remember_me: bool) -> Duration {
+        if remember_me {
+            Duration::days(self.remember_me_days)
+        } else {
+            Duration::hours(self.idle_timeout_hours)
+        }
+    }
+
+    /// When a session used at `now` expires if it sees no further requests
+    pub fn expiry(&self, remember_me: bool, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + self.lifetime(remember_me)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_with_agent(user_agent: &str) -> Session {
+        Session {
+            id: String::new(),
+            user_id: 1,
+            expires_at: String::new(),
+            csrf_token: String::new(),
+            remember_me: false,
+            public_id: String::new(),
+            ip_address: String::new(),
+            user_agent: user_agent.to_string(),
+            created_at: String::new(),
+            last_seen_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_device() {
+        let firefox = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        assert_eq!(session_with_agent(firefox).device(), "Firefox روی Windows");
+
+        let chrome = "Mozilla/5.0 (Linux; Android 14) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0 Mobile Safari/537.36";
+        assert_eq!(session_with_agent(chrome).device(), "Chrome روی Android");
+
+        assert_eq!(session_with_agent("curl/8.5.0").device(), "curl");
+        assert_eq!(session_with_agent("").device(), "نامشخص");
+    }
+
+    #[test]
+    fn test_remember_me_lasts_longer() {
+        let settings = SessionSettings {
+            idle_timeout_hours: 8,
+            remember_me_days: 30,
+        };
+        assert_eq!(settings.lifetime(false), Duration::hours(8));
+        assert_eq!(settings.lifetime(true), Duration::days(30));
+
+        let now = Utc::now();
+        assert_eq!(settings.expiry(false, now), now + Duration::hours(8));
+        assert_eq!(settings.expiry(true, now), now + Duration::days(30));
+    }
+
+    #[test]
+    fn test_expires_at_time() {
+        let mut session = session_with_agent("");
+        session.expires_at = "2024-03-29T10:00:00+03:30".to_string();
+        assert_eq!(
+            session.expires_at_time().unwrap().to_rfc3339(),
+            "2024-03-29T06:30:00+00:00"
+        );
+
+        session.expires_at = "tomorrow".to_string();
+        assert!(session.expires_at_time().is_none());
     }
 }
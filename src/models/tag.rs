@@ -0,0 +1,246 @@
+use serde::Serialize;
+use sqlx::{
+    error::BoxDynError,
+    sqlite::{SqliteTypeInfo, SqliteValueRef},
+    Decode, FromRow, Sqlite, Type,
+};
+
+use crate::utils::localization::persian_to_english_numbers;
+
+/// Longest tag name accepted, in characters
+pub const MAX_TAG_NAME_CHARS: usize = 40;
+
+/// A customer's tag names as one comma-separated column, for queries over `customers`
+pub const CUSTOMER_TAGS_SQL: &str = "COALESCE((SELECT GROUP_CONCAT(tags.name, ',') FROM customer_tags \
+     JOIN tags ON tags.id = customer_tags.tag_id \
+     WHERE customer_tags.customer_id = customers.id), '')";
+
+/// A free-form label customers can be grouped by
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+}
+
+/// A tag with how many customers have it, for the tags page
+#[derive(Debug, Clone, FromRow)]
+pub struct TagSummary {
+    #[sqlx(flatten)]
+    pub tag: Tag,
+    pub customer_count: i64,
+}
+
+impl TagSummary {
+    pub fn list_query(&self) -> String {
+        tag_list_query(&self.tag.name)
+    }
+}
+
+/// Names of a customer's tags, sorted.
+///
+/// Customer queries select them as one comma-separated column; names cannot contain commas.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct TagNames(pub Vec<String>);
+
+impl TagNames {
+    pub fn iter(&self) -> std::slice::Iter<'_, String> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Names as one cell of the customer export
+    pub fn joined(&self) -> String {
+        self.0.join("، ")
+    }
+}
+
+impl From<String> for TagNames {
+    fn from(value: String) -> Self {
+        let mut names: Vec<String> = value
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        TagNames(names)
+    }
+}
+
+impl Type<Sqlite> for TagNames {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for TagNames {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(TagNames::from(<String as Decode<Sqlite>>::decode(value)?))
+    }
+}
+
+/// Whether a tag is being put on customers or taken off them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagAction {
+    #[default]
+    Add,
+    Remove,
+}
+
+impl TagAction {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "remove" => TagAction::Remove,
+            _ => TagAction::Add,
+        }
+    }
+}
+
+/// Form for tagging or untagging customers, from the customer list or a customer's page.
+///
+/// The list sends the chosen customers as repeated `customer_ids` fields, so the form is
+/// read as a list of pairs.
+#[derive(Debug, Default)]
+pub struct TagForm {
+    pub customer_ids: Vec<i64>,
+    pub tag: String,
+    pub action: TagAction,
+    /// Filters of the list the form was sent from, to go back to it
+    pub return_query: String,
+}
+
+impl TagForm {
+    pub fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let mut form = TagForm::default();
+        for (key, value) in fields {
+            match key.as_str() {
+                "customer_ids" => {
+                    if let Ok(id) = persian_to_english_numbers(value.trim()).parse() {
+                        if !form.customer_ids.contains(&id) {
+                            form.customer_ids.push(id);
+                        }
+                    }
+                }
+                "tag" => form.tag = clean_tag_name(&value),
+                "action" => form.action = TagAction::parse(&value),
+                "return_query" => form.return_query = value,
+                _ => {}
+            }
+        }
+        form
+    }
+
+    /// The customer list page the form was sent from.
+    ///
+    /// The query string is only kept if it is made of characters a query string
+    /// built by the list can have, so the form cannot redirect anywhere else.
+    pub fn return_url(&self) -> String {
+        let is_query = self.return_query.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~' | '%' | '+' | '=' | '&')
+        });
+        if is_query && !self.return_query.is_empty() {
+            format!("/?{}", self.return_query)
+        } else {
+            "/".to_string()
+        }
+    }
+}
+
+/// Query string of the customer list filtered to the customers with tag `name`
+pub fn tag_list_query(name: &str) -> String {
+    crate::models::CustomerQuery {
+        tags: name.to_string(),
+        ..Default::default()
+    }
+    .filter_query()
+}
+
+/// A tag name with surrounding space trimmed and inner runs of space collapsed
+pub fn clean_tag_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Tag names in a comma-separated list, as typed into the tag filter.
+///
+/// Persian commas separate names too; repeats are dropped, ignoring case as the tags table does.
+pub fn split_tag_names(value: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in value.split([',', '،']).map(clean_tag_name) {
+        let seen = names
+            .iter()
+            .any(|seen| seen.to_lowercase() == name.to_lowercase());
+        if !name.is_empty() && !seen {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_tag_names() {
+        assert_eq!(
+            split_tag_names(" wholesale ,VIP،  late  payer ,, vip"),
+            vec!["wholesale", "VIP", "late payer"]
+        );
+        assert!(split_tag_names(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_tag_names_from_column() {
+        assert_eq!(
+            TagNames::from("vip,wholesale,late payer".to_string()),
+            TagNames(vec![
+                "late payer".to_string(),
+                "vip".to_string(),
+                "wholesale".to_string()
+            ])
+        );
+        assert!(TagNames::from(String::new()).is_empty());
+    }
+
+    #[test]
+    fn test_tag_form_from_fields() {
+        let form = TagForm::from_fields(vec![
+            ("csrf_token".to_string(), "x".to_string()),
+            ("customer_ids".to_string(), "3".to_string()),
+            ("customer_ids".to_string(), "۱۲".to_string()),
+            ("customer_ids".to_string(), "3".to_string()),
+            ("customer_ids".to_string(), "x".to_string()),
+            ("tag".to_string(), "  late   payer ".to_string()),
+            ("action".to_string(), "remove".to_string()),
+            ("return_query".to_string(), "city=Zanjan".to_string()),
+        ]);
+        assert_eq!(form.customer_ids, vec![3, 12]);
+        assert_eq!(form.tag, "late payer");
+        assert_eq!(form.action, TagAction::Remove);
+        assert_eq!(form.return_url(), "/?city=Zanjan");
+
+        let form = TagForm::from_fields(vec![("tag".to_string(), "vip".to_string())]);
+        assert_eq!(form.action, TagAction::Add);
+        assert!(form.customer_ids.is_empty());
+        assert_eq!(form.return_url(), "/");
+    }
+
+    #[test]
+    fn test_return_url_stays_on_the_list() {
+        for return_query in ["//evil.example", "a=1\r\nSet-Cookie: x=1", "q=x#top"] {
+            let form = TagForm {
+                return_query: return_query.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(form.return_url(), "/");
+        }
+    }
+}
@@ -1,38 +1,67 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use parsidate::ParsiDate;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+use crate::{
+    models::{cheque::ChequeStatus, money::Money},
+    utils::date::iso_to_shamsi,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Transaction {
     pub id: i64,
     pub customer_id: i64,
-    pub amount: f64,
+    pub amount: Money,
+    /// `Cash`, `CardTransfer`, `Cheque`, `Credit` or `Other`
     pub transaction_type: String,
     pub description: Option<String>,
+    /// ISO date, e.g. `2024-07-22`
     pub transaction_date: String,
+    /// `debit` raises what the customer owes, `credit` lowers it
+    pub direction: String,
+}
+
+/// How a transaction's amount affects the customer's balance, as SQL over
+/// `transactions` columns: debits raise what the customer owes, credits lower it,
+/// and bounced or returned cheques do not count at all
+macro_rules! signed_amount_sql {
+    () => {
+        "CASE
+            WHEN EXISTS (SELECT 1 FROM cheques WHERE cheques.transaction_id = transactions.id AND cheques.status IN ('bounced', 'returned')) THEN 0
+            WHEN transactions.direction = 'debit' THEN transactions.amount
+            ELSE -transactions.amount
+        END"
+    };
 }
 
+pub const SIGNED_AMOUNT_SQL: &str = signed_amount_sql!();
+
+/// A customer's balance as a correlated subquery, for queries over `customers`
+pub const CUSTOMER_BALANCE_SQL: &str = concat!(
+    "COALESCE((SELECT SUM(",
+    signed_amount_sql!(),
+    ") FROM transactions WHERE transactions.customer_id = customers.id), 0)"
+);
+
 impl Transaction {
     pub fn formatted_amount(&self) -> String {
-        let amount_str = format!("{:.0}", self.amount);
-        format!(
-            "{} تومان",
-            crate::utils::localization::to_persian_digits(&amount_str)
-        )
+        self.amount.format()
     }
 
-    pub fn formatted_date_shamsi(&self) -> String {
-        if self.transaction_date.is_empty() {
-            return "".to_string();
-        }
+    pub fn direction_enum(&self) -> TransactionDirection {
+        TransactionDirection::parse(&self.direction).unwrap_or(TransactionDirection::Credit)
+    }
 
-        match chrono::NaiveDate::parse_from_str(&self.transaction_date, "%Y-%m-%d %H:%M:%S") {
-            Ok(gregorian_date) => ParsiDate::from_gregorian(gregorian_date)
-                .unwrap()
-                .format("%Y/%m/%d")
-                .to_string(),
-            Err(_) => self.transaction_date.clone(),
-        }
+    pub fn is_debit(&self) -> bool {
+        self.direction_enum() == TransactionDirection::Debit
+    }
+
+    pub fn direction_display_name(&self) -> &'static str {
+        self.direction_enum().display_name()
+    }
+
+    pub fn formatted_date_shamsi(&self) -> String {
+        iso_to_shamsi(&self.transaction_date)
     }
     
     pub fn transaction_type_display_name(&self) -> String {
@@ -42,12 +71,175 @@ impl Transaction {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TransactionForm {
-    pub amount: f64,
+    /// Amount in tomans
+    pub amount: String,
     pub transaction_type: String,
     pub description: Option<String>,
+    /// Shamsi date, e.g. `1403/05/01`
     pub transaction_date: String,
+    pub direction: String,
+    /// Cheque fields, required when `transaction_type` is `Cheque`
+    #[serde(default)]
+    pub cheque_number: String,
+    #[serde(default)]
+    pub bank_name: String,
+    #[serde(default)]
+    pub due_date: String,
+}
+
+impl TransactionForm {
+    pub fn is_cheque(&self) -> bool {
+        TransactionType::from_str(&self.transaction_type) == TransactionType::Cheque
+    }
+}
+
+/// A transaction together with the customer's running balance after it
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct LedgerEntry {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    pub balance: Money,
+    pub cheque_id: Option<i64>,
+    pub cheque_status: Option<String>,
+}
+
+impl LedgerEntry {
+    pub fn formatted_balance(&self) -> String {
+        self.balance.format()
+    }
+
+    pub fn cheque_status_enum(&self) -> Option<ChequeStatus> {
+        self.cheque_status.as_deref().and_then(ChequeStatus::parse)
+    }
+
+    /// Whether the amount is part of the balance; false for bounced or returned cheques
+    pub fn counts_toward_balance(&self) -> bool {
+        self.cheque_status_enum()
+            .is_none_or(|status| status.counts_toward_balance())
+    }
+}
+
+/// Totals of a customer's ledger
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LedgerSummary {
+    pub total_debit: Money,
+    pub total_credit: Money,
+}
+
+impl LedgerSummary {
+    pub fn from_entries(entries: &[LedgerEntry]) -> Self {
+        entries
+            .iter()
+            .filter(|entry| entry.counts_toward_balance())
+            .fold(Self::default(), |mut summary, entry| {
+                if entry.transaction.is_debit() {
+                    summary.total_debit = summary.total_debit + entry.transaction.amount;
+                } else {
+                    summary.total_credit = summary.total_credit + entry.transaction.amount;
+                }
+                summary
+            })
+    }
+
+    /// What the customer owes; negative when they have paid in advance
+    pub fn balance(&self) -> Money {
+        self.total_debit - self.total_credit
+    }
+
+    pub fn formatted_total_debit(&self) -> String {
+        self.total_debit.format()
+    }
+
+    pub fn formatted_total_credit(&self) -> String {
+        self.total_credit.format()
+    }
+
+    pub fn formatted_balance(&self) -> String {
+        self.balance().abs().format()
+    }
+
+    pub fn balance_status(&self) -> BalanceStatus {
+        BalanceStatus::of(self.balance())
+    }
+}
+
+/// Which side a balance is on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BalanceStatus {
+    /// The customer owes us
+    Owing,
+    /// The customer has paid in advance
+    InCredit,
+    Settled,
+}
+
+impl BalanceStatus {
+    pub fn of(balance: Money) -> Self {
+        if balance > Money::ZERO {
+            BalanceStatus::Owing
+        } else if balance < Money::ZERO {
+            BalanceStatus::InCredit
+        } else {
+            BalanceStatus::Settled
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            BalanceStatus::Owing => "بدهکار",
+            BalanceStatus::InCredit => "بستانکار",
+            BalanceStatus::Settled => "تسویه",
+        }
+    }
+
+    pub fn badge_class(&self) -> &'static str {
+        match self {
+            BalanceStatus::Owing => "badge-error",
+            BalanceStatus::InCredit => "badge-success",
+            BalanceStatus::Settled => "badge-info",
+        }
+    }
+}
+
+/// Whether a transaction increases or decreases what the customer owes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TransactionDirection {
+    /// Sale on account: the customer owes more
+    Debit,
+    /// Payment received: the customer owes less
+    Credit,
+}
+
+impl TransactionDirection {
+    pub fn all() -> Vec<TransactionDirection> {
+        vec![TransactionDirection::Credit, TransactionDirection::Debit]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionDirection::Debit => "debit",
+            TransactionDirection::Credit => "credit",
+        }
+    }
+
+    /// Parse a submitted direction, rejecting anything but `debit` and `credit`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "debit" => Some(TransactionDirection::Debit),
+            "credit" => Some(TransactionDirection::Credit),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            TransactionDirection::Debit => "بدهکار (فروش به مشتری)",
+            TransactionDirection::Credit => "بستانکار (دریافت از مشتری)",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 use std::fmt;
 
+use crate::models::role::Permission;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: i64,
@@ -10,6 +13,18 @@ pub struct User {
     pub full_name: String,
     pub role: String,
     pub created_at: String,
+    /// The user must choose a new password before using the app
+    pub must_change_password: bool,
+    /// Deactivated users cannot log in, but keep their history
+    pub is_active: bool,
+    /// Display name of the user's role; filled in for the signed-in user
+    #[sqlx(skip)]
+    #[serde(skip)]
+    pub role_display_name: String,
+    /// What the user's role allows; filled in for the signed-in user
+    #[sqlx(skip)]
+    #[serde(skip)]
+    pub permissions: Vec<Permission>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -55,15 +70,65 @@ impl User {
         self.role == "admin"
     }
 
+    /// Whether this user may change `other`'s account; an admin's is left to admins
+    pub fn can_manage(&self, other: &User) -> bool {
+        self.is_admin() || !other.is_admin()
+    }
+
+    /// Whether the user has a permission, given by name such as `customers.delete`
+    pub fn can(&self, permission: &str) -> bool {
+        self.permissions
+            .iter()
+            .any(|granted| granted.as_str() == permission)
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
     pub fn role_enum(&self) -> UserRole {
         UserRole::from_str(&self.role).unwrap_or(UserRole::User)
     }
 }
 
+/// A user as the JSON API shows them, without the password hash
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserProfile {
+    pub id: i64,
+    pub username: String,
+    pub full_name: String,
+    pub role: String,
+    pub created_at: String,
+    pub must_change_password: bool,
+    pub is_active: bool,
+    /// Names of the user's permissions; only filled in for the signed-in user
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<String>)]
+    pub permissions: Vec<&'static str>,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        UserProfile {
+            permissions: user.permissions.iter().map(|permission| permission.as_str()).collect(),
+            id: user.id,
+            username: user.username,
+            full_name: user.full_name,
+            role: user.role,
+            created_at: user.created_at,
+            must_change_password: user.must_change_password,
+            is_active: user.is_active,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoginForm {
     pub username: String,
     pub password: String,
+    /// Keep the session for longer, across browser restarts
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -73,3 +138,25 @@ pub struct UserForm {
     pub full_name: String,
     pub role: String,
 }
+
+/// Form data for editing a user's name and role
+#[derive(Debug, Deserialize)]
+pub struct EditUserForm {
+    pub full_name: String,
+    pub role: String,
+}
+
+/// Form data for changing one's own password
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordForm {
+    pub current_password: String,
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+/// Form data for an admin setting a user's password
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordForm {
+    pub new_password: String,
+    pub confirm_password: String,
+}
@@ -0,0 +1,24 @@
+use axum::extract::FromRef;
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    models::{
+        document::DocumentSettings, login_throttle::LoginLimits, product::StockSettings,
+        session::SessionSettings,
+    },
+    utils::{media::MediaStore, password::PasswordPolicy},
+};
+
+/// State shared by all handlers.
+///
+/// Handlers extract only the parts they need, e.g. `State<Pool<Sqlite>>`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub pool: Pool<Sqlite>,
+    pub media: MediaStore,
+    pub documents: DocumentSettings,
+    pub login_limits: LoginLimits,
+    pub sessions: SessionSettings,
+    pub password_policy: PasswordPolicy,
+    pub stock: StockSettings,
+}
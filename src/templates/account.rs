@@ -0,0 +1,16 @@
+use crate::models::User;
+use askama::Template;
+
+/// Change password page template
+#[derive(Template)]
+#[template(path = "change_password.html")]
+pub struct ChangePasswordTemplate {
+    /// The user has a temporary password and cannot continue until they change it
+    pub forced: bool,
+    pub min_length: usize,
+    pub policy_description: String,
+    pub error: Option<String>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
@@ -0,0 +1,91 @@
+use crate::models::User;
+use askama::Template;
+
+/// One operation of the JSON API as the docs page lists it
+#[derive(Debug, Clone)]
+pub struct DocOperation {
+    pub method: String,
+    pub path: String,
+    pub tag: String,
+    pub summary: String,
+    pub description: String,
+    pub parameters: Vec<DocField>,
+    /// Schema of the JSON body, for operations that take one
+    pub request_body: Option<String>,
+    pub responses: Vec<DocResponse>,
+}
+
+impl DocOperation {
+    /// Anchor of the operation on the page
+    pub fn anchor(&self) -> String {
+        format!(
+            "{}-{}",
+            self.method,
+            self.path
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect::<String>()
+        )
+    }
+
+    pub fn method_badge_class(&self) -> &'static str {
+        match self.method.as_str() {
+            "get" => "badge-info",
+            "post" | "put" => "badge-success",
+            "delete" => "badge-error",
+            _ => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DocResponse {
+    pub status: String,
+    pub description: String,
+    pub schema: Option<String>,
+}
+
+/// A parameter of an operation or a property of a schema
+#[derive(Debug, Clone)]
+pub struct DocField {
+    pub name: String,
+    /// Where a parameter goes (`path` or `query`); empty for properties
+    pub location: String,
+    pub type_name: String,
+    pub required: bool,
+    pub description: String,
+}
+
+/// A tag of the document with its operations, in path order
+#[derive(Debug, Clone)]
+pub struct DocTag {
+    pub name: String,
+    pub description: String,
+    pub operations: Vec<DocOperation>,
+}
+
+/// A schema from the document's components
+#[derive(Debug, Clone)]
+pub struct DocSchema {
+    pub name: String,
+    pub description: String,
+    /// Type of a schema that is not an object, such as `integer (int64)`
+    pub type_name: String,
+    /// Schemas whose properties this one includes
+    pub includes: Vec<String>,
+    pub properties: Vec<DocField>,
+}
+
+/// Docs page of the JSON API, rendered from its OpenAPI document
+#[derive(Template)]
+#[template(path = "api_docs.html")]
+pub struct ApiDocsTemplate {
+    pub title: String,
+    pub version: String,
+    pub description: String,
+    pub tags: Vec<DocTag>,
+    pub schemas: Vec<DocSchema>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
@@ -0,0 +1,46 @@
+use crate::models::{AuditAction, AuditEntity, AuditEntry, AuditQuery, User};
+use askama::Template;
+
+/// Audit log page template
+#[derive(Template)]
+#[template(path = "audit.html")]
+pub struct AuditTemplate {
+    pub entries: Vec<AuditEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub total_pages: i64,
+    pub query: AuditQuery,
+    pub users: Vec<User>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+impl AuditTemplate {
+    pub fn all_entities(&self) -> [AuditEntity; 5] {
+        AuditEntity::ALL
+    }
+
+    pub fn all_actions(&self) -> [AuditAction; 3] {
+        AuditAction::ALL
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        self.query.entity_filter().is_some()
+            || self.query.entity_id_filter().is_some()
+            || self.query.user_id_filter().is_some()
+            || self.query.action_filter().is_some()
+    }
+
+    pub fn is_selected_user(&self, user: &User) -> bool {
+        self.query.user_id_filter() == Some(user.id)
+    }
+
+    pub fn previous_page_query(&self) -> String {
+        self.query.page_query(self.page - 1)
+    }
+
+    pub fn next_page_query(&self) -> String {
+        self.query.page_query(self.page + 1)
+    }
+}
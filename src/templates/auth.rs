@@ -6,4 +6,5 @@ use askama::Template;
 pub struct LoginTemplate {
     pub error: Option<String>,
     pub flash_message: Option<String>,
+    pub csrf_token: String,
 }
@@ -1,4 +1,4 @@
-use crate::{models::{Product, User}};
+use crate::{models::{Product, ProductVariant, User}};
 use askama::Template;
 
 /// Product catalog page template
@@ -7,6 +7,7 @@ use askama::Template;
 pub struct CatalogTemplate {
     pub products: Vec<Product>,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
     pub flash_message: Option<String>, // Add this field
 }
@@ -16,6 +17,7 @@ pub struct CatalogTemplate {
 #[template(path = "add_product.html")]
 pub struct AddProductTemplate {
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
 }
 
@@ -23,14 +25,39 @@ pub struct AddProductTemplate {
 #[template(path = "product_detail.html")]
 pub struct ProductDetailTemplate {
     pub product: Product,
+    pub variants: Vec<ProductVariant>,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
+    pub flash_message: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "edit_product.html")]
 pub struct EditProductTemplate {
     pub product: Product,
+    /// Stock is computed from the variants when there are any
+    pub has_variants: bool,
     pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "add_variant.html")]
+pub struct AddVariantTemplate {
+    pub product: Product,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "edit_variant.html")]
+pub struct EditVariantTemplate {
+    pub product: Product,
+    pub variant: ProductVariant,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
 }
\ No newline at end of file
@@ -0,0 +1,31 @@
+use crate::models::{ChequeDetail, ChequeStatus, ChequeStatusChange, User};
+use askama::Template;
+
+/// Cheques due this week, or every cheque
+#[derive(Template)]
+#[template(path = "cheques.html")]
+pub struct ChequeListTemplate {
+    pub cheques: Vec<ChequeDetail>,
+    pub show_all: bool,
+    /// Today as an ISO date, to spot overdue cheques
+    pub today: String,
+    pub week_start: String,
+    pub week_end: String,
+    pub total_amount: String,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+/// A cheque with its status history
+#[derive(Template)]
+#[template(path = "cheque_detail.html")]
+pub struct ChequeDetailTemplate {
+    pub cheque: ChequeDetail,
+    pub history: Vec<ChequeStatusChange>,
+    pub next_statuses: Vec<ChequeStatus>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
@@ -1,23 +1,64 @@
-use crate::models::{Customer, Transaction, User};
+use crate::models::{
+    tag::tag_list_query, City, Customer, CustomerForm, CustomerQuery, CustomerSummary,
+    DuplicateMatch, DuplicatePair, ImportColumn, ImportField, ImportRow, ImportSummary,
+    LedgerEntry, LedgerSummary, MergeFieldRow, User,
+};
 use askama::Template;
 
 /// Customer list page template
 #[derive(Template)]
 #[template(path = "list.html")]
 pub struct ListTemplate {
-    pub customers: Vec<Customer>,
+    pub customers: Vec<CustomerSummary>,
+    pub total: i64,
+    pub total_outstanding: String,
+    pub page: i64,
+    pub total_pages: i64,
+    pub query: CustomerQuery,
+    pub cities: Vec<City>,
+    pub job_titles: Vec<String>,
+    /// Every tag's name, suggested in the tag inputs
+    pub tag_names: Vec<String>,
+    pub page_sizes: Vec<i64>,
     pub active_page: &'static str,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub flash_message: Option<String>,
 }
 
+impl ListTemplate {
+    pub fn previous_page_query(&self) -> String {
+        self.query.page_query(self.page - 1)
+    }
+
+    pub fn next_page_query(&self) -> String {
+        self.query.page_query(self.page + 1)
+    }
+
+    pub fn tag_query(&self, name: &str) -> String {
+        tag_list_query(name)
+    }
+
+    /// Whether the signed-in user may tag customers and save segments
+    pub fn can_edit(&self) -> bool {
+        self.current_user
+            .as_ref()
+            .is_some_and(|user| user.can("customers.edit"))
+    }
+}
+
 /// Customer page template
 #[derive(Template)]
 #[template(path = "add.html")]
 pub struct AddTemplate {
     pub active_page: &'static str,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub cities: Vec<crate::models::City>,
+    /// Values to fill the form with when it is shown again
+    pub form: CustomerForm,
+    /// Existing customers the submitted one looks like, waiting for confirmation
+    pub duplicates: Vec<DuplicateMatch>,
     // pub batch_count: i32,
 }
 
@@ -26,13 +67,24 @@ pub struct AddTemplate {
 #[template(path = "detail.html")]
 pub struct DetailTemplate {
     pub customer: Customer,
-    pub transactions: Vec<Transaction>,
+    pub tags: Vec<String>,
+    /// Every tag's name, suggested in the tag input
+    pub tag_names: Vec<String>,
+    pub ledger: Vec<LedgerEntry>,
+    pub summary: LedgerSummary,
     pub transactions_json: String,
     pub active_page: &'static str,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub flash_message: Option<String>,
 }
 
+impl DetailTemplate {
+    pub fn tag_query(&self, name: &str) -> String {
+        tag_list_query(name)
+    }
+}
+
 /// Edit customer page template
 #[derive(Template)]
 #[template(path = "edit.html")]
@@ -40,5 +92,59 @@ pub struct EditTemplate {
     pub customer: Customer,
     pub active_page: &'static str,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub cities: Vec<crate::models::City>,
 }
+
+/// Customer import upload page template
+#[derive(Template)]
+#[template(path = "import.html")]
+pub struct ImportTemplate {
+    pub active_page: &'static str,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+}
+
+/// Customer import preview template, showing what each row will become
+#[derive(Template)]
+#[template(path = "import_preview.html")]
+pub struct ImportPreviewTemplate {
+    pub token: String,
+    pub file_name: String,
+    pub columns: Vec<ImportColumn>,
+    pub fields: Vec<ImportField>,
+    pub rows: Vec<ImportRow>,
+    pub summary: ImportSummary,
+    pub active_page: &'static str,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+}
+
+/// Likely duplicate customers page template
+#[derive(Template)]
+#[template(path = "duplicates.html")]
+pub struct DuplicatesTemplate {
+    /// The strongest pairs, up to the page's limit
+    pub pairs: Vec<DuplicatePair>,
+    /// How many pairs were found in all
+    pub total: usize,
+    pub active_page: &'static str,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+}
+
+/// Customer merge page template
+#[derive(Template)]
+#[template(path = "merge.html")]
+pub struct MergeTemplate {
+    /// The customer that survives the merge
+    pub keep: Customer,
+    /// The customer merged into it and deleted
+    pub remove: Customer,
+    pub fields: Vec<MergeFieldRow>,
+    pub transaction_count: i64,
+    pub invoice_count: i64,
+    pub active_page: &'static str,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+}
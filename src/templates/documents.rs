@@ -0,0 +1,17 @@
+use crate::{models::PrintDocument, utils::localization::to_persian_digits};
+use askama::Template;
+
+/// A standalone, printer-friendly invoice or receipt
+#[derive(Template)]
+#[template(path = "print_document.html")]
+pub struct PrintDocumentTemplate {
+    pub document: PrintDocument,
+    pub pdf_url: String,
+    pub back_url: String,
+}
+
+impl PrintDocumentTemplate {
+    pub fn row_number(&self, index: &usize) -> String {
+        to_persian_digits(&index.to_string())
+    }
+}
@@ -61,6 +61,24 @@ impl ErrorTemplate {
         }
     }
 
+    pub fn for_invalid_csrf_token() -> Self {
+        Self {
+            title: "درخواست نامعتبر".to_string(),
+            message: "این فرم معتبر نیست یا مدت اعتبار آن تمام شده است.".to_string(),
+            details: Some(
+                "برای حفاظت از حساب شما، فقط فرم‌هایی پذیرفته می‌شوند که از همین سامانه ارسال شده باشند."
+                    .to_string(),
+            ),
+            suggestions: vec![
+                "صفحه قبل را دوباره بارگذاری کنید و فرم را از نو ارسال کنید".to_string(),
+                "اگر از سیستم خارج شده‌اید، دوباره وارد شوید".to_string(),
+            ],
+            error_code: Some("403".to_string()),
+            back_url: "javascript:history.back()".to_string(),
+            home_url: "/".to_string(),
+        }
+    }
+
     pub fn for_database_error() -> Self {
         Self {
             title: "خطای پایگاه داده".to_string(),
@@ -101,6 +119,18 @@ impl ErrorTemplate {
         }
     }
 
+    pub fn for_unavailable(details: String) -> Self {
+        Self {
+            title: "این بخش در دسترس نیست".to_string(),
+            message: "این بخش هنوز روی سرور راه‌اندازی نشده است.".to_string(),
+            details: Some(details),
+            suggestions: vec!["این پیام را برای مدیر سیستم بفرستید".to_string()],
+            error_code: Some("503".to_string()),
+            back_url: "javascript:history.back()".to_string(),
+            home_url: "/".to_string(),
+        }
+    }
+
     pub fn for_validation_error(details: String) -> Self {
         Self {
             title: "خطای اعتبارسنجی".to_string(),
@@ -0,0 +1,56 @@
+use crate::models::{InvoiceItem, InvoiceStatus, InvoiceSummary, Permission, SaleItemOption, User};
+use askama::Template;
+
+/// Invoices, optionally filtered by status
+#[derive(Template)]
+#[template(path = "invoices.html")]
+pub struct InvoiceListTemplate {
+    pub invoices: Vec<InvoiceSummary>,
+    pub status: Option<InvoiceStatus>,
+    pub statuses: Vec<InvoiceStatus>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+impl InvoiceListTemplate {
+    pub fn is_selected(&self, status: &InvoiceStatus) -> bool {
+        self.status.as_ref() == Some(status)
+    }
+}
+
+/// Form to start a draft invoice
+#[derive(Template)]
+#[template(path = "invoice_new.html")]
+pub struct NewInvoiceTemplate {
+    /// Customer ids and names for the customer picker
+    pub customers: Vec<(i64, String)>,
+    pub selected_customer_id: i64,
+    /// Today as a Shamsi date, the default invoice date
+    pub today: String,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+/// An invoice with its line items, and the form to add items to a draft
+#[derive(Template)]
+#[template(path = "invoice_detail.html")]
+pub struct InvoiceDetailTemplate {
+    pub invoice: InvoiceSummary,
+    pub items: Vec<InvoiceItem>,
+    pub options: Vec<SaleItemOption>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
+
+impl InvoiceDetailTemplate {
+    /// Whether the viewer may add items to, confirm or cancel the invoice
+    pub fn can_manage(&self) -> bool {
+        self.current_user
+            .as_ref()
+            .is_some_and(|user| user.has_permission(Permission::InvoicesManage))
+    }
+}
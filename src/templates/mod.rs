@@ -1,6 +1,18 @@
+pub mod account;
+pub mod api_docs;
+pub mod audit;
 pub mod auth;
 pub mod catalog;
+pub mod cheques;
 pub mod customers;
+pub mod documents;
 pub mod errors;
+pub mod invoices;
+pub mod roles;
+pub mod segments;
+pub mod sessions;
+pub mod tags;
+pub mod tokens;
 pub mod transactions;
 pub mod users;
+pub mod webhooks;
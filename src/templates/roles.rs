@@ -0,0 +1,50 @@
+use crate::models::{Permission, RoleSummary, User};
+use askama::Template;
+
+/// Roles page template
+#[derive(Template)]
+#[template(path = "roles.html")]
+pub struct RolesTemplate {
+    pub roles: Vec<RoleSummary>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
+
+impl RolesTemplate {
+    pub fn all_permissions(&self) -> [Permission; 12] {
+        Permission::ALL
+    }
+}
+
+/// Form for adding a role, or editing one when `role_id` is set
+#[derive(Template)]
+#[template(path = "role_form.html")]
+pub struct RoleFormTemplate {
+    pub role_id: Option<i64>,
+    pub name: String,
+    pub display_name: String,
+    pub permissions: Vec<Permission>,
+    pub error: Option<String>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+impl RoleFormTemplate {
+    pub fn all_permissions(&self) -> [Permission; 12] {
+        Permission::ALL
+    }
+
+    pub fn is_checked(&self, permission: &Permission) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    pub fn form_action(&self) -> String {
+        match self.role_id {
+            Some(id) => format!("/roles/edit/{}", id),
+            None => "/roles/add".to_string(),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use crate::models::{SegmentSummary, User};
+use askama::Template;
+
+/// Saved segments page
+#[derive(Template)]
+#[template(path = "segments.html")]
+pub struct SegmentsTemplate {
+    pub segments: Vec<SegmentSummary>,
+    pub active_page: &'static str,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub flash_message: Option<String>,
+}
@@ -0,0 +1,21 @@
+use crate::models::{Session, User};
+use askama::Template;
+
+/// The signed-in user's sessions
+#[derive(Template)]
+#[template(path = "sessions.html")]
+pub struct SessionsTemplate {
+    pub sessions: Vec<Session>,
+    /// Public ID of the session viewing the page
+    pub current_public_id: String,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
+
+impl SessionsTemplate {
+    pub fn is_current(&self, session: &Session) -> bool {
+        session.public_id == self.current_public_id
+    }
+}
@@ -0,0 +1,13 @@
+use crate::models::{TagSummary, User};
+use askama::Template;
+
+/// Tags page
+#[derive(Template)]
+#[template(path = "tags.html")]
+pub struct TagsTemplate {
+    pub tags: Vec<TagSummary>,
+    pub active_page: &'static str,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub flash_message: Option<String>,
+}
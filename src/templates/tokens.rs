@@ -0,0 +1,28 @@
+use crate::models::{ApiToken, Permission, User};
+use askama::Template;
+
+/// API tokens page: the signed-in user's tokens, or every user's for admins
+#[derive(Template)]
+#[template(path = "tokens.html")]
+pub struct TokensTemplate {
+    pub tokens: Vec<ApiToken>,
+    /// Listing every user's tokens rather than one's own
+    pub show_all: bool,
+    /// A token just created; shown only once, since only its hash is kept
+    pub new_token: Option<String>,
+    pub lifetimes: Vec<i64>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
+
+impl TokensTemplate {
+    /// Permissions a new token may be given: those of the user's own role
+    pub fn available_scopes(&self) -> Vec<Permission> {
+        self.current_user
+            .as_ref()
+            .map(|user| user.permissions.clone())
+            .unwrap_or_default()
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::{ Customer, Transaction, TransactionType, User };
+use crate::models::{ Cheque, Customer, Transaction, TransactionDirection, TransactionType, User };
 use askama::Template;
 
 #[derive(Template)]
@@ -6,8 +6,10 @@ use askama::Template;
 pub struct AddTransactionTemplate {
     pub customer: Customer,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
     pub transaction_types: Vec<TransactionType>,
+    pub directions: Vec<TransactionDirection>,
 }
 
 #[derive(Template)]
@@ -15,7 +17,27 @@ pub struct AddTransactionTemplate {
 pub struct EditTransactionTemplate {
     pub customer: Customer,
     pub transaction: Transaction,
+    pub cheque: Option<Cheque>,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
     pub transaction_types: Vec<TransactionType>,
-}
\ No newline at end of file
+    pub directions: Vec<TransactionDirection>,
+}
+
+impl EditTransactionTemplate {
+    pub fn cheque_number(&self) -> &str {
+        self.cheque.as_ref().map_or("", |cheque| cheque.cheque_number.as_str())
+    }
+
+    pub fn bank_name(&self) -> &str {
+        self.cheque.as_ref().map_or("", |cheque| cheque.bank_name.as_str())
+    }
+
+    pub fn due_date(&self) -> String {
+        self.cheque
+            .as_ref()
+            .map(|cheque| cheque.formatted_due_date())
+            .unwrap_or_default()
+    }
+}
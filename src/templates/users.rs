@@ -1,4 +1,6 @@
-use crate::models::User;
+use std::collections::HashMap;
+
+use crate::models::{LockoutEvent, LoginFailure, Role, User};
 use askama::Template;
 
 /// User list page template
@@ -6,15 +8,80 @@ use askama::Template;
 #[template(path = "users.html")]
 pub struct UsersTemplate {
     pub users: Vec<User>,
+    pub roles: Vec<Role>,
+    /// Number of open sessions by user ID
+    pub session_counts: HashMap<i64, i64>,
+    /// Usernames and client addresses currently locked out of logging in
+    pub lockouts: Vec<LoginFailure>,
+    pub lockout_events: Vec<LockoutEvent>,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
     pub flash_message: Option<String>,
 }
 
+impl UsersTemplate {
+    /// Display name of a user's role, or its key if the role no longer exists
+    pub fn role_name<'a>(&'a self, user: &'a User) -> &'a str {
+        self.roles
+            .iter()
+            .find(|role| role.name == user.role)
+            .map(|role| role.display_name.as_str())
+            .unwrap_or(&user.role)
+    }
+
+    /// Whether the signed-in user may change this user's account
+    pub fn can_manage(&self, user: &User) -> bool {
+        self.current_user
+            .as_ref()
+            .is_some_and(|current| current.can_manage(user))
+    }
+
+    pub fn session_count(&self, user: &User) -> i64 {
+        self.session_counts.get(&user.id).copied().unwrap_or(0)
+    }
+
+    /// The active lockout on a user's username, if any
+    pub fn lockout_for(&self, user: &User) -> Option<&LoginFailure> {
+        let username = user.username.to_lowercase();
+        self.lockouts
+            .iter()
+            .find(|lockout| lockout.scope == "username" && lockout.key == username)
+    }
+}
+
 /// Add user page template
 #[derive(Template)]
 #[template(path = "add_user.html")]
 pub struct AddUserTemplate {
+    pub roles: Vec<Role>,
+    pub min_length: usize,
+    pub policy_description: String,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+/// Edit user page template
+#[derive(Template)]
+#[template(path = "edit_user.html")]
+pub struct EditUserTemplate {
+    pub user: User,
+    pub roles: Vec<Role>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+/// Admin form for setting a user's password
+#[derive(Template)]
+#[template(path = "reset_password.html")]
+pub struct ResetPasswordTemplate {
+    pub user: User,
+    pub min_length: usize,
+    pub policy_description: String,
+    pub error: Option<String>,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub active_page: &'static str,
 }
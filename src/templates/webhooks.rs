@@ -0,0 +1,83 @@
+use crate::models::{
+    DeliveryStatus, User, WebhookDelivery, WebhookEndpoint, WebhookEndpointSummary, WebhookEvent,
+};
+use askama::Template;
+
+/// Webhook endpoints page
+#[derive(Template)]
+#[template(path = "webhooks.html")]
+pub struct WebhooksTemplate {
+    pub endpoints: Vec<WebhookEndpointSummary>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
+
+/// Form for adding an endpoint, or editing one when `endpoint_id` is set
+#[derive(Template)]
+#[template(path = "webhook_form.html")]
+pub struct WebhookFormTemplate {
+    pub endpoint_id: Option<i64>,
+    pub url: String,
+    pub description: String,
+    pub events: Vec<WebhookEvent>,
+    pub is_active: bool,
+    pub error: Option<String>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+}
+
+impl WebhookFormTemplate {
+    pub fn all_events(&self) -> [WebhookEvent; 7] {
+        WebhookEvent::ALL
+    }
+
+    pub fn is_checked(&self, event: &WebhookEvent) -> bool {
+        self.events.contains(event)
+    }
+
+    pub fn form_action(&self) -> String {
+        match self.endpoint_id {
+            Some(id) => format!("/webhooks/edit/{}", id),
+            None => "/webhooks/add".to_string(),
+        }
+    }
+}
+
+/// Delivery log of one endpoint, shown with its details, or of every endpoint
+#[derive(Template)]
+#[template(path = "webhook_deliveries.html")]
+pub struct WebhookDeliveriesTemplate {
+    pub endpoint: Option<WebhookEndpoint>,
+    pub deliveries: Vec<WebhookDelivery>,
+    /// Status the log is filtered by, if any
+    pub status: Option<DeliveryStatus>,
+    pub current_user: Option<User>,
+    pub csrf_token: String,
+    pub active_page: &'static str,
+    pub flash_message: Option<String>,
+}
+
+impl WebhookDeliveriesTemplate {
+    pub fn all_statuses(&self) -> [DeliveryStatus; 3] {
+        [
+            DeliveryStatus::Pending,
+            DeliveryStatus::Delivered,
+            DeliveryStatus::Failed,
+        ]
+    }
+
+    pub fn is_selected(&self, status: &DeliveryStatus) -> bool {
+        self.status.as_ref() == Some(status)
+    }
+
+    /// Address of this page, which the status filter submits to
+    pub fn page_url(&self) -> String {
+        match &self.endpoint {
+            Some(endpoint) => format!("/webhooks/{}", endpoint.id),
+            None => "/webhooks/deliveries".to_string(),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, Utc};
+use parsidate::ParsiDate;
+
+use crate::utils::localization::{persian_to_english_numbers, to_persian_digits};
+
+/// Format used for Shamsi dates in forms and on screen
+pub const SHAMSI_FORMAT: &str = "%Y/%m/%d";
+
+/// Format used for dates stored in the database
+pub const ISO_FORMAT: &str = "%Y-%m-%d";
+
+/// Format of timestamps written by SQLite's `datetime('now')`
+const ISO_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parse a Shamsi date typed as YYYY/MM/DD, with Persian or English digits
+pub fn parse_shamsi(input: &str) -> Option<ParsiDate> {
+    ParsiDate::parse(persian_to_english_numbers(input.trim()).as_str(), SHAMSI_FORMAT).ok()
+}
+
+/// Format a Gregorian date as a Shamsi YYYY/MM/DD string
+pub fn to_shamsi(date: NaiveDate) -> String {
+    ParsiDate::from_gregorian(date)
+        .map(|date| date.format(SHAMSI_FORMAT))
+        .unwrap_or_default()
+}
+
+/// Convert a Shamsi date typed as YYYY/MM/DD into the ISO date stored in the database
+pub fn shamsi_to_iso(input: &str) -> Option<String> {
+    let date = parse_shamsi(input)?.to_gregorian().ok()?;
+    Some(date.format(ISO_FORMAT).to_string())
+}
+
+/// Parse a stored ISO date, or the date part of an ISO timestamp
+pub fn parse_iso(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    NaiveDate::parse_from_str(value, ISO_FORMAT)
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, ISO_DATETIME_FORMAT)
+                .map(|datetime| datetime.date())
+        })
+        .ok()
+}
+
+/// Show a stored ISO date as Shamsi, falling back to the raw value if it cannot be read
+pub fn iso_to_shamsi(value: &str) -> String {
+    match parse_iso(value) {
+        Some(date) => to_shamsi(date),
+        None => value.to_string(),
+    }
+}
+
+/// Show a stored ISO date spelled out for printed documents, e.g. `۱۰ فروردین ۱۴۰۳`
+pub fn iso_to_shamsi_long(value: &str) -> String {
+    match parse_iso(value).and_then(|date| ParsiDate::from_gregorian(date).ok()) {
+        Some(date) => to_persian_digits(&format!("{} {}", date.day(), date.format("%B %Y"))),
+        None => value.to_string(),
+    }
+}
+
+/// Show a stored UTC timestamp as a local Shamsi date and time, e.g. `۱۴۰۳/۰۱/۱۰ ۱۴:۳۵`.
+///
+/// Accepts RFC 3339 and SQLite `datetime('now')` timestamps.
+pub fn format_timestamp(value: &str) -> String {
+    let timestamp = DateTime::parse_from_rfc3339(value.trim())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value.trim(), ISO_DATETIME_FORMAT)
+                .map(|datetime| datetime.and_utc())
+        });
+    match timestamp {
+        Ok(timestamp) => {
+            let local = timestamp.with_timezone(&Local);
+            to_persian_digits(&format!(
+                "{} {}",
+                to_shamsi(local.date_naive()),
+                local.format("%H:%M")
+            ))
+        }
+        Err(_) => value.to_string(),
+    }
+}
+
+/// Bring a stored date in any format the app has ever written to an ISO date.
+///
+/// Older rows hold Shamsi `YYYY/MM/DD` strings or ISO timestamps.
+pub fn normalize_stored_date(value: &str) -> Option<String> {
+    parse_iso(value)
+        .map(|date| date.format(ISO_FORMAT).to_string())
+        .or_else(|| shamsi_to_iso(value))
+}
+
+/// Today's date in the server's local time zone
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// First (Saturday) and last (Friday) day of the Iranian week containing `date`
+pub fn week_bounds(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let days_since_saturday = (date.weekday().num_days_from_monday() + 2) % 7;
+    let start = date - Duration::days(days_since_saturday as i64);
+    (start, start + Duration::days(6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shamsi() {
+        assert_eq!(parse_shamsi("۱۴۰۳/۰۵/۰۲"), ParsiDate::new(1403, 5, 2).ok());
+        assert_eq!(parse_shamsi(" 1403/05/02 "), ParsiDate::new(1403, 5, 2).ok());
+        assert!(parse_shamsi("1403/5/2").is_none());
+        assert!(parse_shamsi("1404/12/30").is_none());
+        assert!(parse_shamsi("").is_none());
+    }
+
+    #[test]
+    fn test_shamsi_iso_round_trip() {
+        assert_eq!(shamsi_to_iso("۱۴۰۳/۰۱/۱۰").as_deref(), Some("2024-03-29"));
+        assert_eq!(iso_to_shamsi("2024-03-29"), "1403/01/10");
+        assert_eq!(iso_to_shamsi("2024-03-29 12:30:00"), "1403/01/10");
+        assert_eq!(iso_to_shamsi("not a date"), "not a date");
+        assert!(shamsi_to_iso("1403/13/01").is_none());
+        assert_eq!(iso_to_shamsi_long("2024-03-29"), "۱۰ فروردین ۱۴۰۳");
+    }
+
+    #[test]
+    fn test_normalize_stored_date() {
+        assert_eq!(normalize_stored_date("1403/01/10").as_deref(), Some("2024-03-29"));
+        assert_eq!(normalize_stored_date("2024-03-29 08:00:00").as_deref(), Some("2024-03-29"));
+        assert_eq!(normalize_stored_date("2024-03-29").as_deref(), Some("2024-03-29"));
+        assert!(normalize_stored_date("").is_none());
+    }
+
+    #[test]
+    fn test_week_bounds() {
+        // 2024-03-29 is a Friday, the last day of its week
+        let friday = NaiveDate::from_ymd_opt(2024, 3, 29).unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2024, 3, 23).unwrap();
+        assert_eq!(week_bounds(friday), (saturday, friday));
+        assert_eq!(week_bounds(saturday), (saturday, friday));
+        assert_eq!(to_shamsi(friday), "1403/01/10");
+    }
+}
@@ -33,3 +33,116 @@ pub fn to_persian_digits(s: &str) -> String {
         })
         .collect()
 }
+
+/// Fold Persian text typed in different ways to one spelling, for comparing names.
+///
+/// Arabic forms of ی and ک become Persian, hamza and madda forms of ا plain alef,
+/// diacritics and kashida are dropped, half-spaces become spaces, runs of spaces
+/// collapse to one, and Latin letters are lowercased.
+pub fn fold_persian_text(s: &str) -> String {
+    let folded: String = s
+        .chars()
+        .filter(|c| !matches!(c, '\u{064B}'..='\u{065F}' | '\u{0670}' | '\u{0640}'))
+        .map(|c| match c {
+            'ي' | 'ى' | 'ئ' => 'ی',
+            'ك' => 'ک',
+            'أ' | 'إ' | 'آ' | 'ٱ' => 'ا',
+            'ؤ' => 'و',
+            'ة' | 'ۀ' => 'ه',
+            '\u{200C}' => ' ',
+            _ => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+const ONES: [&str; 20] = [
+    "", "یک", "دو", "سه", "چهار", "پنج", "شش", "هفت", "هشت", "نه", "ده", "یازده", "دوازده",
+    "سیزده", "چهارده", "پانزده", "شانزده", "هفده", "هجده", "نوزده",
+];
+const TENS: [&str; 10] = [
+    "", "", "بیست", "سی", "چهل", "پنجاه", "شصت", "هفتاد", "هشتاد", "نود",
+];
+const HUNDREDS: [&str; 10] = [
+    "", "صد", "دویست", "سیصد", "چهارصد", "پانصد", "ششصد", "هفتصد", "هشتصد", "نهصد",
+];
+const SCALES: [&str; 7] = ["", "هزار", "میلیون", "میلیارد", "تریلیون", "کوادریلیون", "کوینتیلیون"];
+
+/// Spell a whole number out in Persian words, e.g. `۱۲۵۰` as `یک هزار و دویست و پنجاه`
+pub fn number_to_persian_words(n: i64) -> String {
+    if n == 0 {
+        return "صفر".to_string();
+    }
+
+    let mut remaining = n.unsigned_abs();
+    let mut groups = Vec::new();
+    let mut scale = 0;
+    while remaining > 0 {
+        let group = (remaining % 1000) as usize;
+        if group != 0 {
+            let words = three_digits_to_words(group);
+            groups.push(if scale == 0 {
+                words
+            } else {
+                format!("{} {}", words, SCALES[scale])
+            });
+        }
+        remaining /= 1000;
+        scale += 1;
+    }
+    groups.reverse();
+
+    let words = groups.join(" و ");
+    if n < 0 {
+        format!("منفی {}", words)
+    } else {
+        words
+    }
+}
+
+/// Words for a number from 1 to 999
+fn three_digits_to_words(n: usize) -> String {
+    let mut parts = Vec::new();
+    if n >= 100 {
+        parts.push(HUNDREDS[n / 100]);
+    }
+    let rest = n % 100;
+    if rest >= 20 {
+        parts.push(TENS[rest / 10]);
+        let ones = rest % 10;
+        if ones > 0 {
+            parts.push(ONES[ones]);
+        }
+    } else if rest > 0 {
+        parts.push(ONES[rest]);
+    }
+    parts.join(" و ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_to_persian_words() {
+        assert_eq!(number_to_persian_words(0), "صفر");
+        assert_eq!(number_to_persian_words(15), "پانزده");
+        assert_eq!(number_to_persian_words(121), "صد و بیست و یک");
+        assert_eq!(number_to_persian_words(1250), "یک هزار و دویست و پنجاه");
+        assert_eq!(
+            number_to_persian_words(2_000_305),
+            "دو میلیون و سیصد و پنج"
+        );
+        assert_eq!(number_to_persian_words(-40), "منفی چهل");
+        assert!(!number_to_persian_words(i64::MIN).is_empty());
+    }
+
+    #[test]
+    fn test_fold_persian_text() {
+        assert_eq!(fold_persian_text("علي  كريمي"), "علی کریمی");
+        assert_eq!(fold_persian_text("آرش مُحَمَّدی"), "ارش محمدی");
+        assert_eq!(fold_persian_text("عبدالله\u{200C}زاده"), "عبدالله زاده");
+        assert_eq!(fold_persian_text(" Ali REZAEI "), "ali rezaei");
+    }
+}
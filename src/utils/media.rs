@@ -0,0 +1,237 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use axum::body::Bytes;
+use image::{ImageFormat, ImageReader, Limits};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, AppResult},
+    utils::localization::to_persian_digits,
+};
+
+/// Largest product image accepted for upload
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest form accepted with an image upload: the image plus the other fields
+pub const MAX_UPLOAD_FORM_BYTES: usize = MAX_IMAGE_BYTES + 64 * 1024;
+
+/// Thumbnails fit inside a square of this many pixels
+const THUMBNAIL_SIZE: u32 = 400;
+
+/// Largest width or height decoded, so a small file cannot expand into a huge bitmap
+const MAX_IMAGE_DIMENSION: u32 = 8000;
+
+/// URL prefix the media directory is served under
+pub const MEDIA_URL_PREFIX: &str = "/media";
+
+/// Subdirectory of the media directory holding product images
+const PRODUCT_IMAGE_DIR: &str = "products";
+
+/// An image file submitted with a form
+#[derive(Debug)]
+pub struct ImageUpload {
+    pub content_type: String,
+    pub bytes: Bytes,
+}
+
+/// Uploaded files on disk, served from `/media`
+#[derive(Debug, Clone)]
+pub struct MediaStore {
+    root: PathBuf,
+}
+
+impl MediaStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create the media directories if they do not exist yet
+    pub async fn create_dirs(&self) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(self.root.join(PRODUCT_IMAGE_DIR)).await
+    }
+
+    /// Check an uploaded product image, store it with a thumbnail, and return its URL
+    pub async fn save_product_image(&self, upload: ImageUpload) -> AppResult<String> {
+        let format = check_image(&upload)?;
+
+        let bytes = upload.bytes.clone();
+        let thumbnail = tokio::task::spawn_blocking(move || make_thumbnail(&bytes, format))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+
+        let extension = format.extensions_str().first().copied().unwrap_or("img");
+        let file_name = format!("{}.{}", Uuid::new_v4(), extension);
+        let url = format!("{}/{}/{}", MEDIA_URL_PREFIX, PRODUCT_IMAGE_DIR, file_name);
+
+        let dir = self.root.join(PRODUCT_IMAGE_DIR);
+        tokio::fs::write(dir.join(&file_name), &upload.bytes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to save image: {}", e)))?;
+        tokio::fs::write(dir.join(thumbnail_url(&file_name)), thumbnail)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to save thumbnail: {}", e)))?;
+
+        println!("🖼️ Product image saved: {}", url);
+        Ok(url)
+    }
+
+    /// Remove an image saved by `save_product_image` and its thumbnail.
+    ///
+    /// Failures are only logged; a leftover file is not worth failing the request over.
+    pub async fn delete_image(&self, url: &str) {
+        let Some(path) = self.image_path(url) else {
+            eprintln!("⚠️  Not deleting image outside the media directory: {}", url);
+            return;
+        };
+
+        let thumbnail = path.with_file_name(thumbnail_url(
+            &path.file_name().unwrap_or_default().to_string_lossy(),
+        ));
+        for file in [path, thumbnail] {
+            match tokio::fs::remove_file(&file).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("⚠️  Failed to delete {}: {}", file.display(), e),
+            }
+        }
+        println!("🗑️ Product image deleted: {}", url);
+    }
+
+    /// Map a product image URL back to its file, rejecting anything that is not a plain file name
+    fn image_path(&self, url: &str) -> Option<PathBuf> {
+        let file_name = url
+            .strip_prefix(MEDIA_URL_PREFIX)?
+            .strip_prefix('/')?
+            .strip_prefix(PRODUCT_IMAGE_DIR)?
+            .strip_prefix('/')?;
+
+        let is_plain_name = !file_name.is_empty()
+            && Path::new(file_name).file_name().map(|n| n == file_name) == Some(true);
+        is_plain_name.then(|| self.root.join(PRODUCT_IMAGE_DIR).join(file_name))
+    }
+}
+
+/// URL (or file name) of the thumbnail generated for an image
+pub fn thumbnail_url(image_url: &str) -> String {
+    match image_url.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}_thumb.{}", stem, extension),
+        None => format!("{}_thumb", image_url),
+    }
+}
+
+/// Error shown when an upload is over `MAX_IMAGE_BYTES`
+pub fn image_too_large() -> AppError {
+    AppError::BadRequest(format!(
+        "حجم تصویر نباید بیشتر از {} مگابایت باشد",
+        to_persian_digits(&(MAX_IMAGE_BYTES / (1024 * 1024)).to_string())
+    ))
+}
+
+/// Make sure an upload is a JPEG, PNG or WebP image within the size limit,
+/// and that its content matches the type the browser declared
+fn check_image(upload: &ImageUpload) -> AppResult<ImageFormat> {
+    if upload.bytes.len() > MAX_IMAGE_BYTES {
+        return Err(image_too_large());
+    }
+
+    let declared = match upload.content_type.as_str() {
+        "image/jpeg" => ImageFormat::Jpeg,
+        "image/png" => ImageFormat::Png,
+        "image/webp" => ImageFormat::WebP,
+        _ => {
+            return Err(AppError::BadRequest(
+                "فقط تصاویر JPEG، PNG یا WebP پذیرفته می‌شوند".to_string(),
+            ))
+        }
+    };
+
+    match image::guess_format(&upload.bytes) {
+        Ok(actual) if actual == declared => Ok(declared),
+        _ => Err(AppError::BadRequest(
+            "محتوای فایل با نوع تصویر اعلام‌شده مطابقت ندارد".to_string(),
+        )),
+    }
+}
+
+/// Decode an image and encode a thumbnail of it in the same format
+fn make_thumbnail(bytes: &[u8], format: ImageFormat) -> AppResult<Vec<u8>> {
+    let invalid = || AppError::BadRequest("فایل تصویر خراب است یا قابل خواندن نیست".to_string());
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|_| invalid())?;
+
+    let mut thumbnail = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut Cursor::new(&mut thumbnail), format)
+        .map_err(|e| AppError::Internal(format!("Failed to encode thumbnail: {}", e)))?;
+    Ok(thumbnail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_upload(width: u32, height: u32) -> ImageUpload {
+        let mut bytes = Vec::new();
+        image::RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        ImageUpload {
+            content_type: "image/png".to_string(),
+            bytes: Bytes::from(bytes),
+        }
+    }
+
+    #[test]
+    fn test_check_image() {
+        assert_eq!(check_image(&png_upload(2, 2)).unwrap(), ImageFormat::Png);
+
+        let mut mislabelled = png_upload(2, 2);
+        mislabelled.content_type = "image/jpeg".to_string();
+        assert!(check_image(&mislabelled).is_err());
+
+        let not_an_image = ImageUpload {
+            content_type: "image/png".to_string(),
+            bytes: Bytes::from_static(b"<svg></svg>"),
+        };
+        assert!(check_image(&not_an_image).is_err());
+
+        let mut svg = png_upload(2, 2);
+        svg.content_type = "image/svg+xml".to_string();
+        assert!(check_image(&svg).is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_and_paths() {
+        let thumbnail = make_thumbnail(&png_upload(1200, 600).bytes, ImageFormat::Png).unwrap();
+        let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (400, 200));
+
+        assert_eq!(
+            thumbnail_url("/media/products/abc.jpg"),
+            "/media/products/abc_thumb.jpg"
+        );
+
+        let store = MediaStore::new("media");
+        assert_eq!(
+            store.image_path("/media/products/abc.png"),
+            Some(PathBuf::from("media/products/abc.png"))
+        );
+        assert_eq!(store.image_path("/media/products/../../etc/passwd"), None);
+        assert_eq!(store.image_path("/media/products/"), None);
+        assert_eq!(store.image_path("https://example.com/a.png"), None);
+    }
+}
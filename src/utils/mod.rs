@@ -1,4 +1,8 @@
+pub mod date;
 pub mod email;
 pub mod localization;
+pub mod media;
 pub mod password;
+pub mod pdf;
 pub mod phone;
+pub mod spreadsheet;
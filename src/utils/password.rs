@@ -1,5 +1,5 @@
 use bcrypt::{hash, verify, DEFAULT_COST};
-use crate::error::AppResult;
+use crate::{error::AppResult, utils::localization::to_persian_digits};
 
 /// Hash a password using bcrypt
 pub fn hash_password(password: &str) -> AppResult<String> {
@@ -9,4 +9,72 @@ pub fn hash_password(password: &str) -> AppResult<String> {
 /// Verify a password against a hash
 pub fn verify_password(password: &str, hash: &str) -> AppResult<bool> {
     Ok(verify(password, hash)?)
-}
\ No newline at end of file
+}
+
+/// Rules new passwords must follow, from the configuration
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_letter: bool,
+    pub require_digit: bool,
+}
+
+impl PasswordPolicy {
+    /// The rules in words, shown under password fields
+    pub fn description(&self) -> String {
+        let mut rules = vec![format!(
+            "حداقل {} کاراکتر",
+            to_persian_digits(&self.min_length.to_string())
+        )];
+        if self.require_letter {
+            rules.push("شامل حرف".to_string());
+        }
+        if self.require_digit {
+            rules.push("شامل عدد".to_string());
+        }
+        rules.join("، ")
+    }
+
+    /// Check a new password, returning the first rule it breaks
+    pub fn check(&self, password: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!(
+                "رمز عبور باید حداقل {} کاراکتر باشد",
+                to_persian_digits(&self.min_length.to_string())
+            ));
+        }
+        if self.require_letter && !password.chars().any(char::is_alphabetic) {
+            return Err("رمز عبور باید دست‌کم یک حرف داشته باشد".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_numeric()) {
+            return Err("رمز عبور باید دست‌کم یک عدد داشته باشد".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_policy() {
+        let policy = PasswordPolicy {
+            min_length: 8,
+            require_letter: true,
+            require_digit: true,
+        };
+        assert!(policy.check("secret12").is_ok());
+        assert!(policy.check("رمزعبور۱۲").is_ok());
+        assert!(policy.check("short1").is_err());
+        assert!(policy.check("12345678").is_err());
+        assert!(policy.check("password").is_err());
+
+        let lenient = PasswordPolicy {
+            min_length: 4,
+            require_letter: false,
+            require_digit: false,
+        };
+        assert!(lenient.check("1234").is_ok());
+    }
+}
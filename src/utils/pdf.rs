@@ -0,0 +1,729 @@
+use std::collections::BTreeMap;
+
+use pdf_writer::{
+    types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap},
+    Filter,
+    Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr,
+};
+use rustybuzz::{ttf_parser::GlyphId, Direction, Face, UnicodeBuffer};
+use unicode_bidi::{Level, ParagraphBidiInfo};
+
+use crate::{
+    error::{AppError, AppResult},
+    models::document::PrintDocument,
+    utils::localization::to_persian_digits,
+};
+
+/// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+
+/// Space kept free at the bottom of each page for the footer
+const FOOTER_HEIGHT: f32 = 40.0;
+
+const TITLE_SIZE: f32 = 16.0;
+const HEADING_SIZE: f32 = 13.0;
+const TEXT_SIZE: f32 = 10.0;
+const SMALL_SIZE: f32 = 8.5;
+
+const ROW_HEIGHT: f32 = 20.0;
+const CELL_PADDING: f32 = 4.0;
+
+const FONT_NAME: Name<'static> = Name(b"F1");
+
+/// Render a printed document as an A4 PDF, right to left, using the given TrueType font.
+///
+/// Text is shaped with the font's own tables so Persian letters join correctly,
+/// and only the glyphs used are embedded.
+pub fn render_pdf(document: &PrintDocument, font_data: &[u8]) -> AppResult<Vec<u8>> {
+    let face = Face::from_slice(font_data, 0).ok_or_else(|| {
+        AppError::Unavailable(
+            "فایل فونت PDF یک فونت TrueType معتبر نیست. مسیر PDF_FONT_PATH را بررسی کنید."
+                .to_string(),
+        )
+    })?;
+
+    let mut writer = PageWriter::new(&face);
+    writer.write_document(document);
+    let pages = writer.finish_pages(document);
+
+    assemble(&face, font_data, pages, writer.used_glyphs, document)
+}
+
+/// A glyph placed along a line, in font units from the start of the line
+struct PlacedGlyph {
+    id: u16,
+    x: f32,
+    y: f32,
+}
+
+/// A line of text shaped into glyphs, in visual (left to right) order
+struct ShapedLine {
+    glyphs: Vec<PlacedGlyph>,
+    /// Advance width in font units
+    width: f32,
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Lays a document out onto pages, remembering which glyphs were used
+struct PageWriter<'a> {
+    face: &'a Face<'a>,
+    units_per_em: f32,
+    pages: Vec<Content>,
+    content: Content,
+    /// Baseline of the next line on the current page
+    y: f32,
+    /// Glyph ids and the text they came from, for embedding and copy/paste
+    used_glyphs: BTreeMap<u16, String>,
+}
+
+impl<'a> PageWriter<'a> {
+    fn new(face: &'a Face<'a>) -> Self {
+        Self {
+            face,
+            units_per_em: face.units_per_em() as f32,
+            pages: Vec::new(),
+            content: Content::new(),
+            y: PAGE_HEIGHT - MARGIN,
+            used_glyphs: BTreeMap::new(),
+        }
+    }
+
+    fn write_document(&mut self, document: &PrintDocument) {
+        self.write_page_header(document);
+        self.write_parties(document);
+        self.write_table(document);
+        self.write_totals(document);
+        self.write_notes(document);
+        self.write_signatures(document);
+    }
+
+    /// Company details on the right, document title, number and date on the left
+    fn write_page_header(&mut self, document: &PrintDocument) {
+        let right = PAGE_WIDTH - MARGIN;
+        let top = PAGE_HEIGHT - MARGIN - TITLE_SIZE;
+
+        let mut y = top;
+        self.text(&document.company.name, TITLE_SIZE, right, y, Align::Right);
+        for line in [&document.company.address, &document.company.phone] {
+            if !line.is_empty() {
+                y -= TEXT_SIZE * 1.6;
+                self.text(line, SMALL_SIZE, right, y, Align::Right);
+            }
+        }
+
+        let mut left_y = top;
+        self.text(&document.title, TITLE_SIZE, MARGIN, left_y, Align::Left);
+        left_y -= TEXT_SIZE * 1.8;
+        let number = format!("شماره: {}", document.formatted_number());
+        self.text(&number, TEXT_SIZE, MARGIN, left_y, Align::Left);
+        left_y -= TEXT_SIZE * 1.6;
+        let date = format!("تاریخ: {}", document.formatted_date());
+        self.text(&date, TEXT_SIZE, MARGIN, left_y, Align::Left);
+
+        if let Some(stamp) = &document.stamp {
+            left_y -= HEADING_SIZE * 1.8;
+            self.content.set_fill_rgb(0.75, 0.1, 0.1);
+            self.text(stamp, HEADING_SIZE, MARGIN, left_y, Align::Left);
+            self.content.set_fill_rgb(0.0, 0.0, 0.0);
+        }
+
+        self.y = y.min(left_y) - TEXT_SIZE;
+        self.rule(self.y);
+        self.y -= TEXT_SIZE * 2.0;
+    }
+
+    /// The customer and any extra details, one label and value per line
+    fn write_parties(&mut self, document: &PrintDocument) {
+        let right = PAGE_WIDTH - MARGIN;
+        let mut rows = vec![("مشتری".to_string(), document.customer_name.clone())];
+        if !document.customer_phone.is_empty() {
+            rows.push(("تلفن".to_string(), document.customer_phone.clone()));
+        }
+        if !document.customer_address.is_empty() {
+            rows.push(("نشانی".to_string(), document.customer_address.clone()));
+        }
+        rows.extend(document.details.iter().cloned());
+
+        for (label, value) in rows {
+            let line = format!("{}: {}", label, value);
+            let line = self.fit(&line, TEXT_SIZE, right - MARGIN);
+            self.text(&line, TEXT_SIZE, right, self.y, Align::Right);
+            self.y -= TEXT_SIZE * 1.8;
+        }
+        self.y -= TEXT_SIZE;
+    }
+
+    fn write_table(&mut self, document: &PrintDocument) {
+        let columns = table_columns(document);
+        self.table_header(&columns);
+
+        for (index, line) in document.lines.iter().enumerate() {
+            if self.y - ROW_HEIGHT < MARGIN + FOOTER_HEIGHT {
+                self.new_page(document);
+                self.table_header(&columns);
+            }
+
+            let row_number = to_persian_digits(&(index + 1).to_string());
+            let cells: Vec<&str> = if document.has_item_columns() {
+                vec![
+                    &row_number,
+                    &line.description,
+                    &line.quantity,
+                    &line.unit_price,
+                    &line.discount,
+                    &line.total,
+                ]
+            } else {
+                vec![&row_number, &line.description, &line.total]
+            };
+            self.table_row(&columns, &cells, false);
+        }
+    }
+
+    fn write_totals(&mut self, document: &PrintDocument) {
+        let right = PAGE_WIDTH - MARGIN;
+        let words = format!("مبلغ به حروف: {}", document.total_in_words());
+        let word_lines = self.wrap(&words, TEXT_SIZE, right - MARGIN);
+
+        self.ensure_space(HEADING_SIZE * 2.5 + word_lines.len() as f32 * TEXT_SIZE * 1.8, document);
+        self.y -= HEADING_SIZE * 0.8;
+        let total = format!("جمع کل: {}", document.formatted_total());
+        self.text(&total, HEADING_SIZE, right, self.y, Align::Right);
+        self.y -= HEADING_SIZE * 1.8;
+        for line in word_lines {
+            self.text(&line, TEXT_SIZE, right, self.y, Align::Right);
+            self.y -= TEXT_SIZE * 1.8;
+        }
+    }
+
+    fn write_notes(&mut self, document: &PrintDocument) {
+        if document.notes.trim().is_empty() {
+            return;
+        }
+        let right = PAGE_WIDTH - MARGIN;
+
+        self.ensure_space(TEXT_SIZE * 4.0, document);
+        self.y -= TEXT_SIZE;
+        self.text("توضیحات:", TEXT_SIZE, right, self.y, Align::Right);
+        self.y -= TEXT_SIZE * 1.8;
+        for paragraph in document.notes.lines() {
+            for line in self.wrap(paragraph, TEXT_SIZE, right - MARGIN) {
+                self.ensure_space(TEXT_SIZE * 1.8, document);
+                self.text(&line, TEXT_SIZE, right, self.y, Align::Right);
+                self.y -= TEXT_SIZE * 1.8;
+            }
+        }
+    }
+
+    fn write_signatures(&mut self, document: &PrintDocument) {
+        self.ensure_space(TEXT_SIZE * 6.0, document);
+        self.y -= TEXT_SIZE * 4.0;
+        let y = self.y;
+        self.text("امضای فروشنده", TEXT_SIZE, PAGE_WIDTH * 0.75, y, Align::Center);
+        self.text("امضای مشتری", TEXT_SIZE, PAGE_WIDTH * 0.25, y, Align::Center);
+    }
+
+    fn table_header(&mut self, columns: &[(&'static str, f32)]) {
+        let titles: Vec<&str> = columns.iter().map(|(title, _)| *title).collect();
+        self.table_row(columns, &titles, true);
+    }
+
+    /// One table row; columns run from the right edge of the page
+    fn table_row(&mut self, columns: &[(&'static str, f32)], cells: &[&str], shaded: bool) {
+        let top = self.y;
+        let bottom = top - ROW_HEIGHT;
+        let width: f32 = columns.iter().map(|(_, width)| width).sum();
+        let left = PAGE_WIDTH - MARGIN - width;
+
+        if shaded {
+            self.content
+                .set_fill_rgb(0.92, 0.92, 0.92)
+                .rect(left, bottom, width, ROW_HEIGHT)
+                .fill_nonzero()
+                .set_fill_rgb(0.0, 0.0, 0.0);
+        }
+        self.content
+            .set_line_width(0.5)
+            .rect(left, bottom, width, ROW_HEIGHT)
+            .stroke();
+
+        let mut cell_right = PAGE_WIDTH - MARGIN;
+        for ((_, column_width), cell) in columns.iter().zip(cells) {
+            let baseline = bottom + (ROW_HEIGHT - TEXT_SIZE) / 2.0 + TEXT_SIZE * 0.2;
+            let text = self.fit(cell, TEXT_SIZE, column_width - CELL_PADDING * 2.0);
+            self.text(&text, TEXT_SIZE, cell_right - CELL_PADDING, baseline, Align::Right);
+
+            cell_right -= column_width;
+            if cell_right > left {
+                self.content
+                    .move_to(cell_right, top)
+                    .line_to(cell_right, bottom)
+                    .stroke();
+            }
+        }
+        self.y = bottom;
+    }
+
+    fn ensure_space(&mut self, height: f32, document: &PrintDocument) {
+        if self.y - height < MARGIN + FOOTER_HEIGHT {
+            self.new_page(document);
+        }
+    }
+
+    fn new_page(&mut self, document: &PrintDocument) {
+        let content = std::mem::replace(&mut self.content, Content::new());
+        self.pages.push(content);
+        self.write_page_header(document);
+    }
+
+    /// Close the last page and put the footer and page number on every page
+    fn finish_pages(&mut self, document: &PrintDocument) -> Vec<Vec<u8>> {
+        let content = std::mem::replace(&mut self.content, Content::new());
+        self.pages.push(content);
+
+        let pages = std::mem::take(&mut self.pages);
+        let count = pages.len();
+        pages
+            .into_iter()
+            .enumerate()
+            .map(|(index, content)| {
+                self.content = content;
+                let footer_y = MARGIN;
+                if !document.company.footer.is_empty() {
+                    let footer = self.fit(&document.company.footer, SMALL_SIZE, PAGE_WIDTH - MARGIN * 2.0);
+                    self.text(&footer, SMALL_SIZE, PAGE_WIDTH / 2.0, footer_y + SMALL_SIZE * 1.8, Align::Center);
+                }
+                let page_number = to_persian_digits(&format!("صفحه {} از {}", index + 1, count));
+                self.text(&page_number, SMALL_SIZE, PAGE_WIDTH / 2.0, footer_y, Align::Center);
+                std::mem::replace(&mut self.content, Content::new()).finish()
+            })
+            .collect()
+    }
+
+    fn rule(&mut self, y: f32) {
+        self.content
+            .set_line_width(0.8)
+            .move_to(MARGIN, y)
+            .line_to(PAGE_WIDTH - MARGIN, y)
+            .stroke();
+    }
+
+    /// Draw a line of text with its baseline at `y`, aligned against `x`
+    fn text(&mut self, text: &str, size: f32, x: f32, y: f32, align: Align) {
+        let line = self.shape(text);
+        if line.glyphs.is_empty() {
+            return;
+        }
+
+        let scale = size / self.units_per_em;
+        let width = line.width * scale;
+        let left = match align {
+            Align::Left => x,
+            Align::Right => x - width,
+            Align::Center => x - width / 2.0,
+        };
+
+        self.content.begin_text().set_font(FONT_NAME, size);
+        for glyph in &line.glyphs {
+            self.content
+                .set_text_matrix([1.0, 0.0, 0.0, 1.0, left + glyph.x * scale, y + glyph.y * scale])
+                .show(Str(&glyph.id.to_be_bytes()));
+        }
+        self.content.end_text();
+    }
+
+    fn text_width(&mut self, text: &str, size: f32) -> f32 {
+        self.shape(text).width * size / self.units_per_em
+    }
+
+    /// Shorten text with an ellipsis until it fits in `max_width`
+    fn fit(&mut self, text: &str, size: f32, max_width: f32) -> String {
+        if self.text_width(text, size) <= max_width {
+            return text.to_string();
+        }
+        let mut chars: Vec<char> = text.chars().collect();
+        while !chars.is_empty() {
+            chars.pop();
+            let shortened = format!("{}…", chars.iter().collect::<String>().trim_end());
+            if self.text_width(&shortened, size) <= max_width {
+                return shortened;
+            }
+        }
+        String::new()
+    }
+
+    /// Break text into lines no wider than `max_width`, at spaces
+    fn wrap(&mut self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut current = String::new();
+        for word in text.split_whitespace() {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+            if current.is_empty() || self.text_width(&candidate, size) <= max_width {
+                current = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut current, word.to_string()));
+            }
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+        lines
+            .into_iter()
+            .map(|line| self.fit(&line, size, max_width))
+            .collect()
+    }
+
+    /// Shape a single line of right-to-left text, splitting it into bidi runs so
+    /// numbers and Latin words keep their own direction
+    fn shape(&mut self, text: &str) -> ShapedLine {
+        let text = text.replace(['\n', '\r', '\t'], " ");
+        let mut line = ShapedLine {
+            glyphs: Vec::new(),
+            width: 0.0,
+        };
+        if text.is_empty() {
+            return line;
+        }
+
+        let bidi = ParagraphBidiInfo::new(&text, Some(Level::rtl()));
+        let (levels, runs) = bidi.visual_runs(0..text.len());
+
+        for run in runs {
+            let run_text = &text[run.clone()];
+            let mut buffer = UnicodeBuffer::new();
+            buffer.push_str(run_text);
+            buffer.set_direction(if levels[run.start].is_rtl() {
+                Direction::RightToLeft
+            } else {
+                Direction::LeftToRight
+            });
+            buffer.guess_segment_properties();
+            let shaped = rustybuzz::shape(self.face, &[], buffer);
+
+            let mut cluster_starts: Vec<usize> = shaped
+                .glyph_infos()
+                .iter()
+                .map(|info| info.cluster as usize)
+                .collect();
+            cluster_starts.sort_unstable();
+            cluster_starts.dedup();
+
+            for (info, position) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                let id = info.glyph_id as u16;
+                let start = info.cluster as usize;
+                let end = cluster_starts
+                    .iter()
+                    .find(|&&next| next > start)
+                    .copied()
+                    .unwrap_or(run_text.len());
+                self.used_glyphs
+                    .entry(id)
+                    .or_insert_with(|| run_text[start..end].to_string());
+
+                line.glyphs.push(PlacedGlyph {
+                    id,
+                    x: line.width + position.x_offset as f32,
+                    y: position.y_offset as f32,
+                });
+                line.width += position.x_advance as f32;
+            }
+        }
+        line
+    }
+}
+
+/// Column titles and widths, from right to left
+fn table_columns(document: &PrintDocument) -> Vec<(&'static str, f32)> {
+    let full_width = PAGE_WIDTH - MARGIN * 2.0;
+    if document.has_item_columns() {
+        let fixed = [30.0, 40.0, 85.0, 75.0, 90.0];
+        vec![
+            ("ردیف", fixed[0]),
+            ("شرح کالا", full_width - fixed.iter().sum::<f32>()),
+            ("تعداد", fixed[1]),
+            ("قیمت واحد", fixed[2]),
+            ("تخفیف", fixed[3]),
+            ("مبلغ", fixed[4]),
+        ]
+    } else {
+        vec![
+            ("ردیف", 30.0),
+            ("شرح", full_width - 150.0),
+            ("مبلغ", 120.0),
+        ]
+    }
+}
+
+/// Write the pages and the embedded font into a PDF file
+fn assemble(
+    face: &Face,
+    font_data: &[u8],
+    pages: Vec<Vec<u8>>,
+    used_glyphs: BTreeMap<u16, String>,
+    document: &PrintDocument,
+) -> AppResult<Vec<u8>> {
+    let mut pdf = Pdf::new();
+    let mut next_id = Ref::new(1);
+    let mut alloc = || next_id.bump();
+
+    let catalog_id = alloc();
+    let page_tree_id = alloc();
+    let info_id = alloc();
+    let font_id = alloc();
+    let cid_font_id = alloc();
+    let descriptor_id = alloc();
+    let font_file_id = alloc();
+    let cmap_id = alloc();
+    let page_ids: Vec<(Ref, Ref)> = pages.iter().map(|_| (alloc(), alloc())).collect();
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.document_info(info_id)
+        .title(TextStr(&format!("{} {}", document.title, document.number)))
+        .producer(TextStr("Rumiland CRM"));
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().map(|(page_id, _)| *page_id))
+        .count(pages.len() as i32);
+
+    for ((page_id, content_id), content) in page_ids.iter().zip(&pages) {
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .parent(page_tree_id)
+            .contents(*content_id);
+        page.resources().fonts().pair(FONT_NAME, font_id);
+        page.finish();
+        pdf.stream(*content_id, &deflate(content))
+            .filter(Filter::FlateDecode);
+    }
+
+    let base_font = format!("RMLCRM+{}", postscript_name(face));
+    let base_font = Name(base_font.as_bytes());
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+    let to_font_units = 1000.0 / face.units_per_em() as f32;
+
+    pdf.type0_font(font_id)
+        .base_font(base_font)
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_font_id)
+        .to_unicode(cmap_id);
+
+    let mut cid_font = pdf.cid_font(cid_font_id);
+    cid_font
+        .subtype(CidFontType::Type2)
+        .base_font(base_font)
+        .system_info(system_info)
+        .font_descriptor(descriptor_id)
+        .cid_to_gid_map_predefined(Name(b"Identity"));
+    let mut widths = cid_font.widths();
+    for &id in used_glyphs.keys() {
+        let advance = face.glyph_hor_advance(GlyphId(id)).unwrap_or(0);
+        widths.consecutive(id, [advance as f32 * to_font_units]);
+    }
+    widths.finish();
+    cid_font.finish();
+
+    let bbox = face.global_bounding_box();
+    pdf.font_descriptor(descriptor_id)
+        .name(base_font)
+        .flags(FontFlags::SYMBOLIC)
+        .bbox(Rect::new(
+            bbox.x_min as f32 * to_font_units,
+            bbox.y_min as f32 * to_font_units,
+            bbox.x_max as f32 * to_font_units,
+            bbox.y_max as f32 * to_font_units,
+        ))
+        .italic_angle(0.0)
+        .ascent(face.ascender() as f32 * to_font_units)
+        .descent(face.descender() as f32 * to_font_units)
+        .cap_height(face.capital_height().unwrap_or(face.ascender()) as f32 * to_font_units)
+        .stem_v(80.0)
+        .font_file2(font_file_id);
+
+    let glyph_ids: Vec<u16> = used_glyphs.keys().copied().collect();
+    let subset = subsetter::subset(font_data, 0, subsetter::Profile::pdf(&glyph_ids))
+        .map_err(|e| AppError::Internal(format!("Failed to subset PDF font: {:?}", e)))?;
+    pdf.stream(font_file_id, &deflate(&subset))
+        .filter(Filter::FlateDecode);
+
+    let mut cmap = UnicodeCmap::new(Name(b"Rumiland-UCS"), system_info);
+    for (id, text) in &used_glyphs {
+        if !text.is_empty() {
+            cmap.pair_with_multiple(*id, text.chars());
+        }
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+
+    Ok(pdf.finish())
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, 6)
+}
+
+/// The font's PostScript name with anything a PDF name cannot hold removed.
+///
+/// PostScript names are ASCII, so dropping the zero bytes of a UTF-16 entry is enough.
+fn postscript_name(face: &Face) -> String {
+    let name: String = face
+        .names()
+        .into_iter()
+        .find(|name| name.name_id == rustybuzz::ttf_parser::name_id::POST_SCRIPT_NAME)
+        .map(|name| {
+            name.name
+                .iter()
+                .map(|&byte| byte as char)
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect()
+        })
+        .unwrap_or_default();
+    if name.is_empty() {
+        "Font".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        document::{CompanyHeader, DocumentKind, DocumentLine},
+        Money,
+    };
+
+    /// A freely licensed font with Persian glyphs, kept with the tests
+    const FONT: &[u8] = include_bytes!("../../tests/fonts/DejaVuSans.ttf");
+
+    fn sample_document(line_count: usize) -> PrintDocument {
+        PrintDocument {
+            kind: DocumentKind::Invoice,
+            number: 12,
+            title: "فاکتور فروش".to_string(),
+            date: "2024-03-29".to_string(),
+            company: CompanyHeader {
+                name: "رومیلند".to_string(),
+                address: "زنجان، خیابان امام".to_string(),
+                phone: "02433334444".to_string(),
+                footer: "با سپاس از خرید شما".to_string(),
+            },
+            customer_name: "علی رضایی".to_string(),
+            customer_phone: "09121234567".to_string(),
+            customer_address: String::new(),
+            details: Vec::new(),
+            lines: (1..=line_count)
+                .map(|index| DocumentLine {
+                    description: format!("چای سبز {}", index),
+                    quantity: "۲".to_string(),
+                    unit_price: "۱۰۰٬۰۰۰ تومان".to_string(),
+                    discount: "۰ تومان".to_string(),
+                    total: "۲۰۰٬۰۰۰ تومان".to_string(),
+                })
+                .collect(),
+            total: Money::parse("12000000").unwrap(),
+            notes: "تحویل در محل".to_string(),
+            stamp: None,
+        }
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    }
+
+    fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack
+            .windows(needle.len())
+            .rposition(|window| window == needle)
+    }
+
+    #[test]
+    fn test_render_is_well_formed_pdf() {
+        // Enough rows to run onto a second page
+        let pdf = render_pdf(&sample_document(40), FONT).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1."));
+        assert!(pdf.ends_with(b"%%EOF"));
+
+        // startxref points at the cross-reference table
+        let startxref = rfind(&pdf, b"startxref\n").unwrap();
+        let xref_offset: usize = std::str::from_utf8(&pdf[startxref..])
+            .unwrap()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(pdf[xref_offset..].starts_with(b"xref\n0 "));
+
+        // Every object in use starts exactly where the table says
+        let trailer = rfind(&pdf, b"trailer\n").unwrap();
+        let xref = std::str::from_utf8(&pdf[xref_offset..trailer]).unwrap();
+        let mut lines = xref.lines().skip(1);
+        let size: usize = lines
+            .next()
+            .and_then(|line| line.strip_prefix("0 "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let entries: Vec<&str> = lines.collect();
+        assert_eq!(entries.len(), size);
+        assert!(entries[0].ends_with(" 65535 f"));
+        for (id, entry) in entries.iter().enumerate().skip(1) {
+            assert!(entry.ends_with(" 00000 n"), "object {} is missing", id);
+            let offset: usize = entry[..10].parse().unwrap();
+            let header = format!("{} 0 obj", id);
+            assert!(
+                pdf[offset..].starts_with(header.as_bytes()),
+                "object {} is misplaced",
+                id
+            );
+        }
+
+        let trailer = std::str::from_utf8(&pdf[trailer..]).unwrap();
+        assert!(trailer.contains(&format!("/Size {}", size)));
+        assert!(trailer.contains("/Root 1 0 R"));
+        assert!(find(&pdf, b"/Count 2").is_some());
+    }
+
+    #[test]
+    fn test_persian_text_is_shaped() {
+        let face = Face::from_slice(FONT, 0).unwrap();
+        let nominal = |c: char| face.glyph_index(c).unwrap().0;
+        let mut writer = PageWriter::new(&face);
+
+        let line = writer.shape("مشتری");
+        assert_eq!(line.glyphs.len(), 5);
+        assert!(line.glyphs.iter().all(|glyph| glyph.id != 0));
+        // Right to left, so the first letter is drawn last, in its joined initial form
+        let first_letter = line.glyphs.last().unwrap().id;
+        assert_ne!(first_letter, nominal('م'));
+        // Copying it out of the PDF still gives the letter
+        assert_eq!(writer.used_glyphs[&first_letter], "م");
+
+        // Numbers keep reading left to right inside right-to-left text
+        let line = writer.shape("شماره 123");
+        let digits: Vec<u16> = line.glyphs.iter().take(3).map(|glyph| glyph.id).collect();
+        assert_eq!(digits, vec![nominal('1'), nominal('2'), nominal('3')]);
+    }
+
+    #[test]
+    fn test_invalid_font_is_reported() {
+        let result = render_pdf(&sample_document(1), b"not a font");
+        assert!(matches!(result, Err(AppError::Unavailable(_))));
+    }
+}
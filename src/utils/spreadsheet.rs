@@ -0,0 +1,115 @@
+use std::io::Cursor;
+
+use calamine::{Data, Reader, Xlsx};
+
+use crate::error::{AppError, AppResult};
+
+/// Largest spreadsheet accepted for import
+pub const MAX_SPREADSHEET_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest import form accepted: the file plus the CSRF field
+pub const MAX_SPREADSHEET_FORM_BYTES: usize = MAX_SPREADSHEET_BYTES + 64 * 1024;
+
+/// Read every row of an uploaded CSV or XLSX file as text cells.
+///
+/// The format is chosen by the file extension; an XLSX file is read from its first sheet.
+pub fn read_rows(file_name: &str, bytes: &[u8]) -> AppResult<Vec<Vec<String>>> {
+    if bytes.len() > MAX_SPREADSHEET_BYTES {
+        return Err(file_too_large());
+    }
+
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "csv" => read_csv(bytes),
+        "xlsx" => read_xlsx(bytes),
+        _ => Err(AppError::BadRequest(
+            "فقط فایل‌های CSV و XLSX پذیرفته می‌شوند".to_string(),
+        )),
+    }
+}
+
+fn read_csv(bytes: &[u8]) -> AppResult<Vec<Vec<String>>> {
+    // Excel saves UTF-8 CSV files with a byte order mark
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+
+    reader
+        .records()
+        .map(|record| {
+            record
+                .map(|record| record.iter().map(str::to_string).collect())
+                .map_err(|_| {
+                    AppError::BadRequest(
+                        "فایل CSV قابل خواندن نیست. آن را با کدگذاری UTF-8 ذخیره کنید".to_string(),
+                    )
+                })
+        })
+        .collect()
+}
+
+fn read_xlsx(bytes: &[u8]) -> AppResult<Vec<Vec<String>>> {
+    let unreadable = || AppError::BadRequest("فایل اکسل قابل خواندن نیست".to_string());
+
+    let mut workbook = Xlsx::new(Cursor::new(bytes)).map_err(|_| unreadable())?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(unreadable)?
+        .map_err(|_| unreadable())?;
+
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(cell_text).collect())
+        .collect())
+}
+
+/// Text of a cell; whole numbers such as phone numbers are written without a decimal point
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+            format!("{}", *value as i64)
+        }
+        other => other.to_string(),
+    }
+}
+
+pub fn file_too_large() -> AppError {
+    AppError::BadRequest("حجم فایل نباید بیشتر از ۵ مگابایت باشد".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let bytes = "\u{feff}نام کامل,شماره تلفن\n\"رومی, علی\",09120000000\nرضا\n".as_bytes();
+        let rows = read_rows("Customers.CSV", bytes).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], vec!["نام کامل", "شماره تلفن"]);
+        assert_eq!(rows[1], vec!["رومی, علی", "09120000000"]);
+        assert_eq!(rows[2], vec!["رضا"]);
+    }
+
+    #[test]
+    fn test_read_rows_rejects_other_formats() {
+        assert!(read_rows("customers.xls", b"").is_err());
+        assert!(read_rows("customers", b"").is_err());
+    }
+
+    #[test]
+    fn test_cell_text() {
+        assert_eq!(cell_text(&Data::Float(9121234567.0)), "9121234567");
+        assert_eq!(cell_text(&Data::Float(1.5)), "1.5");
+        assert_eq!(cell_text(&Data::String("علی".to_string())), "علی");
+        assert_eq!(cell_text(&Data::Empty), "");
+    }
+}
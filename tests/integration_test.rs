@@ -50,4 +50,442 @@ fn test_password_hashing() {
     
     assert!(verify(password, &hashed).unwrap());
     assert!(!verify("wrong_password", &hashed).unwrap());
-}
\ No newline at end of file
+}
+
+/// In-memory database on a single connection, so every query sees the same data
+async fn memory_pool() -> SqlitePool {
+    sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to create test database")
+}
+
+#[tokio::test]
+async fn test_migrations_apply_once() {
+    use rumiland_crm::db::migrations::{migration_status, run_migrations, MIGRATIONS};
+
+    let pool = memory_pool().await;
+
+    run_migrations(&pool).await.expect("First migration run failed");
+    run_migrations(&pool).await.expect("Second migration run failed");
+
+    let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_migrations")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(applied, MIGRATIONS.len() as i64);
+
+    let statuses = migration_status(&pool).await.unwrap();
+    assert!(statuses.iter().all(|status| status.applied_at.is_some()));
+}
+
+#[tokio::test]
+async fn test_migrations_repair_drifted_schema() {
+    use rumiland_crm::db::migrations::{run_migrations, schema_columns};
+
+    let pool = memory_pool().await;
+
+    // A database from before the city and coordinates columns were added
+    sqlx::query(
+        r#"
+        CREATE TABLE customers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            full_name TEXT NOT NULL,
+            company TEXT NOT NULL,
+            email TEXT NOT NULL,
+            phone_number TEXT NOT NULL,
+            sales_count INTEGER NOT NULL DEFAULT 0,
+            notes TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    run_migrations(&pool).await.expect("Migrations failed on drifted schema");
+
+    let schema = schema_columns(&pool).await.unwrap();
+    let (_, columns) = schema
+        .iter()
+        .find(|(table, _)| table == "customers")
+        .expect("customers table missing");
+    assert!(columns.contains(&"city".to_string()));
+    assert!(columns.contains(&"coordinates".to_string()));
+}
+
+#[tokio::test]
+async fn test_customer_balance_from_transaction_directions() {
+    use rumiland_crm::db::migrations::run_migrations;
+    use rumiland_crm::models::transactions::CUSTOMER_BALANCE_SQL;
+
+    let pool = memory_pool().await;
+    run_migrations(&pool).await.unwrap();
+
+    sqlx::query(
+        "INSERT INTO customers (full_name, company, phone_number, notes)
+         VALUES ('علی', 'شرکت', '09121234567', '')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Amounts in rials
+    for (amount, transaction_type, direction) in [
+        (5_000_000, "Credit", "debit"),
+        (2_000_000, "Cash", "credit"),
+        (500_000, "Other", "debit"),
+    ] {
+        sqlx::query(
+            "INSERT INTO transactions (customer_id, amount, transaction_type, transaction_date, direction)
+             VALUES (1, ?, ?, '2024-03-20', ?)",
+        )
+        .bind(amount)
+        .bind(transaction_type)
+        .bind(direction)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let balance: i64 = sqlx::query_scalar(&format!(
+        "SELECT {} FROM customers WHERE id = 1",
+        CUSTOMER_BALANCE_SQL
+    ))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(balance, 3_500_000);
+
+    let invalid = sqlx::query(
+        "INSERT INTO transactions (customer_id, amount, transaction_type, direction)
+         VALUES (1, 10, 'Cash', 'sideways')",
+    )
+    .execute(&pool)
+    .await;
+    assert!(invalid.is_err());
+}
+
+#[tokio::test]
+async fn test_bounced_cheque_leaves_balance() {
+    use rumiland_crm::db::migrations::run_migrations;
+    use rumiland_crm::models::transactions::CUSTOMER_BALANCE_SQL;
+
+    let pool = memory_pool().await;
+    run_migrations(&pool).await.unwrap();
+
+    sqlx::query(
+        "INSERT INTO customers (full_name, company, phone_number, notes)
+         VALUES ('علی', 'شرکت', '09121234567', '')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO transactions (customer_id, amount, transaction_type, transaction_date, direction)
+         VALUES (1, 3000000, 'Credit', '2024-03-20', 'debit'),
+                (1, 3000000, 'Cheque', '2024-03-21', 'credit')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO cheques (transaction_id, cheque_number, bank_name, due_date)
+         VALUES (2, '123456', 'ملت', '2024-04-20')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let balance_sql = format!("SELECT {} FROM customers WHERE id = 1", CUSTOMER_BALANCE_SQL);
+
+    let balance: i64 = sqlx::query_scalar(&balance_sql).fetch_one(&pool).await.unwrap();
+    assert_eq!(balance, 0);
+
+    sqlx::query("UPDATE cheques SET status = 'bounced' WHERE id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let balance: i64 = sqlx::query_scalar(&balance_sql).fetch_one(&pool).await.unwrap();
+    assert_eq!(balance, 3_000_000);
+}
+
+#[tokio::test]
+async fn test_migration_converts_shamsi_dates_to_iso() {
+    use rumiland_crm::db::migrations::run_migrations;
+
+    let pool = memory_pool().await;
+
+    sqlx::query(
+        "CREATE TABLE customers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            full_name TEXT NOT NULL,
+            company TEXT NOT NULL,
+            email TEXT NOT NULL DEFAULT '',
+            phone_number TEXT NOT NULL,
+            notes TEXT NOT NULL
+        )",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO customers (full_name, company, phone_number, notes) VALUES ('علی', 'شرکت', '0912', '')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Transactions as stored before dates were converted
+    sqlx::query(
+        r#"
+        CREATE TABLE transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            customer_id INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            transaction_type TEXT NOT NULL,
+            description TEXT,
+            transaction_date TEXT NOT NULL DEFAULT ''
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        "INSERT INTO transactions (customer_id, amount, transaction_type, transaction_date)
+         VALUES (1, 1.0, 'Cash', '1403/01/10'),
+                (1, 1.0, 'Cash', '۱۴۰۲/۱۲/۲۹'),
+                (1, 1.0, 'Cash', '2024-03-01 10:00:00'),
+                (1, 1.0, 'Cash', '')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    run_migrations(&pool).await.expect("Migrations failed");
+
+    let dates: Vec<String> =
+        sqlx::query_scalar("SELECT transaction_date FROM transactions ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(dates, ["2024-03-29", "2024-03-19", "2024-03-01", ""]);
+}
+
+#[tokio::test]
+async fn test_migration_converts_amounts_to_rials() {
+    use rumiland_crm::db::migrations::run_migrations;
+
+    let pool = memory_pool().await;
+
+    // Tables as they were when amounts were stored as REAL tomans
+    for sql in [
+        "CREATE TABLE customers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            full_name TEXT NOT NULL,
+            company TEXT NOT NULL,
+            email TEXT NOT NULL DEFAULT '',
+            phone_number TEXT NOT NULL,
+            notes TEXT NOT NULL
+        )",
+        "CREATE TABLE transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            customer_id INTEGER NOT NULL,
+            amount REAL NOT NULL,
+            transaction_type TEXT NOT NULL,
+            description TEXT,
+            transaction_date TEXT NOT NULL DEFAULT '',
+            FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
+        )",
+        "CREATE TABLE products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL,
+            price REAL NOT NULL,
+            stock INTEGER NOT NULL,
+            image_url TEXT,
+            created_at TEXT DEFAULT (datetime('now'))
+        )",
+        "INSERT INTO customers (full_name, company, phone_number, notes) VALUES ('علی', 'شرکت', '0912', '')",
+        "INSERT INTO transactions (customer_id, amount, transaction_type, transaction_date)
+         VALUES (1, 150000.0, 'Cheque', '1403/01/10'), (1, 0.1, 'Cash', '1403/01/11')",
+        "INSERT INTO products (name, description, price, stock) VALUES ('میز', '', 850000.5, 3)",
+        "CREATE TABLE cheques (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL UNIQUE,
+            cheque_number TEXT NOT NULL,
+            bank_name TEXT NOT NULL,
+            due_date TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'received',
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+        )",
+        "INSERT INTO cheques (transaction_id, cheque_number, bank_name, due_date)
+         VALUES (1, '1', 'ملت', '1403/02/01')",
+    ] {
+        sqlx::query(sql).execute(&pool).await.unwrap();
+    }
+
+    run_migrations(&pool).await.expect("Migrations failed");
+
+    let amounts: Vec<(i64, String)> =
+        sqlx::query_as("SELECT amount, typeof(amount) FROM transactions ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        amounts,
+        [(1_500_000, "integer".to_string()), (1, "integer".to_string())]
+    );
+
+    let price: i64 = sqlx::query_scalar("SELECT price FROM products")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(price, 8_500_005);
+
+    // Rebuilding transactions must not cascade into the tables that reference it
+    let cheques: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM cheques")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(cheques, 1);
+
+    let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(foreign_keys, 1);
+}
+
+#[tokio::test]
+async fn test_product_variants_belong_to_product() {
+    use rumiland_crm::db::migrations::run_migrations;
+    use rumiland_crm::handlers::catalog::refresh_product_stock;
+    use rumiland_crm::models::{Money, ProductVariant, StockSettings};
+
+    let pool = memory_pool().await;
+    run_migrations(&pool).await.unwrap();
+    sqlx::query("PRAGMA foreign_keys = ON").execute(&pool).await.unwrap();
+
+    sqlx::query("INSERT INTO products (name, description, price, stock) VALUES ('قهوه', '', 1500000, 0)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO product_variants (product_id, variant_name, price, stock)
+         VALUES (1, '۲۵۰ گرمی', 1500000, 10), (1, '۱ کیلویی', 5500005, 4)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let variants = sqlx::query_as::<_, ProductVariant>(
+        "SELECT * FROM product_variants WHERE product_id = 1 ORDER BY id",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(variants.len(), 2);
+    assert_eq!(variants[1].price, Money::parse("550000.5").unwrap());
+
+    // The product's stock is the total of its variants'
+    let stock_settings = StockSettings {
+        low_stock_threshold: 5,
+    };
+    let mut conn = pool.acquire().await.unwrap();
+    refresh_product_stock(&mut conn, stock_settings, 1).await.unwrap();
+    drop(conn);
+    let stock: i32 = sqlx::query_scalar("SELECT stock FROM products WHERE id = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stock, 14);
+
+    sqlx::query("UPDATE product_variants SET stock = 0 WHERE id = 2")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let mut conn = pool.acquire().await.unwrap();
+    refresh_product_stock(&mut conn, stock_settings, 1).await.unwrap();
+    drop(conn);
+    let stock: i32 = sqlx::query_scalar("SELECT stock FROM products WHERE id = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stock, 10);
+
+    sqlx::query("DELETE FROM products WHERE id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM product_variants")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+}
+
+#[tokio::test]
+async fn test_invoice_totals_and_removed_products() {
+    use rumiland_crm::db::migrations::run_migrations;
+    use rumiland_crm::models::{invoice::INVOICE_SUMMARY_SQL, InvoiceItem, InvoiceSummary, Money};
+
+    let pool = memory_pool().await;
+    run_migrations(&pool).await.unwrap();
+    sqlx::query("PRAGMA foreign_keys = ON").execute(&pool).await.unwrap();
+
+    sqlx::query(
+        "INSERT INTO customers (full_name, company, phone_number, notes)
+         VALUES ('علی', 'شرکت', '09121234567', '')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO products (name, description, price, stock) VALUES ('چای', '', 1000000, 10)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO invoices (customer_id, invoice_date) VALUES (1, '2024-03-29')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO invoice_items (invoice_id, product_id, description, quantity, unit_price, discount)
+         VALUES (1, 1, 'چای', 3, 1000000, 200000), (1, 1, 'چای', 1, 1000000, 0)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let summary = sqlx::query_as::<_, InvoiceSummary>(&format!(
+        "{} WHERE invoices.id = 1",
+        INVOICE_SUMMARY_SQL
+    ))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(summary.item_count, 2);
+    assert_eq!(summary.total, Money::parse("380000").unwrap());
+    assert_eq!(summary.customer_name, "علی");
+
+    // Removing a product from the catalog keeps the invoice lines that sold it
+    sqlx::query("DELETE FROM products WHERE id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let items = sqlx::query_as::<_, InvoiceItem>("SELECT * FROM invoice_items ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(items.iter().all(|item| item.product_id.is_none()));
+    assert_eq!(items[0].description, "چای");
+}